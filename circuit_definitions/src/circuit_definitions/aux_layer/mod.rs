@@ -11,8 +11,8 @@ use crate::circuit_definitions::aux_layer::compression_modes::*;
 use crate::circuit_definitions::cs_builder_reference::CsReferenceImplementationBuilder;
 use crate::circuit_definitions::implementations::reference_cs::CSReferenceAssembly;
 
-use crate::recursion_layer_proof_config;
 use crate::zkevm_circuits::recursion::compression::CompressionRecursionConfig;
+use crate::{ProfileError, ProofConfigProfile};
 use snark_wrapper::boojum::config::CSConfig;
 use snark_wrapper::boojum::dag::CircuitResolver;
 use snark_wrapper::boojum::dag::StCircuitResolver;
@@ -220,16 +220,17 @@ impl ZkSyncCompressionLayerCircuit {
         }
     }
 
+    /// Fails if the verification key of the scheduler doesn't belong to any proof config profile
     pub fn from_witness_and_vk(
         witness: Option<Proof<F, H, EXT>>,
         vk: VerificationKey<F, H>,
         circuit_type: u8,
-    ) -> Self {
-        match circuit_type {
+    ) -> Result<Self, ProfileError> {
+        let circuit = match circuit_type {
             1 => Self::CompressionMode1Circuit(CompressionMode1Circuit {
                 witness,
                 config: CompressionRecursionConfig {
-                    proof_config: recursion_layer_proof_config(),
+                    proof_config: ProofConfigProfile::of_vk(&vk)?.recursion_layer_proof_config(),
                     verification_key: vk,
                     _marker: std::marker::PhantomData,
                 },
@@ -277,13 +278,16 @@ impl ZkSyncCompressionLayerCircuit {
                 _marker: std::marker::PhantomData,
             }),
             _ => panic!("wrong circuit_type for compression layer: {}", circuit_type),
-        }
+        };
+
+        Ok(circuit)
     }
 
     pub fn clone_without_witness(&self) -> Self {
         let circuit_type = self.numeric_circuit_type();
         let vk = self.verification_key();
         Self::from_witness_and_vk(None, vk, circuit_type)
+            .expect("verification key was accepted when the circuit was created")
     }
 }
 
@@ -527,16 +531,17 @@ impl ZkSyncCompressionForWrapperCircuit {
         }
     }
 
+    /// Fails if the verification key of the scheduler doesn't belong to any proof config profile
     pub fn from_witness_and_vk(
         witness: Option<Proof<F, H, EXT>>,
         vk: VerificationKey<F, H>,
         circuit_type: u8,
-    ) -> Self {
-        match circuit_type {
+    ) -> Result<Self, ProfileError> {
+        let circuit = match circuit_type {
             1 => Self::CompressionMode1Circuit(CompressionMode1ForWrapperCircuit {
                 witness,
                 config: CompressionRecursionConfig {
-                    proof_config: recursion_layer_proof_config(),
+                    proof_config: ProofConfigProfile::of_vk(&vk)?.recursion_layer_proof_config(),
                     verification_key: vk,
                     _marker: std::marker::PhantomData,
                 },
@@ -584,13 +589,16 @@ impl ZkSyncCompressionForWrapperCircuit {
                 _marker: std::marker::PhantomData,
             }),
             _ => panic!("wrong circuit_type for compression layer: {}", circuit_type),
-        }
+        };
+
+        Ok(circuit)
     }
 
     pub fn clone_without_witness(&self) -> Self {
         let circuit_type = self.numeric_circuit_type();
         let vk = self.verification_key();
         Self::from_witness_and_vk(None, vk, circuit_type)
+            .expect("verification key was accepted when the circuit was created")
     }
 }
 
@@ -667,3 +675,5 @@ pub type ZkSyncSnarkWrapperVK =
 
 pub type EIP4844VerificationKey =
     VerificationKey<GoldilocksField, GoldilocksPoseidon2Sponge<AbsorptionModeOverwrite>>;
+pub type EIP4844Proof =
+    Proof<GoldilocksField, GoldilocksPoseidon2Sponge<AbsorptionModeOverwrite>, GoldilocksExt2>;
@@ -203,6 +203,27 @@ impl<T: Clone + std::fmt::Debug + serde::Serialize + serde::de::DeserializeOwned
         }
     }
 
+    pub fn as_inner(&self) -> &T {
+        match self {
+            ZkSyncBaseLayerStorage::MainVM(inner) => inner,
+            ZkSyncBaseLayerStorage::CodeDecommittmentsSorter(inner) => inner,
+            ZkSyncBaseLayerStorage::CodeDecommitter(inner) => inner,
+            ZkSyncBaseLayerStorage::LogDemuxer(inner) => inner,
+            ZkSyncBaseLayerStorage::KeccakRoundFunction(inner) => inner,
+            ZkSyncBaseLayerStorage::Sha256RoundFunction(inner) => inner,
+            ZkSyncBaseLayerStorage::ECRecover(inner) => inner,
+            ZkSyncBaseLayerStorage::RAMPermutation(inner) => inner,
+            ZkSyncBaseLayerStorage::StorageSorter(inner) => inner,
+            ZkSyncBaseLayerStorage::StorageApplication(inner) => inner,
+            ZkSyncBaseLayerStorage::EventsSorter(inner) => inner,
+            ZkSyncBaseLayerStorage::L1MessagesSorter(inner) => inner,
+            ZkSyncBaseLayerStorage::L1MessagesHasher(inner) => inner,
+            ZkSyncBaseLayerStorage::TransientStorageSorter(inner) => inner,
+            ZkSyncBaseLayerStorage::Secp256r1Verify(inner) => inner,
+            ZkSyncBaseLayerStorage::EIP4844Repack(inner) => inner,
+        }
+    }
+
     pub fn from_inner(numeric_type: u8, inner: T) -> Self {
         use crate::zkevm_circuits::scheduler::aux::BaseLayerCircuitType;
 
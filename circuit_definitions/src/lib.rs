@@ -24,6 +24,8 @@ pub use snark_wrapper;
 use snark_wrapper::boojum::field::goldilocks::GoldilocksField;
 
 use crate::boojum::cs::implementations::prover::ProofConfig;
+use crate::boojum::cs::implementations::verifier::VerificationKey;
+use crate::boojum::cs::oracle::TreeHasher;
 
 pub fn base_layer_proof_config() -> ProofConfig {
     ProofConfig {
@@ -45,6 +47,134 @@ pub fn recursion_layer_proof_config() -> ProofConfig {
     }
 }
 
+// Reduced security parameters, for proving the whole stack in tests.
+// LDE factor is already the minimal one and there is no PoW in production, so only the number
+// of FRI queries goes down. Cap size differs from production one to tell the VKs apart.
+pub const TEST_PROFILE_FRI_LDE_FACTOR: usize = 2;
+pub const TEST_PROFILE_CAP_SIZE: usize = 4;
+pub const TEST_PROFILE_SECURITY_BITS: usize = 20;
+
+/// Set of proof parameters, that setup generation, proving and recursive verification use
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub enum ProofConfigProfile {
+    #[default]
+    Production,
+    /// NOT SECURE, only for tests
+    Test,
+}
+
+impl ProofConfigProfile {
+    pub fn base_layer_proof_config(&self) -> ProofConfig {
+        match self {
+            Self::Production => base_layer_proof_config(),
+            Self::Test => test_profile_proof_config(),
+        }
+    }
+
+    pub fn recursion_layer_proof_config(&self) -> ProofConfig {
+        match self {
+            Self::Production => recursion_layer_proof_config(),
+            Self::Test => test_profile_proof_config(),
+        }
+    }
+
+    /// Profile that produced a verification key with the given parameters
+    pub fn from_vk_parameters(fri_lde_factor: usize, cap_size: usize) -> Option<Self> {
+        match (fri_lde_factor, cap_size) {
+            (BASE_LAYER_FRI_LDE_FACTOR, BASE_LAYER_CAP_SIZE) => Some(Self::Production),
+            (TEST_PROFILE_FRI_LDE_FACTOR, TEST_PROFILE_CAP_SIZE) => Some(Self::Test),
+            _ => None,
+        }
+    }
+
+    /// Profile that produced the verification key
+    pub fn of_vk<H: TreeHasher<GoldilocksField>>(
+        vk: &VerificationKey<GoldilocksField, H>,
+    ) -> Result<Self, ProfileError> {
+        let fri_lde_factor = vk.fixed_parameters.fri_lde_factor;
+        let cap_size = vk.fixed_parameters.cap_size;
+        Self::from_vk_parameters(fri_lde_factor, cap_size).ok_or(ProfileError::UnknownParameters {
+            fri_lde_factor,
+            cap_size,
+        })
+    }
+
+    pub fn matches_proof_config(&self, proof_config: &ProofConfig) -> bool {
+        let expected = self.recursion_layer_proof_config();
+        proof_config.fri_lde_factor == expected.fri_lde_factor
+            && proof_config.merkle_tree_cap_size == expected.merkle_tree_cap_size
+            && proof_config.security_level == expected.security_level
+            && proof_config.pow_bits == expected.pow_bits
+    }
+
+    /// Common profile of the verification keys, they can't be generated with different ones
+    pub fn of_vks<'a, H: TreeHasher<GoldilocksField> + 'a>(
+        vks: impl IntoIterator<Item = &'a VerificationKey<GoldilocksField, H>>,
+    ) -> Result<Self, ProfileError> {
+        let mut profile = None;
+        for vk in vks {
+            let vk_profile = Self::of_vk(vk)?;
+            match profile {
+                None => profile = Some(vk_profile),
+                Some(profile) if profile != vk_profile => {
+                    return Err(ProfileError::Mixed(profile, vk_profile))
+                }
+                Some(_) => {}
+            }
+        }
+
+        profile.ok_or(ProfileError::NoVerificationKeys)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProfileError {
+    /// Verification key parameters don't match any profile
+    UnknownParameters {
+        fri_lde_factor: usize,
+        cap_size: usize,
+    },
+    Mixed(ProofConfigProfile, ProofConfigProfile),
+    NoVerificationKeys,
+}
+
+impl std::fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProfileError::UnknownParameters {
+                fri_lde_factor,
+                cap_size,
+            } => write!(
+                f,
+                "verification key with LDE factor {} and cap size {} doesn't match any proof config profile",
+                fri_lde_factor, cap_size
+            ),
+            ProfileError::Mixed(first, second) => write!(
+                f,
+                "verification keys of {:?} and {:?} proof config profiles can't be mixed",
+                first, second
+            ),
+            ProfileError::NoVerificationKeys => {
+                write!(f, "at least one verification key is needed")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProfileError {}
+
+fn test_profile_proof_config() -> ProofConfig {
+    ProofConfig {
+        fri_lde_factor: TEST_PROFILE_FRI_LDE_FACTOR,
+        merkle_tree_cap_size: TEST_PROFILE_CAP_SIZE,
+        fri_folding_schedule: None,
+        security_level: TEST_PROFILE_SECURITY_BITS,
+        pow_bits: 0,
+    }
+}
+
 pub mod aux_definitions;
 pub mod circuit_definitions;
 pub use circuit_encodings as encodings;
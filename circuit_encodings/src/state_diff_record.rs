@@ -51,4 +51,44 @@ impl StateDiffRecord {
 
         encoding
     }
+    pub fn decode(encoding: &[u8; STATE_DIFF_RECORD_BYTE_ENCODING_LEN]) -> Self {
+        let mut offset = 0;
+        let mut end = 0;
+
+        end += 20;
+        let address = Address::from_slice(&encoding[offset..end]);
+        offset = end;
+
+        end += 32;
+        let key = U256::from_big_endian(&encoding[offset..end]);
+        offset = end;
+
+        end += 32;
+        let mut derived_key = [0u8; 32];
+        derived_key.copy_from_slice(&encoding[offset..end]);
+        offset = end;
+
+        end += 8;
+        let enumeration_index = u64::from_be_bytes(encoding[offset..end].try_into().unwrap());
+        offset = end;
+
+        end += 32;
+        let initial_value = U256::from_big_endian(&encoding[offset..end]);
+        offset = end;
+
+        end += 32;
+        let final_value = U256::from_big_endian(&encoding[offset..end]);
+        offset = end;
+
+        debug_assert_eq!(offset, encoding.len());
+
+        Self {
+            address,
+            key,
+            derived_key,
+            enumeration_index,
+            initial_value,
+            final_value,
+        }
+    }
 }
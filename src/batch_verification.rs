@@ -0,0 +1,439 @@
+//! Verifies all the proofs of a batch that are stored in a data source.
+
+use crate::boojum::cs::implementations::pow::NoPow;
+pub use crate::data_source::ProofId;
+use crate::data_source::{BlockDataSource, SetupDataSource, SourceResult};
+use crate::prover_utils::*;
+use crate::zkevm_circuits::scheduler::aux::BaseLayerCircuitType;
+use circuit_definitions::circuit_definitions::aux_layer::*;
+use circuit_definitions::circuit_definitions::base_layer::*;
+use circuit_definitions::circuit_definitions::recursion_layer::*;
+use rayon::prelude::*;
+use std::fmt::Write;
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum VerificationOutcome {
+    Valid,
+    Invalid,
+    /// Placeholder proof from `ProvingMode::Mock`
+    MockProof,
+    CircuitTypeMismatch {
+        proof: u8,
+        vk: u8,
+    },
+    MissingVerificationKey(String),
+    /// The verification key doesn't belong to any proof config profile
+    InvalidVerificationKey(String),
+    /// The proof is listed by the block source but could not be read
+    LoadError(String),
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ProofVerificationResult {
+    pub id: ProofId,
+    pub description: String,
+    pub outcome: VerificationOutcome,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct BatchVerificationReport {
+    pub results: Vec<ProofVerificationResult>,
+}
+
+impl BatchVerificationReport {
+    pub fn all_valid(&self) -> bool {
+        self.results
+            .iter()
+            .all(|el| el.outcome == VerificationOutcome::Valid)
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &ProofVerificationResult> {
+        self.results
+            .iter()
+            .filter(|el| el.outcome != VerificationOutcome::Valid)
+    }
+
+    pub fn to_table(&self) -> String {
+        let mut table = String::new();
+        writeln!(table, "{:<40} {:<40} {}", "proof", "circuit", "result").unwrap();
+        for result in self.results.iter() {
+            writeln!(
+                table,
+                "{:<40} {:<40} {:?}",
+                result.id.to_string(),
+                result.description,
+                result.outcome
+            )
+            .unwrap();
+        }
+
+        table
+    }
+}
+
+// everything that is needed to verify a single proof, loaded from the sources upfront
+enum VerificationTask {
+    BaseLayer(ZkSyncBaseLayerProof, ZkSyncBaseLayerVerificationKey),
+    RecursionLayer(
+        ZkSyncRecursionLayerStorageType,
+        ZkSyncRecursionLayerProof,
+        ZkSyncRecursionLayerVerificationKey,
+    ),
+    Compression(
+        ZkSyncCompressionLayerCircuit,
+        ZkSyncCompressionLayerProof,
+        ZkSyncCompressionLayerVerificationKey,
+    ),
+    CompressionForWrapper(
+        ZkSyncCompressionForWrapperCircuit,
+        ZkSyncCompressionForWrapperProof,
+        ZkSyncCompressionForWrapperVerificationKey,
+    ),
+    MissingVerificationKey(String, String),
+    InvalidVerificationKey(String, String),
+    LoadError(String, String),
+}
+
+impl VerificationTask {
+    fn description(&self) -> String {
+        match self {
+            VerificationTask::BaseLayer(proof, _) => proof.short_description().to_string(),
+            VerificationTask::RecursionLayer(_, proof, _) => proof.short_description().to_string(),
+            VerificationTask::Compression(circuit, _, _) => circuit.short_description().to_string(),
+            VerificationTask::CompressionForWrapper(circuit, _, _) => {
+                circuit.short_description().to_string()
+            }
+            VerificationTask::MissingVerificationKey(description, _) => description.clone(),
+            VerificationTask::InvalidVerificationKey(description, _) => description.clone(),
+            VerificationTask::LoadError(description, _) => description.clone(),
+        }
+    }
+
+    fn verify(self) -> VerificationOutcome {
+        fn check_types(proof: u8, vk: u8) -> Result<(), VerificationOutcome> {
+            if proof != vk {
+                return Err(VerificationOutcome::CircuitTypeMismatch { proof, vk });
+            }
+            Ok(())
+        }
+
+        fn outcome(is_mock: bool, is_valid: bool) -> VerificationOutcome {
+            match (is_mock, is_valid) {
+                (true, _) => VerificationOutcome::MockProof,
+                (false, true) => VerificationOutcome::Valid,
+                (false, false) => VerificationOutcome::Invalid,
+            }
+        }
+
+        let result = match self {
+            VerificationTask::BaseLayer(proof, vk) => {
+                check_types(proof.numeric_circuit_type(), vk.numeric_circuit_type()).map(|_| {
+                    let circuit_type = proof.numeric_circuit_type();
+                    let (proof, vk) = (proof.into_inner(), vk.into_inner());
+                    outcome(
+                        is_mock_proof(&proof),
+                        verify_base_layer_proof_for_type::<NoPow>(circuit_type, &proof, &vk),
+                    )
+                })
+            }
+            VerificationTask::RecursionLayer(circuit_type, proof, vk) => {
+                check_types(proof.numeric_circuit_type(), vk.numeric_circuit_type()).map(|_| {
+                    let (proof, vk) = (proof.into_inner(), vk.into_inner());
+                    outcome(
+                        is_mock_proof(&proof),
+                        verify_recursion_layer_proof_for_type::<NoPow>(circuit_type, &proof, &vk),
+                    )
+                })
+            }
+            VerificationTask::Compression(circuit, proof, vk) => {
+                check_types(proof.numeric_circuit_type(), vk.numeric_circuit_type()).map(|_| {
+                    let (proof, vk) = (proof.into_inner(), vk.into_inner());
+                    outcome(
+                        is_mock_proof(&proof),
+                        verify_compression_layer_proof::<NoPow>(&circuit, &proof, &vk),
+                    )
+                })
+            }
+            VerificationTask::CompressionForWrapper(circuit, proof, vk) => {
+                check_types(proof.numeric_circuit_type(), vk.numeric_circuit_type()).map(|_| {
+                    let (proof, vk) = (proof.into_inner(), vk.into_inner());
+                    outcome(
+                        is_mock_proof(&proof),
+                        verify_compression_for_wrapper_proof::<NoPow>(&circuit, &proof, &vk),
+                    )
+                })
+            }
+            VerificationTask::MissingVerificationKey(_, error) => {
+                Err(VerificationOutcome::MissingVerificationKey(error))
+            }
+            VerificationTask::InvalidVerificationKey(_, error) => {
+                Err(VerificationOutcome::InvalidVerificationKey(error))
+            }
+            VerificationTask::LoadError(_, error) => Err(VerificationOutcome::LoadError(error)),
+        };
+
+        match result {
+            Ok(outcome) => outcome,
+            Err(outcome) => outcome,
+        }
+    }
+}
+
+fn task_or_missing_vk<VK>(
+    vk: SourceResult<VK>,
+    description: &str,
+    task: impl FnOnce(VK) -> VerificationTask,
+) -> VerificationTask {
+    match vk {
+        Ok(vk) => task(vk),
+        Err(err) => {
+            VerificationTask::MissingVerificationKey(description.to_string(), err.to_string())
+        }
+    }
+}
+
+fn task_or_load_error<P>(
+    proof: SourceResult<P>,
+    id: ProofId,
+    task: impl FnOnce(P) -> VerificationTask,
+) -> VerificationTask {
+    match proof {
+        Ok(proof) => task(proof),
+        Err(err) => VerificationTask::LoadError(id.to_string(), err.to_string()),
+    }
+}
+
+// compression circuits are built over the verification key of the previous layer
+fn previous_compression_vk<S: SetupDataSource>(
+    setup_source: &S,
+    circuit_type: u8,
+) -> SourceResult<ZkSyncCompressionVerificationKey> {
+    match circuit_type {
+        1 => setup_source
+            .get_recursion_layer_vk(ZkSyncRecursionLayerStorageType::SchedulerCircuit as u8)
+            .map(|vk| vk.into_inner()),
+        circuit_type => setup_source
+            .get_compression_vk(circuit_type - 1)
+            .map(|vk| vk.into_inner()),
+    }
+}
+
+// Returns `None` for the proofs that are not verified here (wrapper and blob proofs)
+fn load_task<S: SetupDataSource, B: BlockDataSource>(
+    setup_source: &S,
+    block_source: &B,
+    id: ProofId,
+) -> Option<VerificationTask> {
+    let task = match id {
+        ProofId::BaseLayer {
+            circuit_type,
+            index,
+        } => task_or_load_error(
+            block_source.get_base_layer_proof(circuit_type, index),
+            id,
+            |proof| {
+                let description = proof.short_description();
+                task_or_missing_vk(
+                    setup_source.get_base_layer_vk(circuit_type),
+                    description,
+                    |vk| VerificationTask::BaseLayer(proof, vk),
+                )
+            },
+        ),
+        ProofId::Leaf {
+            circuit_type,
+            index,
+        } => task_or_load_error(
+            block_source.get_leaf_layer_proof(circuit_type, index),
+            id,
+            |proof| {
+                let recursive_type = base_circuit_type_into_recursive_leaf_circuit_type(
+                    BaseLayerCircuitType::from_numeric_value(
+                        ZkSyncRecursionLayerStorageType::from_leaf_u8_to_basic_u8(circuit_type),
+                    ),
+                );
+                let description = proof.short_description();
+                task_or_missing_vk(
+                    setup_source.get_recursion_layer_vk(circuit_type),
+                    description,
+                    |vk| VerificationTask::RecursionLayer(recursive_type, proof, vk),
+                )
+            },
+        ),
+        ProofId::Node {
+            circuit_type,
+            depth,
+            index,
+        } => task_or_load_error(
+            block_source.get_node_layer_proof(circuit_type, depth, index),
+            id,
+            |proof| {
+                let description = proof.short_description();
+                task_or_missing_vk(
+                    setup_source.get_recursion_layer_node_vk(),
+                    description,
+                    |vk| {
+                        VerificationTask::RecursionLayer(
+                            ZkSyncRecursionLayerStorageType::NodeLayerCircuit,
+                            proof,
+                            vk,
+                        )
+                    },
+                )
+            },
+        ),
+        ProofId::RecursionTip => {
+            task_or_load_error(block_source.get_recursive_tip_proof(), id, |proof| {
+                let description = proof.short_description();
+                task_or_missing_vk(setup_source.get_recursion_tip_vk(), description, |vk| {
+                    VerificationTask::RecursionLayer(
+                        ZkSyncRecursionLayerStorageType::RecursionTipCircuit,
+                        proof,
+                        vk,
+                    )
+                })
+            })
+        }
+        ProofId::Scheduler => task_or_load_error(block_source.get_scheduler_proof(), id, |proof| {
+            let description = proof.short_description();
+            task_or_missing_vk(
+                setup_source.get_recursion_layer_vk(
+                    ZkSyncRecursionLayerStorageType::SchedulerCircuit as u8,
+                ),
+                description,
+                |vk| {
+                    VerificationTask::RecursionLayer(
+                        ZkSyncRecursionLayerStorageType::SchedulerCircuit,
+                        proof,
+                        vk,
+                    )
+                },
+            )
+        }),
+        ProofId::Compression { circuit_type } => task_or_load_error(
+            block_source.get_compression_proof(circuit_type),
+            id,
+            |proof| {
+                let description = proof.short_description();
+                let vks =
+                    previous_compression_vk(setup_source, circuit_type).and_then(|previous_vk| {
+                        setup_source
+                            .get_compression_vk(circuit_type)
+                            .map(|vk| (previous_vk, vk))
+                    });
+                task_or_missing_vk(vks, description, |(previous_vk, vk)| {
+                    match ZkSyncCompressionLayerCircuit::from_witness_and_vk(
+                        None,
+                        previous_vk,
+                        circuit_type,
+                    ) {
+                        Ok(circuit) => VerificationTask::Compression(circuit, proof, vk),
+                        Err(err) => VerificationTask::InvalidVerificationKey(
+                            description.to_string(),
+                            err.to_string(),
+                        ),
+                    }
+                })
+            },
+        ),
+        ProofId::CompressionForWrapper { circuit_type } => task_or_load_error(
+            block_source.get_compression_for_wrapper_proof(circuit_type),
+            id,
+            |proof| {
+                let description = proof.short_description();
+                let vks =
+                    previous_compression_vk(setup_source, circuit_type).and_then(|previous_vk| {
+                        setup_source
+                            .get_compression_for_wrapper_vk(circuit_type)
+                            .map(|vk| (previous_vk, vk))
+                    });
+                task_or_missing_vk(vks, description, |(previous_vk, vk)| {
+                    match ZkSyncCompressionForWrapperCircuit::from_witness_and_vk(
+                        None,
+                        previous_vk,
+                        circuit_type,
+                    ) {
+                        Ok(circuit) => VerificationTask::CompressionForWrapper(circuit, proof, vk),
+                        Err(err) => VerificationTask::InvalidVerificationKey(
+                            description.to_string(),
+                            err.to_string(),
+                        ),
+                    }
+                })
+            },
+        ),
+        ProofId::Wrapper { .. } | ProofId::EIP4844Blob { .. } => return None,
+    };
+
+    Some(task)
+}
+
+/// Verifies every base, leaf, node, recursion tip, scheduler and compression proof
+/// that the block source has. Each proof is loaded, verified and dropped by a worker,
+/// so the whole batch is never held in memory.
+/// Results follow the order of the layers, from base to compression.
+/// Proofs that fail to load are reported with `VerificationOutcome::LoadError`
+pub fn verify_batch_proofs<S: SetupDataSource + Sync, B: BlockDataSource + Sync>(
+    setup_source: &S,
+    block_source: &B,
+) -> SourceResult<BatchVerificationReport> {
+    // Proofs are enumerated with `BlockDataSource::list_proofs`, so gaps in the indexes are not an issue
+    let results = block_source
+        .list_proofs()?
+        .into_par_iter()
+        .filter_map(|id| {
+            let task = load_task(setup_source, block_source, id)?;
+            let description = task.description();
+            let outcome = task.verify();
+            Some(ProofVerificationResult {
+                id,
+                description,
+                outcome,
+            })
+        })
+        .collect();
+
+    Ok(BatchVerificationReport { results })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data_source::in_memory_data_source::InMemoryDataSource;
+    use circuit_definitions::base_layer_proof_config;
+
+    #[test]
+    fn batch_report_over_stored_proofs() {
+        let setup_source = InMemoryDataSource::new();
+        let mut block_source = InMemoryDataSource::new();
+        assert!(verify_batch_proofs(&setup_source, &block_source)
+            .unwrap()
+            .results
+            .is_empty());
+
+        // indexes don't have to be contiguous
+        let circuit_type = BaseLayerCircuitType::VM as u8;
+        for index in [0, 2] {
+            let proof = create_mock_proof::<ZkSyncBaseLayerCircuit>(base_layer_proof_config());
+            block_source
+                .set_base_layer_proof(index, ZkSyncBaseLayerProof::from_inner(circuit_type, proof))
+                .unwrap();
+        }
+
+        let report = verify_batch_proofs(&setup_source, &block_source).unwrap();
+        assert_eq!(report.results.len(), 2);
+        assert_eq!(
+            report.results[1].id,
+            ProofId::BaseLayer {
+                circuit_type,
+                index: 2
+            }
+        );
+        assert!(matches!(
+            report.results[0].outcome,
+            VerificationOutcome::MissingVerificationKey(..)
+        ));
+        assert!(!report.all_valid());
+        assert_eq!(report.failures().count(), 2);
+    }
+}
@@ -12,10 +12,9 @@ use circuit_definitions::{
         },
         ZkSyncUniformCircuitInstance,
     },
-    recursion_layer_proof_config,
     zkevm_circuits::eip_4844::input::ELEMENTS_PER_4844_BLOCK,
     zkevm_circuits::scheduler::aux::BaseLayerCircuitType,
-    RECURSION_LAYER_CAP_SIZE, RECURSION_LAYER_FRI_LDE_FACTOR,
+    ProofConfigProfile,
 };
 
 use crossbeam::atomic::AtomicCell;
@@ -32,17 +31,18 @@ use crate::boojum::{
             setup::FinalizationHintsForProver,
             verifier::VerificationKey,
         },
-        oracle::merkle_tree::MerkleTreeWithCap,
+        oracle::{merkle_tree::MerkleTreeWithCap, TreeHasher},
     },
     worker::Worker,
 };
 
 use crate::data_source::SetupDataSource;
+use crate::witness::recursive_aggregation::RecursionArity;
 use crate::zkevm_circuits::base_structures::vm_state::FULL_SPONGE_QUEUE_STATE_WIDTH;
 
+use crate::boojum::cs::implementations::prover::ProofConfig;
 use circuit_definitions::circuit_definitions::recursion_layer::leaf_layer::*;
 use circuit_definitions::circuit_definitions::recursion_layer::*;
-use circuit_definitions::{BASE_LAYER_CAP_SIZE, BASE_LAYER_FRI_LDE_FACTOR};
 use std::collections::VecDeque;
 
 use crate::prover_utils::*;
@@ -155,10 +155,11 @@ fn get_all_basic_circuits(geometry: &GeometryConfig) -> Vec<ZkSyncBaseLayerCircu
 /// Source must contain the verification keys for basic layer, leaf and node.
 fn get_all_recursive_circuits(
     source: &mut dyn SetupDataSource,
+    arity: RecursionArity,
 ) -> crate::data_source::SourceResult<Vec<ZkSyncRecursiveLayerCircuit>> {
-    let mut result = get_leaf_circuits(source)?;
+    let mut result = get_leaf_circuits(source, arity)?;
 
-    result.push(get_node_circuit(source)?);
+    result.push(get_node_circuit(source, arity)?);
     result.push(get_recursion_tip_circuit(source)?);
     result.push(get_scheduler_circuit(source)?);
     return Ok(result);
@@ -167,6 +168,7 @@ fn get_all_recursive_circuits(
 /// Returns all the leaf circuits.
 fn get_leaf_circuits(
     source: &mut dyn SetupDataSource,
+    arity: RecursionArity,
 ) -> crate::data_source::SourceResult<Vec<ZkSyncRecursiveLayerCircuit>> {
     let mut result = vec![];
 
@@ -193,10 +195,11 @@ fn get_leaf_circuits(
         };
 
         use crate::zkevm_circuits::recursion::leaf_layer::LeafLayerRecursionConfig;
+        let profile = ProofConfigProfile::of_vk(vk.as_inner())?;
         let config = LeafLayerRecursionConfig {
-            proof_config: recursion_layer_proof_config(),
+            proof_config: profile.recursion_layer_proof_config(),
             vk_fixed_parameters: vk.into_inner().fixed_parameters,
-            capacity: RECURSION_ARITY,
+            capacity: arity.leaf(),
             _marker: std::marker::PhantomData,
         };
 
@@ -220,6 +223,7 @@ fn get_leaf_circuits(
 /// Returns the node circuit.
 fn get_node_circuit(
     source: &mut dyn SetupDataSource,
+    arity: RecursionArity,
 ) -> crate::data_source::SourceResult<ZkSyncRecursiveLayerCircuit> {
     use crate::zkevm_circuits::recursion::node_layer::input::*;
     let input = RecursionNodeInput::placeholder_witness();
@@ -230,7 +234,7 @@ fn get_node_circuit(
     use crate::boojum::gadgets::queue::QueueTailState;
     let split_points = vec![
             QueueTailState::<GoldilocksField, FULL_SPONGE_QUEUE_STATE_WIDTH>::placeholder_witness();
-            RECURSION_ARITY - 1
+            arity.node() - 1
         ];
     let witness = RecursionNodeInstanceWitness {
         input,
@@ -241,11 +245,12 @@ fn get_node_circuit(
 
     use crate::zkevm_circuits::recursion::node_layer::NodeLayerRecursionConfig;
     use circuit_definitions::circuit_definitions::recursion_layer::node_layer::ZkSyncNodeLayerRecursiveCircuit;
+    let profile = ProofConfigProfile::of_vk(&vk.clone().into_inner())?;
     let config = NodeLayerRecursionConfig {
-        proof_config: recursion_layer_proof_config(),
+        proof_config: profile.recursion_layer_proof_config(),
         vk_fixed_parameters: vk.into_inner().fixed_parameters,
-        leaf_layer_capacity: RECURSION_ARITY,
-        node_layer_capacity: RECURSION_ARITY,
+        leaf_layer_capacity: arity.leaf(),
+        node_layer_capacity: arity.node(),
         _marker: std::marker::PhantomData,
     };
     let circuit = ZkSyncNodeLayerRecursiveCircuit {
@@ -276,7 +281,7 @@ fn get_recursion_tip_circuit(
     use circuit_definitions::circuit_definitions::recursion_layer::recursion_tip::*;
 
     let config = RecursionTipConfig {
-        proof_config: recursion_layer_proof_config(),
+        proof_config: ProofConfigProfile::of_vk(&vk)?.recursion_layer_proof_config(),
         vk_fixed_parameters: vk.fixed_parameters,
         _marker: std::marker::PhantomData,
     };
@@ -314,8 +319,10 @@ fn get_scheduler_circuit(
         .try_into()
         .unwrap();
 
+    // scheduler verifies the recursion tip, but also commits to the node verification key
+    let profile = ProofConfigProfile::of_vks([&node_vk, &recursion_tip_vk])?;
     let config = SchedulerConfig {
-        proof_config: recursion_layer_proof_config(),
+        proof_config: profile.recursion_layer_proof_config(),
         leaf_layer_parameters: leaf_layer_params,
         node_layer_vk: node_vk,
         recursion_tip_vk: recursion_tip_vk.clone(),
@@ -340,17 +347,114 @@ fn get_scheduler_circuit(
 }
 
 /// Contains all the information that prover needs to setup and verify the given circuit.
-pub struct CircuitSetupData {
+/// Tree hasher differs only for the compression for wrapper circuits
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(bound = "H::Output: serde::Serialize + serde::de::DeserializeOwned")]
+pub struct CircuitSetupData<
+    H: TreeHasher<GoldilocksField> = GoldilocksPoseidon2Sponge<AbsorptionModeOverwrite>,
+> {
     pub setup_base: SetupBaseStorage<GoldilocksField, GoldilocksField>,
     pub setup: SetupStorage<GoldilocksField, GoldilocksField>,
-    pub vk: VerificationKey<GoldilocksField, GoldilocksPoseidon2Sponge<AbsorptionModeOverwrite>>,
-    pub setup_tree:
-        MerkleTreeWithCap<GoldilocksField, GoldilocksPoseidon2Sponge<AbsorptionModeOverwrite>>,
+    pub vk: VerificationKey<GoldilocksField, H>,
+    pub setup_tree: MerkleTreeWithCap<GoldilocksField, H>,
     pub vars_hint: DenseVariablesCopyHint,
     pub wits_hint: DenseWitnessCopyHint,
     pub finalization_hint: FinalizationHintsForProver,
 }
 
+pub type CompressionForWrapperSetupData = CircuitSetupData<TreeHasherForWrapper>;
+
+/// Proof config of the proofs that the recursive circuit verifies.
+/// The circuit itself is set up and proven with the same one
+pub fn recursive_circuit_proof_config(circuit: &ZkSyncRecursiveLayerCircuit) -> ProofConfig {
+    let proof_config = match circuit {
+        ZkSyncRecursiveLayerCircuit::SchedulerCircuit(inner) => &inner.config.proof_config,
+        ZkSyncRecursiveLayerCircuit::NodeLayerCircuit(inner) => &inner.config.proof_config,
+        ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForMainVM(inner)
+        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForCodeDecommittmentsSorter(inner)
+        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForCodeDecommitter(inner)
+        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForLogDemuxer(inner)
+        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForKeccakRoundFunction(inner)
+        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForSha256RoundFunction(inner)
+        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForECRecover(inner)
+        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForRAMPermutation(inner)
+        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForStorageSorter(inner)
+        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForStorageApplication(inner)
+        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForEventsSorter(inner)
+        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForL1MessagesSorter(inner)
+        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForL1MessagesHasher(inner)
+        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForTransientStorageSorter(inner)
+        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForSecp256r1Verify(inner)
+        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForEIP4844Repack(inner) => {
+            &inner.config.proof_config
+        }
+        ZkSyncRecursiveLayerCircuit::RecursionTipCircuit(inner) => &inner.config.proof_config,
+    };
+
+    proof_config.clone()
+}
+
+fn recursive_circuit_profile(circuit: &ZkSyncRecursiveLayerCircuit) -> ProofConfigProfile {
+    let proof_config = recursive_circuit_proof_config(circuit);
+    ProofConfigProfile::from_vk_parameters(
+        proof_config.fri_lde_factor,
+        proof_config.merkle_tree_cap_size,
+    )
+    .expect("recursive circuit must be created for one of the profiles")
+}
+
+fn create_recursive_layer_setup_data_for_profile(
+    circuit: ZkSyncRecursiveLayerCircuit,
+    worker: &Worker,
+) -> (
+    SetupBaseStorage<GoldilocksField, GoldilocksField>,
+    SetupStorage<GoldilocksField, GoldilocksField>,
+    VerificationKey<GoldilocksField, GoldilocksPoseidon2Sponge<AbsorptionModeOverwrite>>,
+    MerkleTreeWithCap<GoldilocksField, GoldilocksPoseidon2Sponge<AbsorptionModeOverwrite>>,
+    DenseVariablesCopyHint,
+    DenseWitnessCopyHint,
+    FinalizationHintsForProver,
+) {
+    let proof_config = recursive_circuit_proof_config(&circuit);
+    create_recursive_layer_setup_data(
+        circuit,
+        worker,
+        proof_config.fri_lde_factor,
+        proof_config.merkle_tree_cap_size,
+    )
+}
+
+/// Parameters of setup and verification key generation
+#[derive(Clone)]
+pub struct SetupGenerationOptions {
+    /// Leaf and node circuits aggregate this many proofs. Blocks must then be aggregated with the same arity
+    pub arity: RecursionArity,
+    /// Proof config profile of the base layer. Recursive layers follow the profile of the base layer
+    /// keys in the source, and it must match this one
+    pub profile: ProofConfigProfile,
+    /// Number of setups synthesized at the same time
+    pub max_concurrency: usize,
+    /// Base layer circuits are sized by this geometry.
+    /// Recursive circuits only depend on the base layer VKs in the source, so keys for different
+    /// geometries should be kept in different sources.
+    pub geometry: GeometryConfig,
+    /// Also write the generated setup data to the source, so that provers can read it
+    /// instead of synthesizing the circuit again
+    pub store_setup_data: bool,
+}
+
+impl Default for SetupGenerationOptions {
+    fn default() -> Self {
+        Self {
+            arity: RecursionArity::default(),
+            profile: ProofConfigProfile::Production,
+            max_concurrency: DEFAULT_VK_GENERATION_CONCURRENCY,
+            geometry: crate::geometry_config::get_geometry_config(),
+            store_setup_data: false,
+        }
+    }
+}
+
 /// Generate verification, and setup keys for a given circuit type from a base layer.
 /// If generating the setup data for recursion layers, the 'source' must have verification keys for basic circuits, leaf and node.
 pub fn generate_circuit_setup_data(
@@ -358,12 +462,27 @@ pub fn generate_circuit_setup_data(
     circuit_type: u8,
     source: &mut dyn SetupDataSource,
 ) -> crate::data_source::SourceResult<CircuitSetupData> {
-    let geometry = crate::geometry_config::get_geometry_config();
+    generate_circuit_setup_data_with_options(
+        is_base_layer,
+        circuit_type,
+        source,
+        &SetupGenerationOptions::default(),
+    )
+}
+
+/// Same as `generate_circuit_setup_data`, but with the given arity, profile and geometry.
+/// The result is also written to the source if `store_setup_data` is set
+pub fn generate_circuit_setup_data_with_options(
+    is_base_layer: bool,
+    circuit_type: u8,
+    source: &mut dyn SetupDataSource,
+    options: &SetupGenerationOptions,
+) -> crate::data_source::SourceResult<CircuitSetupData> {
     let worker = Worker::new();
 
     let (setup_base, setup, vk, setup_tree, vars_hint, wits_hint, finalization_hint) =
         if is_base_layer {
-            let circuit = get_all_basic_circuits(&geometry)
+            let circuit = get_all_basic_circuits(&options.geometry)
                 .iter()
                 .find(|circuit| circuit.numeric_circuit_type() == circuit_type)
                 .expect(&format!(
@@ -372,14 +491,15 @@ pub fn generate_circuit_setup_data(
                 ))
                 .clone();
 
+            let proof_config = options.profile.base_layer_proof_config();
             create_base_layer_setup_data(
                 circuit,
                 &worker,
-                BASE_LAYER_FRI_LDE_FACTOR,
-                BASE_LAYER_CAP_SIZE,
+                proof_config.fri_lde_factor,
+                proof_config.merkle_tree_cap_size,
             )
         } else {
-            let circuit = get_all_recursive_circuits(source)?
+            let circuit = get_all_recursive_circuits(source, options.arity)?
                 .iter()
                 .find(|circuit| circuit.numeric_circuit_type() == circuit_type)
                 .expect(&format!(
@@ -388,15 +508,15 @@ pub fn generate_circuit_setup_data(
                 ))
                 .clone();
 
-            create_recursive_layer_setup_data(
-                circuit,
-                &worker,
-                BASE_LAYER_FRI_LDE_FACTOR,
-                BASE_LAYER_CAP_SIZE,
-            )
+            assert_eq!(
+                recursive_circuit_profile(&circuit),
+                options.profile,
+                "verification keys in the source were generated with another proof config profile"
+            );
+            create_recursive_layer_setup_data_for_profile(circuit, &worker)
         };
 
-    Ok(CircuitSetupData {
+    let setup_data = CircuitSetupData {
         setup_base,
         setup,
         vk,
@@ -404,7 +524,16 @@ pub fn generate_circuit_setup_data(
         vars_hint,
         wits_hint,
         finalization_hint,
-    })
+    };
+    if options.store_setup_data {
+        if is_base_layer {
+            source.set_base_layer_setup_data(circuit_type, &setup_data)?;
+        } else {
+            source.set_recursion_layer_setup_data(circuit_type, &setup_data)?;
+        }
+    }
+
+    Ok(setup_data)
 }
 
 /// For backwards compatibility (as zksync-era uses this method).
@@ -415,23 +544,75 @@ pub fn generate_base_layer_vks_and_proofs(
     generate_base_layer_vks(source)
 }
 
+/// Number of setups synthesized at the same time by default.
+/// Every setup for a base layer circuit takes several gigabytes, so by default they are
+/// computed one after another
+pub const DEFAULT_VK_GENERATION_CONCURRENCY: usize = 1;
+
+/// Synthesizes setups for the given circuits with at most `max_concurrency` of them in flight.
+/// Results are returned in the same order as the circuits.
+fn compute_in_parallel<C: Send, R: Send>(
+    circuits: Vec<C>,
+    max_concurrency: usize,
+    compute: impl Fn(C, &Worker) -> R + Sync,
+) -> Vec<R> {
+    use rayon::prelude::*;
+    assert!(max_concurrency > 0, "concurrency limit must be positive");
+
+    // split the cores between the setups that run at the same time
+    let total_threads = std::thread::available_parallelism()
+        .map(|el| el.get())
+        .unwrap_or(1);
+    let threads_per_setup = std::cmp::max(1, total_threads / max_concurrency);
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(max_concurrency)
+        .build()
+        .expect("must create a thread pool");
+    pool.install(|| {
+        circuits
+            .into_par_iter()
+            .map(|circuit| {
+                let worker = Worker::new_with_num_threads(threads_per_setup);
+                compute(circuit, &worker)
+            })
+            .collect()
+    })
+}
+
 /// Generate Verification keys for all base layer circuits.
 pub fn generate_base_layer_vks(
     source: &mut dyn SetupDataSource,
 ) -> crate::data_source::SourceResult<()> {
-    let geometry = crate::geometry_config::get_geometry_config();
-    let worker = Worker::new();
+    generate_base_layer_vks_with_options(source, &SetupGenerationOptions::default())
+}
 
-    for circuit in get_all_basic_circuits(&geometry) {
-        let circuit_type = circuit.numeric_circuit_type();
+/// Same as `generate_base_layer_vks`, but with the given profile, concurrency and geometry.
+/// Keys are written to the source in circuit type order
+pub fn generate_base_layer_vks_with_options(
+    source: &mut dyn SetupDataSource,
+    options: &SetupGenerationOptions,
+) -> crate::data_source::SourceResult<()> {
+    let proof_config = options.profile.base_layer_proof_config();
 
-        let (_, _, vk, _, _, _, finalization_hint) = create_base_layer_setup_data(
-            circuit,
-            &worker,
-            BASE_LAYER_FRI_LDE_FACTOR,
-            BASE_LAYER_CAP_SIZE,
-        );
+    let results = compute_in_parallel(
+        get_all_basic_circuits(&options.geometry),
+        options.max_concurrency,
+        |circuit, worker| {
+            let circuit_type = circuit.numeric_circuit_type();
+            println!("Computing base layer VK for type {:?}", circuit_type);
+
+            let (_, _, vk, _, _, _, finalization_hint) = create_base_layer_setup_data(
+                circuit,
+                worker,
+                proof_config.fri_lde_factor,
+                proof_config.merkle_tree_cap_size,
+            );
+            (circuit_type, vk, finalization_hint)
+        },
+    );
 
+    for (circuit_type, vk, finalization_hint) in results {
         let typed_vk = ZkSyncBaseLayerVerificationKey::from_inner(circuit_type, vk.clone());
         let typed_finalization_hint =
             ZkSyncBaseLayerFinalizationHint::from_inner(circuit_type, finalization_hint.clone());
@@ -453,27 +634,42 @@ pub fn generate_recursive_layer_vks_and_proofs(
 
 pub fn generate_recursive_layer_vks(
     source: &mut dyn SetupDataSource,
+) -> crate::data_source::SourceResult<()> {
+    generate_recursive_layer_vks_with_options(source, &SetupGenerationOptions::default())
+}
+
+/// Same as `generate_recursive_layer_vks`, but with the given arity and concurrency,
+/// up to `max_concurrency` leaf setups are synthesized in parallel.
+/// Node, recursion tip and scheduler depend on the previous layer keys, so they are computed after
+/// all the leaves are written to the source.
+/// Keys follow the profile of the base layer keys in the source, so the profile and geometry
+/// of the options are not used
+pub fn generate_recursive_layer_vks_with_options(
+    source: &mut dyn SetupDataSource,
+    options: &SetupGenerationOptions,
 ) -> crate::data_source::SourceResult<()> {
     // here we rely ONLY on VKs and proofs from the setup, so we keep the geometries and circuits
     // via padding proofs
     let worker = Worker::new();
 
     println!("Computing leaf vks");
-    for circuit in get_leaf_circuits(source)? {
-        println!(
-            "Computing leaf layer VK for type {:?}",
-            circuit.numeric_circuit_type()
-        );
-
-        let numeric_circuit_type = circuit.numeric_circuit_type();
-        let (_setup_base, _setup, vk, _setup_tree, _vars_hint, _wits_hint, finalization_hint) =
-            create_recursive_layer_setup_data(
-                circuit,
-                &worker,
-                RECURSION_LAYER_FRI_LDE_FACTOR,
-                RECURSION_LAYER_CAP_SIZE,
+    let results = compute_in_parallel(
+        get_leaf_circuits(source, options.arity)?,
+        options.max_concurrency,
+        |circuit, worker| {
+            let numeric_circuit_type = circuit.numeric_circuit_type();
+            println!(
+                "Computing leaf layer VK for type {:?}",
+                numeric_circuit_type
             );
 
+            let (_setup_base, _setup, vk, _setup_tree, _vars_hint, _wits_hint, finalization_hint) =
+                create_recursive_layer_setup_data_for_profile(circuit, worker);
+            (numeric_circuit_type, vk, finalization_hint)
+        },
+    );
+
+    for (numeric_circuit_type, vk, finalization_hint) in results {
         let typed_finalization_hint = ZkSyncRecursionLayerFinalizationHint::from_inner(
             numeric_circuit_type,
             finalization_hint.clone(),
@@ -487,15 +683,10 @@ pub fn generate_recursive_layer_vks(
     println!("Computing node vk");
 
     {
-        let circuit = get_node_circuit(source)?;
+        let circuit = get_node_circuit(source, options.arity)?;
 
         let (_setup_base, _setup, vk, _setup_tree, _vars_hint, _wits_hint, finalization_hint) =
-            create_recursive_layer_setup_data(
-                circuit,
-                &worker,
-                RECURSION_LAYER_FRI_LDE_FACTOR,
-                RECURSION_LAYER_CAP_SIZE,
-            );
+            create_recursive_layer_setup_data_for_profile(circuit, &worker);
 
         let typed_finalization_hint =
             ZkSyncRecursionLayerFinalizationHint::NodeLayerCircuit(finalization_hint.clone());
@@ -520,12 +711,7 @@ pub fn generate_recursion_tip_vk(
     let recursion_tip_circuit = get_recursion_tip_circuit(source)?;
 
     let (_setup_base, _setup, vk, _setup_tree, _vars_hint, _wits_hint, finalization_hint) =
-        create_recursive_layer_setup_data(
-            recursion_tip_circuit,
-            &worker,
-            RECURSION_LAYER_FRI_LDE_FACTOR,
-            RECURSION_LAYER_CAP_SIZE,
-        );
+        create_recursive_layer_setup_data_for_profile(recursion_tip_circuit, &worker);
 
     source.set_recursion_tip_vk(ZkSyncRecursionLayerVerificationKey::RecursionTipCircuit(
         vk.clone(),
@@ -543,12 +729,7 @@ pub fn generate_scheduler_vk(
     let scheduler_circuit = get_scheduler_circuit(source)?;
 
     let (_setup_base, _setup, vk, _setup_tree, _vars_hint, _wits_hint, finalization_hint) =
-        create_recursive_layer_setup_data(
-            scheduler_circuit,
-            &worker,
-            RECURSION_LAYER_FRI_LDE_FACTOR,
-            RECURSION_LAYER_CAP_SIZE,
-        );
+        create_recursive_layer_setup_data_for_profile(scheduler_circuit, &worker);
 
     source.set_recursion_layer_vk(ZkSyncRecursionLayerVerificationKey::SchedulerCircuit(
         vk.clone(),
@@ -605,6 +786,33 @@ mod test {
         generate_recursive_layer_vks_and_proofs(&mut source).expect("must compute setup");
     }
 
+    #[test]
+    fn test_compute_in_parallel_keeps_order() {
+        let inputs: Vec<usize> = (0..16).collect();
+        // later inputs finish first
+        let results = compute_in_parallel(inputs.clone(), 4, |el, _| {
+            std::thread::sleep(std::time::Duration::from_millis(5 * (16 - el) as u64));
+            el * 2
+        });
+        assert_eq!(results, inputs.iter().map(|el| el * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_basic_circuits_follow_geometry() {
+        let geometry = crate::toolset::get_testing_geometry_config();
+        let circuits = get_all_basic_circuits(&geometry);
+        assert_eq!(
+            circuits.len(),
+            get_all_basic_circuits(&crate::geometry_config::get_geometry_config()).len()
+        );
+        match &circuits[0] {
+            ZkSyncBaseLayerCircuit::MainVM(inner) => {
+                assert_eq!(*inner.config, geometry.cycles_per_vm_snapshot as usize)
+            }
+            _ => panic!("first basic circuit must be the main VM"),
+        }
+    }
+
     #[ignore = "too slow"]
     #[test]
     fn test_generate_recursion_tip() {
@@ -0,0 +1,1026 @@
+//! In-memory cache over another data source, so that keys, hints and proofs
+//! are not loaded from disk every time they are needed.
+
+use super::{BlockDataSource, ProofId, SetupDataSource, SourceResult, VkId};
+use crate::compute_setups::{CircuitSetupData, CompressionForWrapperSetupData};
+use circuit_definitions::boojum::cs::implementations::setup::FinalizationHintsForProver;
+use circuit_definitions::circuit_definitions::aux_layer::{
+    EIP4844Proof, EIP4844VerificationKey, ZkSyncCompressionForWrapperFinalizationHint,
+    ZkSyncCompressionForWrapperProof, ZkSyncCompressionForWrapperVerificationKey,
+    ZkSyncCompressionLayerFinalizationHint, ZkSyncCompressionLayerProof,
+    ZkSyncCompressionLayerVerificationKey, ZkSyncSnarkWrapperProof, ZkSyncSnarkWrapperSetup,
+    ZkSyncSnarkWrapperVK,
+};
+use circuit_definitions::circuit_definitions::base_layer::{
+    ZkSyncBaseLayerFinalizationHint, ZkSyncBaseLayerProof, ZkSyncBaseLayerVerificationKey,
+};
+use circuit_definitions::circuit_definitions::recursion_layer::{
+    ZkSyncRecursionLayerFinalizationHint, ZkSyncRecursionLayerProof,
+    ZkSyncRecursionLayerStorageType, ZkSyncRecursionLayerVerificationKey,
+};
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CachePolicy {
+    /// Reads are cached, writes go to the backing source and drop the cached value
+    ReadThrough,
+    /// Writes go both to the backing source and to the cache
+    #[default]
+    WriteThrough,
+    /// Writes only go to the cache, the backing source is updated on `flush`,
+    /// which must be called before the cache is dropped
+    WriteBack,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub entries: usize,
+    /// Estimated size of the cached values
+    pub bytes: usize,
+    pub pending_writes: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum CacheKey {
+    Vk(VkId),
+    // hints are keyed the same way as the verification keys
+    FinalizationHint(VkId),
+    WrapperSetup(u8),
+    Proof(ProofId),
+}
+
+type PendingWrite<S> = Box<dyn Fn(&mut S) -> SourceResult<()> + Send>;
+
+struct CacheEntry {
+    value: Box<dyn Any + Send>,
+    size: usize,
+    last_used: u64,
+}
+
+/// Size of the value in the bincode encoding
+fn serialized_size<T: serde::Serialize>(value: &T) -> usize {
+    bincode::serialized_size(value).expect("cached value must be serializable") as usize
+}
+
+#[derive(Default)]
+struct ByteCounter(usize);
+
+impl std::io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Size of the value for the types that are written with their own encoding, like the snark wrapper ones
+fn written_size(write: impl FnOnce(&mut ByteCounter) -> std::io::Result<()>) -> usize {
+    let mut counter = ByteCounter::default();
+    write(&mut counter).expect("writing to memory must not fail");
+    counter.0
+}
+
+struct CacheState<S> {
+    entries: HashMap<CacheKey, CacheEntry>,
+    // writes that are not applied to the backing source yet, in the order they were made
+    pending: Vec<(CacheKey, PendingWrite<S>)>,
+    tick: u64,
+    bytes: usize,
+    hits: usize,
+    misses: usize,
+}
+
+impl<S> CacheState<S> {
+    fn get<T: Clone + 'static>(&mut self, key: &CacheKey) -> Option<T> {
+        self.tick += 1;
+        let entry = self.entries.get_mut(key)?;
+        entry.last_used = self.tick;
+        self.hits += 1;
+
+        Some(
+            entry
+                .value
+                .downcast_ref::<T>()
+                .expect("cached value must have the type of its key")
+                .clone(),
+        )
+    }
+
+    fn is_pending(&self, key: &CacheKey) -> bool {
+        self.pending.iter().any(|(el, _)| el == key)
+    }
+
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.bytes -= entry.size;
+        }
+    }
+
+    fn insert<T: Send + 'static>(
+        &mut self,
+        key: CacheKey,
+        value: T,
+        size: usize,
+        max_bytes: Option<usize>,
+    ) {
+        self.tick += 1;
+        self.remove(&key);
+
+        let Some(max_bytes) = max_bytes else {
+            self.insert_entry(key, value, size);
+            return;
+        };
+        // a value that doesn't fit alone is not cached instead of evicting everything else
+        if size > max_bytes && !self.is_pending(&key) {
+            return;
+        }
+        self.insert_entry(key, value, size);
+
+        // values with pending writes are never evicted, so the bound may be exceeded until `flush`
+        while self.bytes > max_bytes {
+            let evicted = self
+                .entries
+                .iter()
+                .filter(|(key, _)| !self.is_pending(key))
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| *key);
+            match evicted {
+                Some(key) => self.remove(&key),
+                None => break,
+            }
+        }
+    }
+
+    fn insert_entry<T: Send + 'static>(&mut self, key: CacheKey, value: T, size: usize) {
+        self.bytes += size;
+        self.entries.insert(
+            key,
+            CacheEntry {
+                value: Box::new(value),
+                size,
+                last_used: self.tick,
+            },
+        );
+    }
+
+    fn flush_into(&mut self, backing: &mut S) -> SourceResult<()> {
+        while let Some((_, write)) = self.pending.first() {
+            write(backing)?;
+            self.pending.remove(0);
+        }
+
+        Ok(())
+    }
+}
+
+/// Caches the values of the backing source in memory.
+/// Setup data is not cached and always goes to the backing source.
+/// The source can be shared between threads by reference, both traits
+/// are implemented for `&CachedDataSource` as well.
+/// With `CachePolicy::WriteBack` call `flush` before dropping the cache to get write errors,
+/// dropping it only makes a best-effort attempt to apply the pending writes
+pub struct CachedDataSource<S> {
+    backing: RwLock<S>,
+    state: Mutex<CacheState<S>>,
+    policy: CachePolicy,
+    max_bytes: Option<usize>,
+}
+
+impl<S> CachedDataSource<S> {
+    /// `max_bytes` bounds the estimated size of the cached values, least recently used ones are evicted first
+    pub fn new(backing: S, policy: CachePolicy, max_bytes: Option<usize>) -> Self {
+        assert!(
+            max_bytes != Some(0),
+            "cache must be able to hold at least one byte"
+        );
+
+        Self {
+            backing: RwLock::new(backing),
+            state: Mutex::new(CacheState {
+                entries: HashMap::new(),
+                pending: vec![],
+                tick: 0,
+                bytes: 0,
+                hits: 0,
+                misses: 0,
+            }),
+            policy,
+            max_bytes,
+        }
+    }
+
+    pub fn policy(&self) -> CachePolicy {
+        self.policy
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let state = self.state.lock().unwrap();
+        CacheStats {
+            hits: state.hits,
+            misses: state.misses,
+            entries: state.entries.len(),
+            bytes: state.bytes,
+            pending_writes: state.pending.len(),
+        }
+    }
+
+    /// Drops all cached values that don't have pending writes
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        let evicted: Vec<_> = state
+            .entries
+            .keys()
+            .filter(|key| !state.is_pending(key))
+            .copied()
+            .collect();
+        for key in evicted {
+            state.remove(&key);
+        }
+    }
+
+    /// Applies pending writes to the backing source in the order they were made.
+    /// If one of them fails, it and all the following ones are kept.
+    /// Must be called once the writes are done, dropping the cache doesn't report errors
+    pub fn flush(&self) -> SourceResult<()> {
+        let mut backing = self.backing.write().unwrap();
+        self.state.lock().unwrap().flush_into(&mut backing)
+    }
+
+    /// Access to the backing source, pending writes are not visible there
+    pub fn with_backing<R>(&self, f: impl FnOnce(&S) -> R) -> R {
+        f(&self.backing.read().unwrap())
+    }
+
+    fn cached<T: Clone + Send + serde::Serialize + 'static>(
+        &self,
+        key: CacheKey,
+        load: impl FnOnce(&S) -> SourceResult<T>,
+    ) -> SourceResult<T> {
+        self.cached_sized(key, serialized_size, load)
+    }
+
+    fn cached_sized<T: Clone + Send + 'static>(
+        &self,
+        key: CacheKey,
+        size: impl FnOnce(&T) -> usize,
+        load: impl FnOnce(&S) -> SourceResult<T>,
+    ) -> SourceResult<T> {
+        if let Some(value) = self.state.lock().unwrap().get(&key) {
+            return Ok(value);
+        }
+
+        // writers to the backing source are excluded until the loaded value is cached
+        let backing = self.backing.read().unwrap();
+        let value = load(&backing)?;
+        let mut state = self.state.lock().unwrap();
+        // a write-back may have happened in the meantime, that value is newer
+        if let Some(value) = state.get(&key) {
+            return Ok(value);
+        }
+        state.misses += 1;
+        state.insert(key, value.clone(), size(&value), self.max_bytes);
+
+        Ok(value)
+    }
+
+    fn store<T: Clone + Send + serde::Serialize + 'static>(
+        &self,
+        key: CacheKey,
+        value: T,
+        store: impl Fn(&mut S, T) -> SourceResult<()> + Send + 'static,
+    ) -> SourceResult<()>
+    where
+        S: 'static,
+    {
+        self.store_sized(key, value, serialized_size, store)
+    }
+
+    fn store_sized<T: Clone + Send + 'static>(
+        &self,
+        key: CacheKey,
+        value: T,
+        size: impl FnOnce(&T) -> usize,
+        store: impl Fn(&mut S, T) -> SourceResult<()> + Send + 'static,
+    ) -> SourceResult<()>
+    where
+        S: 'static,
+    {
+        match self.policy {
+            CachePolicy::ReadThrough => {
+                let mut backing = self.backing.write().unwrap();
+                store(&mut backing, value)?;
+                self.state.lock().unwrap().remove(&key);
+            }
+            CachePolicy::WriteThrough => {
+                let mut backing = self.backing.write().unwrap();
+                store(&mut backing, value.clone())?;
+                let size = size(&value);
+                self.state
+                    .lock()
+                    .unwrap()
+                    .insert(key, value, size, self.max_bytes);
+            }
+            CachePolicy::WriteBack => {
+                let size = size(&value);
+                let mut state = self.state.lock().unwrap();
+                state.pending.retain(|(el, _)| *el != key);
+                let pending = value.clone();
+                let write: PendingWrite<S> =
+                    Box::new(move |backing: &mut S| store(backing, pending.clone()));
+                state.pending.push((key, write));
+                state.insert(key, value, size, self.max_bytes);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Keys of the given kind that are only written to the cache so far
+    fn pending_keys<T>(&self, filter: impl Fn(&CacheKey) -> Option<T>) -> Vec<T> {
+        self.state
+            .lock()
+            .unwrap()
+            .pending
+            .iter()
+            .filter_map(|(key, _)| filter(key))
+            .collect()
+    }
+}
+
+impl<S> Drop for CachedDataSource<S> {
+    // only a fallback for a missed `flush`, errors can't be returned from here
+    fn drop(&mut self) {
+        let backing = self.backing.get_mut().unwrap_or_else(|el| el.into_inner());
+        let state = self.state.get_mut().unwrap_or_else(|el| el.into_inner());
+        if let Err(err) = state.flush_into(backing) {
+            println!(
+                "Failed to flush {} pending writes of the cached data source: {}",
+                state.pending.len(),
+                err
+            );
+        }
+    }
+}
+
+fn wrapper_setup_size(setup: &ZkSyncSnarkWrapperSetup) -> usize {
+    written_size(|el| setup.clone().into_inner().write(el))
+}
+
+fn wrapper_vk_size(vk: &ZkSyncSnarkWrapperVK) -> usize {
+    written_size(|el| vk.clone().into_inner().write(el))
+}
+
+fn wrapper_proof_size(proof: &ZkSyncSnarkWrapperProof) -> usize {
+    written_size(|el| proof.clone().into_inner().write(el))
+}
+
+fn node_vk_id() -> VkId {
+    VkId::RecursionLayer {
+        circuit_type: ZkSyncRecursionLayerStorageType::NodeLayerCircuit as u8,
+    }
+}
+
+fn recursion_tip_vk_id() -> VkId {
+    VkId::RecursionLayer {
+        circuit_type: ZkSyncRecursionLayerStorageType::RecursionTipCircuit as u8,
+    }
+}
+
+macro_rules! impl_cached_data_source {
+    ([$($generics:tt)*] $source:ty) => {
+        impl<$($generics)*> SetupDataSource for $source
+        where
+            S: SetupDataSource + 'static,
+        {
+            fn get_base_layer_vk(
+                &self,
+                circuit_type: u8,
+            ) -> SourceResult<ZkSyncBaseLayerVerificationKey> {
+                self.cached(CacheKey::Vk(VkId::BaseLayer { circuit_type }), |el| {
+                    el.get_base_layer_vk(circuit_type)
+                })
+            }
+            fn get_base_layer_finalization_hint(
+                &self,
+                circuit_type: u8,
+            ) -> SourceResult<ZkSyncBaseLayerFinalizationHint> {
+                self.cached(
+                    CacheKey::FinalizationHint(VkId::BaseLayer { circuit_type }),
+                    |el| el.get_base_layer_finalization_hint(circuit_type),
+                )
+            }
+            fn get_recursion_layer_vk(
+                &self,
+                circuit_type: u8,
+            ) -> SourceResult<ZkSyncRecursionLayerVerificationKey> {
+                self.cached(
+                    CacheKey::Vk(VkId::RecursionLayer { circuit_type }),
+                    |el| el.get_recursion_layer_vk(circuit_type),
+                )
+            }
+            fn get_recursion_layer_node_vk(
+                &self,
+            ) -> SourceResult<ZkSyncRecursionLayerVerificationKey> {
+                self.cached(CacheKey::Vk(node_vk_id()), |el| {
+                    el.get_recursion_layer_node_vk()
+                })
+            }
+            fn get_recursion_tip_vk(&self) -> SourceResult<ZkSyncRecursionLayerVerificationKey> {
+                self.cached(CacheKey::Vk(recursion_tip_vk_id()), |el| {
+                    el.get_recursion_tip_vk()
+                })
+            }
+            fn get_recursion_layer_finalization_hint(
+                &self,
+                circuit_type: u8,
+            ) -> SourceResult<ZkSyncRecursionLayerFinalizationHint> {
+                self.cached(
+                    CacheKey::FinalizationHint(VkId::RecursionLayer { circuit_type }),
+                    |el| el.get_recursion_layer_finalization_hint(circuit_type),
+                )
+            }
+            fn get_recursion_layer_node_finalization_hint(
+                &self,
+            ) -> SourceResult<ZkSyncRecursionLayerFinalizationHint> {
+                self.cached(CacheKey::FinalizationHint(node_vk_id()), |el| {
+                    el.get_recursion_layer_node_finalization_hint()
+                })
+            }
+            fn get_recursion_tip_finalization_hint(
+                &self,
+            ) -> SourceResult<ZkSyncRecursionLayerFinalizationHint> {
+                self.cached(CacheKey::FinalizationHint(recursion_tip_vk_id()), |el| {
+                    el.get_recursion_tip_finalization_hint()
+                })
+            }
+            fn get_compression_vk(
+                &self,
+                circuit_type: u8,
+            ) -> SourceResult<ZkSyncCompressionLayerVerificationKey> {
+                self.cached(CacheKey::Vk(VkId::Compression { circuit_type }), |el| {
+                    el.get_compression_vk(circuit_type)
+                })
+            }
+            fn get_compression_hint(
+                &self,
+                circuit_type: u8,
+            ) -> SourceResult<ZkSyncCompressionLayerFinalizationHint> {
+                self.cached(
+                    CacheKey::FinalizationHint(VkId::Compression { circuit_type }),
+                    |el| el.get_compression_hint(circuit_type),
+                )
+            }
+            fn get_compression_for_wrapper_vk(
+                &self,
+                circuit_type: u8,
+            ) -> SourceResult<ZkSyncCompressionForWrapperVerificationKey> {
+                self.cached(
+                    CacheKey::Vk(VkId::CompressionForWrapper { circuit_type }),
+                    |el| el.get_compression_for_wrapper_vk(circuit_type),
+                )
+            }
+            fn get_compression_for_wrapper_hint(
+                &self,
+                circuit_type: u8,
+            ) -> SourceResult<ZkSyncCompressionForWrapperFinalizationHint> {
+                self.cached(
+                    CacheKey::FinalizationHint(VkId::CompressionForWrapper { circuit_type }),
+                    |el| el.get_compression_for_wrapper_hint(circuit_type),
+                )
+            }
+            fn get_wrapper_setup(&self, circuit_type: u8) -> SourceResult<ZkSyncSnarkWrapperSetup> {
+                self.cached_sized(
+                    CacheKey::WrapperSetup(circuit_type),
+                    wrapper_setup_size,
+                    |el| el.get_wrapper_setup(circuit_type),
+                )
+            }
+            fn get_wrapper_vk(&self, circuit_type: u8) -> SourceResult<ZkSyncSnarkWrapperVK> {
+                self.cached_sized(
+                    CacheKey::Vk(VkId::Wrapper { circuit_type }),
+                    wrapper_vk_size,
+                    |el| el.get_wrapper_vk(circuit_type),
+                )
+            }
+
+            fn set_base_layer_vk(
+                &mut self,
+                vk: ZkSyncBaseLayerVerificationKey,
+            ) -> SourceResult<()> {
+                let circuit_type = vk.numeric_circuit_type();
+                self.store(
+                    CacheKey::Vk(VkId::BaseLayer { circuit_type }),
+                    vk,
+                    |el, vk| el.set_base_layer_vk(vk),
+                )
+            }
+            fn set_base_layer_finalization_hint(
+                &mut self,
+                hint: ZkSyncBaseLayerFinalizationHint,
+            ) -> SourceResult<()> {
+                let circuit_type = hint.numeric_circuit_type();
+                self.store(
+                    CacheKey::FinalizationHint(VkId::BaseLayer { circuit_type }),
+                    hint,
+                    |el, hint| el.set_base_layer_finalization_hint(hint),
+                )
+            }
+            fn set_recursion_layer_vk(
+                &mut self,
+                vk: ZkSyncRecursionLayerVerificationKey,
+            ) -> SourceResult<()> {
+                let circuit_type = vk.numeric_circuit_type();
+                self.store(
+                    CacheKey::Vk(VkId::RecursionLayer { circuit_type }),
+                    vk,
+                    |el, vk| el.set_recursion_layer_vk(vk),
+                )
+            }
+            fn set_recursion_layer_node_vk(
+                &mut self,
+                vk: ZkSyncRecursionLayerVerificationKey,
+            ) -> SourceResult<()> {
+                self.store(CacheKey::Vk(node_vk_id()), vk, |el, vk| {
+                    el.set_recursion_layer_node_vk(vk)
+                })
+            }
+            fn set_recursion_tip_vk(
+                &mut self,
+                vk: ZkSyncRecursionLayerVerificationKey,
+            ) -> SourceResult<()> {
+                self.store(CacheKey::Vk(recursion_tip_vk_id()), vk, |el, vk| {
+                    el.set_recursion_tip_vk(vk)
+                })
+            }
+            fn set_recursion_layer_finalization_hint(
+                &mut self,
+                hint: ZkSyncRecursionLayerFinalizationHint,
+            ) -> SourceResult<()> {
+                let circuit_type = hint.numeric_circuit_type();
+                self.store(
+                    CacheKey::FinalizationHint(VkId::RecursionLayer { circuit_type }),
+                    hint,
+                    |el, hint| el.set_recursion_layer_finalization_hint(hint),
+                )
+            }
+            fn set_recursion_layer_node_finalization_hint(
+                &mut self,
+                hint: ZkSyncRecursionLayerFinalizationHint,
+            ) -> SourceResult<()> {
+                self.store(CacheKey::FinalizationHint(node_vk_id()), hint, |el, hint| {
+                    el.set_recursion_layer_node_finalization_hint(hint)
+                })
+            }
+            fn set_recursion_tip_finalization_hint(
+                &mut self,
+                hint: ZkSyncRecursionLayerFinalizationHint,
+            ) -> SourceResult<()> {
+                self.store(
+                    CacheKey::FinalizationHint(recursion_tip_vk_id()),
+                    hint,
+                    |el, hint| el.set_recursion_tip_finalization_hint(hint),
+                )
+            }
+            fn set_compression_vk(
+                &mut self,
+                vk: ZkSyncCompressionLayerVerificationKey,
+            ) -> SourceResult<()> {
+                let circuit_type = vk.numeric_circuit_type();
+                self.store(
+                    CacheKey::Vk(VkId::Compression { circuit_type }),
+                    vk,
+                    |el, vk| el.set_compression_vk(vk),
+                )
+            }
+            fn set_compression_hint(
+                &mut self,
+                hint: ZkSyncCompressionLayerFinalizationHint,
+            ) -> SourceResult<()> {
+                let circuit_type = hint.numeric_circuit_type();
+                self.store(
+                    CacheKey::FinalizationHint(VkId::Compression { circuit_type }),
+                    hint,
+                    |el, hint| el.set_compression_hint(hint),
+                )
+            }
+            fn set_compression_for_wrapper_vk(
+                &mut self,
+                vk: ZkSyncCompressionForWrapperVerificationKey,
+            ) -> SourceResult<()> {
+                let circuit_type = vk.numeric_circuit_type();
+                self.store(
+                    CacheKey::Vk(VkId::CompressionForWrapper { circuit_type }),
+                    vk,
+                    |el, vk| el.set_compression_for_wrapper_vk(vk),
+                )
+            }
+            fn set_compression_for_wrapper_hint(
+                &mut self,
+                hint: ZkSyncCompressionForWrapperFinalizationHint,
+            ) -> SourceResult<()> {
+                let circuit_type = hint.numeric_circuit_type();
+                self.store(
+                    CacheKey::FinalizationHint(VkId::CompressionForWrapper { circuit_type }),
+                    hint,
+                    |el, hint| el.set_compression_for_wrapper_hint(hint),
+                )
+            }
+            fn set_wrapper_setup(&mut self, setup: ZkSyncSnarkWrapperSetup) -> SourceResult<()> {
+                let circuit_type = setup.numeric_circuit_type();
+                self.store_sized(
+                    CacheKey::WrapperSetup(circuit_type),
+                    setup,
+                    wrapper_setup_size,
+                    |el, setup| el.set_wrapper_setup(setup),
+                )
+            }
+            fn set_wrapper_vk(&mut self, vk: ZkSyncSnarkWrapperVK) -> SourceResult<()> {
+                let circuit_type = vk.numeric_circuit_type();
+                self.store_sized(
+                    CacheKey::Vk(VkId::Wrapper { circuit_type }),
+                    vk,
+                    wrapper_vk_size,
+                    |el, vk| el.set_wrapper_vk(vk),
+                )
+            }
+            fn get_eip4844_vk(&self) -> SourceResult<EIP4844VerificationKey> {
+                self.cached(CacheKey::Vk(VkId::EIP4844Blob), |el| el.get_eip4844_vk())
+            }
+            fn get_eip4844_finalization_hint(&self) -> SourceResult<FinalizationHintsForProver> {
+                self.cached(CacheKey::FinalizationHint(VkId::EIP4844Blob), |el| {
+                    el.get_eip4844_finalization_hint()
+                })
+            }
+            fn set_eip4844_vk(&mut self, vk: EIP4844VerificationKey) -> SourceResult<()> {
+                self.store(CacheKey::Vk(VkId::EIP4844Blob), vk, |el, vk| {
+                    el.set_eip4844_vk(vk)
+                })
+            }
+            fn set_eip4844_finalization_hint(
+                &mut self,
+                hint: FinalizationHintsForProver,
+            ) -> SourceResult<()> {
+                self.store(
+                    CacheKey::FinalizationHint(VkId::EIP4844Blob),
+                    hint,
+                    |el, hint| el.set_eip4844_finalization_hint(hint),
+                )
+            }
+
+            // setup data is too large to be kept in memory next to the backing source
+            fn get_base_layer_setup_data(&self, circuit_type: u8) -> SourceResult<CircuitSetupData> {
+                self.with_backing(|el| el.get_base_layer_setup_data(circuit_type))
+            }
+            fn get_recursion_layer_setup_data(
+                &self,
+                circuit_type: u8,
+            ) -> SourceResult<CircuitSetupData> {
+                self.with_backing(|el| el.get_recursion_layer_setup_data(circuit_type))
+            }
+            fn get_compression_setup_data(
+                &self,
+                circuit_type: u8,
+            ) -> SourceResult<CircuitSetupData> {
+                self.with_backing(|el| el.get_compression_setup_data(circuit_type))
+            }
+            fn get_compression_for_wrapper_setup_data(
+                &self,
+                circuit_type: u8,
+            ) -> SourceResult<CompressionForWrapperSetupData> {
+                self.with_backing(|el| el.get_compression_for_wrapper_setup_data(circuit_type))
+            }
+
+            fn set_base_layer_setup_data(
+                &mut self,
+                circuit_type: u8,
+                data: &CircuitSetupData,
+            ) -> SourceResult<()> {
+                self.backing
+                    .write()
+                    .unwrap()
+                    .set_base_layer_setup_data(circuit_type, data)
+            }
+            fn set_recursion_layer_setup_data(
+                &mut self,
+                circuit_type: u8,
+                data: &CircuitSetupData,
+            ) -> SourceResult<()> {
+                self.backing
+                    .write()
+                    .unwrap()
+                    .set_recursion_layer_setup_data(circuit_type, data)
+            }
+            fn set_compression_setup_data(
+                &mut self,
+                circuit_type: u8,
+                data: &CircuitSetupData,
+            ) -> SourceResult<()> {
+                self.backing
+                    .write()
+                    .unwrap()
+                    .set_compression_setup_data(circuit_type, data)
+            }
+            fn set_compression_for_wrapper_setup_data(
+                &mut self,
+                circuit_type: u8,
+                data: &CompressionForWrapperSetupData,
+            ) -> SourceResult<()> {
+                self.backing
+                    .write()
+                    .unwrap()
+                    .set_compression_for_wrapper_setup_data(circuit_type, data)
+            }
+
+            fn list_vks(&self) -> SourceResult<Vec<VkId>> {
+                let mut result = self.with_backing(|el| el.list_vks())?;
+                result.extend(self.pending_keys(|key| match key {
+                    CacheKey::Vk(id) => Some(*id),
+                    _ => None,
+                }));
+                result.sort();
+                result.dedup();
+
+                Ok(result)
+            }
+        }
+
+        impl<$($generics)*> BlockDataSource for $source
+        where
+            S: BlockDataSource + 'static,
+        {
+            fn get_base_layer_proof(
+                &self,
+                circuit_type: u8,
+                index: usize,
+            ) -> SourceResult<ZkSyncBaseLayerProof> {
+                self.cached(
+                    CacheKey::Proof(ProofId::BaseLayer {
+                        circuit_type,
+                        index,
+                    }),
+                    |el| el.get_base_layer_proof(circuit_type, index),
+                )
+            }
+            fn get_leaf_layer_proof(
+                &self,
+                circuit_type: u8,
+                index: usize,
+            ) -> SourceResult<ZkSyncRecursionLayerProof> {
+                self.cached(
+                    CacheKey::Proof(ProofId::Leaf {
+                        circuit_type,
+                        index,
+                    }),
+                    |el| el.get_leaf_layer_proof(circuit_type, index),
+                )
+            }
+            fn get_node_layer_proof(
+                &self,
+                circuit_type: u8,
+                step: usize,
+                index: usize,
+            ) -> SourceResult<ZkSyncRecursionLayerProof> {
+                self.cached(
+                    CacheKey::Proof(ProofId::Node {
+                        circuit_type,
+                        depth: step,
+                        index,
+                    }),
+                    |el| el.get_node_layer_proof(circuit_type, step, index),
+                )
+            }
+            fn get_scheduler_proof(&self) -> SourceResult<ZkSyncRecursionLayerProof> {
+                self.cached(CacheKey::Proof(ProofId::Scheduler), |el| {
+                    el.get_scheduler_proof()
+                })
+            }
+            fn get_compression_proof(
+                &self,
+                circuit_type: u8,
+            ) -> SourceResult<ZkSyncCompressionLayerProof> {
+                self.cached(
+                    CacheKey::Proof(ProofId::Compression { circuit_type }),
+                    |el| el.get_compression_proof(circuit_type),
+                )
+            }
+            fn get_compression_for_wrapper_proof(
+                &self,
+                circuit_type: u8,
+            ) -> SourceResult<ZkSyncCompressionForWrapperProof> {
+                self.cached(
+                    CacheKey::Proof(ProofId::CompressionForWrapper { circuit_type }),
+                    |el| el.get_compression_for_wrapper_proof(circuit_type),
+                )
+            }
+            fn get_wrapper_proof(&self, circuit_type: u8) -> SourceResult<ZkSyncSnarkWrapperProof> {
+                self.cached_sized(
+                    CacheKey::Proof(ProofId::Wrapper { circuit_type }),
+                    wrapper_proof_size,
+                    |el| el.get_wrapper_proof(circuit_type),
+                )
+            }
+
+            fn set_base_layer_proof(
+                &mut self,
+                index: usize,
+                proof: ZkSyncBaseLayerProof,
+            ) -> SourceResult<()> {
+                let circuit_type = proof.numeric_circuit_type();
+                self.store(
+                    CacheKey::Proof(ProofId::BaseLayer {
+                        circuit_type,
+                        index,
+                    }),
+                    proof,
+                    move |el, proof| el.set_base_layer_proof(index, proof),
+                )
+            }
+            fn set_leaf_layer_proof(
+                &mut self,
+                index: usize,
+                proof: ZkSyncRecursionLayerProof,
+            ) -> SourceResult<()> {
+                let circuit_type = proof.numeric_circuit_type();
+                self.store(
+                    CacheKey::Proof(ProofId::Leaf {
+                        circuit_type,
+                        index,
+                    }),
+                    proof,
+                    move |el, proof| el.set_leaf_layer_proof(index, proof),
+                )
+            }
+            fn set_node_layer_proof(
+                &mut self,
+                circuit_type: u8,
+                step: usize,
+                index: usize,
+                proof: ZkSyncRecursionLayerProof,
+            ) -> SourceResult<()> {
+                self.store(
+                    CacheKey::Proof(ProofId::Node {
+                        circuit_type,
+                        depth: step,
+                        index,
+                    }),
+                    proof,
+                    move |el, proof| el.set_node_layer_proof(circuit_type, step, index, proof),
+                )
+            }
+            fn set_scheduler_proof(&mut self, proof: ZkSyncRecursionLayerProof) -> SourceResult<()> {
+                self.store(CacheKey::Proof(ProofId::Scheduler), proof, |el, proof| {
+                    el.set_scheduler_proof(proof)
+                })
+            }
+            fn set_compression_proof(
+                &mut self,
+                proof: ZkSyncCompressionLayerProof,
+            ) -> SourceResult<()> {
+                let circuit_type = proof.numeric_circuit_type();
+                self.store(
+                    CacheKey::Proof(ProofId::Compression { circuit_type }),
+                    proof,
+                    |el, proof| el.set_compression_proof(proof),
+                )
+            }
+            fn set_compression_for_wrapper_proof(
+                &mut self,
+                proof: ZkSyncCompressionForWrapperProof,
+            ) -> SourceResult<()> {
+                let circuit_type = proof.numeric_circuit_type();
+                self.store(
+                    CacheKey::Proof(ProofId::CompressionForWrapper { circuit_type }),
+                    proof,
+                    |el, proof| el.set_compression_for_wrapper_proof(proof),
+                )
+            }
+            fn set_wrapper_proof(&mut self, proof: ZkSyncSnarkWrapperProof) -> SourceResult<()> {
+                let circuit_type = proof.numeric_circuit_type();
+                self.store_sized(
+                    CacheKey::Proof(ProofId::Wrapper { circuit_type }),
+                    proof,
+                    wrapper_proof_size,
+                    |el, proof| el.set_wrapper_proof(proof),
+                )
+            }
+
+            fn set_recursive_tip_proof(
+                &mut self,
+                proof: ZkSyncRecursionLayerProof,
+            ) -> SourceResult<()> {
+                self.store(CacheKey::Proof(ProofId::RecursionTip), proof, |el, proof| {
+                    el.set_recursive_tip_proof(proof)
+                })
+            }
+            fn get_recursive_tip_proof(&self) -> SourceResult<ZkSyncRecursionLayerProof> {
+                self.cached(CacheKey::Proof(ProofId::RecursionTip), |el| {
+                    el.get_recursive_tip_proof()
+                })
+            }
+            fn get_eip4844_proof(&self, index: usize) -> SourceResult<EIP4844Proof> {
+                self.cached(CacheKey::Proof(ProofId::EIP4844Blob { index }), |el| {
+                    el.get_eip4844_proof(index)
+                })
+            }
+            fn set_eip4844_proof(&mut self, index: usize, proof: EIP4844Proof) -> SourceResult<()> {
+                self.store(
+                    CacheKey::Proof(ProofId::EIP4844Blob { index }),
+                    proof,
+                    move |el, proof| el.set_eip4844_proof(index, proof),
+                )
+            }
+
+            fn list_proofs(&self) -> SourceResult<Vec<ProofId>> {
+                let mut result = self.with_backing(|el| el.list_proofs())?;
+                result.extend(self.pending_keys(|key| match key {
+                    CacheKey::Proof(id) => Some(*id),
+                    _ => None,
+                }));
+                result.sort();
+                result.dedup();
+
+                Ok(result)
+            }
+        }
+    };
+}
+
+impl_cached_data_source!([S] CachedDataSource<S>);
+impl_cached_data_source!(['a, S] &'a CachedDataSource<S>);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data_source::in_memory_data_source::InMemoryDataSource;
+    use crate::prover_utils::create_mock_proof;
+    use crate::zkevm_circuits::scheduler::aux::BaseLayerCircuitType;
+    use circuit_definitions::base_layer_proof_config;
+    use circuit_definitions::circuit_definitions::base_layer::ZkSyncBaseLayerCircuit;
+
+    fn mock_base_layer_proof(circuit_type: u8) -> ZkSyncBaseLayerProof {
+        let proof = create_mock_proof::<ZkSyncBaseLayerCircuit>(base_layer_proof_config());
+        ZkSyncBaseLayerProof::from_inner(circuit_type, proof)
+    }
+
+    #[test]
+    fn write_back_and_eviction() {
+        let circuit_type = BaseLayerCircuitType::VM as u8;
+        let proof_size = serialized_size(&mock_base_layer_proof(circuit_type));
+        // room for two proofs
+        let cache = CachedDataSource::new(
+            InMemoryDataSource::new(),
+            CachePolicy::WriteBack,
+            Some(2 * proof_size + 1),
+        );
+
+        // provers share the cache by reference
+        std::thread::scope(|scope| {
+            for index in 0..3 {
+                let mut source = &cache;
+                scope.spawn(move || {
+                    source
+                        .set_base_layer_proof(index, mock_base_layer_proof(circuit_type))
+                        .unwrap()
+                });
+            }
+        });
+        // pending values are kept over the bound
+        assert_eq!(cache.stats().entries, 3);
+        assert_eq!(cache.stats().bytes, 3 * proof_size);
+        assert_eq!(cache.stats().pending_writes, 3);
+        assert!(cache.with_backing(|el| el.list_proofs().unwrap().is_empty()));
+        assert_eq!(
+            cache.list_base_layer_proofs(circuit_type).unwrap(),
+            vec![0, 1, 2]
+        );
+        assert!(cache.get_base_layer_proof(circuit_type, 1).is_ok());
+        assert_eq!(cache.stats().hits, 1);
+
+        cache.flush().unwrap();
+        assert_eq!(cache.stats().pending_writes, 0);
+        assert_eq!(
+            cache
+                .with_backing(|el| el.list_base_layer_proofs(circuit_type))
+                .unwrap(),
+            vec![0, 1, 2]
+        );
+
+        // once flushed, least recently used values are evicted
+        cache.clear();
+        for index in 0..3 {
+            cache.get_base_layer_proof(circuit_type, index).unwrap();
+        }
+        let stats = cache.stats();
+        assert_eq!(stats.entries, 2);
+        assert_eq!(stats.bytes, 2 * proof_size);
+        assert_eq!(stats.misses, 3);
+        cache.get_base_layer_proof(circuit_type, 2).unwrap();
+        assert_eq!(cache.stats().hits, stats.hits + 1);
+    }
+}
@@ -1,7 +1,8 @@
-use super::{BlockDataSource, SetupDataSource, SourceResult};
+use super::{BlockDataSource, ProofId, SetupDataSource, SourceResult, VkId};
+use crate::compute_setups::{CircuitSetupData, CompressionForWrapperSetupData};
 use circuit_definitions::boojum::cs::implementations::setup::FinalizationHintsForProver;
 use circuit_definitions::circuit_definitions::aux_layer::{
-    EIP4844VerificationKey, ZkSyncCompressionForWrapperFinalizationHint,
+    EIP4844Proof, EIP4844VerificationKey, ZkSyncCompressionForWrapperFinalizationHint,
     ZkSyncCompressionForWrapperProof, ZkSyncCompressionForWrapperVerificationKey,
     ZkSyncCompressionLayerFinalizationHint, ZkSyncCompressionLayerProof,
     ZkSyncCompressionLayerVerificationKey, ZkSyncSnarkWrapperProof, ZkSyncSnarkWrapperSetup,
@@ -12,7 +13,7 @@ use circuit_definitions::circuit_definitions::base_layer::{
 };
 use circuit_definitions::circuit_definitions::recursion_layer::{
     ZkSyncRecursionLayerFinalizationHint, ZkSyncRecursionLayerProof,
-    ZkSyncRecursionLayerVerificationKey,
+    ZkSyncRecursionLayerStorageType, ZkSyncRecursionLayerVerificationKey,
 };
 use std::collections::HashMap;
 use std::io::{Error, ErrorKind};
@@ -33,6 +34,13 @@ pub struct InMemoryDataSource {
     compression_for_wrapper_hint: HashMap<u8, ZkSyncCompressionForWrapperFinalizationHint>,
     wrapper_setup: HashMap<u8, ZkSyncSnarkWrapperSetup>,
     wrapper_vk: HashMap<u8, ZkSyncSnarkWrapperVK>,
+    eip4844_vk: Option<EIP4844VerificationKey>,
+    eip4844_finalization_hint: Option<FinalizationHintsForProver>,
+    // setup data is kept serialized, it's not cloneable and is mostly moved around as a whole
+    base_layer_setup_data: HashMap<u8, Vec<u8>>,
+    recursion_layer_setup_data: HashMap<u8, Vec<u8>>,
+    compression_setup_data: HashMap<u8, Vec<u8>>,
+    compression_for_wrapper_setup_data: HashMap<u8, Vec<u8>>,
 
     ///data structures required for holding [`BlockDataSource`] result
     base_layer_proofs: HashMap<(u8, usize), ZkSyncBaseLayerProof>,
@@ -43,6 +51,7 @@ pub struct InMemoryDataSource {
     compression_proof: HashMap<u8, ZkSyncCompressionLayerProof>,
     compression_for_wrapper_proof: HashMap<u8, ZkSyncCompressionForWrapperProof>,
     wrapper_proof: HashMap<u8, ZkSyncSnarkWrapperProof>,
+    eip4844_proofs: HashMap<usize, EIP4844Proof>,
 }
 
 impl InMemoryDataSource {
@@ -62,6 +71,12 @@ impl InMemoryDataSource {
             compression_for_wrapper_hint: HashMap::new(),
             wrapper_setup: HashMap::new(),
             wrapper_vk: HashMap::new(),
+            eip4844_vk: None,
+            eip4844_finalization_hint: None,
+            base_layer_setup_data: HashMap::new(),
+            recursion_layer_setup_data: HashMap::new(),
+            compression_setup_data: HashMap::new(),
+            compression_for_wrapper_setup_data: HashMap::new(),
             base_layer_proofs: HashMap::new(),
             leaf_layer_proofs: HashMap::new(),
             node_layer_proofs: HashMap::new(),
@@ -70,17 +85,40 @@ impl InMemoryDataSource {
             compression_proof: HashMap::new(),
             compression_for_wrapper_proof: HashMap::new(),
             wrapper_proof: HashMap::new(),
+            eip4844_proofs: HashMap::new(),
         }
     }
 }
 
+fn get_serialized<T: serde::de::DeserializeOwned>(
+    storage: &HashMap<u8, Vec<u8>>,
+    circuit_type: u8,
+) -> SourceResult<T> {
+    let data = storage.get(&circuit_type).ok_or(Box::new(Error::new(
+        ErrorKind::NotFound,
+        format!("no data for circuit type {}", circuit_type),
+    )))?;
+
+    bincode::deserialize(data).map_err(|el| Box::new(el) as Box<dyn std::error::Error>)
+}
+
+fn set_serialized<T: serde::Serialize>(
+    storage: &mut HashMap<u8, Vec<u8>>,
+    circuit_type: u8,
+    data: &T,
+) -> SourceResult<()> {
+    let data = bincode::serialize(data).map_err(|el| Box::new(el) as Box<dyn std::error::Error>)?;
+    storage.insert(circuit_type, data);
+    Ok(())
+}
+
 impl SetupDataSource for InMemoryDataSource {
     fn get_base_layer_vk(&self, circuit_type: u8) -> SourceResult<ZkSyncBaseLayerVerificationKey> {
         self.base_layer_vk
             .get(&circuit_type)
             .cloned()
             .ok_or(Box::new(Error::new(
-                ErrorKind::Other,
+                ErrorKind::NotFound,
                 format!("no data for circuit type {}", circuit_type),
             )))
     }
@@ -93,7 +131,7 @@ impl SetupDataSource for InMemoryDataSource {
             .get(&circuit_type)
             .cloned()
             .ok_or(Box::new(Error::new(
-                ErrorKind::Other,
+                ErrorKind::NotFound,
                 format!("no data for circuit type {}", circuit_type),
             )))
     }
@@ -106,7 +144,7 @@ impl SetupDataSource for InMemoryDataSource {
             .get(&circuit_type)
             .cloned()
             .ok_or(Box::new(Error::new(
-                ErrorKind::Other,
+                ErrorKind::NotFound,
                 format!("no data for circuit type {}", circuit_type),
             )))
     }
@@ -115,7 +153,7 @@ impl SetupDataSource for InMemoryDataSource {
         self.recursion_layer_node_vk
             .clone()
             .ok_or(Box::new(Error::new(
-                ErrorKind::Other,
+                ErrorKind::NotFound,
                 format!("no data for recursion layer node vk"),
             )))
     }
@@ -128,7 +166,7 @@ impl SetupDataSource for InMemoryDataSource {
             .get(&circuit_type)
             .cloned()
             .ok_or(Box::new(Error::new(
-                ErrorKind::Other,
+                ErrorKind::NotFound,
                 format!("no data for circuit type {}", circuit_type),
             )))
     }
@@ -139,7 +177,7 @@ impl SetupDataSource for InMemoryDataSource {
         self.recursion_layer_node_finalization_hint
             .clone()
             .ok_or(Box::new(Error::new(
-                ErrorKind::Other,
+                ErrorKind::NotFound,
                 format!("no data for recursion layer node vk"),
             )))
     }
@@ -152,7 +190,7 @@ impl SetupDataSource for InMemoryDataSource {
             .get(&circuit_type)
             .cloned()
             .ok_or(Box::new(Error::new(
-                ErrorKind::Other,
+                ErrorKind::NotFound,
                 format!("no data for circuit type {}", circuit_type),
             )))
     }
@@ -165,7 +203,7 @@ impl SetupDataSource for InMemoryDataSource {
             .get(&circuit_type)
             .cloned()
             .ok_or(Box::new(Error::new(
-                ErrorKind::Other,
+                ErrorKind::NotFound,
                 format!("no data for circuit type {}", circuit_type),
             )))
     }
@@ -178,7 +216,7 @@ impl SetupDataSource for InMemoryDataSource {
             .get(&circuit_type)
             .cloned()
             .ok_or(Box::new(Error::new(
-                ErrorKind::Other,
+                ErrorKind::NotFound,
                 format!("no data for circuit type {}", circuit_type),
             )))
     }
@@ -191,7 +229,7 @@ impl SetupDataSource for InMemoryDataSource {
             .get(&circuit_type)
             .cloned()
             .ok_or(Box::new(Error::new(
-                ErrorKind::Other,
+                ErrorKind::NotFound,
                 format!("no data for circuit type {}", circuit_type),
             )))
     }
@@ -201,7 +239,7 @@ impl SetupDataSource for InMemoryDataSource {
             .get(&circuit_type)
             .cloned()
             .ok_or(Box::new(Error::new(
-                ErrorKind::Other,
+                ErrorKind::NotFound,
                 format!("no data for circuit type {}", circuit_type),
             )))
     }
@@ -211,7 +249,7 @@ impl SetupDataSource for InMemoryDataSource {
             .get(&circuit_type)
             .cloned()
             .ok_or(Box::new(Error::new(
-                ErrorKind::Other,
+                ErrorKind::NotFound,
                 format!("no data for circuit type {}", circuit_type),
             )))
     }
@@ -312,7 +350,7 @@ impl SetupDataSource for InMemoryDataSource {
 
     fn get_recursion_tip_vk(&self) -> SourceResult<ZkSyncRecursionLayerVerificationKey> {
         self.recursion_tip_vk.clone().ok_or(Box::new(Error::new(
-            ErrorKind::Other,
+            ErrorKind::NotFound,
             format!("no data for recursion tip vk"),
         )))
     }
@@ -322,7 +360,7 @@ impl SetupDataSource for InMemoryDataSource {
         self.recursion_tip_finalization_hint
             .clone()
             .ok_or(Box::new(Error::new(
-                ErrorKind::Other,
+                ErrorKind::NotFound,
                 format!("no data for recursion tip finalization hint"),
             )))
     }
@@ -340,6 +378,127 @@ impl SetupDataSource for InMemoryDataSource {
         self.recursion_tip_finalization_hint = Some(hint);
         Ok(())
     }
+
+    fn get_eip4844_vk(&self) -> SourceResult<EIP4844VerificationKey> {
+        self.eip4844_vk.clone().ok_or(Box::new(Error::new(
+            ErrorKind::NotFound,
+            format!("no data for eip4844 vk"),
+        )))
+    }
+    fn get_eip4844_finalization_hint(&self) -> SourceResult<FinalizationHintsForProver> {
+        self.eip4844_finalization_hint
+            .clone()
+            .ok_or(Box::new(Error::new(
+                ErrorKind::NotFound,
+                format!("no data for eip4844 finalization hint"),
+            )))
+    }
+    fn set_eip4844_vk(&mut self, vk: EIP4844VerificationKey) -> SourceResult<()> {
+        self.eip4844_vk = Some(vk);
+        Ok(())
+    }
+    fn set_eip4844_finalization_hint(
+        &mut self,
+        hint: FinalizationHintsForProver,
+    ) -> SourceResult<()> {
+        self.eip4844_finalization_hint = Some(hint);
+        Ok(())
+    }
+
+    fn get_base_layer_setup_data(&self, circuit_type: u8) -> SourceResult<CircuitSetupData> {
+        get_serialized(&self.base_layer_setup_data, circuit_type)
+    }
+    fn get_recursion_layer_setup_data(&self, circuit_type: u8) -> SourceResult<CircuitSetupData> {
+        get_serialized(&self.recursion_layer_setup_data, circuit_type)
+    }
+    fn get_compression_setup_data(&self, circuit_type: u8) -> SourceResult<CircuitSetupData> {
+        get_serialized(&self.compression_setup_data, circuit_type)
+    }
+    fn get_compression_for_wrapper_setup_data(
+        &self,
+        circuit_type: u8,
+    ) -> SourceResult<CompressionForWrapperSetupData> {
+        get_serialized(&self.compression_for_wrapper_setup_data, circuit_type)
+    }
+
+    fn set_base_layer_setup_data(
+        &mut self,
+        circuit_type: u8,
+        data: &CircuitSetupData,
+    ) -> SourceResult<()> {
+        set_serialized(&mut self.base_layer_setup_data, circuit_type, data)
+    }
+    fn set_recursion_layer_setup_data(
+        &mut self,
+        circuit_type: u8,
+        data: &CircuitSetupData,
+    ) -> SourceResult<()> {
+        set_serialized(&mut self.recursion_layer_setup_data, circuit_type, data)
+    }
+    fn set_compression_setup_data(
+        &mut self,
+        circuit_type: u8,
+        data: &CircuitSetupData,
+    ) -> SourceResult<()> {
+        set_serialized(&mut self.compression_setup_data, circuit_type, data)
+    }
+    fn set_compression_for_wrapper_setup_data(
+        &mut self,
+        circuit_type: u8,
+        data: &CompressionForWrapperSetupData,
+    ) -> SourceResult<()> {
+        set_serialized(
+            &mut self.compression_for_wrapper_setup_data,
+            circuit_type,
+            data,
+        )
+    }
+
+    fn list_vks(&self) -> SourceResult<Vec<VkId>> {
+        let mut result: Vec<_> = self
+            .base_layer_vk
+            .keys()
+            .map(|&circuit_type| VkId::BaseLayer { circuit_type })
+            .chain(
+                self.recursion_layer_vk
+                    .keys()
+                    .map(|&circuit_type| VkId::RecursionLayer { circuit_type }),
+            )
+            .chain(
+                self.recursion_layer_node_vk
+                    .as_ref()
+                    .map(|_| VkId::RecursionLayer {
+                        circuit_type: ZkSyncRecursionLayerStorageType::NodeLayerCircuit as u8,
+                    }),
+            )
+            .chain(
+                self.recursion_tip_vk
+                    .as_ref()
+                    .map(|_| VkId::RecursionLayer {
+                        circuit_type: ZkSyncRecursionLayerStorageType::RecursionTipCircuit as u8,
+                    }),
+            )
+            .chain(
+                self.compression_vk
+                    .keys()
+                    .map(|&circuit_type| VkId::Compression { circuit_type }),
+            )
+            .chain(
+                self.compression_for_wrapper_vk
+                    .keys()
+                    .map(|&circuit_type| VkId::CompressionForWrapper { circuit_type }),
+            )
+            .chain(
+                self.wrapper_vk
+                    .keys()
+                    .map(|&circuit_type| VkId::Wrapper { circuit_type }),
+            )
+            .chain(self.eip4844_vk.as_ref().map(|_| VkId::EIP4844Blob))
+            .collect();
+        result.sort();
+
+        Ok(result)
+    }
 }
 
 impl BlockDataSource for InMemoryDataSource {
@@ -352,7 +511,7 @@ impl BlockDataSource for InMemoryDataSource {
             .get(&(circuit_type, index))
             .cloned()
             .ok_or(Box::new(Error::new(
-                ErrorKind::Other,
+                ErrorKind::NotFound,
                 format!(
                     "no base layer proof for circuit type {} index {}",
                     circuit_type, index
@@ -369,7 +528,7 @@ impl BlockDataSource for InMemoryDataSource {
             .get(&(circuit_type, index))
             .cloned()
             .ok_or(Box::new(Error::new(
-                ErrorKind::Other,
+                ErrorKind::NotFound,
                 format!(
                     "no leaf layer proof for circuit type {} index {}",
                     circuit_type, index
@@ -387,7 +546,7 @@ impl BlockDataSource for InMemoryDataSource {
             .get(&(circuit_type, step, index))
             .cloned()
             .ok_or(Box::new(Error::new(
-                ErrorKind::Other,
+                ErrorKind::NotFound,
                 format!(
                     "no node layer proof for circuit type {} index {} step {}",
                     circuit_type, index, step
@@ -397,7 +556,7 @@ impl BlockDataSource for InMemoryDataSource {
 
     fn get_scheduler_proof(&self) -> SourceResult<ZkSyncRecursionLayerProof> {
         self.scheduler_proof.clone().ok_or(Box::new(Error::new(
-            ErrorKind::Other,
+            ErrorKind::NotFound,
             format!("no scheduler proof"),
         )))
     }
@@ -407,7 +566,7 @@ impl BlockDataSource for InMemoryDataSource {
             .get(&circuit_type)
             .cloned()
             .ok_or(Box::new(Error::new(
-                ErrorKind::Other,
+                ErrorKind::NotFound,
                 format!("no data for circuit type {}", circuit_type),
             )))
     }
@@ -420,7 +579,7 @@ impl BlockDataSource for InMemoryDataSource {
             .get(&circuit_type)
             .cloned()
             .ok_or(Box::new(Error::new(
-                ErrorKind::Other,
+                ErrorKind::NotFound,
                 format!("no data for circuit type {}", circuit_type),
             )))
     }
@@ -430,7 +589,7 @@ impl BlockDataSource for InMemoryDataSource {
             .get(&circuit_type)
             .cloned()
             .ok_or(Box::new(Error::new(
-                ErrorKind::Other,
+                ErrorKind::NotFound,
                 format!("no data for circuit type {}", circuit_type),
             )))
     }
@@ -500,8 +659,79 @@ impl BlockDataSource for InMemoryDataSource {
 
     fn get_recursive_tip_proof(&self) -> SourceResult<ZkSyncRecursionLayerProof> {
         self.recursion_tip_proof.clone().ok_or(Box::new(Error::new(
-            ErrorKind::Other,
+            ErrorKind::NotFound,
             format!("no recursion tip proof"),
         )))
     }
+
+    fn get_eip4844_proof(&self, index: usize) -> SourceResult<EIP4844Proof> {
+        self.eip4844_proofs
+            .get(&index)
+            .cloned()
+            .ok_or(Box::new(Error::new(
+                ErrorKind::NotFound,
+                format!("no eip4844 proof for blob {}", index),
+            )))
+    }
+    fn set_eip4844_proof(&mut self, index: usize, proof: EIP4844Proof) -> SourceResult<()> {
+        self.eip4844_proofs.insert(index, proof);
+        Ok(())
+    }
+
+    fn list_proofs(&self) -> SourceResult<Vec<ProofId>> {
+        let mut result: Vec<_> = self
+            .base_layer_proofs
+            .keys()
+            .map(|&(circuit_type, index)| ProofId::BaseLayer {
+                circuit_type,
+                index,
+            })
+            .chain(
+                self.leaf_layer_proofs
+                    .keys()
+                    .map(|&(circuit_type, index)| ProofId::Leaf {
+                        circuit_type,
+                        index,
+                    }),
+            )
+            .chain(
+                self.node_layer_proofs
+                    .keys()
+                    .map(|&(circuit_type, depth, index)| ProofId::Node {
+                        circuit_type,
+                        depth,
+                        index,
+                    }),
+            )
+            .chain(
+                self.recursion_tip_proof
+                    .as_ref()
+                    .map(|_| ProofId::RecursionTip),
+            )
+            .chain(self.scheduler_proof.as_ref().map(|_| ProofId::Scheduler))
+            .chain(
+                self.compression_proof
+                    .keys()
+                    .map(|&circuit_type| ProofId::Compression { circuit_type }),
+            )
+            .chain(
+                self.compression_for_wrapper_proof
+                    .keys()
+                    .map(|&circuit_type| ProofId::CompressionForWrapper { circuit_type }),
+            )
+            .chain(
+                self.wrapper_proof
+                    .keys()
+                    .map(|&circuit_type| ProofId::Wrapper { circuit_type }),
+            )
+            .chain(
+                self.eip4844_proofs
+                    .keys()
+                    .map(|&index| ProofId::EIP4844Blob { index }),
+            )
+            .collect();
+        result.sort();
+
+        Ok(result)
+    }
 }
@@ -1,7 +1,11 @@
-use super::{BlockDataSource, SetupDataSource, SourceResult};
+use super::storage_format::{
+    exists, find_file, list_dir, open_verified_file, read_file, read_metadata, write_file,
+    write_file_with_metadata, StorageFormat,
+};
+use super::{BlockDataSource, ProofId, SetupDataSource, SourceResult, VkId};
 use circuit_definitions::boojum::cs::implementations::setup::FinalizationHintsForProver;
 use circuit_definitions::circuit_definitions::aux_layer::{
-    EIP4844VerificationKey, ZkSyncCompressionForWrapperFinalizationHint,
+    EIP4844Proof, EIP4844VerificationKey, ZkSyncCompressionForWrapperFinalizationHint,
     ZkSyncCompressionForWrapperProof, ZkSyncCompressionForWrapperVerificationKey,
     ZkSyncCompressionLayerFinalizationHint, ZkSyncCompressionLayerProof,
     ZkSyncCompressionLayerVerificationKey, ZkSyncSnarkWrapperProof, ZkSyncSnarkWrapperSetup,
@@ -12,7 +16,7 @@ use circuit_definitions::circuit_definitions::base_layer::{
 };
 use circuit_definitions::circuit_definitions::recursion_layer::{
     ZkSyncRecursionLayerFinalizationHint, ZkSyncRecursionLayerProof,
-    ZkSyncRecursionLayerVerificationKey,
+    ZkSyncRecursionLayerStorageType, ZkSyncRecursionLayerVerificationKey,
 };
 use serde::{Deserialize, Serialize};
 
@@ -20,9 +24,12 @@ use crate::snark_wrapper::franklin_crypto::bellman::plonk::better_better_cs::pro
 use crate::snark_wrapper::franklin_crypto::bellman::plonk::better_better_cs::setup::Setup as SnarkSetup;
 use crate::snark_wrapper::franklin_crypto::bellman::plonk::better_better_cs::setup::VerificationKey as SnarkVK;
 
+use crate::compute_setups::{CircuitSetupData, CompressionForWrapperSetupData};
+use crate::data_source::expected_vks;
+use crate::proof_wrapper_utils::WrapperConfig;
 use derivative::*;
+use std::error::Error;
 use std::sync::Arc;
-use std::{error::Error, fs::File};
 
 #[derive(Derivative)]
 #[derivative(Clone, Debug)]
@@ -41,33 +48,162 @@ impl Default for LocalFileDataSource {
 }
 
 impl LocalFileDataSource {
-    fn get_proof<T: for<'de> Deserialize<'de>>(&self, file_name: String) -> SourceResult<T> {
-        let file = File::open(format!("{}/{}.json", self.block_data_location, file_name))
-            .map_err(|el| Box::new(el) as Box<dyn Error>)?;
-        let result = serde_json::from_reader(file).map_err(|el| Box::new(el) as Box<dyn Error>)?;
+    pub fn new(setup_data_location: String, block_data_location: String) -> Self {
+        Self {
+            setup_data_location,
+            block_data_location,
+        }
+    }
 
-        Ok(result)
+    /// Source over the same directories that writes files in the given format
+    pub fn with_format(self, format: StorageFormat) -> ConfiguredLocalFileDataSource {
+        ConfiguredLocalFileDataSource {
+            setup_data_location: self.setup_data_location,
+            block_data_location: self.block_data_location,
+            format,
+            protocol_version: None,
+            check_protocol_version: false,
+        }
     }
 
-    fn set_proof<T: Serialize>(&self, file_name: String, proof: T) -> SourceResult<()> {
-        let file = File::create(format!("{}/{}.json", self.block_data_location, file_name))
+    /// creates folders if missing
+    pub fn create_folders_for_storing_data(&self) {
+        self.configured().create_folders_for_storing_data()
+    }
+
+    // files are written in the default format and without a protocol version
+    fn configured(&self) -> ConfiguredLocalFileDataSource {
+        self.clone().into()
+    }
+
+    pub fn write_pretty<T: Serialize>(filepath: String, proof: T) -> SourceResult<()> {
+        std::fs::write(&filepath, serde_json::to_string_pretty(&proof).unwrap())
             .map_err(|el| Box::new(el) as Box<dyn Error>)?;
-        serde_json::to_writer(file, &proof).map_err(|el| Box::new(el) as Box<dyn Error>)?;
         Ok(())
     }
+}
 
-    fn get_setup_data<T: for<'de> Deserialize<'de>>(&self, file_name: String) -> SourceResult<T> {
-        let file = File::open(format!("{}/{}.json", self.setup_data_location, file_name))
-            .map_err(|el| Box::new(el) as Box<dyn Error>)?;
-        let result = serde_json::from_reader(file).map_err(|el| Box::new(el) as Box<dyn Error>)?;
+impl From<LocalFileDataSource> for ConfiguredLocalFileDataSource {
+    fn from(source: LocalFileDataSource) -> Self {
+        source.with_format(StorageFormat::default())
+    }
+}
 
-        Ok(result)
+/// `LocalFileDataSource` with a storage format and a protocol version for the written files.
+/// Files in any format are accepted on read
+#[derive(Clone, Debug)]
+pub struct ConfiguredLocalFileDataSource {
+    pub setup_data_location: String,
+    pub block_data_location: String,
+    format: StorageFormat,
+    protocol_version: Option<String>,
+    check_protocol_version: bool,
+}
+
+impl ConfiguredLocalFileDataSource {
+    /// Records the version in the metadata of the written files.
+    /// Files are written without a version unless it is given
+    pub fn with_protocol_version(mut self, protocol_version: &str) -> Self {
+        self.protocol_version = Some(protocol_version.to_string());
+        self
+    }
+
+    /// Rejects setup data whose metadata has another protocol version than the written one
+    pub fn with_protocol_version_check(mut self) -> Self {
+        self.check_protocol_version = true;
+        self
+    }
+
+    pub fn format(&self) -> StorageFormat {
+        self.format
+    }
+
+    fn storage_format(&self) -> StorageFormat {
+        self.format
+    }
+
+    fn written_protocol_version(&self) -> Option<&str> {
+        self.protocol_version.as_deref()
+    }
+
+    fn checks_protocol_version(&self) -> bool {
+        self.check_protocol_version
+    }
+
+    // missing files are left for the read itself to report
+    fn verify_protocol_version(&self, path: &str, format: StorageFormat) -> SourceResult<()> {
+        if !self.checks_protocol_version() {
+            return Ok(());
+        }
+        let Some(file_path) = find_file(path, format) else {
+            return Ok(());
+        };
+
+        let found = read_metadata(&file_path)?.and_then(|el| el.protocol_version);
+        if found.as_deref() == self.written_protocol_version() {
+            return Ok(());
+        }
+        Err(Box::new(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "{} belongs to protocol version {}, expected {}",
+                file_path,
+                found.as_deref().unwrap_or("unknown"),
+                self.written_protocol_version().unwrap_or("unknown")
+            ),
+        )))
+    }
+
+    fn get_proof<T: for<'de> Deserialize<'de>>(&self, file_name: String) -> SourceResult<T> {
+        read_file(
+            &format!("{}/{}", self.block_data_location, file_name),
+            self.storage_format(),
+        )
+    }
+
+    fn set_proof<T: Serialize>(&self, file_name: String, proof: T) -> SourceResult<()> {
+        write_file(
+            &format!("{}/{}", self.block_data_location, file_name),
+            self.storage_format(),
+            &proof,
+            false,
+            self.written_protocol_version(),
+        )
+    }
+
+    fn get_setup_data<T: for<'de> Deserialize<'de>>(&self, file_name: String) -> SourceResult<T> {
+        let path = format!("{}/{}", self.setup_data_location, file_name);
+        self.verify_protocol_version(&path, self.storage_format())?;
+        read_file(&path, self.storage_format())
     }
 
     fn set_setup_data<T: Serialize>(&self, file_name: String, data: T) -> SourceResult<()> {
-        LocalFileDataSource::write_pretty(
-            format!("{}/{}.json", self.setup_data_location, file_name),
+        write_file(
+            &format!("{}/{}", self.setup_data_location, file_name),
+            self.storage_format(),
+            &data,
+            true,
+            self.written_protocol_version(),
+        )
+    }
+
+    // setup data is too big for JSON
+    fn get_binary_setup_data<T: for<'de> Deserialize<'de>>(
+        &self,
+        file_name: String,
+    ) -> SourceResult<T> {
+        let path = format!("{}/{}", self.setup_data_location, file_name);
+        self.verify_protocol_version(&path, self.storage_format().for_setup_data())?;
+        read_file(&path, self.storage_format().for_setup_data())
+    }
+
+    fn set_binary_setup_data<T: Serialize>(&self, file_name: String, data: &T) -> SourceResult<()> {
+        write_file(
+            &format!("{}/{}", self.setup_data_location, file_name),
+            self.storage_format().for_setup_data(),
             data,
+            false,
+            self.written_protocol_version(),
         )
     }
 
@@ -87,14 +223,17 @@ impl LocalFileDataSource {
             }
         }
     }
-    pub fn write_pretty<T: Serialize>(filepath: String, proof: T) -> SourceResult<()> {
-        std::fs::write(&filepath, serde_json::to_string_pretty(&proof).unwrap())
-            .map_err(|el| Box::new(el) as Box<dyn Error>)?;
-        Ok(())
-    }
 }
 
-impl SetupDataSource for LocalFileDataSource {
+// Parses `{prefix}{n}_{m}...` into the numbers
+fn parse_indexes(name: &str, prefix: &str) -> Option<Vec<usize>> {
+    name.strip_prefix(prefix)?
+        .split('_')
+        .map(|el| el.parse().ok())
+        .collect()
+}
+
+impl SetupDataSource for ConfiguredLocalFileDataSource {
     fn get_base_layer_vk(&self, circuit_type: u8) -> SourceResult<ZkSyncBaseLayerVerificationKey> {
         self.get_setup_data(format!("base_layer/vk_{}", circuit_type))
     }
@@ -162,11 +301,10 @@ impl SetupDataSource for LocalFileDataSource {
         println!("Read wrapper setup from file. Can take a while.");
         let start = std::time::Instant::now();
 
-        let mut file = File::open(format!(
+        let mut file = open_verified_file(&format!(
             "{}/aux_layer/wrapper_setup_{}.setup",
             self.setup_data_location, circuit_type
-        ))
-        .map_err(|el| Box::new(el) as Box<dyn Error>)?;
+        ))?;
 
         let result =
             Arc::new(SnarkSetup::read(&mut file).map_err(|el| Box::new(el) as Box<dyn Error>)?);
@@ -178,11 +316,10 @@ impl SetupDataSource for LocalFileDataSource {
         Ok(result)
     }
     fn get_wrapper_vk(&self, circuit_type: u8) -> SourceResult<ZkSyncSnarkWrapperVK> {
-        let mut file = File::open(format!(
+        let mut file = open_verified_file(&format!(
             "{}/aux_layer/wrapper_vk_{}.key",
             self.setup_data_location, circuit_type
-        ))
-        .map_err(|el| Box::new(el) as Box<dyn Error>)?;
+        ))?;
 
         let result = ZkSyncSnarkWrapperVK::from_inner(
             circuit_type,
@@ -276,16 +413,16 @@ impl SetupDataSource for LocalFileDataSource {
         let start = std::time::Instant::now();
 
         let circuit_type = setup.numeric_circuit_type();
-        let mut file = File::create(format!(
+        let file_path = format!(
             "{}/aux_layer/wrapper_setup_{}.setup",
             self.setup_data_location, circuit_type
-        ))
-        .map_err(|el| Box::new(el) as Box<dyn Error>)?;
-
-        setup
-            .into_inner()
-            .write(&mut file)
-            .map_err(|el| Box::new(el) as Box<dyn Error>)?;
+        );
+        write_file_with_metadata(&file_path, self.written_protocol_version(), |file| {
+            setup
+                .into_inner()
+                .write(file)
+                .map_err(|el| Box::new(el) as Box<dyn Error>)
+        })?;
 
         println!("Wrapper setup written to file. Took {:?}", start.elapsed());
 
@@ -293,17 +430,31 @@ impl SetupDataSource for LocalFileDataSource {
     }
     fn set_wrapper_vk(&mut self, vk: ZkSyncSnarkWrapperVK) -> SourceResult<()> {
         let circuit_type = vk.numeric_circuit_type();
-        let mut file = File::create(format!(
+        let file_path = format!(
             "{}/aux_layer/wrapper_vk_{}.key",
             self.setup_data_location, circuit_type
-        ))
-        .map_err(|el| Box::new(el) as Box<dyn Error>)?;
-
-        vk.into_inner()
-            .write(&mut file)
-            .map_err(|el| Box::new(el) as Box<dyn Error>)?;
+        );
+        write_file_with_metadata(&file_path, self.written_protocol_version(), |file| {
+            vk.into_inner()
+                .write(file)
+                .map_err(|el| Box::new(el) as Box<dyn Error>)
+        })
+    }
 
-        Ok(())
+    fn get_eip4844_vk(&self) -> SourceResult<EIP4844VerificationKey> {
+        self.get_setup_data("base_layer/eip4844_vk".to_string())
+    }
+    fn get_eip4844_finalization_hint(&self) -> SourceResult<FinalizationHintsForProver> {
+        self.get_setup_data("base_layer/eip4844_finalization_hint".to_string())
+    }
+    fn set_eip4844_vk(&mut self, vk: EIP4844VerificationKey) -> SourceResult<()> {
+        self.set_setup_data("base_layer/eip4844_vk".to_string(), vk)
+    }
+    fn set_eip4844_finalization_hint(
+        &mut self,
+        hint: FinalizationHintsForProver,
+    ) -> SourceResult<()> {
+        self.set_setup_data("base_layer/eip4844_finalization_hint".to_string(), hint)
     }
 
     fn get_recursion_tip_vk(&self) -> SourceResult<ZkSyncRecursionLayerVerificationKey> {
@@ -329,9 +480,122 @@ impl SetupDataSource for LocalFileDataSource {
             hint,
         )
     }
+
+    fn get_base_layer_setup_data(&self, circuit_type: u8) -> SourceResult<CircuitSetupData> {
+        self.get_binary_setup_data(format!("base_layer/setup_data_{}", circuit_type))
+    }
+    fn get_recursion_layer_setup_data(&self, circuit_type: u8) -> SourceResult<CircuitSetupData> {
+        self.get_binary_setup_data(format!("recursion_layer/setup_data_{}", circuit_type))
+    }
+    fn get_compression_setup_data(&self, circuit_type: u8) -> SourceResult<CircuitSetupData> {
+        self.get_binary_setup_data(format!("aux_layer/compression_setup_data_{}", circuit_type))
+    }
+    fn get_compression_for_wrapper_setup_data(
+        &self,
+        circuit_type: u8,
+    ) -> SourceResult<CompressionForWrapperSetupData> {
+        self.get_binary_setup_data(format!(
+            "aux_layer/compression_for_wrapper_setup_data_{}",
+            circuit_type
+        ))
+    }
+
+    fn set_base_layer_setup_data(
+        &mut self,
+        circuit_type: u8,
+        data: &CircuitSetupData,
+    ) -> SourceResult<()> {
+        self.set_binary_setup_data(format!("base_layer/setup_data_{}", circuit_type), data)
+    }
+    fn set_recursion_layer_setup_data(
+        &mut self,
+        circuit_type: u8,
+        data: &CircuitSetupData,
+    ) -> SourceResult<()> {
+        self.set_binary_setup_data(format!("recursion_layer/setup_data_{}", circuit_type), data)
+    }
+    fn set_compression_setup_data(
+        &mut self,
+        circuit_type: u8,
+        data: &CircuitSetupData,
+    ) -> SourceResult<()> {
+        self.set_binary_setup_data(
+            format!("aux_layer/compression_setup_data_{}", circuit_type),
+            data,
+        )
+    }
+    fn set_compression_for_wrapper_setup_data(
+        &mut self,
+        circuit_type: u8,
+        data: &CompressionForWrapperSetupData,
+    ) -> SourceResult<()> {
+        self.set_binary_setup_data(
+            format!(
+                "aux_layer/compression_for_wrapper_setup_data_{}",
+                circuit_type
+            ),
+            data,
+        )
+    }
+
+    fn list_vks(&self) -> SourceResult<Vec<VkId>> {
+        let aux_layer_vks = (1..=WrapperConfig::MAX_COMPRESSION_LAYERS).flat_map(|circuit_type| {
+            [
+                VkId::Compression { circuit_type },
+                VkId::CompressionForWrapper { circuit_type },
+                VkId::Wrapper { circuit_type },
+            ]
+        });
+
+        let mut result: Vec<_> = expected_vks(None)
+            .into_iter()
+            .chain(aux_layer_vks)
+            .chain(std::iter::once(VkId::EIP4844Blob))
+            .filter(|id| {
+                let file_name = match *id {
+                    VkId::BaseLayer { circuit_type } => {
+                        format!("base_layer/vk_{}", circuit_type)
+                    }
+                    VkId::RecursionLayer { circuit_type }
+                        if circuit_type
+                            == ZkSyncRecursionLayerStorageType::NodeLayerCircuit as u8 =>
+                    {
+                        "recursion_layer/vk_node".to_string()
+                    }
+                    VkId::RecursionLayer { circuit_type }
+                        if circuit_type
+                            == ZkSyncRecursionLayerStorageType::RecursionTipCircuit as u8 =>
+                    {
+                        "recursion_layer/vk_recursion_tip".to_string()
+                    }
+                    VkId::RecursionLayer { circuit_type } => {
+                        format!("recursion_layer/vk_{}", circuit_type)
+                    }
+                    VkId::Compression { circuit_type } => {
+                        format!("aux_layer/compression_vk_{}", circuit_type)
+                    }
+                    VkId::CompressionForWrapper { circuit_type } => {
+                        format!("aux_layer/compression_for_wrapper_vk_{}", circuit_type)
+                    }
+                    VkId::EIP4844Blob => "base_layer/eip4844_vk".to_string(),
+                    VkId::Wrapper { circuit_type } => {
+                        return std::path::Path::new(&format!(
+                            "{}/aux_layer/wrapper_vk_{}.key",
+                            self.setup_data_location, circuit_type
+                        ))
+                        .exists()
+                    }
+                };
+                exists(&format!("{}/{}", self.setup_data_location, file_name))
+            })
+            .collect();
+        result.sort();
+
+        Ok(result)
+    }
 }
 
-impl BlockDataSource for LocalFileDataSource {
+impl BlockDataSource for ConfiguredLocalFileDataSource {
     fn get_base_layer_proof(
         &self,
         circuit_type: u8,
@@ -382,11 +646,10 @@ impl BlockDataSource for LocalFileDataSource {
         ))
     }
     fn get_wrapper_proof(&self, circuit_type: u8) -> SourceResult<ZkSyncSnarkWrapperProof> {
-        let mut file = File::open(format!(
+        let mut file = open_verified_file(&format!(
             "{}/aux_layer/wrapper_proof_{}.proof",
             self.block_data_location, circuit_type
-        ))
-        .map_err(|el| Box::new(el) as Box<dyn Error>)?;
+        ))?;
 
         let result = ZkSyncSnarkWrapperProof::from_inner(
             circuit_type,
@@ -459,18 +722,16 @@ impl BlockDataSource for LocalFileDataSource {
     }
     fn set_wrapper_proof(&mut self, proof: ZkSyncSnarkWrapperProof) -> SourceResult<()> {
         let circuit_type = proof.numeric_circuit_type();
-        let mut file = File::create(format!(
+        let file_path = format!(
             "{}/aux_layer/wrapper_proof_{}.proof",
             self.block_data_location, circuit_type
-        ))
-        .map_err(|el| Box::new(el) as Box<dyn Error>)?;
-
-        proof
-            .into_inner()
-            .write(&mut file)
-            .map_err(|el| Box::new(el) as Box<dyn Error>)?;
-
-        Ok(())
+        );
+        write_file_with_metadata(&file_path, self.written_protocol_version(), |file| {
+            proof
+                .into_inner()
+                .write(file)
+                .map_err(|el| Box::new(el) as Box<dyn Error>)
+        })
     }
     fn set_recursive_tip_proof(&mut self, proof: ZkSyncRecursionLayerProof) -> SourceResult<()> {
         self.set_proof("recursion_layer/recursive_tip_proof".to_string(), proof)
@@ -479,4 +740,402 @@ impl BlockDataSource for LocalFileDataSource {
     fn get_recursive_tip_proof(&self) -> SourceResult<ZkSyncRecursionLayerProof> {
         self.get_proof("recursion_layer/recursive_tip_proof".to_string())
     }
+
+    fn get_eip4844_proof(&self, index: usize) -> SourceResult<EIP4844Proof> {
+        self.get_proof(format!("base_layer/eip4844_proof_{}", index))
+    }
+    fn set_eip4844_proof(&mut self, index: usize, proof: EIP4844Proof) -> SourceResult<()> {
+        self.set_proof(format!("base_layer/eip4844_proof_{}", index), proof)
+    }
+
+    fn list_proofs(&self) -> SourceResult<Vec<ProofId>> {
+        let mut result = vec![];
+
+        for name in list_dir(&format!("{}/base_layer", self.block_data_location))? {
+            if let Some(&[circuit_type, index]) =
+                parse_indexes(&name, "basic_circuit_proof_").as_deref()
+            {
+                if let Ok(circuit_type) = u8::try_from(circuit_type) {
+                    result.push(ProofId::BaseLayer {
+                        circuit_type,
+                        index,
+                    });
+                }
+            } else if let Some(&[index]) = parse_indexes(&name, "eip4844_proof_").as_deref() {
+                result.push(ProofId::EIP4844Blob { index });
+            }
+        }
+
+        for name in list_dir(&format!("{}/recursion_layer", self.block_data_location))? {
+            if let Some(&[circuit_type, index]) =
+                parse_indexes(&name, "leaf_layer_proof_").as_deref()
+            {
+                if let Ok(circuit_type) = u8::try_from(circuit_type) {
+                    result.push(ProofId::Leaf {
+                        circuit_type,
+                        index,
+                    });
+                }
+            } else if let Some(&[circuit_type, depth, index]) =
+                parse_indexes(&name, "node_layer_proof_").as_deref()
+            {
+                if let Ok(circuit_type) = u8::try_from(circuit_type) {
+                    result.push(ProofId::Node {
+                        circuit_type,
+                        depth,
+                        index,
+                    });
+                }
+            } else if name == "recursive_tip_proof" {
+                result.push(ProofId::RecursionTip);
+            } else if name == "scheduler_proof" {
+                result.push(ProofId::Scheduler);
+            }
+        }
+
+        for name in list_dir(&format!("{}/aux_layer", self.block_data_location))? {
+            if let Some(&[circuit_type]) = parse_indexes(&name, "compression_proof_").as_deref() {
+                if let Ok(circuit_type) = u8::try_from(circuit_type) {
+                    result.push(ProofId::Compression { circuit_type });
+                }
+            } else if let Some(&[circuit_type]) =
+                parse_indexes(&name, "compression_for_wrapper_proof_").as_deref()
+            {
+                if let Ok(circuit_type) = u8::try_from(circuit_type) {
+                    result.push(ProofId::CompressionForWrapper { circuit_type });
+                }
+            }
+        }
+
+        // wrapper proofs are stored in their own format
+        for circuit_type in 1..=WrapperConfig::MAX_COMPRESSION_LAYERS {
+            if std::path::Path::new(&format!(
+                "{}/aux_layer/wrapper_proof_{}.proof",
+                self.block_data_location, circuit_type
+            ))
+            .exists()
+            {
+                result.push(ProofId::Wrapper { circuit_type });
+            }
+        }
+        result.sort();
+
+        Ok(result)
+    }
+}
+
+// reads and writes go through the default configuration
+impl SetupDataSource for LocalFileDataSource {
+    fn get_base_layer_vk(&self, circuit_type: u8) -> SourceResult<ZkSyncBaseLayerVerificationKey> {
+        self.configured().get_base_layer_vk(circuit_type)
+    }
+    fn get_base_layer_finalization_hint(
+        &self,
+        circuit_type: u8,
+    ) -> SourceResult<ZkSyncBaseLayerFinalizationHint> {
+        self.configured()
+            .get_base_layer_finalization_hint(circuit_type)
+    }
+    fn get_recursion_layer_vk(
+        &self,
+        circuit_type: u8,
+    ) -> SourceResult<ZkSyncRecursionLayerVerificationKey> {
+        self.configured().get_recursion_layer_vk(circuit_type)
+    }
+    fn get_recursion_layer_node_vk(&self) -> SourceResult<ZkSyncRecursionLayerVerificationKey> {
+        self.configured().get_recursion_layer_node_vk()
+    }
+    fn get_recursion_layer_finalization_hint(
+        &self,
+        circuit_type: u8,
+    ) -> SourceResult<ZkSyncRecursionLayerFinalizationHint> {
+        self.configured()
+            .get_recursion_layer_finalization_hint(circuit_type)
+    }
+    fn get_recursion_layer_node_finalization_hint(
+        &self,
+    ) -> SourceResult<ZkSyncRecursionLayerFinalizationHint> {
+        self.configured()
+            .get_recursion_layer_node_finalization_hint()
+    }
+    fn get_compression_vk(
+        &self,
+        circuit_type: u8,
+    ) -> SourceResult<ZkSyncCompressionLayerVerificationKey> {
+        self.configured().get_compression_vk(circuit_type)
+    }
+    fn get_compression_hint(
+        &self,
+        circuit_type: u8,
+    ) -> SourceResult<ZkSyncCompressionLayerFinalizationHint> {
+        self.configured().get_compression_hint(circuit_type)
+    }
+    fn get_compression_for_wrapper_vk(
+        &self,
+        circuit_type: u8,
+    ) -> SourceResult<ZkSyncCompressionForWrapperVerificationKey> {
+        self.configured()
+            .get_compression_for_wrapper_vk(circuit_type)
+    }
+    fn get_compression_for_wrapper_hint(
+        &self,
+        circuit_type: u8,
+    ) -> SourceResult<ZkSyncCompressionForWrapperFinalizationHint> {
+        self.configured()
+            .get_compression_for_wrapper_hint(circuit_type)
+    }
+    fn get_wrapper_setup(&self, circuit_type: u8) -> SourceResult<ZkSyncSnarkWrapperSetup> {
+        self.configured().get_wrapper_setup(circuit_type)
+    }
+    fn get_wrapper_vk(&self, circuit_type: u8) -> SourceResult<ZkSyncSnarkWrapperVK> {
+        self.configured().get_wrapper_vk(circuit_type)
+    }
+    fn set_base_layer_vk(&mut self, vk: ZkSyncBaseLayerVerificationKey) -> SourceResult<()> {
+        self.configured().set_base_layer_vk(vk)
+    }
+    fn set_base_layer_finalization_hint(
+        &mut self,
+        hint: ZkSyncBaseLayerFinalizationHint,
+    ) -> SourceResult<()> {
+        self.configured().set_base_layer_finalization_hint(hint)
+    }
+    fn set_recursion_layer_vk(
+        &mut self,
+        vk: ZkSyncRecursionLayerVerificationKey,
+    ) -> SourceResult<()> {
+        self.configured().set_recursion_layer_vk(vk)
+    }
+    fn set_recursion_layer_node_vk(
+        &mut self,
+        vk: ZkSyncRecursionLayerVerificationKey,
+    ) -> SourceResult<()> {
+        self.configured().set_recursion_layer_node_vk(vk)
+    }
+    fn set_recursion_layer_finalization_hint(
+        &mut self,
+        hint: ZkSyncRecursionLayerFinalizationHint,
+    ) -> SourceResult<()> {
+        self.configured()
+            .set_recursion_layer_finalization_hint(hint)
+    }
+    fn set_recursion_layer_node_finalization_hint(
+        &mut self,
+        hint: ZkSyncRecursionLayerFinalizationHint,
+    ) -> SourceResult<()> {
+        self.configured()
+            .set_recursion_layer_node_finalization_hint(hint)
+    }
+    fn set_compression_vk(
+        &mut self,
+        vk: ZkSyncCompressionLayerVerificationKey,
+    ) -> SourceResult<()> {
+        self.configured().set_compression_vk(vk)
+    }
+    fn set_compression_hint(
+        &mut self,
+        hint: ZkSyncCompressionLayerFinalizationHint,
+    ) -> SourceResult<()> {
+        self.configured().set_compression_hint(hint)
+    }
+    fn set_compression_for_wrapper_vk(
+        &mut self,
+        vk: ZkSyncCompressionForWrapperVerificationKey,
+    ) -> SourceResult<()> {
+        self.configured().set_compression_for_wrapper_vk(vk)
+    }
+    fn set_compression_for_wrapper_hint(
+        &mut self,
+        hint: ZkSyncCompressionForWrapperFinalizationHint,
+    ) -> SourceResult<()> {
+        self.configured().set_compression_for_wrapper_hint(hint)
+    }
+    fn set_wrapper_setup(&mut self, setup: ZkSyncSnarkWrapperSetup) -> SourceResult<()> {
+        self.configured().set_wrapper_setup(setup)
+    }
+    fn set_wrapper_vk(&mut self, vk: ZkSyncSnarkWrapperVK) -> SourceResult<()> {
+        self.configured().set_wrapper_vk(vk)
+    }
+    fn get_eip4844_vk(&self) -> SourceResult<EIP4844VerificationKey> {
+        self.configured().get_eip4844_vk()
+    }
+    fn get_eip4844_finalization_hint(&self) -> SourceResult<FinalizationHintsForProver> {
+        self.configured().get_eip4844_finalization_hint()
+    }
+    fn set_eip4844_vk(&mut self, vk: EIP4844VerificationKey) -> SourceResult<()> {
+        self.configured().set_eip4844_vk(vk)
+    }
+    fn set_eip4844_finalization_hint(
+        &mut self,
+        hint: FinalizationHintsForProver,
+    ) -> SourceResult<()> {
+        self.configured().set_eip4844_finalization_hint(hint)
+    }
+    fn get_recursion_tip_vk(&self) -> SourceResult<ZkSyncRecursionLayerVerificationKey> {
+        self.configured().get_recursion_tip_vk()
+    }
+    fn get_recursion_tip_finalization_hint(
+        &self,
+    ) -> SourceResult<ZkSyncRecursionLayerFinalizationHint> {
+        self.configured().get_recursion_tip_finalization_hint()
+    }
+    fn set_recursion_tip_vk(
+        &mut self,
+        vk: ZkSyncRecursionLayerVerificationKey,
+    ) -> SourceResult<()> {
+        self.configured().set_recursion_tip_vk(vk)
+    }
+    fn set_recursion_tip_finalization_hint(
+        &mut self,
+        hint: ZkSyncRecursionLayerFinalizationHint,
+    ) -> SourceResult<()> {
+        self.configured().set_recursion_tip_finalization_hint(hint)
+    }
+    fn get_base_layer_setup_data(&self, circuit_type: u8) -> SourceResult<CircuitSetupData> {
+        self.configured().get_base_layer_setup_data(circuit_type)
+    }
+    fn get_recursion_layer_setup_data(&self, circuit_type: u8) -> SourceResult<CircuitSetupData> {
+        self.configured()
+            .get_recursion_layer_setup_data(circuit_type)
+    }
+    fn get_compression_setup_data(&self, circuit_type: u8) -> SourceResult<CircuitSetupData> {
+        self.configured().get_compression_setup_data(circuit_type)
+    }
+    fn get_compression_for_wrapper_setup_data(
+        &self,
+        circuit_type: u8,
+    ) -> SourceResult<CompressionForWrapperSetupData> {
+        self.configured()
+            .get_compression_for_wrapper_setup_data(circuit_type)
+    }
+    fn set_base_layer_setup_data(
+        &mut self,
+        circuit_type: u8,
+        data: &CircuitSetupData,
+    ) -> SourceResult<()> {
+        self.configured()
+            .set_base_layer_setup_data(circuit_type, data)
+    }
+    fn set_recursion_layer_setup_data(
+        &mut self,
+        circuit_type: u8,
+        data: &CircuitSetupData,
+    ) -> SourceResult<()> {
+        self.configured()
+            .set_recursion_layer_setup_data(circuit_type, data)
+    }
+    fn set_compression_setup_data(
+        &mut self,
+        circuit_type: u8,
+        data: &CircuitSetupData,
+    ) -> SourceResult<()> {
+        self.configured()
+            .set_compression_setup_data(circuit_type, data)
+    }
+    fn set_compression_for_wrapper_setup_data(
+        &mut self,
+        circuit_type: u8,
+        data: &CompressionForWrapperSetupData,
+    ) -> SourceResult<()> {
+        self.configured()
+            .set_compression_for_wrapper_setup_data(circuit_type, data)
+    }
+    fn list_vks(&self) -> SourceResult<Vec<VkId>> {
+        self.configured().list_vks()
+    }
+}
+
+impl BlockDataSource for LocalFileDataSource {
+    fn get_base_layer_proof(
+        &self,
+        circuit_type: u8,
+        index: usize,
+    ) -> SourceResult<ZkSyncBaseLayerProof> {
+        self.configured().get_base_layer_proof(circuit_type, index)
+    }
+    fn get_leaf_layer_proof(
+        &self,
+        circuit_type: u8,
+        index: usize,
+    ) -> SourceResult<ZkSyncRecursionLayerProof> {
+        self.configured().get_leaf_layer_proof(circuit_type, index)
+    }
+    fn get_node_layer_proof(
+        &self,
+        circuit_type: u8,
+        step: usize,
+        index: usize,
+    ) -> SourceResult<ZkSyncRecursionLayerProof> {
+        self.configured()
+            .get_node_layer_proof(circuit_type, step, index)
+    }
+    fn get_scheduler_proof(&self) -> SourceResult<ZkSyncRecursionLayerProof> {
+        self.configured().get_scheduler_proof()
+    }
+    fn get_compression_proof(&self, circuit_type: u8) -> SourceResult<ZkSyncCompressionLayerProof> {
+        self.configured().get_compression_proof(circuit_type)
+    }
+    fn get_compression_for_wrapper_proof(
+        &self,
+        circuit_type: u8,
+    ) -> SourceResult<ZkSyncCompressionForWrapperProof> {
+        self.configured()
+            .get_compression_for_wrapper_proof(circuit_type)
+    }
+    fn get_wrapper_proof(&self, circuit_type: u8) -> SourceResult<ZkSyncSnarkWrapperProof> {
+        self.configured().get_wrapper_proof(circuit_type)
+    }
+    fn set_base_layer_proof(
+        &mut self,
+        index: usize,
+        proof: ZkSyncBaseLayerProof,
+    ) -> SourceResult<()> {
+        self.configured().set_base_layer_proof(index, proof)
+    }
+    fn set_leaf_layer_proof(
+        &mut self,
+        index: usize,
+        proof: ZkSyncRecursionLayerProof,
+    ) -> SourceResult<()> {
+        self.configured().set_leaf_layer_proof(index, proof)
+    }
+    fn set_node_layer_proof(
+        &mut self,
+        circuit_type: u8,
+        step: usize,
+        index: usize,
+        proof: ZkSyncRecursionLayerProof,
+    ) -> SourceResult<()> {
+        self.configured()
+            .set_node_layer_proof(circuit_type, step, index, proof)
+    }
+    fn set_scheduler_proof(&mut self, proof: ZkSyncRecursionLayerProof) -> SourceResult<()> {
+        self.configured().set_scheduler_proof(proof)
+    }
+    fn set_compression_proof(&mut self, proof: ZkSyncCompressionLayerProof) -> SourceResult<()> {
+        self.configured().set_compression_proof(proof)
+    }
+    fn set_compression_for_wrapper_proof(
+        &mut self,
+        proof: ZkSyncCompressionForWrapperProof,
+    ) -> SourceResult<()> {
+        self.configured().set_compression_for_wrapper_proof(proof)
+    }
+    fn set_wrapper_proof(&mut self, proof: ZkSyncSnarkWrapperProof) -> SourceResult<()> {
+        self.configured().set_wrapper_proof(proof)
+    }
+    fn set_recursive_tip_proof(&mut self, proof: ZkSyncRecursionLayerProof) -> SourceResult<()> {
+        self.configured().set_recursive_tip_proof(proof)
+    }
+    fn get_recursive_tip_proof(&self) -> SourceResult<ZkSyncRecursionLayerProof> {
+        self.configured().get_recursive_tip_proof()
+    }
+    fn get_eip4844_proof(&self, index: usize) -> SourceResult<EIP4844Proof> {
+        self.configured().get_eip4844_proof(index)
+    }
+    fn set_eip4844_proof(&mut self, index: usize, proof: EIP4844Proof) -> SourceResult<()> {
+        self.configured().set_eip4844_proof(index, proof)
+    }
+    fn list_proofs(&self) -> SourceResult<Vec<ProofId>> {
+        self.configured().list_proofs()
+    }
 }
@@ -0,0 +1,232 @@
+//! Copying of everything stored in one data source into another, e.g. to change the storage format.
+
+use super::{base_circuit_types, BlockDataSource, ProofId, SetupDataSource, SourceResult};
+use crate::proof_wrapper_utils::WrapperConfig;
+use circuit_definitions::circuit_definitions::recursion_layer::*;
+use std::io::ErrorKind;
+
+/// Whether the error reports a missing item, as opposed to a failed read
+pub(crate) fn is_missing(err: &(dyn std::error::Error + 'static)) -> bool {
+    err.downcast_ref::<std::io::Error>()
+        .map(|el| el.kind() == ErrorKind::NotFound)
+        .unwrap_or(false)
+}
+
+/// Copies the value if it exists in the source, any other read error is propagated.
+/// Returns whether the value was copied
+fn copy_item<T>(
+    value: SourceResult<T>,
+    set: impl FnOnce(T) -> SourceResult<()>,
+) -> SourceResult<bool> {
+    match value {
+        Ok(value) => {
+            set(value)?;
+            Ok(true)
+        }
+        Err(err) if is_missing(err.as_ref()) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Copies all keys, hints and setup data. Returns the number of copied items.
+/// Items are considered missing if the source reports them with `ErrorKind::NotFound`
+pub fn copy_setup_data(
+    from: &dyn SetupDataSource,
+    to: &mut dyn SetupDataSource,
+) -> SourceResult<usize> {
+    let mut copied = 0;
+
+    for circuit_type in base_circuit_types() {
+        copied += copy_item(from.get_base_layer_vk(circuit_type), |el| {
+            to.set_base_layer_vk(el)
+        })? as usize;
+        copied += copy_item(from.get_base_layer_finalization_hint(circuit_type), |el| {
+            to.set_base_layer_finalization_hint(el)
+        })? as usize;
+        copied += copy_item(from.get_base_layer_setup_data(circuit_type), |el| {
+            to.set_base_layer_setup_data(circuit_type, &el)
+        })? as usize;
+    }
+
+    for circuit_type in ZkSyncRecursionLayerStorageType::as_iter_u8() {
+        copied += match circuit_type {
+            a if a == ZkSyncRecursionLayerStorageType::NodeLayerCircuit as u8 => {
+                copy_item(from.get_recursion_layer_node_vk(), |el| {
+                    to.set_recursion_layer_node_vk(el)
+                })? as usize
+                    + copy_item(from.get_recursion_layer_node_finalization_hint(), |el| {
+                        to.set_recursion_layer_node_finalization_hint(el)
+                    })? as usize
+            }
+            a if a == ZkSyncRecursionLayerStorageType::RecursionTipCircuit as u8 => {
+                copy_item(from.get_recursion_tip_vk(), |el| {
+                    to.set_recursion_tip_vk(el)
+                })? as usize
+                    + copy_item(from.get_recursion_tip_finalization_hint(), |el| {
+                        to.set_recursion_tip_finalization_hint(el)
+                    })? as usize
+            }
+            a => {
+                copy_item(from.get_recursion_layer_vk(a), |el| {
+                    to.set_recursion_layer_vk(el)
+                })? as usize
+                    + copy_item(from.get_recursion_layer_finalization_hint(a), |el| {
+                        to.set_recursion_layer_finalization_hint(el)
+                    })? as usize
+            }
+        };
+        copied += copy_item(from.get_recursion_layer_setup_data(circuit_type), |el| {
+            to.set_recursion_layer_setup_data(circuit_type, &el)
+        })? as usize;
+    }
+
+    for circuit_type in 1..=WrapperConfig::MAX_COMPRESSION_LAYERS {
+        copied += copy_item(from.get_compression_vk(circuit_type), |el| {
+            to.set_compression_vk(el)
+        })? as usize;
+        copied += copy_item(from.get_compression_hint(circuit_type), |el| {
+            to.set_compression_hint(el)
+        })? as usize;
+        copied += copy_item(from.get_compression_setup_data(circuit_type), |el| {
+            to.set_compression_setup_data(circuit_type, &el)
+        })? as usize;
+        copied += copy_item(from.get_compression_for_wrapper_vk(circuit_type), |el| {
+            to.set_compression_for_wrapper_vk(el)
+        })? as usize;
+        copied += copy_item(from.get_compression_for_wrapper_hint(circuit_type), |el| {
+            to.set_compression_for_wrapper_hint(el)
+        })? as usize;
+        copied += copy_item(
+            from.get_compression_for_wrapper_setup_data(circuit_type),
+            |el| to.set_compression_for_wrapper_setup_data(circuit_type, &el),
+        )? as usize;
+        copied += copy_item(from.get_wrapper_vk(circuit_type), |el| {
+            to.set_wrapper_vk(el)
+        })? as usize;
+        copied += copy_item(from.get_wrapper_setup(circuit_type), |el| {
+            to.set_wrapper_setup(el)
+        })? as usize;
+    }
+
+    copied += copy_item(from.get_eip4844_vk(), |el| to.set_eip4844_vk(el))? as usize;
+    copied += copy_item(from.get_eip4844_finalization_hint(), |el| {
+        to.set_eip4844_finalization_hint(el)
+    })? as usize;
+
+    Ok(copied)
+}
+
+/// Copies all proofs the source lists. Returns the number of copied proofs
+pub fn copy_block_data(
+    from: &dyn BlockDataSource,
+    to: &mut dyn BlockDataSource,
+) -> SourceResult<usize> {
+    let mut copied = 0;
+
+    for id in from.list_proofs()? {
+        let is_copied = match id {
+            ProofId::BaseLayer {
+                circuit_type,
+                index,
+            } => copy_item(from.get_base_layer_proof(circuit_type, index), |el| {
+                to.set_base_layer_proof(index, el)
+            })?,
+            ProofId::Leaf {
+                circuit_type,
+                index,
+            } => copy_item(from.get_leaf_layer_proof(circuit_type, index), |el| {
+                to.set_leaf_layer_proof(index, el)
+            })?,
+            ProofId::Node {
+                circuit_type,
+                depth,
+                index,
+            } => copy_item(
+                from.get_node_layer_proof(circuit_type, depth, index),
+                |el| to.set_node_layer_proof(circuit_type, depth, index, el),
+            )?,
+            ProofId::RecursionTip => copy_item(from.get_recursive_tip_proof(), |el| {
+                to.set_recursive_tip_proof(el)
+            })?,
+            ProofId::Scheduler => {
+                copy_item(from.get_scheduler_proof(), |el| to.set_scheduler_proof(el))?
+            }
+            ProofId::Compression { circuit_type } => {
+                copy_item(from.get_compression_proof(circuit_type), |el| {
+                    to.set_compression_proof(el)
+                })?
+            }
+            ProofId::CompressionForWrapper { circuit_type } => {
+                copy_item(from.get_compression_for_wrapper_proof(circuit_type), |el| {
+                    to.set_compression_for_wrapper_proof(el)
+                })?
+            }
+            ProofId::Wrapper { circuit_type } => {
+                copy_item(from.get_wrapper_proof(circuit_type), |el| {
+                    to.set_wrapper_proof(el)
+                })?
+            }
+            ProofId::EIP4844Blob { index } => copy_item(from.get_eip4844_proof(index), |el| {
+                to.set_eip4844_proof(index, el)
+            })?,
+        };
+        copied += is_copied as usize;
+    }
+
+    Ok(copied)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data_source::local_file_data_source::LocalFileDataSource;
+    use crate::data_source::storage_format::StorageFormat;
+    use crate::prover_utils::create_mock_proof;
+    use crate::zkevm_circuits::scheduler::aux::BaseLayerCircuitType;
+    use circuit_definitions::base_layer_proof_config;
+    use circuit_definitions::circuit_definitions::base_layer::*;
+
+    #[test]
+    fn copy_proofs_between_formats() {
+        let root = std::env::temp_dir().join(format!("migration_{}", std::process::id()));
+        let source_for = |name: &str, format| {
+            let location = root.join(name).to_str().unwrap().to_string();
+            let source = LocalFileDataSource::new(location.clone(), location).with_format(format);
+            source.create_folders_for_storing_data();
+            source
+        };
+        let mut json = source_for("json", StorageFormat::Json);
+        let mut compressed = source_for("compressed", StorageFormat::CompressedBincode);
+
+        let circuit_type = BaseLayerCircuitType::VM as u8;
+        // the proof in between is not done yet
+        for index in [0, 2] {
+            let proof = create_mock_proof::<ZkSyncBaseLayerCircuit>(base_layer_proof_config());
+            json.set_base_layer_proof(index, ZkSyncBaseLayerProof::from_inner(circuit_type, proof))
+                .unwrap();
+        }
+        // blob proofs have the same shape as the base layer ones
+        json.set_eip4844_proof(
+            0,
+            create_mock_proof::<ZkSyncBaseLayerCircuit>(base_layer_proof_config()),
+        )
+        .unwrap();
+
+        assert_eq!(copy_block_data(&json, &mut compressed).unwrap(), 3);
+        assert_eq!(compressed.list_eip4844_proofs().unwrap(), vec![0]);
+        assert_eq!(copy_setup_data(&json, &mut compressed).unwrap(), 0);
+        assert!(root
+            .join("compressed/base_layer/basic_circuit_proof_1_2.bin.gz")
+            .exists());
+        assert_eq!(
+            bincode::serialize(&compressed.get_base_layer_proof(circuit_type, 2).unwrap()).unwrap(),
+            bincode::serialize(&json.get_base_layer_proof(circuit_type, 2).unwrap()).unwrap()
+        );
+        assert_eq!(
+            compressed.list_base_layer_proofs(circuit_type).unwrap(),
+            vec![0, 2]
+        );
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
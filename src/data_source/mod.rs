@@ -5,9 +5,181 @@ use circuit_definitions::circuit_definitions::aux_layer::*;
 use circuit_definitions::circuit_definitions::base_layer::*;
 use circuit_definitions::circuit_definitions::recursion_layer::*;
 
+use crate::compute_setups::{CircuitSetupData, CompressionForWrapperSetupData};
+use crate::proof_wrapper_utils::WrapperConfig;
+use crate::zkevm_circuits::scheduler::aux::BaseLayerCircuitType;
+
 pub type SourceResult<T> = Result<T, Box<dyn Error>>;
+pub mod cached_data_source;
 pub mod in_memory_data_source;
 pub mod local_file_data_source;
+pub mod migration;
+pub mod setup_registry;
+pub mod status;
+pub mod storage_format;
+
+/// Key of a proof in the `BlockDataSource`
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub enum ProofId {
+    BaseLayer {
+        circuit_type: u8,
+        index: usize,
+    },
+    /// Circuit type is the recursive one
+    Leaf {
+        circuit_type: u8,
+        index: usize,
+    },
+    Node {
+        circuit_type: u8,
+        depth: usize,
+        index: usize,
+    },
+    RecursionTip,
+    Scheduler,
+    Compression {
+        circuit_type: u8,
+    },
+    CompressionForWrapper {
+        circuit_type: u8,
+    },
+    Wrapper {
+        circuit_type: u8,
+    },
+    /// Standalone proof of a single blob
+    EIP4844Blob {
+        index: usize,
+    },
+}
+
+impl std::fmt::Display for ProofId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProofId::BaseLayer {
+                circuit_type,
+                index,
+            } => write!(f, "base {} #{}", circuit_type, index),
+            ProofId::Leaf {
+                circuit_type,
+                index,
+            } => write!(f, "leaf {} #{}", circuit_type, index),
+            ProofId::Node {
+                circuit_type,
+                depth,
+                index,
+            } => write!(f, "node {} depth {} #{}", circuit_type, depth, index),
+            ProofId::RecursionTip => write!(f, "recursion tip"),
+            ProofId::Scheduler => write!(f, "scheduler"),
+            ProofId::Compression { circuit_type } => write!(f, "compression {}", circuit_type),
+            ProofId::CompressionForWrapper { circuit_type } => {
+                write!(f, "compression for wrapper {}", circuit_type)
+            }
+            ProofId::Wrapper { circuit_type } => write!(f, "wrapper {}", circuit_type),
+            ProofId::EIP4844Blob { index } => write!(f, "eip4844 blob #{}", index),
+        }
+    }
+}
+
+/// Key of a verification key in the `SetupDataSource`.
+/// Node and recursion tip are recursion layer keys with their storage types
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub enum VkId {
+    BaseLayer {
+        circuit_type: u8,
+    },
+    RecursionLayer {
+        circuit_type: u8,
+    },
+    Compression {
+        circuit_type: u8,
+    },
+    CompressionForWrapper {
+        circuit_type: u8,
+    },
+    Wrapper {
+        circuit_type: u8,
+    },
+    /// Key of the standalone blob proofs
+    EIP4844Blob,
+}
+
+impl std::fmt::Display for VkId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VkId::BaseLayer { circuit_type } => write!(f, "base {}", circuit_type),
+            VkId::RecursionLayer { circuit_type } => write!(f, "recursion {}", circuit_type),
+            VkId::Compression { circuit_type } => write!(f, "compression {}", circuit_type),
+            VkId::CompressionForWrapper { circuit_type } => {
+                write!(f, "compression for wrapper {}", circuit_type)
+            }
+            VkId::Wrapper { circuit_type } => write!(f, "wrapper {}", circuit_type),
+            VkId::EIP4844Blob => write!(f, "eip4844 blob"),
+        }
+    }
+}
+
+/// Numeric types of all the base layer circuits that have keys and proofs
+pub fn base_circuit_types() -> impl Iterator<Item = u8> {
+    ((BaseLayerCircuitType::VM as u8)..=(BaseLayerCircuitType::Secp256r1Verify as u8))
+        .chain(std::iter::once(BaseLayerCircuitType::EIP4844Repack as u8))
+}
+
+/// All the verification keys needed to prove a block.
+/// Aux layer keys are only included for the given wrapper config
+pub fn expected_vks(wrapper_config: Option<WrapperConfig>) -> Vec<VkId> {
+    let mut result: Vec<_> = base_circuit_types()
+        .map(|circuit_type| VkId::BaseLayer { circuit_type })
+        .chain(
+            ZkSyncRecursionLayerStorageType::as_iter_u8()
+                .map(|circuit_type| VkId::RecursionLayer { circuit_type }),
+        )
+        .collect();
+
+    if let Some(config) = wrapper_config {
+        result.extend(
+            config
+                .get_compression_types()
+                .into_iter()
+                .map(|circuit_type| VkId::Compression { circuit_type }),
+        );
+        result.push(VkId::CompressionForWrapper {
+            circuit_type: config.get_compression_for_wrapper_type(),
+        });
+        result.push(VkId::Wrapper {
+            circuit_type: config.get_wrapper_type(),
+        });
+    }
+
+    result
+}
+
+fn setup_data_not_stored(layer: &str, circuit_type: u8) -> Box<dyn Error> {
+    Box::new(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!(
+            "{} setup data for circuit type {} is not stored by this source",
+            layer, circuit_type
+        ),
+    ))
+}
+
+fn eip4844_not_stored(what: &str) -> Box<dyn Error> {
+    Box::new(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!("EIP-4844 {} is not stored by this source", what),
+    ))
+}
+
+fn listing_not_supported(what: &str) -> Box<dyn Error> {
+    Box::new(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!("this source can't list its {}", what),
+    ))
+}
 
 // Object save trait to just get things for SYSTEM
 pub trait SetupDataSource {
@@ -96,6 +268,92 @@ pub trait SetupDataSource {
     ) -> SourceResult<()>;
     fn set_wrapper_setup(&mut self, setup: ZkSyncSnarkWrapperSetup) -> SourceResult<()>;
     fn set_wrapper_vk(&mut self, vk: ZkSyncSnarkWrapperVK) -> SourceResult<()>;
+
+    // Standalone EIP-4844 circuit that proves blobs one by one.
+    // Sources that don't store it report it as missing with `ErrorKind::NotFound`
+    fn get_eip4844_vk(&self) -> SourceResult<EIP4844VerificationKey> {
+        Err(eip4844_not_stored("verification key"))
+    }
+    fn get_eip4844_finalization_hint(&self) -> SourceResult<FinalizationHintsForProver> {
+        Err(eip4844_not_stored("finalization hint"))
+    }
+    fn set_eip4844_vk(&mut self, _vk: EIP4844VerificationKey) -> SourceResult<()> {
+        Err(eip4844_not_stored("verification key"))
+    }
+    fn set_eip4844_finalization_hint(
+        &mut self,
+        _hint: FinalizationHintsForProver,
+    ) -> SourceResult<()> {
+        Err(eip4844_not_stored("finalization hint"))
+    }
+
+    // Complete setup data, so that provers don't need to synthesize circuits for the setup.
+    // Recursion layer data is keyed by the numeric type, that includes node, tip and scheduler.
+    // Sources that don't store setup data report it as missing with `ErrorKind::NotFound`
+    fn get_base_layer_setup_data(&self, circuit_type: u8) -> SourceResult<CircuitSetupData> {
+        Err(setup_data_not_stored("base layer", circuit_type))
+    }
+    fn get_recursion_layer_setup_data(&self, circuit_type: u8) -> SourceResult<CircuitSetupData> {
+        Err(setup_data_not_stored("recursion layer", circuit_type))
+    }
+    fn get_compression_setup_data(&self, circuit_type: u8) -> SourceResult<CircuitSetupData> {
+        Err(setup_data_not_stored("compression", circuit_type))
+    }
+    fn get_compression_for_wrapper_setup_data(
+        &self,
+        circuit_type: u8,
+    ) -> SourceResult<CompressionForWrapperSetupData> {
+        Err(setup_data_not_stored(
+            "compression for wrapper",
+            circuit_type,
+        ))
+    }
+
+    fn set_base_layer_setup_data(
+        &mut self,
+        circuit_type: u8,
+        _data: &CircuitSetupData,
+    ) -> SourceResult<()> {
+        Err(setup_data_not_stored("base layer", circuit_type))
+    }
+    fn set_recursion_layer_setup_data(
+        &mut self,
+        circuit_type: u8,
+        _data: &CircuitSetupData,
+    ) -> SourceResult<()> {
+        Err(setup_data_not_stored("recursion layer", circuit_type))
+    }
+    fn set_compression_setup_data(
+        &mut self,
+        circuit_type: u8,
+        _data: &CircuitSetupData,
+    ) -> SourceResult<()> {
+        Err(setup_data_not_stored("compression", circuit_type))
+    }
+    fn set_compression_for_wrapper_setup_data(
+        &mut self,
+        circuit_type: u8,
+        _data: &CompressionForWrapperSetupData,
+    ) -> SourceResult<()> {
+        Err(setup_data_not_stored(
+            "compression for wrapper",
+            circuit_type,
+        ))
+    }
+
+    /// Verification keys present in the source, sorted.
+    /// Sources that can't enumerate their contents report `ErrorKind::Unsupported`
+    fn list_vks(&self) -> SourceResult<Vec<VkId>> {
+        Err(listing_not_supported("verification keys"))
+    }
+
+    fn missing_vks(&self, wrapper_config: Option<WrapperConfig>) -> SourceResult<Vec<VkId>> {
+        let present = self.list_vks()?;
+        Ok(expected_vks(wrapper_config)
+            .into_iter()
+            .filter(|el| !present.contains(el))
+            .collect())
+    }
 }
 
 // Object save trait to just get things for BLOCK
@@ -151,4 +409,65 @@ pub trait BlockDataSource {
 
     fn set_recursive_tip_proof(&mut self, proof: ZkSyncRecursionLayerProof) -> SourceResult<()>;
     fn get_recursive_tip_proof(&self) -> SourceResult<ZkSyncRecursionLayerProof>;
+
+    // Proofs of the standalone EIP-4844 circuit, indexed by the blob index.
+    // Sources that don't store them report them as missing with `ErrorKind::NotFound`
+    fn get_eip4844_proof(&self, index: usize) -> SourceResult<EIP4844Proof> {
+        Err(eip4844_not_stored(&format!("proof for blob {}", index)))
+    }
+    fn set_eip4844_proof(&mut self, index: usize, _proof: EIP4844Proof) -> SourceResult<()> {
+        Err(eip4844_not_stored(&format!("proof for blob {}", index)))
+    }
+
+    /// Proofs present in the source, sorted.
+    /// Sources that can't enumerate their contents report `ErrorKind::Unsupported`
+    fn list_proofs(&self) -> SourceResult<Vec<ProofId>> {
+        Err(listing_not_supported("proofs"))
+    }
+
+    /// Indexes of the base layer proofs of the given circuit type
+    fn list_base_layer_proofs(&self, base_circuit_type: u8) -> SourceResult<Vec<usize>> {
+        Ok(self
+            .list_proofs()?
+            .into_iter()
+            .filter_map(|el| match el {
+                ProofId::BaseLayer {
+                    circuit_type,
+                    index,
+                } if circuit_type == base_circuit_type => Some(index),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Indexes of the stored blob proofs
+    fn list_eip4844_proofs(&self) -> SourceResult<Vec<usize>> {
+        Ok(self
+            .list_proofs()?
+            .into_iter()
+            .filter_map(|el| match el {
+                ProofId::EIP4844Blob { index } => Some(index),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Node steps that have at least one proof for the given recursive circuit type
+    fn list_node_layer_steps(&self, recursive_circuit_type: u8) -> SourceResult<Vec<usize>> {
+        let mut steps: Vec<_> = self
+            .list_proofs()?
+            .into_iter()
+            .filter_map(|el| match el {
+                ProofId::Node {
+                    circuit_type,
+                    depth,
+                    ..
+                } if circuit_type == recursive_circuit_type => Some(depth),
+                _ => None,
+            })
+            .collect();
+        steps.sort_unstable();
+        steps.dedup();
+        Ok(steps)
+    }
 }
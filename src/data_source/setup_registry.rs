@@ -0,0 +1,312 @@
+//! Setups of several protocol versions kept side by side, one directory per version and geometry.
+
+use super::local_file_data_source::{ConfiguredLocalFileDataSource, LocalFileDataSource};
+use super::storage_format::{find_file, list_dir, read_metadata, StorageFormat};
+use super::SourceResult;
+use crate::sha3::{Digest, Keccak256};
+use crate::toolset::GeometryConfig;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::io::ErrorKind;
+
+const GEOMETRY_HASH_LEN: usize = 16;
+const LAYERS: [&str; 3] = ["base_layer", "recursion_layer", "aux_layer"];
+
+/// Short hash of the circuit capacities, keys of different geometries are not interchangeable
+pub fn geometry_hash(geometry: &GeometryConfig) -> String {
+    let limits = [
+        geometry.cycles_per_vm_snapshot as u64,
+        geometry.cycles_code_decommitter_sorter as u64,
+        geometry.cycles_per_code_decommitter as u64,
+        geometry.cycles_per_log_demuxer as u64,
+        geometry.cycles_per_keccak256_circuit as u64,
+        geometry.cycles_per_sha256_circuit as u64,
+        geometry.cycles_per_ecrecover_circuit as u64,
+        geometry.cycles_per_ram_permutation as u64,
+        geometry.cycles_per_storage_sorter as u64,
+        geometry.cycles_per_storage_application as u64,
+        geometry.cycles_per_events_or_l1_messages_sorter as u64,
+        geometry.limit_for_l1_messages_pudata_hasher as u64,
+        geometry.cycles_per_transient_storage_sorter as u64,
+        geometry.cycles_per_secp256r1_verify_circuit as u64,
+    ];
+    let bytes: Vec<u8> = limits.iter().flat_map(|el| el.to_be_bytes()).collect();
+
+    hex::encode(&Keccak256::digest(&bytes)[..GEOMETRY_HASH_LEN / 2])
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct SetupVersion {
+    pub protocol_version: String,
+    pub geometry_hash: String,
+}
+
+impl SetupVersion {
+    pub fn new(protocol_version: &str, geometry: &GeometryConfig) -> Self {
+        Self {
+            protocol_version: protocol_version.to_string(),
+            geometry_hash: geometry_hash(geometry),
+        }
+    }
+
+    /// Setup of the version is stored in `{protocol_version}_{geometry_hash}`
+    pub fn dir_name(&self) -> String {
+        format!("{}_{}", self.protocol_version, self.geometry_hash)
+    }
+
+    pub fn from_dir_name(name: &str) -> Option<Self> {
+        let (protocol_version, geometry_hash) = name.rsplit_once('_')?;
+        if protocol_version.is_empty()
+            || geometry_hash.len() != GEOMETRY_HASH_LEN
+            || !geometry_hash.chars().all(|el| el.is_ascii_hexdigit())
+        {
+            return None;
+        }
+
+        Some(Self {
+            protocol_version: protocol_version.to_string(),
+            geometry_hash: geometry_hash.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for SetupVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (geometry {})",
+            self.protocol_version, self.geometry_hash
+        )
+    }
+}
+
+/// Directory with the setups of all the protocol versions in use.
+/// Sources opened through the registry only accept setup data
+/// written for their own protocol version
+#[derive(Clone, Debug)]
+pub struct SetupRegistry {
+    pub root: String,
+    pub format: StorageFormat,
+}
+
+impl SetupRegistry {
+    pub fn new(root: String, format: StorageFormat) -> Self {
+        Self { root, format }
+    }
+
+    /// Versions that have a setup directory, sorted
+    pub fn list_versions(&self) -> SourceResult<Vec<SetupVersion>> {
+        let entries = match std::fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(el) if el.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(el) => return Err(Box::new(el)),
+        };
+
+        let mut result = vec![];
+        for entry in entries {
+            let entry = entry.map_err(|el| Box::new(el) as Box<dyn Error>)?;
+            if !entry.path().is_dir() {
+                continue;
+            }
+            if let Some(version) = SetupVersion::from_dir_name(&entry.file_name().to_string_lossy())
+            {
+                result.push(version);
+            }
+        }
+        result.sort();
+
+        Ok(result)
+    }
+
+    fn source_for(&self, version: &SetupVersion) -> ConfiguredLocalFileDataSource {
+        LocalFileDataSource {
+            setup_data_location: format!("{}/{}", self.root, version.dir_name()),
+            ..Default::default()
+        }
+        .with_format(self.format)
+        .with_protocol_version(&version.protocol_version)
+        .with_protocol_version_check()
+    }
+
+    /// Creates the directory for the version if it is missing.
+    /// Everything written through the returned source is tagged with the version
+    pub fn create(&self, version: &SetupVersion) -> SourceResult<ConfiguredLocalFileDataSource> {
+        let source = self.source_for(version);
+        for layer in LAYERS {
+            std::fs::create_dir_all(format!("{}/{}", source.setup_data_location, layer))
+                .map_err(|el| Box::new(el) as Box<dyn Error>)?;
+        }
+
+        Ok(source)
+    }
+
+    /// Opens the setup of the version, refusing it if any of its files belongs to another version
+    pub fn open(&self, version: &SetupVersion) -> SourceResult<ConfiguredLocalFileDataSource> {
+        let versions = self.list_versions()?;
+        if !versions.contains(version) {
+            let available: Vec<_> = versions.iter().map(|el| el.to_string()).collect();
+            return Err(Box::new(std::io::Error::new(
+                ErrorKind::NotFound,
+                format!(
+                    "no setup for {}, available: [{}]",
+                    version,
+                    available.join(", ")
+                ),
+            )));
+        }
+
+        let foreign_files = self.foreign_files(version)?;
+        if !foreign_files.is_empty() {
+            return Err(Box::new(std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "setup for {} has files of other protocol versions: {}",
+                    version,
+                    foreign_files.join(", ")
+                ),
+            )));
+        }
+
+        Ok(self.source_for(version))
+    }
+
+    /// Setup to prove blocks of the given protocol version with the given geometry
+    pub fn load(
+        &self,
+        protocol_version: &str,
+        geometry: &GeometryConfig,
+    ) -> SourceResult<ConfiguredLocalFileDataSource> {
+        self.open(&SetupVersion::new(protocol_version, geometry))
+    }
+
+    /// Files of the version that were written for another protocol version, or without metadata
+    pub fn foreign_files(&self, version: &SetupVersion) -> SourceResult<Vec<String>> {
+        let location = format!("{}/{}", self.root, version.dir_name());
+        let mut files = vec![];
+        for layer in LAYERS {
+            let dir = format!("{}/{}", location, layer);
+            for name in list_dir(&dir)? {
+                if let Some(file_path) = find_file(&format!("{}/{}", dir, name), self.format) {
+                    files.push(file_path);
+                }
+            }
+        }
+        // wrapper setup and VK are written in their own encoding, not in a storage format
+        files.extend(wrapper_files(&format!("{}/aux_layer", location))?);
+
+        let mut result = vec![];
+        for file_path in files {
+            let found = read_metadata(&file_path)?.and_then(|el| el.protocol_version);
+            if found.as_deref() != Some(version.protocol_version.as_str()) {
+                result.push(file_path);
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// Paths of the wrapper setup and VK files in the directory, sorted
+fn wrapper_files(dir: &str) -> SourceResult<Vec<String>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(el) if el.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(el) => return Err(Box::new(el)),
+    };
+
+    let mut result = vec![];
+    for entry in entries {
+        let entry = entry.map_err(|el| Box::new(el) as Box<dyn Error>)?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let is_setup = file_name.starts_with("wrapper_setup_") && file_name.ends_with(".setup");
+        let is_vk = file_name.starts_with("wrapper_vk_") && file_name.ends_with(".key");
+        if is_setup || is_vk {
+            result.push(format!("{}/{}", dir, file_name));
+        }
+    }
+    result.sort();
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data_source::SetupDataSource;
+    use crate::toolset::get_testing_geometry_config;
+    use circuit_definitions::boojum::cs::implementations::setup::FinalizationHintsForProver;
+
+    #[test]
+    fn versions_are_not_mixed() {
+        let root = std::env::temp_dir().join(format!("setup_registry_{}", std::process::id()));
+        let registry = SetupRegistry::new(root.to_str().unwrap().to_string(), StorageFormat::Json);
+        let geometry = get_testing_geometry_config();
+        let old = SetupVersion::new("24", &geometry);
+        let new = SetupVersion::new("25", &geometry);
+        assert!(registry.list_versions().unwrap().is_empty());
+        assert_eq!(
+            SetupVersion::from_dir_name(&old.dir_name()),
+            Some(old.clone())
+        );
+
+        let mut old_source = registry.create(&old).unwrap();
+        old_source
+            .set_eip4844_finalization_hint(FinalizationHintsForProver::default())
+            .unwrap();
+        registry
+            .create(&new)
+            .unwrap()
+            .set_eip4844_finalization_hint(FinalizationHintsForProver::default())
+            .unwrap();
+        assert_eq!(
+            registry.list_versions().unwrap(),
+            vec![old.clone(), new.clone()]
+        );
+        assert!(registry
+            .load("24", &geometry)
+            .unwrap()
+            .get_eip4844_finalization_hint()
+            .is_ok());
+        assert!(registry.load("23", &geometry).is_err());
+        assert!(registry
+            .load("24", &crate::geometry_config::get_geometry_config())
+            .is_err());
+
+        // a key of the new version put into the old one
+        for file in [
+            "eip4844_finalization_hint.json",
+            "eip4844_finalization_hint.json.meta",
+        ] {
+            std::fs::copy(
+                root.join(new.dir_name()).join("base_layer").join(file),
+                root.join(old.dir_name()).join("base_layer").join(file),
+            )
+            .unwrap();
+        }
+        assert_eq!(registry.foreign_files(&old).unwrap().len(), 1);
+        // wrapper keys are checked as well, one without metadata is foreign
+        let wrapper_vk = root
+            .join(old.dir_name())
+            .join("aux_layer")
+            .join("wrapper_vk_1.key");
+        std::fs::write(&wrapper_vk, [0u8; 4]).unwrap();
+        assert_eq!(
+            registry.foreign_files(&old).unwrap(),
+            vec![
+                root.join(old.dir_name())
+                    .join("base_layer")
+                    .join("eip4844_finalization_hint.json")
+                    .to_str()
+                    .unwrap()
+                    .to_string(),
+                wrapper_vk.to_str().unwrap().to_string(),
+            ]
+        );
+        assert!(registry.open(&old).is_err());
+        assert!(old_source.get_eip4844_finalization_hint().is_err());
+        assert!(registry.open(&new).is_ok());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,326 @@
+//! Shows which stages of the proving pipeline are done for a block.
+
+use super::{BlockDataSource, ProofId, SourceResult};
+use crate::proof_wrapper_utils::WrapperConfig;
+use crate::proving_pipeline::RecursionQueue;
+use crate::witness::aggregation_plan::CircuitTypeAggregationPlan;
+use crate::witness::recursive_aggregation::RecursionArity;
+use crate::zkevm_circuits::scheduler::aux::BaseLayerCircuitType;
+use circuit_definitions::circuit_definitions::recursion_layer::*;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum PipelineStage {
+    BaseLayer,
+    LeafLayer,
+    NodeLayer,
+    RecursionTip,
+    Scheduler,
+    Compression,
+    Wrapper,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct StageStatus {
+    pub stage: PipelineStage,
+    pub proofs: usize,
+    pub complete: bool,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct BlockStatus {
+    pub stages: Vec<StageStatus>,
+}
+
+/// Number of base layer circuits of every type in the block, as in its recursion queues
+pub fn expected_base_layer_proofs(recursion_queues: &[RecursionQueue]) -> BTreeMap<u8, usize> {
+    recursion_queues
+        .iter()
+        .filter(|(_, queue, _)| queue.num_items > 0)
+        .map(|(circuit_type, queue, _)| (*circuit_type as u8, queue.num_items as usize))
+        .collect()
+}
+
+impl BlockStatus {
+    /// Stage is complete if every proof that the next stage needs is present:
+    /// - the expected number of base layer proofs for every circuit type
+    /// - every leaf and node that the arity makes out of the expected base layer proofs
+    /// Compression and wrapper are checked for the given wrapper config,
+    /// or for any number of compression layers if there is none
+    pub fn from_source(
+        source: &dyn BlockDataSource,
+        expected_base_layer_proofs: &BTreeMap<u8, usize>,
+        arity: RecursionArity,
+        wrapper_config: Option<WrapperConfig>,
+    ) -> SourceResult<Self> {
+        let proofs = source.list_proofs()?;
+
+        let mut base_layer = BTreeMap::<u8, usize>::new();
+        let mut leaf_layer = BTreeMap::<u8, usize>::new();
+        // proofs per depth for every recursive circuit type
+        let mut node_layer = BTreeMap::<u8, BTreeMap<usize, usize>>::new();
+        for id in proofs.iter() {
+            match *id {
+                ProofId::BaseLayer { circuit_type, .. } => {
+                    *base_layer.entry(circuit_type).or_default() += 1
+                }
+                ProofId::Leaf { circuit_type, .. } => {
+                    *leaf_layer.entry(circuit_type).or_default() += 1
+                }
+                ProofId::Node {
+                    circuit_type,
+                    depth,
+                    ..
+                } => {
+                    *node_layer
+                        .entry(circuit_type)
+                        .or_default()
+                        .entry(depth)
+                        .or_default() += 1
+                }
+                _ => {}
+            }
+        }
+        let count = |filter: fn(&ProofId) -> bool| proofs.iter().filter(|el| filter(el)).count();
+        let has = |id: ProofId| proofs.contains(&id);
+
+        let base_layer_complete = !expected_base_layer_proofs.is_empty()
+            && expected_base_layer_proofs
+                .iter()
+                .all(|(circuit_type, &expected)| {
+                    base_layer.get(circuit_type).copied().unwrap_or(0) >= expected
+                });
+        // leaves and nodes that the given arity makes out of the expected base layer proofs
+        let plans: Vec<(u8, CircuitTypeAggregationPlan)> = expected_base_layer_proofs
+            .iter()
+            .map(|(&circuit_type, &expected)| {
+                let leaf_type = base_circuit_type_into_recursive_leaf_circuit_type(
+                    BaseLayerCircuitType::from_numeric_value(circuit_type),
+                ) as u8;
+                (
+                    leaf_type,
+                    CircuitTypeAggregationPlan::new(circuit_type, expected, arity),
+                )
+            })
+            .collect();
+        let leaves_complete = !plans.is_empty()
+            && plans.iter().all(|(leaf_type, plan)| {
+                leaf_layer.get(leaf_type).copied().unwrap_or(0) >= plan.leaves.len()
+            });
+        let nodes_complete = !plans.is_empty()
+            && plans.iter().all(|(leaf_type, plan)| {
+                let depths = node_layer.get(leaf_type);
+                plan.nodes_per_depth
+                    .iter()
+                    .enumerate()
+                    .all(|(depth, &expected)| {
+                        depths.and_then(|el| el.get(&depth)).copied().unwrap_or(0) >= expected
+                    })
+            });
+
+        let (compression_complete, wrapper_complete) = match wrapper_config {
+            Some(config) => (
+                config
+                    .get_compression_types()
+                    .into_iter()
+                    .all(|circuit_type| has(ProofId::Compression { circuit_type }))
+                    && has(ProofId::CompressionForWrapper {
+                        circuit_type: config.get_compression_for_wrapper_type(),
+                    }),
+                has(ProofId::Wrapper {
+                    circuit_type: config.get_wrapper_type(),
+                }),
+            ),
+            None => (
+                count(|el| matches!(el, ProofId::CompressionForWrapper { .. })) > 0,
+                count(|el| matches!(el, ProofId::Wrapper { .. })) > 0,
+            ),
+        };
+
+        let stages = vec![
+            StageStatus {
+                stage: PipelineStage::BaseLayer,
+                proofs: base_layer.values().sum(),
+                complete: base_layer_complete,
+            },
+            StageStatus {
+                stage: PipelineStage::LeafLayer,
+                proofs: leaf_layer.values().sum(),
+                complete: leaves_complete,
+            },
+            StageStatus {
+                stage: PipelineStage::NodeLayer,
+                proofs: node_layer.values().flat_map(|el| el.values()).sum(),
+                complete: nodes_complete,
+            },
+            StageStatus {
+                stage: PipelineStage::RecursionTip,
+                proofs: count(|el| matches!(el, ProofId::RecursionTip)),
+                complete: has(ProofId::RecursionTip),
+            },
+            StageStatus {
+                stage: PipelineStage::Scheduler,
+                proofs: count(|el| matches!(el, ProofId::Scheduler)),
+                complete: has(ProofId::Scheduler),
+            },
+            StageStatus {
+                stage: PipelineStage::Compression,
+                proofs: count(|el| {
+                    matches!(
+                        el,
+                        ProofId::Compression { .. } | ProofId::CompressionForWrapper { .. }
+                    )
+                }),
+                complete: compression_complete,
+            },
+            StageStatus {
+                stage: PipelineStage::Wrapper,
+                proofs: count(|el| matches!(el, ProofId::Wrapper { .. })),
+                complete: wrapper_complete,
+            },
+        ];
+
+        Ok(Self { stages })
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.stages.iter().all(|el| el.complete)
+    }
+
+    /// First stage that still has to be proven
+    pub fn next_stage(&self) -> Option<PipelineStage> {
+        self.stages
+            .iter()
+            .find(|el| !el.complete)
+            .map(|el| el.stage)
+    }
+
+    pub fn to_table(&self) -> String {
+        let mut table = String::new();
+        writeln!(table, "{:<20} {:<10} {}", "stage", "proofs", "complete").unwrap();
+        for status in self.stages.iter() {
+            writeln!(
+                table,
+                "{:<20} {:<10} {}",
+                format!("{:?}", status.stage),
+                status.proofs,
+                status.complete
+            )
+            .unwrap();
+        }
+
+        table
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data_source::in_memory_data_source::InMemoryDataSource;
+    use crate::data_source::{SetupDataSource, VkId};
+    use crate::prover_utils::create_mock_proof;
+    use circuit_definitions::circuit_definitions::base_layer::*;
+    use circuit_definitions::{base_layer_proof_config, recursion_layer_proof_config};
+
+    #[test]
+    fn status_and_listing_of_stored_proofs() {
+        let mut source = InMemoryDataSource::new();
+        let circuit_type = BaseLayerCircuitType::VM as u8;
+        let expected = BTreeMap::from([(circuit_type, 3)]);
+        // two leaves of 2 and 1 base proofs, and a single node over them
+        let arity = RecursionArity::new(2, 2);
+        let status = BlockStatus::from_source(&source, &expected, arity, None).unwrap();
+        assert_eq!(status.next_stage(), Some(PipelineStage::BaseLayer));
+        assert_eq!(
+            source
+                .missing_vks(Some(WrapperConfig::new(1)))
+                .unwrap()
+                .len(),
+            source.missing_vks(None).unwrap().len() + 2
+        );
+        assert!(source
+            .missing_vks(None)
+            .unwrap()
+            .contains(&VkId::RecursionLayer {
+                circuit_type: ZkSyncRecursionLayerStorageType::NodeLayerCircuit as u8
+            }));
+
+        for index in 0..3 {
+            let proof = create_mock_proof::<ZkSyncBaseLayerCircuit>(base_layer_proof_config());
+            source
+                .set_base_layer_proof(index, ZkSyncBaseLayerProof::from_inner(circuit_type, proof))
+                .unwrap();
+        }
+        assert_eq!(
+            source.list_base_layer_proofs(circuit_type).unwrap(),
+            vec![0, 1, 2]
+        );
+        assert!(source
+            .list_node_layer_steps(circuit_type)
+            .unwrap()
+            .is_empty());
+
+        // some of the base layer proofs are still missing
+        let status = BlockStatus::from_source(
+            &source,
+            &BTreeMap::from([
+                (circuit_type, 3),
+                (BaseLayerCircuitType::StorageApplicator as u8, 1),
+            ]),
+            arity,
+            None,
+        )
+        .unwrap();
+        assert_eq!(status.next_stage(), Some(PipelineStage::BaseLayer));
+
+        let status = BlockStatus::from_source(&source, &expected, arity, None).unwrap();
+        assert_eq!(status.stages[0].proofs, 3);
+        assert_eq!(status.next_stage(), Some(PipelineStage::LeafLayer));
+        assert!(!status.is_complete());
+
+        let leaf_type = base_circuit_type_into_recursive_leaf_circuit_type(
+            BaseLayerCircuitType::from_numeric_value(circuit_type),
+        ) as u8;
+        let recursive_proof =
+            || create_mock_proof::<ZkSyncRecursiveLayerCircuit>(recursion_layer_proof_config());
+        source
+            .set_leaf_layer_proof(
+                0,
+                ZkSyncRecursionLayerProof::from_inner(leaf_type, recursive_proof()),
+            )
+            .unwrap();
+        // the second leaf is still missing
+        let status = BlockStatus::from_source(&source, &expected, arity, None).unwrap();
+        assert_eq!(status.next_stage(), Some(PipelineStage::LeafLayer));
+
+        source
+            .set_leaf_layer_proof(
+                1,
+                ZkSyncRecursionLayerProof::from_inner(leaf_type, recursive_proof()),
+            )
+            .unwrap();
+        let status = BlockStatus::from_source(&source, &expected, arity, None).unwrap();
+        assert_eq!(status.stages[1].proofs, 2);
+        assert_eq!(status.next_stage(), Some(PipelineStage::NodeLayer));
+
+        // with the default arity a single leaf would be enough
+        let status =
+            BlockStatus::from_source(&source, &expected, RecursionArity::default(), None).unwrap();
+        assert_eq!(status.next_stage(), Some(PipelineStage::NodeLayer));
+
+        source
+            .set_node_layer_proof(
+                leaf_type,
+                0,
+                0,
+                ZkSyncRecursionLayerProof::from_inner(
+                    ZkSyncRecursionLayerStorageType::NodeLayerCircuit as u8,
+                    recursive_proof(),
+                ),
+            )
+            .unwrap();
+        let status = BlockStatus::from_source(&source, &expected, arity, None).unwrap();
+        assert_eq!(status.next_stage(), Some(PipelineStage::RecursionTip));
+    }
+}
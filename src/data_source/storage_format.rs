@@ -0,0 +1,536 @@
+use super::SourceResult;
+use crate::sha2::{Digest, Sha256};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+// first bytes of every gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Encoding of the files written by `LocalFileDataSource`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum StorageFormat {
+    #[default]
+    Json,
+    Bincode,
+    CompressedBincode,
+}
+
+impl StorageFormat {
+    pub const ALL: [StorageFormat; 3] = [
+        StorageFormat::Json,
+        StorageFormat::Bincode,
+        StorageFormat::CompressedBincode,
+    ];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            StorageFormat::Json => "json",
+            StorageFormat::Bincode => "bin",
+            StorageFormat::CompressedBincode => "bin.gz",
+        }
+    }
+
+    /// Format to store setup data in, which is too big for JSON
+    pub fn for_setup_data(&self) -> Self {
+        match self {
+            StorageFormat::Json => StorageFormat::Bincode,
+            format => *format,
+        }
+    }
+
+    pub fn from_extension(path: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|format| path.ends_with(&format!(".{}", format.extension())))
+    }
+
+    /// Detects the format of a file by its first bytes, falling back to the extension.
+    /// Compressed files are recognized by the gzip header regardless of the name
+    pub fn detect(path: &str, header: &[u8]) -> Option<Self> {
+        if header.starts_with(&GZIP_MAGIC) {
+            return Some(StorageFormat::CompressedBincode);
+        }
+        match Self::from_extension(path) {
+            Some(StorageFormat::CompressedBincode) => None,
+            Some(format) => Some(format),
+            None => match header.iter().find(|el| !el.is_ascii_whitespace()) {
+                Some(b'{') | Some(b'[') | Some(b'"') => Some(StorageFormat::Json),
+                _ => Some(StorageFormat::Bincode),
+            },
+        }
+    }
+
+    pub fn serialize<T: Serialize, W: Write>(
+        &self,
+        data: &T,
+        writer: W,
+        pretty: bool,
+    ) -> SourceResult<()> {
+        let mut writer = BufWriter::new(writer);
+        match self {
+            StorageFormat::Json if pretty => serde_json::to_writer_pretty(&mut writer, data)
+                .map_err(|el| Box::new(el) as Box<dyn Error>)?,
+            StorageFormat::Json => serde_json::to_writer(&mut writer, data)
+                .map_err(|el| Box::new(el) as Box<dyn Error>)?,
+            StorageFormat::Bincode => bincode::serialize_into(&mut writer, data)
+                .map_err(|el| Box::new(el) as Box<dyn Error>)?,
+            StorageFormat::CompressedBincode => {
+                let mut encoder = GzEncoder::new(&mut writer, Compression::default());
+                bincode::serialize_into(&mut encoder, data)
+                    .map_err(|el| Box::new(el) as Box<dyn Error>)?;
+                encoder
+                    .finish()
+                    .map_err(|el| Box::new(el) as Box<dyn Error>)?;
+            }
+        }
+        writer
+            .flush()
+            .map_err(|el| Box::new(el) as Box<dyn Error>)?;
+
+        Ok(())
+    }
+
+    pub fn deserialize<T: DeserializeOwned, R: Read>(&self, reader: R) -> SourceResult<T> {
+        let result = match self {
+            StorageFormat::Json => {
+                serde_json::from_reader(reader).map_err(|el| Box::new(el) as Box<dyn Error>)?
+            }
+            StorageFormat::Bincode => {
+                bincode::deserialize_from(reader).map_err(|el| Box::new(el) as Box<dyn Error>)?
+            }
+            StorageFormat::CompressedBincode => bincode::deserialize_from(GzDecoder::new(reader))
+                .map_err(|el| Box::new(el) as Box<dyn Error>)?,
+        };
+
+        Ok(result)
+    }
+}
+
+impl FromStr for StorageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(StorageFormat::Json),
+            "bincode" | "bin" => Ok(StorageFormat::Bincode),
+            "compressed-bincode" | "bin.gz" => Ok(StorageFormat::CompressedBincode),
+            _ => Err(format!(
+                "unknown storage format {}, expected json, bincode or compressed-bincode",
+                s
+            )),
+        }
+    }
+}
+
+/// Sidecar stored next to every artifact as `{file}.meta`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArtifactMetadata {
+    /// sha256 of the file contents
+    pub checksum: String,
+    pub size: u64,
+    pub layer: Option<String>,
+    pub circuit_type: Option<u8>,
+    /// Only recorded if the writer was given one
+    pub protocol_version: Option<String>,
+}
+
+impl ArtifactMetadata {
+    /// Layer is the name of the directory, and circuit type is the first number in the file name
+    /// (as in `base_layer/basic_circuit_proof_{circuit_type}_{index}`)
+    fn describe(file_path: &str) -> (Option<String>, Option<u8>) {
+        let path = Path::new(file_path);
+        let layer = path
+            .parent()
+            .and_then(|el| el.file_name())
+            .map(|el| el.to_string_lossy().to_string());
+        let circuit_type = path
+            .file_name()
+            .and_then(|el| el.to_str())
+            .and_then(|el| el.split('.').next())
+            .and_then(|el| el.split('_').find_map(|part| part.parse::<u8>().ok()));
+
+        (layer, circuit_type)
+    }
+}
+
+pub fn metadata_path(file_path: &str) -> String {
+    format!("{}.meta", file_path)
+}
+
+/// Returns `None` for artifacts written without metadata
+pub fn read_metadata(file_path: &str) -> SourceResult<Option<ArtifactMetadata>> {
+    match File::open(metadata_path(file_path)) {
+        Ok(file) => serde_json::from_reader(BufReader::new(file))
+            .map(Some)
+            .map_err(|el| {
+                Box::new(corrupted(
+                    file_path,
+                    format!("unreadable metadata sidecar: {}", el),
+                )) as Box<dyn Error>
+            }),
+        Err(el) if el.kind() == ErrorKind::NotFound => Ok(None),
+        Err(el) => Err(Box::new(el)),
+    }
+}
+
+fn corrupted(file_path: &str, reason: String) -> std::io::Error {
+    std::io::Error::new(
+        ErrorKind::InvalidData,
+        format!("corrupted file {}: {}", file_path, reason),
+    )
+}
+
+// Hashes everything that goes through it
+struct HashingWriter<W: Write> {
+    inner: W,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn file_checksum(file_path: &str) -> SourceResult<(String, u64)> {
+    let mut file = File::open(file_path).map_err(|el| Box::new(el) as Box<dyn Error>)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1 << 20];
+    let mut size = 0u64;
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|el| Box::new(el) as Box<dyn Error>)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+
+    Ok((hex::encode(hasher.finalize()), size))
+}
+
+/// Checks the file against its metadata sidecar, if there is one
+pub fn verify_file(file_path: &str) -> SourceResult<()> {
+    let Some(metadata) = read_metadata(file_path)? else {
+        return Ok(());
+    };
+    let (checksum, size) = file_checksum(file_path)?;
+    if size != metadata.size {
+        return Err(Box::new(corrupted(
+            file_path,
+            format!(
+                "size is {} bytes, but {} bytes were written",
+                size, metadata.size
+            ),
+        )));
+    }
+    if checksum != metadata.checksum {
+        return Err(Box::new(corrupted(
+            file_path,
+            format!(
+                "checksum mismatch, expected {} but got {}",
+                metadata.checksum, checksum
+            ),
+        )));
+    }
+
+    Ok(())
+}
+
+/// Writes to a temporary file next to the destination, syncs it and renames it into place,
+/// so that the destination always holds either the old or the new complete contents
+pub fn write_atomically(
+    file_path: &str,
+    write: impl FnOnce(&mut File) -> SourceResult<()>,
+) -> SourceResult<()> {
+    let tmp_path = format!("{}.tmp", file_path);
+    let mut file = File::create(&tmp_path).map_err(|el| Box::new(el) as Box<dyn Error>)?;
+    let result =
+        write(&mut file).and_then(|_| file.sync_all().map_err(|el| Box::new(el) as Box<dyn Error>));
+    if let Err(err) = result {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(err);
+    }
+    drop(file);
+
+    std::fs::rename(&tmp_path, file_path).map_err(|el| Box::new(el) as Box<dyn Error>)?;
+    // make the rename itself durable
+    if let Some(dir) = Path::new(file_path).parent() {
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `{path}.{extension}` exists for any of the formats
+pub fn exists(path: &str) -> bool {
+    StorageFormat::ALL
+        .into_iter()
+        .any(|format| Path::new(&format!("{}.{}", path, format.extension())).exists())
+}
+
+// `{path}.{extension}` for the preferred format first, then for all the others
+fn candidates(path: &str, preferred: StorageFormat) -> impl Iterator<Item = String> + '_ {
+    std::iter::once(preferred)
+        .chain(
+            StorageFormat::ALL
+                .into_iter()
+                .filter(move |el| *el != preferred),
+        )
+        .map(move |format| format!("{}.{}", path, format.extension()))
+}
+
+/// The file that `read_file` would read for the path, if there is any
+pub fn find_file(path: &str, preferred: StorageFormat) -> Option<String> {
+    candidates(path, preferred).find(|el| Path::new(el).exists())
+}
+
+/// Names of the artifacts in the directory, without the storage format extension.
+/// Metadata sidecars, temporary files and files of other types are skipped
+pub fn list_dir(dir: &str) -> SourceResult<Vec<String>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(el) if el.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(el) => return Err(Box::new(el)),
+    };
+
+    let mut result = vec![];
+    for entry in entries {
+        let entry = entry.map_err(|el| Box::new(el) as Box<dyn Error>)?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if let Some(format) = StorageFormat::from_extension(&file_name) {
+            let name = &file_name[..file_name.len() - format.extension().len() - 1];
+            result.push(name.to_string());
+        }
+    }
+    result.sort();
+    result.dedup();
+
+    Ok(result)
+}
+
+/// Reads `{path}.{extension}` for the preferred format, or for any other format if it is missing.
+/// The file is verified against its metadata sidecar first.
+/// Returns an error of `NotFound` kind if there is no such file in any format
+pub fn read_file<T: DeserializeOwned>(path: &str, preferred: StorageFormat) -> SourceResult<T> {
+    for file_path in candidates(path, preferred) {
+        let file = match File::open(&file_path) {
+            Ok(file) => file,
+            Err(el) if el.kind() == ErrorKind::NotFound => continue,
+            Err(el) => return Err(Box::new(el)),
+        };
+        verify_file(&file_path)?;
+        let mut reader = BufReader::new(file);
+        let header = reader
+            .fill_buf()
+            .map_err(|el| Box::new(el) as Box<dyn Error>)?;
+        let format = StorageFormat::detect(&file_path, header).ok_or_else(|| {
+            Box::new(std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!("{} has no gzip header", file_path),
+            )) as Box<dyn Error>
+        })?;
+
+        return format.deserialize(reader).map_err(|el| {
+            Box::new(std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!("failed to read {} as {:?}: {}", file_path, format, el),
+            )) as Box<dyn Error>
+        });
+    }
+
+    Err(Box::new(std::io::Error::new(
+        ErrorKind::NotFound,
+        format!("no file for {} in any storage format", path),
+    )))
+}
+
+/// Atomically writes the file together with its metadata sidecar.
+/// For artifacts with their own encoding, like the snark wrapper setup, VK and proof
+pub fn write_file_with_metadata(
+    file_path: &str,
+    protocol_version: Option<&str>,
+    write: impl FnOnce(&mut dyn Write) -> SourceResult<()>,
+) -> SourceResult<()> {
+    // a crash between the two renames leaves the new file without a sidecar instead of
+    // the new file with the stale sidecar
+    match std::fs::remove_file(metadata_path(file_path)) {
+        Err(el) if el.kind() != ErrorKind::NotFound => return Err(Box::new(el)),
+        _ => {}
+    }
+
+    let mut checksum = None;
+    write_atomically(file_path, |file| {
+        let mut writer = HashingWriter {
+            inner: file,
+            hasher: Sha256::new(),
+            size: 0,
+        };
+        // snark artifacts are written in many small pieces
+        let mut buffered = BufWriter::new(&mut writer);
+        write(&mut buffered)?;
+        buffered
+            .flush()
+            .map_err(|el| Box::new(el) as Box<dyn Error>)?;
+        drop(buffered);
+        checksum = Some((hex::encode(writer.hasher.finalize()), writer.size));
+        Ok(())
+    })?;
+
+    let (checksum, size) = checksum.expect("must be computed on write");
+    let (layer, circuit_type) = ArtifactMetadata::describe(file_path);
+    let metadata = ArtifactMetadata {
+        checksum,
+        size,
+        layer,
+        circuit_type,
+        protocol_version: protocol_version.map(|el| el.to_string()),
+    };
+    write_atomically(&metadata_path(file_path), |file| {
+        serde_json::to_writer_pretty(file, &metadata).map_err(|el| Box::new(el) as Box<dyn Error>)
+    })
+}
+
+/// Opens the file after checking it against its metadata sidecar, if there is one
+pub fn open_verified_file(file_path: &str) -> SourceResult<File> {
+    let file = File::open(file_path).map_err(|el| Box::new(el) as Box<dyn Error>)?;
+    verify_file(file_path)?;
+
+    Ok(file)
+}
+
+/// Atomically writes the data to `{path}.{extension}`, together with its metadata sidecar.
+/// Copies of the file in the other formats are removed afterwards
+pub fn write_file<T: Serialize>(
+    path: &str,
+    format: StorageFormat,
+    data: &T,
+    pretty: bool,
+    protocol_version: Option<&str>,
+) -> SourceResult<()> {
+    let file_path = format!("{}.{}", path, format.extension());
+    write_file_with_metadata(&file_path, protocol_version, |writer| {
+        format.serialize(data, writer, pretty)
+    })?;
+
+    // copies in other formats would be read instead of the new file if they are preferred
+    for other in StorageFormat::ALL.into_iter().filter(|el| *el != format) {
+        let other_path = format!("{}.{}", path, other.extension());
+        for stale in [metadata_path(&other_path), other_path] {
+            match std::fs::remove_file(&stale) {
+                Err(el) if el.kind() != ErrorKind::NotFound => return Err(Box::new(el)),
+                _ => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn roundtrip_and_detect_formats() {
+        let dir = std::env::temp_dir().join(format!("storage_format_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let data: Vec<(u8, String)> = vec![(1, "vk".to_string()), (2, "hint".to_string())];
+
+        for format in StorageFormat::ALL {
+            let path = dir.join(format!("{:?}", format));
+            let path = path.to_str().unwrap();
+            write_file(path, format, &data, true, None).unwrap();
+
+            let header = std::fs::read(format!("{}.{}", path, format.extension())).unwrap();
+            assert_eq!(
+                StorageFormat::detect(&format!("{}.{}", path, format.extension()), &header),
+                Some(format)
+            );
+            // found even if another format is preferred
+            let restored: Vec<(u8, String)> =
+                read_file(path, StorageFormat::Json.for_setup_data()).unwrap();
+            assert_eq!(restored, data);
+        }
+
+        // truncated or modified files are reported as corrupted
+        let path = dir.join("base_layer_proof_3_0");
+        let path = path.to_str().unwrap();
+        write_file(path, StorageFormat::Json, &data, false, None).unwrap();
+        let file_path = format!("{}.json", path);
+        let metadata = read_metadata(&file_path).unwrap().unwrap();
+        assert_eq!(metadata.circuit_type, Some(3));
+        assert_eq!(
+            metadata.layer.as_deref(),
+            dir.file_name().and_then(|el| el.to_str())
+        );
+        let contents = std::fs::read(&file_path).unwrap();
+        std::fs::write(&file_path, &contents[..contents.len() - 1]).unwrap();
+        let err = read_file::<Vec<(u8, String)>>(path, StorageFormat::Json).unwrap_err();
+        assert!(err.to_string().contains("corrupted file"));
+        assert!(!Path::new(&format!("{}.tmp", file_path)).exists());
+
+        // files in their own encoding get the same sidecar
+        let raw_path = dir.join("wrapper_vk_5.key");
+        let raw_path = raw_path.to_str().unwrap();
+        write_file_with_metadata(raw_path, Some("25"), |writer| {
+            writer
+                .write_all(&[1, 2, 3])
+                .map_err(|el| Box::new(el) as Box<dyn Error>)
+        })
+        .unwrap();
+        let metadata = read_metadata(raw_path).unwrap().unwrap();
+        assert_eq!(metadata.circuit_type, Some(5));
+        assert_eq!(metadata.protocol_version.as_deref(), Some("25"));
+        assert!(open_verified_file(raw_path).is_ok());
+        std::fs::write(raw_path, [1, 2, 4]).unwrap();
+        assert!(open_verified_file(raw_path).is_err());
+
+        // rewriting in another format doesn't leave the old copy to be read
+        let path = dir.join("rewritten");
+        let path = path.to_str().unwrap();
+        write_file(path, StorageFormat::Bincode, &1u8, false, None).unwrap();
+        write_file(path, StorageFormat::Json, &2u8, false, None).unwrap();
+        assert_eq!(read_file::<u8>(path, StorageFormat::Bincode).unwrap(), 2);
+        assert!(!Path::new(&format!("{}.bin", path)).exists());
+        assert!(!Path::new(&metadata_path(&format!("{}.bin", path))).exists());
+
+        let missing = read_file::<u8>(dir.join("missing").to_str().unwrap(), StorageFormat::Json);
+        let err = missing.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<std::io::Error>().unwrap().kind(),
+            ErrorKind::NotFound
+        );
+        assert_eq!(
+            "compressed-bincode".parse::<StorageFormat>(),
+            Ok(StorageFormat::CompressedBincode)
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,6 +1,7 @@
 use crate::blake2::Blake2s256;
 use crate::ethereum_types::{Address, U256};
-use crate::run_vms::{run_vms, RunVMsResult, RunVmError};
+use crate::run_vms::{run_vms_with_options, RunVMsResult, RunVmError};
+pub use crate::run_vms::RunVmOptions;
 pub use crate::run_vms::SCHEDULER_TIMESTAMP;
 use crate::snark_wrapper::boojum::field::goldilocks::GoldilocksExt2;
 use crate::snark_wrapper::boojum::gadgets::recursion::recursive_tree_hasher::CircuitGoldilocksPoseidon2Sponge;
@@ -57,9 +58,63 @@ pub fn run<
 ) -> (
     SchedulerCircuitInstanceWitness<MainField, CircuitGoldilocksPoseidon2Sponge, GoldilocksExt2>,
     BlockAuxilaryOutputWitness<MainField>,
+) {
+    run_with_options(
+        caller,
+        entry_point_address,
+        entry_point_code,
+        initial_heap_content,
+        zk_porter_is_available,
+        default_aa_code_hash,
+        evm_simulator_code_hash,
+        used_bytecodes,
+        ram_verification_queries,
+        cycle_limit,
+        geometry,
+        storage,
+        tree,
+        trusted_setup_path,
+        eip_4844_repack_inputs,
+        circuit_callback,
+        queue_simulator_callback,
+        RunVmOptions::default(),
+    )
+}
+
+/// Same as `run`, with additional checks enabled by the options
+pub fn run_with_options<
+    S: Storage,
+    CB: FnMut(ZkSyncBaseLayerCircuit),
+    QSCB: FnMut(
+        u64,
+        RecursionQueueSimulator<MainField>,
+        Vec<ClosedFormInputCompactFormWitness<MainField>>,
+    ),
+>(
+    caller: Address,                 // for real block must be zero
+    entry_point_address: Address,    // for real block must be the bootloader
+    entry_point_code: Vec<[u8; 32]>, // for read block must be a bootloader code
+    initial_heap_content: Vec<u8>,   // bootloader starts with non-deterministic heap
+    zk_porter_is_available: bool,
+    default_aa_code_hash: U256,
+    evm_simulator_code_hash: U256,
+    used_bytecodes: std::collections::HashMap<U256, Vec<[u8; 32]>>, // auxilary information to avoid passing a full set of all used codes
+    ram_verification_queries: Vec<(u32, U256)>, // we may need to check that after the bootloader's memory is filled
+    cycle_limit: usize,
+    geometry: GeometryConfig,
+    storage: S,
+    tree: &mut impl BinarySparseStorageTree<256, 32, 32, 8, 32, Blake2s256, ZkSyncStorageLeaf>,
+    trusted_setup_path: &str,
+    eip_4844_repack_inputs: [Option<Vec<u8>>; MAX_4844_BLOBS_PER_BLOCK],
+    circuit_callback: CB,
+    queue_simulator_callback: QSCB,
+    options: RunVmOptions,
+) -> (
+    SchedulerCircuitInstanceWitness<MainField, CircuitGoldilocksPoseidon2Sponge, GoldilocksExt2>,
+    BlockAuxilaryOutputWitness<MainField>,
 ) {
     let mut out_of_circuit_tracer = GenericNoopTracer::<_>::new();
-    match run_vms(
+    match run_vms_with_options(
         caller,
         entry_point_address,
         entry_point_code,
@@ -78,6 +133,7 @@ pub fn run<
         circuit_callback,
         queue_simulator_callback,
         &mut out_of_circuit_tracer,
+        options,
     ) {
         Ok((scheduler_circuit_witness, aux_data)) => (scheduler_circuit_witness, aux_data),
         Err(err) => {
@@ -88,6 +144,10 @@ pub fn run<
                 RunVmError::OutOfCircuitExecutionError(msg) => {
                     format!("Out-of-circuit execution error: {msg}")
                 }
+                RunVmError::StorageInconsistency(discrepancies) => {
+                    let msg = RunVmError::describe_storage_inconsistency(&discrepancies);
+                    format!("Storage inconsistency error: {msg}")
+                }
             };
             panic!("{error_text}");
         }
@@ -0,0 +1,439 @@
+use crate::ethereum_types::{Address, U256};
+use crate::witness::tree::{EnumeratedBinaryLeaf, ZKSyncTestingTree, ZkSyncStorageLeaf};
+use crate::zk_evm::aux_structures::LogQuery;
+use crate::zk_evm::testing::storage::InMemoryStorage;
+use crate::zkevm_circuits::base_structures::state_diff_record::STATE_DIFF_RECORD_BYTE_ENCODING_LEN;
+use circuit_definitions::encodings::state_diff_record::StateDiffRecord;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+pub type StateDumpResult<T> = Result<T, Box<dyn Error>>;
+
+/// Full state of the rollup shard, e.g. taken from some real node
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    pub next_enumeration_index: u64,
+    pub entries: Vec<StateSnapshotEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateSnapshotEntry {
+    pub address: Address,
+    pub key: U256,
+    pub enumeration_index: u64,
+    pub value: U256,
+}
+
+/// Tree and storage that agree with each other, so witness generation can start from them.
+/// The tree only knows derived keys, so we also keep preimages to be able to export the state back
+pub struct ImportedState {
+    pub tree: ZKSyncTestingTree,
+    pub storage: InMemoryStorage,
+    pub preimages: HashMap<[u8; 32], (Address, U256)>,
+}
+
+impl ImportedState {
+    pub fn empty() -> Self {
+        Self {
+            tree: ZKSyncTestingTree::new(),
+            storage: InMemoryStorage::new(),
+            preimages: HashMap::new(),
+        }
+    }
+
+    /// Applies records in order, same as storage application does. Records with zero enumeration index
+    /// are initial writes and get enumerated by the tree, all others are repeated writes.
+    /// If the dump doesn't start from the empty state then repeated writes can touch leafs that we do not know yet,
+    /// and those are inserted with the enumeration index from the record.
+    /// Nothing is changed if some record is invalid
+    pub fn apply_diff_records(&mut self, records: &[StateDiffRecord]) -> StateDumpResult<()> {
+        let mut final_leafs: HashMap<[u8; 32], ZkSyncStorageLeaf> = HashMap::new();
+        let mut order = vec![];
+        let mut preimages = vec![];
+        let mut next_enumeration_index = self.tree.next_enumeration_index;
+
+        for record in records.iter() {
+            check_derived_key(&record.address, &record.key, &record.derived_key)?;
+
+            let existing = final_leafs
+                .get(&record.derived_key)
+                .or_else(|| self.tree.leafs.get(&record.derived_key))
+                .copied();
+
+            let enumeration_index = if record.enumeration_index == 0 {
+                if let Some(existing) = existing {
+                    return Err(format!(
+                        "initial write into already enumerated leaf {} with index {}",
+                        hex::encode(&record.derived_key),
+                        existing.index
+                    )
+                    .into());
+                }
+                let index = next_enumeration_index;
+                next_enumeration_index += 1;
+
+                index
+            } else {
+                if let Some(existing) = existing {
+                    if existing.index != record.enumeration_index {
+                        return Err(format!(
+                            "repeated write into leaf {} with index {}, while it's enumerated as {}",
+                            hex::encode(&record.derived_key),
+                            record.enumeration_index,
+                            existing.index
+                        )
+                        .into());
+                    }
+                }
+                next_enumeration_index =
+                    std::cmp::max(next_enumeration_index, record.enumeration_index + 1);
+
+                record.enumeration_index
+            };
+
+            let mut leaf = ZkSyncStorageLeaf::empty();
+            record.final_value.to_big_endian(leaf.value_ref_mut());
+            leaf.set_index(enumeration_index);
+            if final_leafs.insert(record.derived_key, leaf).is_none() {
+                order.push(record.derived_key);
+            }
+            preimages.push((record.derived_key, (record.address, record.key)));
+        }
+        self.check_unique_indexes(&order, &final_leafs)?;
+
+        self.preimages.extend(preimages);
+        self.insert_leafs(order, final_leafs);
+        self.tree.next_enumeration_index =
+            std::cmp::max(self.tree.next_enumeration_index, next_enumeration_index);
+
+        Ok(())
+    }
+
+    /// Nothing is changed if some entry is invalid
+    pub fn apply_snapshot(&mut self, snapshot: &StateSnapshot) -> StateDumpResult<()> {
+        let mut final_leafs = HashMap::new();
+        let mut order = vec![];
+        let mut preimages = vec![];
+
+        for entry in snapshot.entries.iter() {
+            if entry.enumeration_index == 0
+                || entry.enumeration_index >= snapshot.next_enumeration_index
+            {
+                return Err(format!(
+                    "invalid enumeration index {} for address {:?} and key {:x}, next enumeration index is {}",
+                    entry.enumeration_index, entry.address, entry.key, snapshot.next_enumeration_index
+                )
+                .into());
+            }
+            let derived_key = LogQuery::derive_final_address_for_params(&entry.address, &entry.key);
+
+            let mut leaf = ZkSyncStorageLeaf::empty();
+            entry.value.to_big_endian(leaf.value_ref_mut());
+            leaf.set_index(entry.enumeration_index);
+            if final_leafs.insert(derived_key, leaf).is_some() {
+                return Err(format!(
+                    "duplicate entry for address {:?} and key {:x}",
+                    entry.address, entry.key
+                )
+                .into());
+            }
+            order.push(derived_key);
+            preimages.push((derived_key, (entry.address, entry.key)));
+        }
+        self.check_unique_indexes(&order, &final_leafs)?;
+
+        self.preimages.extend(preimages);
+        self.insert_leafs(order, final_leafs);
+        self.tree.next_enumeration_index = std::cmp::max(
+            self.tree.next_enumeration_index,
+            snapshot.next_enumeration_index,
+        );
+
+        Ok(())
+    }
+
+    // every enumeration index must belong to a single leaf, including the leafs already in the tree
+    fn check_unique_indexes(
+        &self,
+        order: &[[u8; 32]],
+        final_leafs: &HashMap<[u8; 32], ZkSyncStorageLeaf>,
+    ) -> StateDumpResult<()> {
+        let mut owners: HashMap<u64, [u8; 32]> = self
+            .tree
+            .leafs
+            .iter()
+            .filter(|(derived_key, _)| !final_leafs.contains_key(*derived_key))
+            .map(|(derived_key, leaf)| (leaf.index, *derived_key))
+            .collect();
+
+        for derived_key in order.iter() {
+            let index = final_leafs[derived_key].index;
+            if let Some(owner) = owners.insert(index, *derived_key) {
+                return Err(format!(
+                    "enumeration index {} is used by both {} and {}",
+                    index,
+                    hex::encode(&owner),
+                    hex::encode(derived_key)
+                )
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn insert_leafs(
+        &mut self,
+        order: Vec<[u8; 32]>,
+        mut final_leafs: HashMap<[u8; 32], ZkSyncStorageLeaf>,
+    ) {
+        let mut storage_logs = Vec::with_capacity(order.len());
+        let mut leafs = Vec::with_capacity(order.len());
+        for derived_key in order.iter() {
+            let leaf = final_leafs.remove(derived_key).unwrap();
+            let (address, key) = self.preimages[derived_key];
+            storage_logs.push((0, address, key, U256::from_big_endian(leaf.value())));
+            leafs.push(leaf);
+        }
+
+        self.storage.populate(storage_logs);
+        self.tree.insert_many_enumerated_leafs(&order, leafs);
+    }
+
+    /// Every known leaf as a repeated write with zero initial value, ordered by enumeration index,
+    /// so applying the result to the empty state gives the same tree
+    pub fn export_diff_records(&self) -> StateDumpResult<Vec<StateDiffRecord>> {
+        let mut records = Vec::with_capacity(self.tree.leafs.len());
+        for (derived_key, leaf) in self.tree.leafs.iter() {
+            let Some((address, key)) = self.preimages.get(derived_key).copied() else {
+                return Err(
+                    format!("preimage for leaf {} is unknown", hex::encode(derived_key)).into(),
+                );
+            };
+
+            records.push(StateDiffRecord {
+                address,
+                key,
+                derived_key: *derived_key,
+                enumeration_index: leaf.index,
+                initial_value: U256::zero(),
+                final_value: U256::from_big_endian(leaf.value()),
+            });
+        }
+        records.sort_by_key(|el| el.enumeration_index);
+
+        Ok(records)
+    }
+
+    pub fn export_snapshot(&self) -> StateDumpResult<StateSnapshot> {
+        let entries = self
+            .export_diff_records()?
+            .into_iter()
+            .map(|el| StateSnapshotEntry {
+                address: el.address,
+                key: el.key,
+                enumeration_index: el.enumeration_index,
+                value: el.final_value,
+            })
+            .collect();
+
+        Ok(StateSnapshot {
+            next_enumeration_index: self.tree.next_enumeration_index,
+            entries,
+        })
+    }
+}
+
+fn check_derived_key(address: &Address, key: &U256, derived_key: &[u8; 32]) -> StateDumpResult<()> {
+    let expected = LogQuery::derive_final_address_for_params(address, key);
+    if &expected != derived_key {
+        return Err(format!(
+            "derived key {} doesn't match address {:?} and key {:x}, expected {}",
+            hex::encode(derived_key),
+            address,
+            key,
+            hex::encode(&expected)
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Reads a dump that is a plain concatenation of `StateDiffRecord` encodings
+pub fn read_diff_records(path: impl AsRef<Path>) -> StateDumpResult<Vec<StateDiffRecord>> {
+    let mut content = vec![];
+    File::open(path)?.read_to_end(&mut content)?;
+    if content.len() % STATE_DIFF_RECORD_BYTE_ENCODING_LEN != 0 {
+        return Err(format!(
+            "dump length {} is not a multiple of the record length {}",
+            content.len(),
+            STATE_DIFF_RECORD_BYTE_ENCODING_LEN
+        )
+        .into());
+    }
+
+    let records = content
+        .array_chunks::<STATE_DIFF_RECORD_BYTE_ENCODING_LEN>()
+        .map(StateDiffRecord::decode)
+        .collect();
+
+    Ok(records)
+}
+
+pub fn write_diff_records(
+    path: impl AsRef<Path>,
+    records: &[StateDiffRecord],
+) -> StateDumpResult<()> {
+    let mut file = File::create(path)?;
+    for record in records.iter() {
+        file.write_all(&record.encode())?;
+    }
+
+    Ok(())
+}
+
+pub fn read_state_snapshot(path: impl AsRef<Path>) -> StateDumpResult<StateSnapshot> {
+    let file = File::open(path)?;
+
+    Ok(serde_json::from_reader(file)?)
+}
+
+pub fn write_state_snapshot(
+    path: impl AsRef<Path>,
+    snapshot: &StateSnapshot,
+) -> StateDumpResult<()> {
+    let file = File::create(path)?;
+    serde_json::to_writer(file, snapshot)?;
+
+    Ok(())
+}
+
+pub fn state_from_diff_records(records: &[StateDiffRecord]) -> StateDumpResult<ImportedState> {
+    let mut state = ImportedState::empty();
+    state.apply_diff_records(records)?;
+
+    Ok(state)
+}
+
+pub fn state_from_snapshot(snapshot: &StateSnapshot) -> StateDumpResult<ImportedState> {
+    let mut state = ImportedState::empty();
+    state.apply_snapshot(snapshot)?;
+
+    Ok(state)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::witness::tree::BinarySparseStorageTree;
+
+    fn record(address: u64, key: u64, enumeration_index: u64, value: u64) -> StateDiffRecord {
+        let address = Address::from_low_u64_be(address);
+        let key = U256::from(key);
+        StateDiffRecord {
+            address,
+            key,
+            derived_key: LogQuery::derive_final_address_for_params(&address, &key),
+            enumeration_index,
+            initial_value: U256::zero(),
+            final_value: U256::from(value),
+        }
+    }
+
+    #[test]
+    fn import_matches_storage_application() {
+        // two batches: initial writes, then repeated write and one more initial write
+        let records = vec![
+            record(0x8001, 1, 0, 10),
+            record(0x8001, 2, 0, 20),
+            record(0x8002, 1, 0, 30),
+            record(0x8001, 2, 2, 21),
+            record(0x8003, 5, 0, 40),
+        ];
+        let state = state_from_diff_records(&records).unwrap();
+
+        let mut reference = ZKSyncTestingTree::empty();
+        for el in records.iter() {
+            let mut leaf = ZkSyncStorageLeaf::empty();
+            el.final_value.to_big_endian(leaf.value_ref_mut());
+            reference.insert_leaf(&el.derived_key, leaf);
+        }
+        assert_eq!(state.tree.root(), reference.root());
+        assert_eq!(
+            state.tree.next_enumeration_index(),
+            reference.next_enumeration_index()
+        );
+
+        // and back
+        let exported = state.export_diff_records().unwrap();
+        assert_eq!(exported.len(), 4);
+        let encoded: Vec<_> = exported.iter().map(|el| el.encode()).collect();
+        let decoded: Vec<_> = encoded.iter().map(StateDiffRecord::decode).collect();
+        let reimported = state_from_diff_records(&decoded).unwrap();
+        assert_eq!(reimported.tree.root(), state.tree.root());
+
+        let snapshot = state.export_snapshot().unwrap();
+        let from_snapshot = state_from_snapshot(&snapshot).unwrap();
+        assert_eq!(from_snapshot.tree.root(), state.tree.root());
+        assert_eq!(
+            from_snapshot.tree.next_enumeration_index(),
+            state.tree.next_enumeration_index()
+        );
+    }
+
+    #[test]
+    fn inconsistent_records_are_rejected() {
+        let records = vec![record(0x8001, 1, 0, 10), record(0x8001, 1, 0, 11)];
+        assert!(state_from_diff_records(&records).is_err());
+
+        let records = vec![record(0x8001, 1, 0, 10), record(0x8001, 1, 5, 11)];
+        assert!(state_from_diff_records(&records).is_err());
+
+        let mut wrong_key = record(0x8001, 1, 0, 10);
+        wrong_key.derived_key[0] ^= 1;
+        assert!(state_from_diff_records(&[wrong_key]).is_err());
+
+        // different keys with the same enumeration index
+        let records = vec![record(0x8001, 1, 3, 10), record(0x8001, 2, 3, 11)];
+        assert!(state_from_diff_records(&records).is_err());
+        let records = vec![record(0x8001, 1, 0, 10), record(0x8001, 2, 1, 11)];
+        assert!(state_from_diff_records(&records).is_err());
+        let snapshot = StateSnapshot {
+            next_enumeration_index: 3,
+            entries: vec![
+                StateSnapshotEntry {
+                    address: Address::from_low_u64_be(0x8001),
+                    key: U256::from(1),
+                    enumeration_index: 1,
+                    value: U256::from(10),
+                },
+                StateSnapshotEntry {
+                    address: Address::from_low_u64_be(0x8001),
+                    key: U256::from(2),
+                    enumeration_index: 1,
+                    value: U256::from(11),
+                },
+            ],
+        };
+        assert!(state_from_snapshot(&snapshot).is_err());
+    }
+
+    #[test]
+    fn rejected_records_leave_state_untouched() {
+        let mut state = state_from_diff_records(&[record(0x8001, 1, 0, 10)]).unwrap();
+        let root = state.tree.root();
+
+        // the first record is fine, the second one reuses index of the existing leaf
+        let records = vec![record(0x8002, 1, 0, 20), record(0x8003, 1, 1, 30)];
+        assert!(state.apply_diff_records(&records).is_err());
+        assert_eq!(state.tree.root(), root);
+        assert_eq!(state.tree.next_enumeration_index(), 2);
+        assert_eq!(state.preimages.len(), 1);
+        assert_eq!(state.export_diff_records().unwrap().len(), 1);
+    }
+}
@@ -52,8 +52,11 @@ pub use circuit_sequencer_api::INITIAL_MONOTONIC_CYCLE_COUNTER;
 pub mod helper;
 pub(crate) mod tests;
 
+pub mod batch_verification;
 pub mod compute_setups;
 pub mod proof_wrapper_utils;
+pub mod proving_pipeline;
+pub mod vk_manifest;
 
 pub use tests::complex_tests::utils::empty_node_proof;
 
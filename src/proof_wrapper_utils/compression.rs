@@ -12,7 +12,8 @@ pub(crate) fn compute_compression_vks_and_write<DS: SetupDataSource + BlockDataS
         ));
 
         let compression_circuit =
-            ZkSyncCompressionLayerCircuit::from_witness_and_vk(None, vk, circuit_type);
+            ZkSyncCompressionLayerCircuit::from_witness_and_vk(None, vk, circuit_type)
+                .expect("VK of previous circuit should belong to a proof config profile");
         let proof_config = compression_circuit.proof_config_for_compression_step();
 
         let (_, _, vk, _, _, _, finalization_hint) = create_compression_layer_setup_data(
@@ -37,61 +38,62 @@ pub(crate) fn compute_compression_vks_and_write<DS: SetupDataSource + BlockDataS
     }
 }
 
+/// Proves every compression layer that doesn't have a proof, VK and hint in the source yet
 pub(crate) fn compute_compression_circuits<DS: SetupDataSource + BlockDataSource>(
     source: &mut DS,
     config: WrapperConfig,
     worker: &Worker,
-) {
+) -> SourceResult<()> {
     for circuit_type in config.get_compression_types() {
         if source.get_compression_proof(circuit_type).is_err()
             || source.get_compression_vk(circuit_type).is_err()
             || source.get_compression_hint(circuit_type).is_err()
         {
-            let proof = get_proof_for_previous_circuit(source, circuit_type).expect(&format!(
-                "Proof of previous circuit should be present. Current circuit type: {}",
-                circuit_type
-            ));
-            let vk = get_vk_for_previous_circuit(source, circuit_type).expect(&format!(
-                "VK of previous circuit should be present. Current circuit type: {}",
-                circuit_type
-            ));
+            let proof = get_proof_for_previous_circuit(source, circuit_type).map_err(|el| {
+                format!(
+                    "Proof of previous circuit should be present. Current circuit type: {}: {}",
+                    circuit_type, el
+                )
+            })?;
+            let vk = get_vk_for_previous_circuit(source, circuit_type).map_err(|el| {
+                format!(
+                    "VK of previous circuit should be present. Current circuit type: {}: {}",
+                    circuit_type, el
+                )
+            })?;
 
             let compression_circuit =
-                ZkSyncCompressionLayerCircuit::from_witness_and_vk(Some(proof), vk, circuit_type);
+                ZkSyncCompressionLayerCircuit::from_witness_and_vk(Some(proof), vk, circuit_type)?;
 
             let (vk, finalization_hint, proof) =
-                compute_compression_circuit_inner(compression_circuit, &worker);
-
-            source
-                .set_compression_vk(ZkSyncCompressionLayerStorage::from_inner(
-                    circuit_type,
-                    vk.clone(),
-                ))
-                .unwrap();
-            source
-                .set_compression_hint(ZkSyncCompressionLayerStorage::from_inner(
-                    circuit_type,
-                    finalization_hint.clone(),
-                ))
-                .unwrap();
-            source
-                .set_compression_proof(ZkSyncCompressionLayerStorage::from_inner(
-                    circuit_type,
-                    proof,
-                ))
-                .unwrap();
+                compute_compression_circuit_inner(compression_circuit, &worker)?;
+
+            source.set_compression_vk(ZkSyncCompressionLayerStorage::from_inner(
+                circuit_type,
+                vk.clone(),
+            ))?;
+            source.set_compression_hint(ZkSyncCompressionLayerStorage::from_inner(
+                circuit_type,
+                finalization_hint.clone(),
+            ))?;
+            source.set_compression_proof(ZkSyncCompressionLayerStorage::from_inner(
+                circuit_type,
+                proof,
+            ))?;
         }
     }
+
+    Ok(())
 }
 
 fn compute_compression_circuit_inner(
     circuit: ZkSyncCompressionLayerCircuit,
     worker: &Worker,
-) -> (
+) -> SourceResult<(
     ZkSyncCompressionVerificationKey,
     FinalizationHintsForProver,
     ZkSyncCompressionProof,
-) {
+)> {
     let start = std::time::Instant::now();
 
     let circuit_type = circuit.numeric_circuit_type();
@@ -131,7 +133,9 @@ fn compute_compression_circuit_inner(
 
     let is_valid = verify_compression_layer_proof::<NoPow>(&setup_circuit, &proof, &vk);
 
-    assert!(is_valid);
+    if !is_valid {
+        return Err(format!("Proof for compression {} is not valid", circuit_type).into());
+    }
 
     println!(
         "Compression {} is done, taken {:?}",
@@ -139,5 +143,5 @@ fn compute_compression_circuit_inner(
         start.elapsed()
     );
 
-    (vk, finalization_hint, proof)
+    Ok((vk, finalization_hint, proof))
 }
@@ -14,7 +14,8 @@ pub(crate) fn compute_compression_for_wrapper_vk_and_write<
         None,
         vk,
         compression_for_wrapper_type,
-    );
+    )
+    .expect("VK of previous circuit should belong to a proof config profile");
 
     let proof_config = circuit.proof_config_for_compression_step();
 
@@ -39,11 +40,12 @@ pub(crate) fn compute_compression_for_wrapper_vk_and_write<
         .unwrap();
 }
 
+/// Proves the last compression layer unless its proof, VK and hint are in the source already
 pub(crate) fn compute_compression_for_wrapper_circuit<DS: SetupDataSource + BlockDataSource>(
     source: &mut DS,
     config: WrapperConfig,
     worker: &Worker,
-) {
+) -> SourceResult<()> {
     let circuit_type = config.get_compression_for_wrapper_type();
 
     if source.get_compression_for_wrapper_vk(circuit_type).is_err()
@@ -54,51 +56,51 @@ pub(crate) fn compute_compression_for_wrapper_circuit<DS: SetupDataSource + Bloc
             .get_compression_for_wrapper_proof(circuit_type)
             .is_err()
     {
-        let proof = get_proof_for_previous_circuit(source, circuit_type).expect(&format!(
-            "Proof of previous circuit should be present. Current circuit type: {}",
-            circuit_type
-        ));
-        let vk = get_vk_for_previous_circuit(source, circuit_type).expect(&format!(
-            "VK of previous circuit should be present. Current circuit type: {}",
-            circuit_type
-        ));
+        let proof = get_proof_for_previous_circuit(source, circuit_type).map_err(|el| {
+            format!(
+                "Proof of previous circuit should be present. Current circuit type: {}: {}",
+                circuit_type, el
+            )
+        })?;
+        let vk = get_vk_for_previous_circuit(source, circuit_type).map_err(|el| {
+            format!(
+                "VK of previous circuit should be present. Current circuit type: {}: {}",
+                circuit_type, el
+            )
+        })?;
 
         let compression_circuit =
-            ZkSyncCompressionForWrapperCircuit::from_witness_and_vk(Some(proof), vk, circuit_type);
+            ZkSyncCompressionForWrapperCircuit::from_witness_and_vk(Some(proof), vk, circuit_type)?;
 
         let (vk, finalization_hint, proof) =
-            compute_compression_for_wrapper_circuit_inner(compression_circuit, worker);
+            compute_compression_for_wrapper_circuit_inner(compression_circuit, worker)?;
 
         // we did it above
-        source
-            .set_compression_for_wrapper_vk(ZkSyncCompressionLayerStorage::from_inner(
-                circuit_type,
-                vk.clone(),
-            ))
-            .unwrap();
-        source
-            .set_compression_for_wrapper_hint(ZkSyncCompressionLayerStorage::from_inner(
-                circuit_type,
-                finalization_hint.clone(),
-            ))
-            .unwrap();
-        source
-            .set_compression_for_wrapper_proof(ZkSyncCompressionLayerStorage::from_inner(
-                circuit_type,
-                proof,
-            ))
-            .unwrap();
+        source.set_compression_for_wrapper_vk(ZkSyncCompressionLayerStorage::from_inner(
+            circuit_type,
+            vk.clone(),
+        ))?;
+        source.set_compression_for_wrapper_hint(ZkSyncCompressionLayerStorage::from_inner(
+            circuit_type,
+            finalization_hint.clone(),
+        ))?;
+        source.set_compression_for_wrapper_proof(ZkSyncCompressionLayerStorage::from_inner(
+            circuit_type,
+            proof,
+        ))?;
     }
+
+    Ok(())
 }
 
 fn compute_compression_for_wrapper_circuit_inner(
     circuit: ZkSyncCompressionForWrapperCircuit,
     worker: &Worker,
-) -> (
+) -> SourceResult<(
     ZkSyncCompressionVerificationKeyForWrapper,
     FinalizationHintsForProver,
     ZkSyncCompressionProofForWrapper,
-) {
+)> {
     let start = std::time::Instant::now();
 
     let circuit_type = circuit.numeric_circuit_type();
@@ -141,7 +143,13 @@ fn compute_compression_for_wrapper_circuit_inner(
 
     let is_valid = verify_compression_for_wrapper_proof::<NoPow>(&setup_circuit, &proof, &vk);
 
-    assert!(is_valid);
+    if !is_valid {
+        return Err(format!(
+            "Proof for compression for wrapper {} is not valid",
+            circuit_type
+        )
+        .into());
+    }
 
     println!(
         "Compression for wrapper {} is done, taken {:?}",
@@ -149,5 +157,5 @@ fn compute_compression_for_wrapper_circuit_inner(
         start.elapsed()
     );
 
-    (vk, finalization_hint, proof)
+    Ok((vk, finalization_hint, proof))
 }
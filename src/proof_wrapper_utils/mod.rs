@@ -133,15 +133,18 @@ pub fn wrap_proof(
 
     // 1. All but one layers of compression with Goldilocks Poseidon2 hash
     println!("Computing a sequence of compressing circuits");
-    compute_compression_circuits(&mut source, config, &worker);
+    compute_compression_circuits(&mut source, config, &worker)
+        .expect("Failed to compute compression circuits");
     println!("Done computing a sequence of compressing circuits");
     // 2. Final compression with Bn256 Poseidon2 hash
     println!("Computing a Boojum circuit using Bn256 Poseidon2 hash");
-    compute_compression_for_wrapper_circuit(&mut source, config, &worker);
+    compute_compression_for_wrapper_circuit(&mut source, config, &worker)
+        .expect("Failed to compute compression for wrapper circuit");
     println!("Done computing a Boojum circuit using Bn256 Poseidon2 hash");
     // 3. Wrapper
     println!("Computing a Bellman circuit over Bn256");
-    compute_wrapper_proof_and_vk(&mut source, config, &bellman_worker);
+    compute_wrapper_proof_and_vk(&mut source, config, &bellman_worker)
+        .expect("Failed to compute wrapper proof");
     println!("Done computing a Bellman circuit over Bn256");
 
     // Get and return wrapper proof and vk
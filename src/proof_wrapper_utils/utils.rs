@@ -4,19 +4,33 @@ pub(crate) const CRS_FILE_ENV_VAR: &str = "CRS_FILE";
 
 /// Just to check if the file and environment variable are not forgotten
 pub fn check_trusted_setup_file_existace() {
-    let crs_file_str = std::env::var(CRS_FILE_ENV_VAR).expect("CRS_FILE env variable:");
-    let crs_file_path = std::path::Path::new(&crs_file_str);
-    let _crs_file = std::fs::File::open(&crs_file_path)
-        .expect(format!("Trying to open CRS FILE: {:?}", crs_file_path).as_str());
+    if let Err(err) = try_check_trusted_setup_file_existace() {
+        panic!("{}", err);
+    }
+}
+
+/// Same as `check_trusted_setup_file_existace`, but returns an error instead of panicking
+pub fn try_check_trusted_setup_file_existace() -> SourceResult<()> {
+    open_trusted_setup_file().map(|_| ())
 }
 
 /// Uploads trusted setup file to the RAM
 pub fn get_trusted_setup() -> Crs<Bn256, CrsForMonomialForm> {
-    let crs_file_str = std::env::var(CRS_FILE_ENV_VAR).expect("CRS_FILE env variable:");
+    try_get_trusted_setup().unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Same as `get_trusted_setup`, but returns an error instead of panicking
+pub fn try_get_trusted_setup() -> SourceResult<Crs<Bn256, CrsForMonomialForm>> {
+    let crs_file = open_trusted_setup_file()?;
+    Crs::read(&crs_file).map_err(|el| format!("Trying to read CRS FILE: {}", el).into())
+}
+
+fn open_trusted_setup_file() -> SourceResult<std::fs::File> {
+    let crs_file_str =
+        std::env::var(CRS_FILE_ENV_VAR).map_err(|el| format!("CRS_FILE env variable: {}", el))?;
     let crs_file_path = std::path::Path::new(&crs_file_str);
-    let crs_file = std::fs::File::open(&crs_file_path)
-        .expect(format!("Trying to open CRS FILE: {:?}", crs_file_path).as_str());
-    Crs::read(&crs_file).expect(format!("Trying to read CRS FILE: {:?}", crs_file_path).as_str())
+    std::fs::File::open(&crs_file_path)
+        .map_err(|el| format!("Trying to open CRS FILE: {:?}: {}", crs_file_path, el).into())
 }
 
 /// Computes wrapper public input from stark one
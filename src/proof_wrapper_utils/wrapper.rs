@@ -24,11 +24,12 @@ pub fn get_wrapper_setup_and_vk_from_compression_vk(
     )
 }
 
+/// Computes wrapper setup, vk and proof, skipping the ones that are in the source already
 pub(crate) fn compute_wrapper_proof_and_vk<DS: SetupDataSource + BlockDataSource>(
     source: &mut DS,
     config: WrapperConfig,
     worker: &BellmanWorker,
-) {
+) -> SourceResult<()> {
     let wrapper_type = config.get_wrapper_type();
 
     // {
@@ -56,22 +57,23 @@ pub(crate) fn compute_wrapper_proof_and_vk<DS: SetupDataSource + BlockDataSource
 
     println!("Computing Bn256 wrapper setup");
     if source.get_wrapper_setup(wrapper_type).is_err() {
-        let vk = source.get_compression_for_wrapper_vk(wrapper_type).unwrap();
+        let vk = source.get_compression_for_wrapper_vk(wrapper_type)?;
 
         let snark_setup = compute_wrapper_setup_inner(vk, config, worker);
 
         let snark_setup =
             ZkSyncCompressionLayerStorage::from_inner(wrapper_type, Arc::new(snark_setup));
-        source.set_wrapper_setup(snark_setup).unwrap();
+        source.set_wrapper_setup(snark_setup)?;
     }
 
     println!("Computing Bn256 wrapper vk");
     if source.get_wrapper_vk(wrapper_type).is_err() {
         let start = std::time::Instant::now();
-        let snark_setup = source.get_wrapper_setup(wrapper_type).unwrap();
+        let snark_setup = source.get_wrapper_setup(wrapper_type)?;
 
-        let crs_mons = get_trusted_setup();
-        let snark_vk = SnarkVK::from_setup(&snark_setup.into_inner(), worker, &crs_mons).unwrap();
+        let crs_mons = try_get_trusted_setup()?;
+        let snark_vk = SnarkVK::from_setup(&snark_setup.into_inner(), worker, &crs_mons)
+            .map_err(|el| format!("Failed to compute wrapper vk: {:?}", el))?;
 
         println!(
             "Wrapper vk {} is done, taken {:?}",
@@ -80,31 +82,33 @@ pub(crate) fn compute_wrapper_proof_and_vk<DS: SetupDataSource + BlockDataSource
         );
 
         let snark_vk = ZkSyncCompressionLayerStorage::from_inner(wrapper_type, snark_vk);
-        source.set_wrapper_vk(snark_vk).unwrap();
+        source.set_wrapper_vk(snark_vk)?;
     }
 
     println!("Computing Bn256 wrapper proof");
     if source.get_wrapper_proof(wrapper_type).is_err() {
-        let proof = source
-            .get_compression_for_wrapper_proof(wrapper_type)
-            .unwrap();
-        let vk = source.get_compression_for_wrapper_vk(wrapper_type).unwrap();
+        let proof = source.get_compression_for_wrapper_proof(wrapper_type)?;
+        let vk = source.get_compression_for_wrapper_vk(wrapper_type)?;
 
-        let snark_setup = source.get_wrapper_setup(wrapper_type).unwrap();
+        let snark_setup = source.get_wrapper_setup(wrapper_type)?;
 
-        let snark_proof = compute_wrapper_proof_inner(proof, vk, snark_setup, config, worker);
+        let snark_proof = compute_wrapper_proof_inner(proof, vk, snark_setup, config, worker)?;
 
         println!("Verifying");
-        let snark_vk = source.get_wrapper_vk(wrapper_type).unwrap();
+        let snark_vk = source.get_wrapper_vk(wrapper_type)?;
         use crate::snark_wrapper::franklin_crypto::bellman::plonk::better_better_cs::verifier::verify;
         let is_valid =
             verify::<_, _, RollingKeccakTranscript<Fr>>(&snark_vk.into_inner(), &snark_proof, None)
-                .unwrap();
-        assert!(is_valid);
+                .map_err(|el| format!("Failed to verify wrapper proof: {:?}", el))?;
+        if !is_valid {
+            return Err(format!("Wrapper proof {} is not valid", wrapper_type).into());
+        }
 
         let snark_proof = ZkSyncCompressionLayerStorage::from_inner(wrapper_type, snark_proof);
-        source.set_wrapper_proof(snark_proof).unwrap();
+        source.set_wrapper_proof(snark_proof)?;
     }
+
+    Ok(())
 }
 
 pub(crate) fn compute_wrapper_setup_inner(
@@ -209,8 +213,8 @@ fn compute_wrapper_proof_inner(
     snark_setup: ZkSyncSnarkWrapperSetup,
     config: WrapperConfig,
     worker: &BellmanWorker,
-) -> SnarkProof<Bn256, ZkSyncSnarkWrapperCircuit> {
-    check_trusted_setup_file_existace();
+) -> SourceResult<SnarkProof<Bn256, ZkSyncSnarkWrapperCircuit>> {
+    try_check_trusted_setup_file_existace()?;
 
     let start = std::time::Instant::now();
     let wrapper_type = config.get_wrapper_type();
@@ -242,10 +246,14 @@ fn compute_wrapper_proof_inner(
     };
 
     println!("Synthesizing Bn256 Bellman wrapping proof");
-    wrapper_circuit.synthesize(&mut assembly).unwrap();
+    wrapper_circuit
+        .synthesize(&mut assembly)
+        .map_err(|el| format!("Failed to synthesize wrapper circuit: {:?}", el))?;
 
     assembly.finalize_to_size_log_2(L1_VERIFIER_DOMAIN_SIZE_LOG);
-    assert!(assembly.is_satisfied());
+    if !assembly.is_satisfied() {
+        return Err("Bn256 Bellman wrapper circuit is not satisfied".into());
+    }
     println!("Bn256 Bellman wrapper circuit is SATISFIED");
 
     println!(
@@ -254,7 +262,7 @@ fn compute_wrapper_proof_inner(
         wrapper_type
     );
 
-    let crs_mons = get_trusted_setup();
+    let crs_mons = try_get_trusted_setup()?;
 
     println!("Proving");
     let proof =
@@ -266,7 +274,7 @@ fn compute_wrapper_proof_inner(
                 TranscriptForWrapper,
                 ZkSyncCompressionWrapper,
             >, RollingKeccakTranscript<Fr>>(worker, &snark_setup, &crs_mons, None)
-            .unwrap();
+            .map_err(|el| format!("Failed to prove wrapper circuit: {:?}", el))?;
 
     println!(
         "Wrapper proof {} is done, taken {:?}",
@@ -274,5 +282,5 @@ fn compute_wrapper_proof_inner(
         start.elapsed()
     );
 
-    proof
+    Ok(proof)
 }
@@ -34,163 +34,62 @@ type H = GoldilocksPoseidon2Sponge<AbsorptionModeOverwrite>;
 
 use crate::boojum::cs::implementations::setup::FinalizationHintsForProver;
 
-pub fn create_base_layer_setup_data(
-    circuit: ZkSyncBaseLayerCircuit,
+use crate::boojum::cs::implementations::transcript::Transcript;
+use crate::boojum::cs::oracle::TreeHasher;
+
+pub type TreeHasherForWrapper = Poseidon2Sponge<Bn256, F, AbsorptionModeReplacement<Fr>, 2, 3>;
+pub type TranscriptForWrapper = Poseidon2Transcript<Bn256, F, AbsorptionModeReplacement<Fr>, 2, 3>;
+
+/// Circuit of any layer (base, recursive, compression or compression for wrapper),
+/// that can be set up, proven and verified in the same way
+pub trait ProvableCircuit: Sized {
+    type TreeHasher: TreeHasher<F, Output = <Self::Transcript as Transcript<F>>::CompatibleCap>;
+    type Transcript: Transcript<F, TransciptParameters = ()>;
+
+    fn numeric_circuit_type(&self) -> u8;
+    fn short_description(&self) -> &'static str;
+    /// Whether the witness contains proofs that are verified inside of the circuit
+    fn verifies_proofs(&self) -> bool;
+
+    /// Synthesizes the circuit for setup, returns the assembly and the hint to pad it
+    fn synthesize_for_setup(
+        self,
+    ) -> (
+        CSReferenceAssembly<F, P, SetupCSConfig>,
+        FinalizationHintsForProver,
+    );
+    fn synthesize_for_proving(
+        self,
+        finalization_hint: &FinalizationHintsForProver,
+    ) -> CSReferenceAssembly<F, P, ProvingCSConfig>;
+
+    fn verify<POW: PoWRunner>(
+        &self,
+        proof: &CircuitProof<Self>,
+        vk: &CircuitVerificationKey<Self>,
+    ) -> bool;
+}
+
+pub type CircuitProof<C> = Proof<F, <C as ProvableCircuit>::TreeHasher, EXT>;
+pub type CircuitVerificationKey<C> = VerificationKey<F, <C as ProvableCircuit>::TreeHasher>;
+pub type CircuitSetupTree<C> = MerkleTreeWithCap<F, <C as ProvableCircuit>::TreeHasher>;
+pub type CircuitFinalizationHint = FinalizationHintsForProver;
+
+pub fn create_setup_data<C: ProvableCircuit>(
+    circuit: C,
     worker: &Worker,
     fri_lde_factor: usize,
     merkle_tree_cap_size: usize,
 ) -> (
     SetupBaseStorage<F, P>,
     SetupStorage<F, P>,
-    VerificationKey<F, H>,
-    MerkleTreeWithCap<F, H>,
+    CircuitVerificationKey<C>,
+    CircuitSetupTree<C>,
     DenseVariablesCopyHint,
     DenseWitnessCopyHint,
-    FinalizationHintsForProver,
+    CircuitFinalizationHint,
 ) {
-    use crate::boojum::cs::cs_builder::new_builder;
-    use crate::boojum::cs::cs_builder_reference::CsReferenceImplementationBuilder;
-
-    let geometry = circuit.geometry();
-    let (max_trace_len, num_vars) = circuit.size_hint();
-
-    let builder_impl = CsReferenceImplementationBuilder::<GoldilocksField, P, SetupCSConfig>::new(
-        geometry,
-        max_trace_len.unwrap(),
-    );
-
-    let builder = new_builder::<_, GoldilocksField>(builder_impl);
-
-    let (cs, finalization_hint) = match circuit {
-        ZkSyncBaseLayerCircuit::MainVM(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            let (_, finalization_hint) = cs.pad_and_shrink();
-            (cs.into_assembly::<std::alloc::Global>(), finalization_hint)
-        }
-        ZkSyncBaseLayerCircuit::CodeDecommittmentsSorter(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            let (_, finalization_hint) = cs.pad_and_shrink();
-            (cs.into_assembly::<std::alloc::Global>(), finalization_hint)
-        }
-        ZkSyncBaseLayerCircuit::CodeDecommitter(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            let (_, finalization_hint) = cs.pad_and_shrink();
-            (cs.into_assembly::<std::alloc::Global>(), finalization_hint)
-        }
-        ZkSyncBaseLayerCircuit::LogDemuxer(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            let (_, finalization_hint) = cs.pad_and_shrink();
-            (cs.into_assembly::<std::alloc::Global>(), finalization_hint)
-        }
-        ZkSyncBaseLayerCircuit::KeccakRoundFunction(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            let (_, finalization_hint) = cs.pad_and_shrink();
-            (cs.into_assembly::<std::alloc::Global>(), finalization_hint)
-        }
-        ZkSyncBaseLayerCircuit::Sha256RoundFunction(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            let (_, finalization_hint) = cs.pad_and_shrink();
-            (cs.into_assembly::<std::alloc::Global>(), finalization_hint)
-        }
-        ZkSyncBaseLayerCircuit::ECRecover(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            let (_, finalization_hint) = cs.pad_and_shrink();
-            (cs.into_assembly::<std::alloc::Global>(), finalization_hint)
-        }
-        ZkSyncBaseLayerCircuit::RAMPermutation(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            let (_, finalization_hint) = cs.pad_and_shrink();
-            (cs.into_assembly::<std::alloc::Global>(), finalization_hint)
-        }
-        ZkSyncBaseLayerCircuit::StorageSorter(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            let (_, finalization_hint) = cs.pad_and_shrink();
-            (cs.into_assembly::<std::alloc::Global>(), finalization_hint)
-        }
-        ZkSyncBaseLayerCircuit::StorageApplication(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            let (_, finalization_hint) = cs.pad_and_shrink();
-            (cs.into_assembly::<std::alloc::Global>(), finalization_hint)
-        }
-        ZkSyncBaseLayerCircuit::EventsSorter(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            let (_, finalization_hint) = cs.pad_and_shrink();
-            (cs.into_assembly::<std::alloc::Global>(), finalization_hint)
-        }
-        ZkSyncBaseLayerCircuit::L1MessagesSorter(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            let (_, finalization_hint) = cs.pad_and_shrink();
-            (cs.into_assembly::<std::alloc::Global>(), finalization_hint)
-        }
-        ZkSyncBaseLayerCircuit::L1MessagesHasher(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            let (_, finalization_hint) = cs.pad_and_shrink();
-            (cs.into_assembly::<std::alloc::Global>(), finalization_hint)
-        }
-        ZkSyncBaseLayerCircuit::TransientStorageSorter(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            let (_, finalization_hint) = cs.pad_and_shrink();
-            (cs.into_assembly::<std::alloc::Global>(), finalization_hint)
-        }
-        ZkSyncBaseLayerCircuit::Secp256r1Verify(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            let (_, finalization_hint) = cs.pad_and_shrink();
-            (cs.into_assembly::<std::alloc::Global>(), finalization_hint)
-        }
-        ZkSyncBaseLayerCircuit::EIP4844Repack(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            let (_, finalization_hint) = cs.pad_and_shrink();
-            (cs.into_assembly::<std::alloc::Global>(), finalization_hint)
-        }
-    };
+    let (cs, finalization_hint) = circuit.synthesize_for_setup();
 
     let (setup_base, setup, vk, setup_tree, vars_hint, witness_hints) =
         cs.get_full_setup(worker, fri_lde_factor, merkle_tree_cap_size);
@@ -206,168 +105,516 @@ pub fn create_base_layer_setup_data(
     )
 }
 
-use crate::boojum::cs::implementations::proof::Proof;
-use crate::boojum::cs::implementations::prover::ProofConfig;
-use crate::boojum::cs::implementations::transcript::GoldilocksPoisedon2Transcript;
-
-use crate::boojum::cs::implementations::pow::PoWRunner;
-
-pub fn prove_base_layer_circuit<POW: PoWRunner>(
-    circuit: ZkSyncBaseLayerCircuit,
+pub fn prove_circuit<C: ProvableCircuit, POW: PoWRunner>(
+    circuit: C,
     worker: &Worker,
     proof_config: ProofConfig,
     setup_base: &SetupBaseStorage<F, P>,
     setup: &SetupStorage<F, P>,
-    setup_tree: &MerkleTreeWithCap<F, H>,
-    vk: &VerificationKey<F, H>,
+    setup_tree: &CircuitSetupTree<C>,
+    vk: &CircuitVerificationKey<C>,
     vars_hint: &DenseVariablesCopyHint,
     wits_hint: &DenseWitnessCopyHint,
-    finalization_hint: &FinalizationHintsForProver,
-) -> Proof<F, H, EXT> {
-    use crate::boojum::cs::cs_builder::new_builder;
-    use crate::boojum::cs::cs_builder_reference::CsReferenceImplementationBuilder;
+    finalization_hint: &CircuitFinalizationHint,
+) -> CircuitProof<C> {
+    let cs = circuit.synthesize_for_proving(finalization_hint);
 
-    let geometry = circuit.geometry();
-    let (max_trace_len, num_vars) = circuit.size_hint();
+    cs.prove_from_precomputations::<EXT, C::Transcript, C::TreeHasher, POW>(
+        proof_config,
+        setup_base,
+        setup,
+        setup_tree,
+        vk,
+        vars_hint,
+        wits_hint,
+        (),
+        worker,
+    )
+}
 
-    let builder_impl = CsReferenceImplementationBuilder::<GoldilocksField, P, ProvingCSConfig>::new(
-        geometry,
-        max_trace_len.unwrap(),
-    );
-    let builder = new_builder::<_, GoldilocksField>(builder_impl);
+pub fn verify_proof<C: ProvableCircuit, POW: PoWRunner>(
+    circuit: &C,
+    proof: &CircuitProof<C>,
+    vk: &CircuitVerificationKey<C>,
+) -> bool {
+    if is_mock_proof(proof) {
+        return false;
+    }
+    circuit.verify::<POW>(proof, vk)
+}
 
-    let cs = match circuit {
-        ZkSyncBaseLayerCircuit::MainVM(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            cs.pad_and_shrink_using_hint(finalization_hint);
-            cs.into_assembly::<std::alloc::Global>()
-        }
-        ZkSyncBaseLayerCircuit::CodeDecommittmentsSorter(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            cs.pad_and_shrink_using_hint(finalization_hint);
-            cs.into_assembly::<std::alloc::Global>()
-        }
-        ZkSyncBaseLayerCircuit::CodeDecommitter(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            cs.pad_and_shrink_using_hint(finalization_hint);
-            cs.into_assembly::<std::alloc::Global>()
-        }
-        ZkSyncBaseLayerCircuit::LogDemuxer(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            cs.pad_and_shrink_using_hint(finalization_hint);
-            cs.into_assembly::<std::alloc::Global>()
-        }
-        ZkSyncBaseLayerCircuit::KeccakRoundFunction(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            cs.pad_and_shrink_using_hint(finalization_hint);
-            cs.into_assembly::<std::alloc::Global>()
-        }
-        ZkSyncBaseLayerCircuit::Sha256RoundFunction(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            cs.pad_and_shrink_using_hint(finalization_hint);
-            cs.into_assembly::<std::alloc::Global>()
-        }
-        ZkSyncBaseLayerCircuit::ECRecover(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            cs.pad_and_shrink_using_hint(finalization_hint);
-            cs.into_assembly::<std::alloc::Global>()
-        }
-        ZkSyncBaseLayerCircuit::RAMPermutation(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            cs.pad_and_shrink_using_hint(finalization_hint);
-            cs.into_assembly::<std::alloc::Global>()
-        }
-        ZkSyncBaseLayerCircuit::StorageSorter(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            cs.pad_and_shrink_using_hint(finalization_hint);
-            cs.into_assembly::<std::alloc::Global>()
-        }
-        ZkSyncBaseLayerCircuit::StorageApplication(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            cs.pad_and_shrink_using_hint(finalization_hint);
-            cs.into_assembly::<std::alloc::Global>()
-        }
-        ZkSyncBaseLayerCircuit::EventsSorter(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            cs.pad_and_shrink_using_hint(finalization_hint);
-            cs.into_assembly::<std::alloc::Global>()
-        }
-        ZkSyncBaseLayerCircuit::L1MessagesSorter(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            cs.pad_and_shrink_using_hint(finalization_hint);
-            cs.into_assembly::<std::alloc::Global>()
+/// Marks mock proofs, that are emitted instead of the real ones in `ProvingMode::Mock`.
+/// Mock proofs also have empty oracle caps, that is never the case for a real proof
+pub const MOCK_PROOF_MARKER: u64 = u64::from_be_bytes(*b"MOCKPRF!");
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ProvingMode {
+    #[default]
+    Real,
+    /// Only checks that circuits are satisfied and emits mock proofs, for testing the
+    /// aggregation and data handling without spending hours on proving
+    Mock,
+}
+
+pub fn create_mock_proof<C: ProvableCircuit>(proof_config: ProofConfig) -> CircuitProof<C> {
+    Proof {
+        proof_config,
+        public_inputs: vec![],
+        witness_oracle_cap: vec![],
+        stage_2_oracle_cap: vec![],
+        quotient_oracle_cap: vec![],
+        final_fri_monomials: [vec![], vec![]],
+        values_at_z: vec![],
+        values_at_z_omega: vec![],
+        values_at_0: vec![],
+        fri_base_oracle_cap: vec![],
+        fri_intermediate_oracles_caps: vec![],
+        queries_per_fri_repetition: vec![],
+        pow_challenge: MOCK_PROOF_MARKER,
+        _marker: std::marker::PhantomData,
+    }
+}
+
+pub fn is_mock_proof<HS: TreeHasher<F>>(proof: &Proof<F, HS, EXT>) -> bool {
+    proof.pow_challenge == MOCK_PROOF_MARKER
+        && proof.witness_oracle_cap.is_empty()
+        && proof.queries_per_fri_repetition.is_empty()
+}
+
+pub fn check_circuit_satisfiability<C: ProvableCircuit>(
+    circuit: C,
+    worker: &Worker,
+    finalization_hint: &CircuitFinalizationHint,
+) -> bool {
+    let mut cs = circuit.synthesize_for_proving(finalization_hint);
+    cs.check_if_satisfied(worker)
+}
+
+/// Proving in `ProvingMode::Mock`. Circuits that verify proofs can't be synthesized over mock
+/// proofs, so only the circuits without proofs in the witness (base layer) are checked.
+/// Panics if the circuit is not satisfied
+pub fn mock_prove_circuit<C: ProvableCircuit>(
+    circuit: C,
+    worker: &Worker,
+    proof_config: ProofConfig,
+    finalization_hint: &CircuitFinalizationHint,
+) -> CircuitProof<C> {
+    if !circuit.verifies_proofs() {
+        let description = circuit.short_description();
+        assert!(
+            check_circuit_satisfiability(circuit, worker, finalization_hint),
+            "circuit {} is not satisfied",
+            description
+        );
+    }
+
+    create_mock_proof::<C>(proof_config)
+}
+
+/// Runs the body for the inner circuit of any base layer circuit type.
+/// `ZkSyncBaseLayerCircuit` must be in scope
+macro_rules! for_each_base_layer_circuit {
+    ($circuit:expr, $inner:ident => $body:expr) => {
+        match $circuit {
+            ZkSyncBaseLayerCircuit::MainVM($inner) => $body,
+            ZkSyncBaseLayerCircuit::CodeDecommittmentsSorter($inner) => $body,
+            ZkSyncBaseLayerCircuit::CodeDecommitter($inner) => $body,
+            ZkSyncBaseLayerCircuit::LogDemuxer($inner) => $body,
+            ZkSyncBaseLayerCircuit::KeccakRoundFunction($inner) => $body,
+            ZkSyncBaseLayerCircuit::Sha256RoundFunction($inner) => $body,
+            ZkSyncBaseLayerCircuit::ECRecover($inner) => $body,
+            ZkSyncBaseLayerCircuit::RAMPermutation($inner) => $body,
+            ZkSyncBaseLayerCircuit::StorageSorter($inner) => $body,
+            ZkSyncBaseLayerCircuit::StorageApplication($inner) => $body,
+            ZkSyncBaseLayerCircuit::EventsSorter($inner) => $body,
+            ZkSyncBaseLayerCircuit::L1MessagesSorter($inner) => $body,
+            ZkSyncBaseLayerCircuit::L1MessagesHasher($inner) => $body,
+            ZkSyncBaseLayerCircuit::TransientStorageSorter($inner) => $body,
+            ZkSyncBaseLayerCircuit::Secp256r1Verify($inner) => $body,
+            ZkSyncBaseLayerCircuit::EIP4844Repack($inner) => $body,
         }
-        ZkSyncBaseLayerCircuit::L1MessagesHasher(inner) => {
+    };
+}
+pub(crate) use for_each_base_layer_circuit;
+
+impl ProvableCircuit for ZkSyncBaseLayerCircuit {
+    type TreeHasher = H;
+    type Transcript = TR;
+
+    fn numeric_circuit_type(&self) -> u8 {
+        ZkSyncBaseLayerCircuit::numeric_circuit_type(self)
+    }
+
+    fn short_description(&self) -> &'static str {
+        ZkSyncBaseLayerCircuit::short_description(self)
+    }
+
+    fn verifies_proofs(&self) -> bool {
+        false
+    }
+
+    fn synthesize_for_setup(
+        self,
+    ) -> (
+        CSReferenceAssembly<F, P, SetupCSConfig>,
+        FinalizationHintsForProver,
+    ) {
+        use crate::boojum::cs::cs_builder::new_builder;
+        use crate::boojum::cs::cs_builder_reference::CsReferenceImplementationBuilder;
+
+        let geometry = self.geometry();
+        let (max_trace_len, num_vars) = self.size_hint();
+
+        let builder_impl =
+            CsReferenceImplementationBuilder::<GoldilocksField, P, SetupCSConfig>::new(
+                geometry,
+                max_trace_len.unwrap(),
+            );
+        let builder = new_builder::<_, GoldilocksField>(builder_impl);
+
+        for_each_base_layer_circuit!(self, inner => {
             let builder = inner.configure_builder_proxy(builder);
             let mut cs = builder.build(num_vars.unwrap());
             inner.add_tables_proxy(&mut cs);
             inner.synthesize_proxy(&mut cs);
-            cs.pad_and_shrink_using_hint(finalization_hint);
-            cs.into_assembly::<std::alloc::Global>()
-        }
-        ZkSyncBaseLayerCircuit::TransientStorageSorter(inner) => {
+            let (_, finalization_hint) = cs.pad_and_shrink();
+            (cs.into_assembly::<std::alloc::Global>(), finalization_hint)
+        })
+    }
+
+    fn synthesize_for_proving(
+        self,
+        finalization_hint: &FinalizationHintsForProver,
+    ) -> CSReferenceAssembly<F, P, ProvingCSConfig> {
+        use crate::boojum::cs::cs_builder::new_builder;
+        use crate::boojum::cs::cs_builder_reference::CsReferenceImplementationBuilder;
+
+        let geometry = self.geometry();
+        let (max_trace_len, num_vars) = self.size_hint();
+
+        let builder_impl =
+            CsReferenceImplementationBuilder::<GoldilocksField, P, ProvingCSConfig>::new(
+                geometry,
+                max_trace_len.unwrap(),
+            );
+        let builder = new_builder::<_, GoldilocksField>(builder_impl);
+
+        for_each_base_layer_circuit!(self, inner => {
             let builder = inner.configure_builder_proxy(builder);
             let mut cs = builder.build(num_vars.unwrap());
             inner.add_tables_proxy(&mut cs);
             inner.synthesize_proxy(&mut cs);
             cs.pad_and_shrink_using_hint(finalization_hint);
             cs.into_assembly::<std::alloc::Global>()
+        })
+    }
+
+    fn verify<POW: PoWRunner>(&self, proof: &Proof<F, H, EXT>, vk: &VerificationKey<F, H>) -> bool {
+        verify_base_layer_proof_for_type::<POW>(self.numeric_circuit_type(), proof, vk)
+    }
+}
+
+macro_rules! for_each_recursive_layer_circuit {
+    ($circuit:expr, $inner:ident => $body:expr) => {
+        match $circuit {
+            ZkSyncRecursiveLayerCircuit::SchedulerCircuit($inner) => $body,
+            ZkSyncRecursiveLayerCircuit::NodeLayerCircuit($inner) => $body,
+            ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForMainVM($inner)
+            | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForCodeDecommittmentsSorter($inner)
+            | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForCodeDecommitter($inner)
+            | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForLogDemuxer($inner)
+            | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForKeccakRoundFunction($inner)
+            | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForSha256RoundFunction($inner)
+            | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForECRecover($inner)
+            | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForRAMPermutation($inner)
+            | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForStorageSorter($inner)
+            | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForStorageApplication($inner)
+            | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForEventsSorter($inner)
+            | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForL1MessagesSorter($inner)
+            | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForL1MessagesHasher($inner)
+            | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForTransientStorageSorter($inner)
+            | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForSecp256r1Verify($inner)
+            | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForEIP4844Repack($inner) => $body,
+            ZkSyncRecursiveLayerCircuit::RecursionTipCircuit($inner) => $body,
         }
-        ZkSyncBaseLayerCircuit::Secp256r1Verify(inner) => {
+    };
+}
+
+impl ProvableCircuit for ZkSyncRecursiveLayerCircuit {
+    type TreeHasher = H;
+    type Transcript = TR;
+
+    fn numeric_circuit_type(&self) -> u8 {
+        ZkSyncRecursiveLayerCircuit::numeric_circuit_type(self)
+    }
+
+    fn short_description(&self) -> &'static str {
+        ZkSyncRecursiveLayerCircuit::short_description(self)
+    }
+
+    fn verifies_proofs(&self) -> bool {
+        true
+    }
+
+    fn synthesize_for_setup(
+        self,
+    ) -> (
+        CSReferenceAssembly<F, P, SetupCSConfig>,
+        FinalizationHintsForProver,
+    ) {
+        use crate::boojum::cs::cs_builder::new_builder;
+        use crate::boojum::cs::cs_builder_reference::CsReferenceImplementationBuilder;
+
+        let round_function = ZkSyncDefaultRoundFunction::default();
+
+        let geometry = self.geometry();
+        let (max_trace_len, num_vars) = self.size_hint();
+
+        let builder_impl =
+            CsReferenceImplementationBuilder::<GoldilocksField, P, SetupCSConfig>::new(
+                geometry,
+                max_trace_len.unwrap(),
+            );
+        let builder = new_builder::<_, GoldilocksField>(builder_impl);
+
+        for_each_recursive_layer_circuit!(self, inner => {
             let builder = inner.configure_builder_proxy(builder);
             let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
-            cs.pad_and_shrink_using_hint(finalization_hint);
-            cs.into_assembly::<std::alloc::Global>()
-        }
-        ZkSyncBaseLayerCircuit::EIP4844Repack(inner) => {
+            inner.add_tables(&mut cs);
+            inner.synthesize_into_cs(&mut cs, &round_function);
+            let (_, finalization_hint) = cs.pad_and_shrink();
+            (cs.into_assembly::<std::alloc::Global>(), finalization_hint)
+        })
+    }
+
+    fn synthesize_for_proving(
+        self,
+        finalization_hint: &FinalizationHintsForProver,
+    ) -> CSReferenceAssembly<F, P, ProvingCSConfig> {
+        use crate::boojum::cs::cs_builder::new_builder;
+        use crate::boojum::cs::cs_builder_reference::CsReferenceImplementationBuilder;
+
+        let round_function = ZkSyncDefaultRoundFunction::default();
+
+        let geometry = self.geometry();
+        let (max_trace_len, num_vars) = self.size_hint();
+
+        use crate::boojum::config::CSConfig;
+        let builder_impl = CsReferenceImplementationBuilder::<
+            GoldilocksField,
+            P,
+            ProvingCSConfig,
+            crate::boojum::dag::StCircuitResolver<
+                GoldilocksField,
+                <ProvingCSConfig as CSConfig>::ResolverConfig,
+            >,
+        >::new(geometry, max_trace_len.unwrap());
+        let builder = new_builder::<_, GoldilocksField>(builder_impl);
+
+        for_each_recursive_layer_circuit!(self, inner => {
             let builder = inner.configure_builder_proxy(builder);
             let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables_proxy(&mut cs);
-            inner.synthesize_proxy(&mut cs);
+            inner.add_tables(&mut cs);
+            inner.synthesize_into_cs(&mut cs, &round_function);
             cs.pad_and_shrink_using_hint(finalization_hint);
             cs.into_assembly::<std::alloc::Global>()
+        })
+    }
+
+    fn verify<POW: PoWRunner>(&self, proof: &Proof<F, H, EXT>, vk: &VerificationKey<F, H>) -> bool {
+        let verifier_builder = self.into_dyn_verifier_builder();
+        let verifier = verifier_builder.create_verifier();
+        verifier.verify::<H, TR, POW>((), vk, proof)
+    }
+}
+
+// both compression layers are made of the same circuits, they only differ in the hasher of the last one
+fn synthesize_compression_for_setup<CF: ProofCompressionFunction>(
+    circuit: CompressionLayerCircuit<CF>,
+) -> (
+    CSReferenceAssembly<GoldilocksField, GoldilocksField, SetupCSConfig>,
+    FinalizationHintsForProver,
+) {
+    use crate::boojum::cs::cs_builder::new_builder;
+    use crate::boojum::cs::cs_builder_reference::CsReferenceImplementationBuilder;
+
+    let geometry = circuit.geometry();
+    let (max_trace_len, num_vars) = circuit.size_hint();
+
+    let builder_impl = CsReferenceImplementationBuilder::<GoldilocksField, P, SetupCSConfig>::new(
+        geometry,
+        max_trace_len.unwrap(),
+    );
+    let builder = new_builder::<_, GoldilocksField>(builder_impl);
+
+    let builder = circuit.configure_builder_proxy(builder);
+    let mut cs = builder.build(num_vars.unwrap());
+    circuit.add_tables(&mut cs);
+    circuit.synthesize_into_cs(&mut cs);
+    let (_, finalization_hint) = cs.pad_and_shrink();
+    (cs.into_assembly::<std::alloc::Global>(), finalization_hint)
+}
+
+fn synthesize_compression_for_proving<CF: ProofCompressionFunction>(
+    circuit: CompressionLayerCircuit<CF>,
+    finalization_hint: &FinalizationHintsForProver,
+) -> CSReferenceAssembly<GoldilocksField, GoldilocksField, ProvingCSConfig> {
+    use crate::boojum::cs::cs_builder::new_builder;
+    use crate::boojum::cs::cs_builder_reference::CsReferenceImplementationBuilder;
+
+    let geometry = circuit.geometry();
+    let (max_trace_len, num_vars) = circuit.size_hint();
+
+    let builder_impl = CsReferenceImplementationBuilder::<GoldilocksField, P, ProvingCSConfig>::new(
+        geometry,
+        max_trace_len.unwrap(),
+    );
+    let builder = new_builder::<_, GoldilocksField>(builder_impl);
+
+    let builder = circuit.configure_builder_proxy(builder);
+    let mut cs = builder.build(num_vars.unwrap());
+    circuit.add_tables(&mut cs);
+    circuit.synthesize_into_cs(&mut cs);
+    cs.pad_and_shrink_using_hint(finalization_hint);
+    cs.into_assembly::<std::alloc::Global>()
+}
+
+macro_rules! for_each_compression_circuit {
+    ($circuit_enum:ident, $circuit:expr, $inner:ident => $body:expr) => {
+        match $circuit {
+            $circuit_enum::CompressionMode1Circuit($inner) => $body,
+            $circuit_enum::CompressionMode2Circuit($inner) => $body,
+            $circuit_enum::CompressionMode3Circuit($inner) => $body,
+            $circuit_enum::CompressionMode4Circuit($inner) => $body,
+            $circuit_enum::CompressionMode5Circuit($inner) => $body,
         }
     };
+}
+
+impl ProvableCircuit for ZkSyncCompressionLayerCircuit {
+    type TreeHasher = H;
+    type Transcript = TR;
+
+    fn numeric_circuit_type(&self) -> u8 {
+        ZkSyncCompressionLayerCircuit::numeric_circuit_type(self)
+    }
+
+    fn short_description(&self) -> &'static str {
+        ZkSyncCompressionLayerCircuit::short_description(self)
+    }
+
+    fn verifies_proofs(&self) -> bool {
+        true
+    }
+
+    fn synthesize_for_setup(
+        self,
+    ) -> (
+        CSReferenceAssembly<F, P, SetupCSConfig>,
+        FinalizationHintsForProver,
+    ) {
+        for_each_compression_circuit!(ZkSyncCompressionLayerCircuit, self, inner => {
+            synthesize_compression_for_setup(inner)
+        })
+    }
+
+    fn synthesize_for_proving(
+        self,
+        finalization_hint: &FinalizationHintsForProver,
+    ) -> CSReferenceAssembly<F, P, ProvingCSConfig> {
+        for_each_compression_circuit!(ZkSyncCompressionLayerCircuit, self, inner => {
+            synthesize_compression_for_proving(inner, finalization_hint)
+        })
+    }
+
+    fn verify<POW: PoWRunner>(&self, proof: &Proof<F, H, EXT>, vk: &VerificationKey<F, H>) -> bool {
+        let verifier_builder = self.into_dyn_verifier_builder();
+        let verifier = verifier_builder.create_verifier();
+        verifier.verify::<H, TR, POW>((), vk, proof)
+    }
+}
+
+impl ProvableCircuit for ZkSyncCompressionForWrapperCircuit {
+    type TreeHasher = TreeHasherForWrapper;
+    type Transcript = TranscriptForWrapper;
+
+    fn numeric_circuit_type(&self) -> u8 {
+        ZkSyncCompressionForWrapperCircuit::numeric_circuit_type(self)
+    }
+
+    fn short_description(&self) -> &'static str {
+        ZkSyncCompressionForWrapperCircuit::short_description(self)
+    }
+
+    fn verifies_proofs(&self) -> bool {
+        true
+    }
+
+    fn synthesize_for_setup(
+        self,
+    ) -> (
+        CSReferenceAssembly<F, P, SetupCSConfig>,
+        FinalizationHintsForProver,
+    ) {
+        for_each_compression_circuit!(ZkSyncCompressionForWrapperCircuit, self, inner => {
+            synthesize_compression_for_setup(inner)
+        })
+    }
+
+    fn synthesize_for_proving(
+        self,
+        finalization_hint: &FinalizationHintsForProver,
+    ) -> CSReferenceAssembly<F, P, ProvingCSConfig> {
+        for_each_compression_circuit!(ZkSyncCompressionForWrapperCircuit, self, inner => {
+            synthesize_compression_for_proving(inner, finalization_hint)
+        })
+    }
+
+    fn verify<POW: PoWRunner>(
+        &self,
+        proof: &Proof<F, TreeHasherForWrapper, EXT>,
+        vk: &VerificationKey<F, TreeHasherForWrapper>,
+    ) -> bool {
+        let verifier_builder = self.into_dyn_verifier_builder();
+        let verifier = verifier_builder.create_verifier();
+        verifier.verify::<TreeHasherForWrapper, TranscriptForWrapper, POW>((), vk, proof)
+    }
+}
+
+// Layer specific functions are kept for compatibility, they are the same as the generic ones.
+
+pub fn create_base_layer_setup_data(
+    circuit: ZkSyncBaseLayerCircuit,
+    worker: &Worker,
+    fri_lde_factor: usize,
+    merkle_tree_cap_size: usize,
+) -> (
+    SetupBaseStorage<F, P>,
+    SetupStorage<F, P>,
+    VerificationKey<F, H>,
+    MerkleTreeWithCap<F, H>,
+    DenseVariablesCopyHint,
+    DenseWitnessCopyHint,
+    FinalizationHintsForProver,
+) {
+    create_setup_data(circuit, worker, fri_lde_factor, merkle_tree_cap_size)
+}
 
-    cs.prove_from_precomputations::<EXT, TR, H, POW>(
+pub fn prove_base_layer_circuit<POW: PoWRunner>(
+    circuit: ZkSyncBaseLayerCircuit,
+    worker: &Worker,
+    proof_config: ProofConfig,
+    setup_base: &SetupBaseStorage<F, P>,
+    setup: &SetupStorage<F, P>,
+    setup_tree: &MerkleTreeWithCap<F, H>,
+    vk: &VerificationKey<F, H>,
+    vars_hint: &DenseVariablesCopyHint,
+    wits_hint: &DenseWitnessCopyHint,
+    finalization_hint: &FinalizationHintsForProver,
+) -> Proof<F, H, EXT> {
+    prove_circuit::<_, POW>(
+        circuit,
+        worker,
         proof_config,
         setup_base,
         setup,
@@ -375,8 +622,7 @@ pub fn prove_base_layer_circuit<POW: PoWRunner>(
         vk,
         vars_hint,
         wits_hint,
-        (),
-        worker,
+        finalization_hint,
     )
 }
 
@@ -385,9 +631,7 @@ pub fn verify_base_layer_proof<POW: PoWRunner>(
     proof: &Proof<F, H, EXT>,
     vk: &VerificationKey<F, H>,
 ) -> bool {
-    let verifier_builder = dyn_verifier_builder_for_circuit_type(circuit.numeric_circuit_type());
-    let verifier = verifier_builder.create_verifier();
-    verifier.verify::<H, TR, POW>((), vk, proof)
+    verify_proof::<_, POW>(circuit, proof, vk)
 }
 
 pub fn verify_base_layer_proof_for_type<POW: PoWRunner>(
@@ -395,6 +639,9 @@ pub fn verify_base_layer_proof_for_type<POW: PoWRunner>(
     proof: &Proof<F, H, EXT>,
     vk: &VerificationKey<F, H>,
 ) -> bool {
+    if is_mock_proof(proof) {
+        return false;
+    }
     let verifier_builder = dyn_verifier_builder_for_circuit_type(circuit_type);
     let verifier = verifier_builder.create_verifier();
     verifier.verify::<H, TR, POW>((), vk, proof)
@@ -414,82 +661,7 @@ pub fn create_recursive_layer_setup_data(
     DenseWitnessCopyHint,
     FinalizationHintsForProver,
 ) {
-    use crate::boojum::cs::cs_builder::new_builder;
-    use crate::boojum::cs::cs_builder_reference::CsReferenceImplementationBuilder;
-
-    let round_function = ZkSyncDefaultRoundFunction::default();
-
-    let geometry = circuit.geometry();
-    let (max_trace_len, num_vars) = circuit.size_hint();
-
-    let builder_impl = CsReferenceImplementationBuilder::<GoldilocksField, P, SetupCSConfig>::new(
-        geometry,
-        max_trace_len.unwrap(),
-    );
-    let builder = new_builder::<_, GoldilocksField>(builder_impl);
-
-    let (cs, finalization_hint) = match circuit {
-        ZkSyncRecursiveLayerCircuit::SchedulerCircuit(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables(&mut cs);
-            inner.synthesize_into_cs(&mut cs, &round_function);
-            let (_, finalization_hint) = cs.pad_and_shrink();
-            (cs.into_assembly::<std::alloc::Global>(), finalization_hint)
-        }
-        ZkSyncRecursiveLayerCircuit::NodeLayerCircuit(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables(&mut cs);
-            inner.synthesize_into_cs(&mut cs, &round_function);
-            let (_, finalization_hint) = cs.pad_and_shrink();
-            (cs.into_assembly::<std::alloc::Global>(), finalization_hint)
-        }
-        ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForMainVM(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForCodeDecommittmentsSorter(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForCodeDecommitter(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForLogDemuxer(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForKeccakRoundFunction(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForSha256RoundFunction(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForECRecover(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForRAMPermutation(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForStorageSorter(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForStorageApplication(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForEventsSorter(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForL1MessagesSorter(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForL1MessagesHasher(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForTransientStorageSorter(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForSecp256r1Verify(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForEIP4844Repack(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables(&mut cs);
-            inner.synthesize_into_cs(&mut cs, &round_function);
-            let (_, finalization_hint) = cs.pad_and_shrink();
-            (cs.into_assembly::<std::alloc::Global>(), finalization_hint)
-        }
-        ZkSyncRecursiveLayerCircuit::RecursionTipCircuit(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables(&mut cs);
-            inner.synthesize_into_cs(&mut cs, &round_function);
-            let (_, finalization_hint) = cs.pad_and_shrink();
-            (cs.into_assembly::<std::alloc::Global>(), finalization_hint)
-        }
-    };
-
-    let (setup_base, setup, vk, setup_tree, vars_hint, witness_hints) =
-        cs.get_full_setup(worker, fri_lde_factor, merkle_tree_cap_size);
-
-    (
-        setup_base,
-        setup,
-        vk,
-        setup_tree,
-        vars_hint,
-        witness_hints,
-        finalization_hint,
-    )
+    create_setup_data(circuit, worker, fri_lde_factor, merkle_tree_cap_size)
 }
 
 pub fn prove_recursion_layer_circuit<POW: PoWRunner>(
@@ -504,77 +676,9 @@ pub fn prove_recursion_layer_circuit<POW: PoWRunner>(
     wits_hint: &DenseWitnessCopyHint,
     finalization_hint: &FinalizationHintsForProver,
 ) -> Proof<F, H, EXT> {
-    use crate::boojum::cs::cs_builder::new_builder;
-    use crate::boojum::cs::cs_builder_reference::CsReferenceImplementationBuilder;
-
-    let round_function = ZkSyncDefaultRoundFunction::default();
-
-    let geometry = circuit.geometry();
-    let (max_trace_len, num_vars) = circuit.size_hint();
-
-    use crate::boojum::config::CSConfig;
-    let builder_impl = CsReferenceImplementationBuilder::<
-        GoldilocksField,
-        P,
-        ProvingCSConfig,
-        crate::boojum::dag::StCircuitResolver<
-            GoldilocksField,
-            <ProvingCSConfig as CSConfig>::ResolverConfig,
-        >,
-    >::new(geometry, max_trace_len.unwrap());
-    let builder = new_builder::<_, GoldilocksField>(builder_impl);
-
-    let cs = match circuit {
-        ZkSyncRecursiveLayerCircuit::SchedulerCircuit(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables(&mut cs);
-            inner.synthesize_into_cs(&mut cs, &round_function);
-            cs.pad_and_shrink_using_hint(finalization_hint);
-            cs.into_assembly::<std::alloc::Global>()
-        }
-        ZkSyncRecursiveLayerCircuit::NodeLayerCircuit(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables(&mut cs);
-            inner.synthesize_into_cs(&mut cs, &round_function);
-            cs.pad_and_shrink_using_hint(finalization_hint);
-            cs.into_assembly::<std::alloc::Global>()
-        }
-        ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForMainVM(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForCodeDecommittmentsSorter(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForCodeDecommitter(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForLogDemuxer(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForKeccakRoundFunction(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForSha256RoundFunction(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForECRecover(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForRAMPermutation(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForStorageSorter(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForStorageApplication(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForEventsSorter(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForL1MessagesSorter(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForL1MessagesHasher(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForTransientStorageSorter(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForSecp256r1Verify(inner)
-        | ZkSyncRecursiveLayerCircuit::LeafLayerCircuitForEIP4844Repack(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables(&mut cs);
-            inner.synthesize_into_cs(&mut cs, &round_function);
-            cs.pad_and_shrink_using_hint(finalization_hint);
-            cs.into_assembly::<std::alloc::Global>()
-        }
-        ZkSyncRecursiveLayerCircuit::RecursionTipCircuit(inner) => {
-            let builder = inner.configure_builder_proxy(builder);
-            let mut cs = builder.build(num_vars.unwrap());
-            inner.add_tables(&mut cs);
-            inner.synthesize_into_cs(&mut cs, &round_function);
-            cs.pad_and_shrink_using_hint(finalization_hint);
-            cs.into_assembly::<std::alloc::Global>()
-        }
-    };
-
-    cs.prove_from_precomputations::<EXT, TR, H, POW>(
+    prove_circuit::<_, POW>(
+        circuit,
+        worker,
         proof_config,
         setup_base,
         setup,
@@ -582,8 +686,7 @@ pub fn prove_recursion_layer_circuit<POW: PoWRunner>(
         vk,
         vars_hint,
         wits_hint,
-        (),
-        worker,
+        finalization_hint,
     )
 }
 
@@ -592,9 +695,7 @@ pub fn verify_recursion_layer_proof<POW: PoWRunner>(
     proof: &Proof<F, H, EXT>,
     vk: &VerificationKey<F, H>,
 ) -> bool {
-    let verifier_builder = circuit.into_dyn_verifier_builder();
-    let verifier = verifier_builder.create_verifier();
-    verifier.verify::<H, TR, POW>((), vk, proof)
+    verify_proof::<_, POW>(circuit, proof, vk)
 }
 
 pub fn verify_recursion_layer_proof_for_type<POW: PoWRunner>(
@@ -602,6 +703,9 @@ pub fn verify_recursion_layer_proof_for_type<POW: PoWRunner>(
     proof: &Proof<F, H, EXT>,
     vk: &VerificationKey<F, H>,
 ) -> bool {
+    if is_mock_proof(proof) {
+        return false;
+    }
     let verifier_builder = dyn_verifier_builder_for_recursive_circuit_type(circuit_type);
     let verifier = verifier_builder.create_verifier();
     verifier.verify::<H, TR, POW>((), vk, proof)
@@ -621,53 +725,7 @@ pub fn create_compression_layer_setup_data(
     DenseWitnessCopyHint,
     FinalizationHintsForProver,
 ) {
-    use crate::boojum::cs::cs_builder::new_builder;
-    use crate::boojum::cs::cs_builder_reference::CsReferenceImplementationBuilder;
-
-    fn synthesize_inner<CF: ProofCompressionFunction>(
-        circuit: CompressionLayerCircuit<CF>,
-    ) -> (
-        CSReferenceAssembly<GoldilocksField, GoldilocksField, SetupCSConfig>,
-        FinalizationHintsForProver,
-    ) {
-        let geometry = circuit.geometry();
-        let (max_trace_len, num_vars) = circuit.size_hint();
-
-        let builder_impl =
-            CsReferenceImplementationBuilder::<GoldilocksField, P, SetupCSConfig>::new(
-                geometry,
-                max_trace_len.unwrap(),
-            );
-        let builder = new_builder::<_, GoldilocksField>(builder_impl);
-
-        let builder = circuit.configure_builder_proxy(builder);
-        let mut cs = builder.build(num_vars.unwrap());
-        circuit.add_tables(&mut cs);
-        circuit.synthesize_into_cs(&mut cs);
-        let (_, finalization_hint) = cs.pad_and_shrink();
-        (cs.into_assembly::<std::alloc::Global>(), finalization_hint)
-    }
-
-    let (cs, finalization_hint) = match circuit {
-        ZkSyncCompressionLayerCircuit::CompressionMode1Circuit(inner) => synthesize_inner(inner),
-        ZkSyncCompressionLayerCircuit::CompressionMode2Circuit(inner) => synthesize_inner(inner),
-        ZkSyncCompressionLayerCircuit::CompressionMode3Circuit(inner) => synthesize_inner(inner),
-        ZkSyncCompressionLayerCircuit::CompressionMode4Circuit(inner) => synthesize_inner(inner),
-        ZkSyncCompressionLayerCircuit::CompressionMode5Circuit(inner) => synthesize_inner(inner),
-    };
-
-    let (setup_base, setup, vk, setup_tree, vars_hint, witness_hints) =
-        cs.get_full_setup(worker, fri_lde_factor, merkle_tree_cap_size);
-
-    (
-        setup_base,
-        setup,
-        vk,
-        setup_tree,
-        vars_hint,
-        witness_hints,
-        finalization_hint,
-    )
+    create_setup_data(circuit, worker, fri_lde_factor, merkle_tree_cap_size)
 }
 
 pub fn prove_compression_layer_circuit<POW: PoWRunner>(
@@ -682,50 +740,9 @@ pub fn prove_compression_layer_circuit<POW: PoWRunner>(
     wits_hint: &DenseWitnessCopyHint,
     finalization_hint: &FinalizationHintsForProver,
 ) -> Proof<F, H, EXT> {
-    use crate::boojum::cs::cs_builder::new_builder;
-    use crate::boojum::cs::cs_builder_reference::CsReferenceImplementationBuilder;
-
-    fn synthesize_inner<CF: ProofCompressionFunction>(
-        circuit: CompressionLayerCircuit<CF>,
-        finalization_hint: &FinalizationHintsForProver,
-    ) -> CSReferenceAssembly<GoldilocksField, GoldilocksField, ProvingCSConfig> {
-        let geometry = circuit.geometry();
-        let (max_trace_len, num_vars) = circuit.size_hint();
-
-        let builder_impl =
-            CsReferenceImplementationBuilder::<GoldilocksField, P, ProvingCSConfig>::new(
-                geometry,
-                max_trace_len.unwrap(),
-            );
-        let builder = new_builder::<_, GoldilocksField>(builder_impl);
-
-        let builder = circuit.configure_builder_proxy(builder);
-        let mut cs = builder.build(num_vars.unwrap());
-        circuit.add_tables(&mut cs);
-        circuit.synthesize_into_cs(&mut cs);
-        cs.pad_and_shrink_using_hint(finalization_hint);
-        cs.into_assembly::<std::alloc::Global>()
-    }
-
-    let cs = match circuit {
-        ZkSyncCompressionLayerCircuit::CompressionMode1Circuit(inner) => {
-            synthesize_inner(inner, finalization_hint)
-        }
-        ZkSyncCompressionLayerCircuit::CompressionMode2Circuit(inner) => {
-            synthesize_inner(inner, finalization_hint)
-        }
-        ZkSyncCompressionLayerCircuit::CompressionMode3Circuit(inner) => {
-            synthesize_inner(inner, finalization_hint)
-        }
-        ZkSyncCompressionLayerCircuit::CompressionMode4Circuit(inner) => {
-            synthesize_inner(inner, finalization_hint)
-        }
-        ZkSyncCompressionLayerCircuit::CompressionMode5Circuit(inner) => {
-            synthesize_inner(inner, finalization_hint)
-        }
-    };
-
-    cs.prove_from_precomputations::<EXT, TR, H, POW>(
+    prove_circuit::<_, POW>(
+        circuit,
+        worker,
         proof_config,
         setup_base,
         setup,
@@ -733,8 +750,7 @@ pub fn prove_compression_layer_circuit<POW: PoWRunner>(
         vk,
         vars_hint,
         wits_hint,
-        (),
-        worker,
+        finalization_hint,
     )
 }
 
@@ -743,14 +759,9 @@ pub fn verify_compression_layer_proof<POW: PoWRunner>(
     proof: &Proof<F, H, EXT>,
     vk: &VerificationKey<F, H>,
 ) -> bool {
-    let verifier_builder = circuit.into_dyn_verifier_builder();
-    let verifier = verifier_builder.create_verifier();
-    verifier.verify::<H, TR, POW>((), vk, proof)
+    verify_proof::<_, POW>(circuit, proof, vk)
 }
 
-pub type TreeHasherForWrapper = Poseidon2Sponge<Bn256, F, AbsorptionModeReplacement<Fr>, 2, 3>;
-pub type TranscriptForWrapper = Poseidon2Transcript<Bn256, F, AbsorptionModeReplacement<Fr>, 2, 3>;
-
 pub fn create_compression_for_wrapper_setup_data(
     circuit: ZkSyncCompressionForWrapperCircuit,
     worker: &Worker,
@@ -765,63 +776,7 @@ pub fn create_compression_for_wrapper_setup_data(
     DenseWitnessCopyHint,
     FinalizationHintsForProver,
 ) {
-    use crate::boojum::cs::cs_builder::new_builder;
-    use crate::boojum::cs::cs_builder_reference::CsReferenceImplementationBuilder;
-
-    fn synthesize_inner<CF: ProofCompressionFunction>(
-        circuit: CompressionLayerCircuit<CF>,
-    ) -> (
-        CSReferenceAssembly<GoldilocksField, GoldilocksField, SetupCSConfig>,
-        FinalizationHintsForProver,
-    ) {
-        let geometry = circuit.geometry();
-        let (max_trace_len, num_vars) = circuit.size_hint();
-
-        let builder_impl =
-            CsReferenceImplementationBuilder::<GoldilocksField, P, SetupCSConfig>::new(
-                geometry,
-                max_trace_len.unwrap(),
-            );
-        let builder = new_builder::<_, GoldilocksField>(builder_impl);
-
-        let builder = circuit.configure_builder_proxy(builder);
-        let mut cs = builder.build(num_vars.unwrap());
-        circuit.add_tables(&mut cs);
-        circuit.synthesize_into_cs(&mut cs);
-        let (_, finalization_hint) = cs.pad_and_shrink();
-        (cs.into_assembly::<std::alloc::Global>(), finalization_hint)
-    }
-
-    let (cs, finalization_hint) = match circuit {
-        ZkSyncCompressionForWrapperCircuit::CompressionMode1Circuit(inner) => {
-            synthesize_inner(inner)
-        }
-        ZkSyncCompressionForWrapperCircuit::CompressionMode2Circuit(inner) => {
-            synthesize_inner(inner)
-        }
-        ZkSyncCompressionForWrapperCircuit::CompressionMode3Circuit(inner) => {
-            synthesize_inner(inner)
-        }
-        ZkSyncCompressionForWrapperCircuit::CompressionMode4Circuit(inner) => {
-            synthesize_inner(inner)
-        }
-        ZkSyncCompressionForWrapperCircuit::CompressionMode5Circuit(inner) => {
-            synthesize_inner(inner)
-        }
-    };
-
-    let (setup_base, setup, vk, setup_tree, vars_hint, witness_hints) =
-        cs.get_full_setup(worker, fri_lde_factor, merkle_tree_cap_size);
-
-    (
-        setup_base,
-        setup,
-        vk,
-        setup_tree,
-        vars_hint,
-        witness_hints,
-        finalization_hint,
-    )
+    create_setup_data(circuit, worker, fri_lde_factor, merkle_tree_cap_size)
 }
 
 pub fn prove_compression_for_wrapper_circuit<POW: PoWRunner>(
@@ -836,50 +791,9 @@ pub fn prove_compression_for_wrapper_circuit<POW: PoWRunner>(
     wits_hint: &DenseWitnessCopyHint,
     finalization_hint: &FinalizationHintsForProver,
 ) -> Proof<F, TreeHasherForWrapper, EXT> {
-    use crate::boojum::cs::cs_builder::new_builder;
-    use crate::boojum::cs::cs_builder_reference::CsReferenceImplementationBuilder;
-
-    fn synthesize_inner<CF: ProofCompressionFunction>(
-        circuit: CompressionLayerCircuit<CF>,
-        finalization_hint: &FinalizationHintsForProver,
-    ) -> CSReferenceAssembly<GoldilocksField, GoldilocksField, ProvingCSConfig> {
-        let geometry = circuit.geometry();
-        let (max_trace_len, num_vars) = circuit.size_hint();
-
-        let builder_impl =
-            CsReferenceImplementationBuilder::<GoldilocksField, P, ProvingCSConfig>::new(
-                geometry,
-                max_trace_len.unwrap(),
-            );
-        let builder = new_builder::<_, GoldilocksField>(builder_impl);
-
-        let builder = circuit.configure_builder_proxy(builder);
-        let mut cs = builder.build(num_vars.unwrap());
-        circuit.add_tables(&mut cs);
-        circuit.synthesize_into_cs(&mut cs);
-        cs.pad_and_shrink_using_hint(finalization_hint);
-        cs.into_assembly::<std::alloc::Global>()
-    }
-
-    let cs = match circuit {
-        ZkSyncCompressionForWrapperCircuit::CompressionMode1Circuit(inner) => {
-            synthesize_inner(inner, finalization_hint)
-        }
-        ZkSyncCompressionForWrapperCircuit::CompressionMode2Circuit(inner) => {
-            synthesize_inner(inner, finalization_hint)
-        }
-        ZkSyncCompressionForWrapperCircuit::CompressionMode3Circuit(inner) => {
-            synthesize_inner(inner, finalization_hint)
-        }
-        ZkSyncCompressionForWrapperCircuit::CompressionMode4Circuit(inner) => {
-            synthesize_inner(inner, finalization_hint)
-        }
-        ZkSyncCompressionForWrapperCircuit::CompressionMode5Circuit(inner) => {
-            synthesize_inner(inner, finalization_hint)
-        }
-    };
-
-    cs.prove_from_precomputations::<EXT, TranscriptForWrapper, TreeHasherForWrapper, POW>(
+    prove_circuit::<_, POW>(
+        circuit,
+        worker,
         proof_config,
         setup_base,
         setup,
@@ -887,8 +801,7 @@ pub fn prove_compression_for_wrapper_circuit<POW: PoWRunner>(
         vk,
         vars_hint,
         wits_hint,
-        (),
-        worker,
+        finalization_hint,
     )
 }
 
@@ -897,7 +810,5 @@ pub fn verify_compression_for_wrapper_proof<POW: PoWRunner>(
     proof: &Proof<F, TreeHasherForWrapper, EXT>,
     vk: &VerificationKey<F, TreeHasherForWrapper>,
 ) -> bool {
-    let verifier_builder = circuit.into_dyn_verifier_builder();
-    let verifier = verifier_builder.create_verifier();
-    verifier.verify::<TreeHasherForWrapper, TranscriptForWrapper, POW>((), vk, proof)
+    verify_proof::<_, POW>(circuit, proof, vk)
 }
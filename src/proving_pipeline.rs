@@ -0,0 +1,804 @@
+//! Local orchestrator that takes the output of `run`/`run_vms` and proves the block
+//! all the way to the SNARK wrapper.
+
+use crate::boojum::cs::implementations::pow::NoPow;
+use crate::boojum::cs::implementations::proof::Proof;
+use crate::boojum::cs::oracle::TreeHasher;
+use crate::boojum::field::goldilocks::{GoldilocksExt2, GoldilocksField};
+use crate::boojum::field::U64Representable;
+use crate::boojum::gadgets::recursion::recursive_tree_hasher::CircuitGoldilocksPoseidon2Sponge;
+use crate::boojum::worker::Worker;
+use crate::compute_setups::{
+    compute_leaf_params, recursive_circuit_proof_config, CircuitSetupData,
+};
+use crate::data_source::in_memory_data_source::InMemoryDataSource;
+use crate::data_source::migration::is_missing;
+use crate::data_source::{BlockDataSource, SetupDataSource, SourceResult};
+use crate::franklin_crypto::bellman::worker::Worker as BellmanWorker;
+use crate::proof_wrapper_utils::{
+    compute_compression_circuits, compute_compression_for_wrapper_circuit,
+    compute_wrapper_proof_and_vk, try_check_trusted_setup_file_existace, WrapperConfig,
+};
+use crate::prover_utils::*;
+use crate::witness::recursive_aggregation::{
+    compute_node_vk_commitment, create_leaf_witnesses_with_arity, create_node_witnesses_with_arity,
+    RecursionArity,
+};
+use crate::witness::utils::take_sponge_like_queue_state_from_simulator;
+use crate::zkevm_circuits::recursion::leaf_layer::input::RecursionLeafParametersWitness;
+use crate::zkevm_circuits::scheduler::aux::BaseLayerCircuitType;
+use crate::zkevm_circuits::scheduler::input::SchedulerCircuitInstanceWitness;
+use circuit_definitions::circuit_definitions::aux_layer::*;
+use circuit_definitions::circuit_definitions::base_layer::*;
+use circuit_definitions::circuit_definitions::recursion_layer::*;
+use circuit_definitions::encodings::recursion_request::RecursionQueueSimulator;
+use circuit_definitions::ProofConfigProfile;
+use std::collections::HashMap;
+
+type F = GoldilocksField;
+
+pub type RecursionQueue = (
+    u64, // circuit type
+    RecursionQueueSimulator<F>,
+    Vec<ZkSyncBaseLayerClosedFormInput<F>>,
+);
+
+pub type LeafAggregation = (
+    u64,                         // type of the basic circuit
+    RecursionQueueSimulator<F>,  // chunk
+    ZkSyncRecursiveLayerCircuit, // circuit for that chunk
+);
+
+/// Everything that `run` returns or passes to its callbacks
+pub struct BlockProvingInput {
+    pub base_layer_circuits: Vec<ZkSyncBaseLayerCircuit>,
+    pub recursion_queues: Vec<RecursionQueue>,
+    // block specific data, e.g. EIP-4844 witnesses, must already be filled in
+    pub scheduler_witness:
+        SchedulerCircuitInstanceWitness<F, CircuitGoldilocksPoseidon2Sponge, GoldilocksExt2>,
+}
+
+/// Proves a block layer by layer. Setup source must contain verification keys for all the
+/// base and recursive circuits. Every proof is stored in the block source, and steps whose
+/// proofs are already there are skipped, so an interrupted run can be resumed.
+/// In `ProvingMode::Mock` setup data is not computed, and the run stops at the scheduler.
+pub struct ProvingPipeline<'a, S: SetupDataSource, B: BlockDataSource> {
+    setup_source: &'a mut S,
+    block_source: &'a mut B,
+    wrapper_config: WrapperConfig,
+    arity: RecursionArity,
+    mode: ProvingMode,
+    profile: ProofConfigProfile,
+    worker: Worker,
+}
+
+fn recursive_layer_setup(
+    circuit: ZkSyncRecursiveLayerCircuit,
+    worker: &Worker,
+) -> CircuitSetupData {
+    let proof_config = recursive_circuit_proof_config(&circuit);
+    let (setup_base, setup, vk, setup_tree, vars_hint, wits_hint, finalization_hint) =
+        create_recursive_layer_setup_data(
+            circuit,
+            worker,
+            proof_config.fri_lde_factor,
+            proof_config.merkle_tree_cap_size,
+        );
+
+    CircuitSetupData {
+        setup_base,
+        setup,
+        vk,
+        setup_tree,
+        vars_hint,
+        wits_hint,
+        finalization_hint,
+    }
+}
+
+fn base_layer_setup(
+    circuit: ZkSyncBaseLayerCircuit,
+    worker: &Worker,
+    profile: ProofConfigProfile,
+) -> CircuitSetupData {
+    let proof_config = profile.base_layer_proof_config();
+    let (setup_base, setup, vk, setup_tree, vars_hint, wits_hint, finalization_hint) =
+        create_base_layer_setup_data(
+            circuit,
+            worker,
+            proof_config.fri_lde_factor,
+            proof_config.merkle_tree_cap_size,
+        );
+
+    CircuitSetupData {
+        setup_base,
+        setup,
+        vk,
+        setup_tree,
+        vars_hint,
+        wits_hint,
+        finalization_hint,
+    }
+}
+
+impl<'a, S: SetupDataSource, B: BlockDataSource> ProvingPipeline<'a, S, B> {
+    pub fn new(
+        setup_source: &'a mut S,
+        block_source: &'a mut B,
+        wrapper_config: WrapperConfig,
+    ) -> Self {
+        Self {
+            setup_source,
+            block_source,
+            wrapper_config,
+            arity: RecursionArity::default(),
+            mode: ProvingMode::Real,
+            profile: ProofConfigProfile::Production,
+            worker: Worker::new(),
+        }
+    }
+
+    /// Must match the arity that leaf and node verification keys were generated with
+    pub fn with_recursion_arity(mut self, arity: RecursionArity) -> Self {
+        self.arity = arity;
+        self
+    }
+
+    pub fn with_proving_mode(mut self, mode: ProvingMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Must match the profile that the verification keys were generated with.
+    /// Recursive layers follow the profile of the proofs they aggregate
+    pub fn with_proof_config_profile(mut self, profile: ProofConfigProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Runs all the steps and returns the wrapper proof
+    pub fn run(&mut self, input: BlockProvingInput) -> SourceResult<ZkSyncSnarkWrapperProof> {
+        self.run_recursion(input)?;
+        self.prove_compression_and_wrapper()
+    }
+
+    /// Runs the steps up to the scheduler and returns the scheduler proof
+    pub fn run_recursion(
+        &mut self,
+        input: BlockProvingInput,
+    ) -> SourceResult<ZkSyncRecursionLayerProof> {
+        let BlockProvingInput {
+            base_layer_circuits,
+            mut recursion_queues,
+            scheduler_witness,
+        } = input;
+
+        // proofs are matched with the queues by circuit type
+        recursion_queues.sort_by_key(|(circuit_type, _, _)| *circuit_type);
+
+        self.prove_base_layer(&base_layer_circuits)?;
+        drop(base_layer_circuits);
+
+        let leaf_aggregations = self.prove_leaf_layer(&recursion_queues)?;
+        let node_depths = self.prove_node_layer(leaf_aggregations)?;
+        let tip_proof = self.prove_recursion_tip(&recursion_queues, &node_depths)?;
+        self.prove_scheduler(scheduler_witness, tip_proof)
+    }
+
+    // proofs left by a mock run must never get into a real one
+    fn check_proof_mode<HS: TreeHasher<F>>(
+        &self,
+        proof: &Proof<F, HS, GoldilocksExt2>,
+        description: &str,
+    ) -> SourceResult<()> {
+        if self.mode == ProvingMode::Real && is_mock_proof(proof) {
+            return Err(format!("{} is a mock proof", description).into());
+        }
+
+        Ok(())
+    }
+
+    fn recursion_layer_vk_for_circuit(
+        &self,
+        circuit: &ZkSyncRecursiveLayerCircuit,
+    ) -> SourceResult<ZkSyncRecursionLayerVerificationKey> {
+        match circuit {
+            ZkSyncRecursiveLayerCircuit::NodeLayerCircuit(..) => {
+                self.setup_source.get_recursion_layer_node_vk()
+            }
+            ZkSyncRecursiveLayerCircuit::RecursionTipCircuit(..) => {
+                self.setup_source.get_recursion_tip_vk()
+            }
+            _ => self
+                .setup_source
+                .get_recursion_layer_vk(circuit.numeric_circuit_type()),
+        }
+    }
+
+    // reuses setup data while consecutive circuits have the same type
+    fn prove_recursive_circuit(
+        &self,
+        circuit: ZkSyncRecursiveLayerCircuit,
+        setup_cache: &mut Option<(u8, CircuitSetupData)>,
+    ) -> SourceResult<ZkSyncRecursionLayerProof> {
+        let circuit_type = circuit.numeric_circuit_type();
+        let description = circuit.short_description();
+
+        if self.mode == ProvingMode::Mock {
+            let proof = create_mock_proof::<ZkSyncRecursiveLayerCircuit>(
+                recursive_circuit_proof_config(&circuit),
+            );
+            return Ok(ZkSyncRecursionLayerProof::from_inner(circuit_type, proof));
+        }
+
+        if setup_cache.as_ref().map(|el| el.0) != Some(circuit_type) {
+            // precomputed setup data is used if the source has it
+            let setup_data = match self
+                .setup_source
+                .get_recursion_layer_setup_data(circuit_type)
+            {
+                Ok(setup_data) => setup_data,
+                Err(err) if is_missing(err.as_ref()) => {
+                    println!("Creating setup data for {}", description);
+                    recursive_layer_setup(circuit.clone(), &self.worker)
+                }
+                Err(err) => return Err(err),
+            };
+            let expected_vk = self.recursion_layer_vk_for_circuit(&circuit)?;
+            if expected_vk.into_inner() != setup_data.vk {
+                return Err(format!(
+                    "verification key for {} doesn't match the one in setup data source",
+                    description
+                )
+                .into());
+            }
+            *setup_cache = Some((circuit_type, setup_data));
+        }
+        let (_, setup_data) = setup_cache.as_ref().unwrap();
+
+        println!("Proving {}", description);
+        let now = std::time::Instant::now();
+        let proof = prove_recursion_layer_circuit::<NoPow>(
+            circuit.clone(),
+            &self.worker,
+            recursive_circuit_proof_config(&circuit),
+            &setup_data.setup_base,
+            &setup_data.setup,
+            &setup_data.setup_tree,
+            &setup_data.vk,
+            &setup_data.vars_hint,
+            &setup_data.wits_hint,
+            &setup_data.finalization_hint,
+        );
+        println!("Proving is DONE, taken {:?}", now.elapsed());
+
+        if !verify_recursion_layer_proof::<NoPow>(&circuit, &proof, &setup_data.vk) {
+            return Err(format!("proof for {} is not valid", description).into());
+        }
+
+        Ok(ZkSyncRecursionLayerProof::from_inner(circuit_type, proof))
+    }
+
+    /// Proves every base layer circuit that doesn't have a proof yet.
+    /// Circuits of the same type are indexed in the order they were emitted
+    pub fn prove_base_layer(&mut self, circuits: &[ZkSyncBaseLayerCircuit]) -> SourceResult<()> {
+        let mut instance_indexes: HashMap<u8, usize> = HashMap::new();
+        let mut setup_cache: Option<(u8, CircuitSetupData)> = None;
+
+        for circuit in circuits.iter() {
+            let circuit_type = circuit.numeric_circuit_type();
+            let description = circuit.short_description();
+            let instance_idx = instance_indexes.entry(circuit_type).or_insert(0);
+            let idx = *instance_idx;
+            *instance_idx += 1;
+
+            // only the proofs that are missing are computed, other errors are reported
+            match self.block_source.get_base_layer_proof(circuit_type, idx) {
+                Ok(_) => continue,
+                Err(err) if is_missing(err.as_ref()) => {}
+                Err(err) => return Err(err),
+            }
+
+            if self.mode == ProvingMode::Mock {
+                let finalization_hint = self
+                    .setup_source
+                    .get_base_layer_finalization_hint(circuit_type)?
+                    .into_inner();
+                if !check_circuit_satisfiability(circuit.clone(), &self.worker, &finalization_hint)
+                {
+                    return Err(format!("{} {} is not satisfied", description, idx).into());
+                }
+                let proof = create_mock_proof::<ZkSyncBaseLayerCircuit>(
+                    self.profile.base_layer_proof_config(),
+                );
+                self.block_source.set_base_layer_proof(
+                    idx,
+                    ZkSyncBaseLayerProof::from_inner(circuit_type, proof),
+                )?;
+                continue;
+            }
+
+            if setup_cache.as_ref().map(|el| el.0) != Some(circuit_type) {
+                let expected_vk = self.setup_source.get_base_layer_vk(circuit_type)?;
+                let vk_profile = ProofConfigProfile::of_vk(expected_vk.as_inner())?;
+                if vk_profile != self.profile {
+                    return Err(format!(
+                        "verification key for {} is of {:?} proof config profile, but pipeline uses {:?}",
+                        description, vk_profile, self.profile
+                    )
+                    .into());
+                }
+                let setup_data = match self.setup_source.get_base_layer_setup_data(circuit_type) {
+                    Ok(setup_data) => setup_data,
+                    Err(err) if is_missing(err.as_ref()) => {
+                        println!("Creating setup data for {}", description);
+                        base_layer_setup(circuit.clone(), &self.worker, self.profile)
+                    }
+                    Err(err) => return Err(err),
+                };
+                if expected_vk.into_inner() != setup_data.vk {
+                    return Err(format!(
+                        "verification key for {} doesn't match the one in setup data source",
+                        description
+                    )
+                    .into());
+                }
+                setup_cache = Some((circuit_type, setup_data));
+            }
+            let (_, setup_data) = setup_cache.as_ref().unwrap();
+
+            println!("Proving {} {}", description, idx);
+            let now = std::time::Instant::now();
+            let proof = prove_base_layer_circuit::<NoPow>(
+                circuit.clone(),
+                &self.worker,
+                self.profile.base_layer_proof_config(),
+                &setup_data.setup_base,
+                &setup_data.setup,
+                &setup_data.setup_tree,
+                &setup_data.vk,
+                &setup_data.vars_hint,
+                &setup_data.wits_hint,
+                &setup_data.finalization_hint,
+            );
+            println!("Proving is DONE, taken {:?}", now.elapsed());
+
+            if !verify_base_layer_proof::<NoPow>(circuit, &proof, &setup_data.vk) {
+                return Err(format!("proof for {} {} is not valid", description, idx).into());
+            }
+
+            self.block_source
+                .set_base_layer_proof(idx, ZkSyncBaseLayerProof::from_inner(circuit_type, proof))?;
+        }
+
+        Ok(())
+    }
+
+    fn leaf_params(&mut self) -> SourceResult<Vec<(u8, RecursionLeafParametersWitness<F>)>> {
+        compute_leaf_params(&mut *self.setup_source)
+    }
+
+    /// Creates and proves leaf circuits over the base layer proofs.
+    /// Returns leaf aggregations for every circuit type, in the order of `recursion_queues`
+    pub fn prove_leaf_layer(
+        &mut self,
+        recursion_queues: &[RecursionQueue],
+    ) -> SourceResult<Vec<Vec<LeafAggregation>>> {
+        let leaf_params = self.leaf_params()?;
+        let mut all_leaf_aggregations = vec![];
+
+        for (circuit_type, queue, closed_form_inputs) in recursion_queues.iter() {
+            if queue.num_items == 0 {
+                // there were no circuits of this type
+                all_leaf_aggregations.push(vec![]);
+                continue;
+            }
+
+            let base_circuit_type = *circuit_type as u8;
+            let recursive_circuit_type = base_circuit_type_into_recursive_leaf_circuit_type(
+                BaseLayerCircuitType::from_numeric_value(base_circuit_type),
+            ) as u8;
+
+            let mut proofs = Vec::with_capacity(queue.num_items as usize);
+            for idx in 0..(queue.num_items as usize) {
+                let proof = self
+                    .block_source
+                    .get_base_layer_proof(base_circuit_type, idx)?;
+                self.check_proof_mode(proof.as_inner(), "base layer proof")?;
+                proofs.push(proof);
+            }
+            let vk = self.setup_source.get_base_layer_vk(base_circuit_type)?;
+            let params = leaf_params
+                .iter()
+                .find(|el| el.0 == base_circuit_type)
+                .cloned()
+                .ok_or_else(|| {
+                    format!("no leaf parameters for circuit type {}", base_circuit_type)
+                })?;
+
+            let (aggregations, _) = create_leaf_witnesses_with_arity(
+                (*circuit_type, queue.clone(), closed_form_inputs.clone()),
+                proofs,
+                vk,
+                params,
+                self.arity,
+            )?;
+
+            let mut setup_cache = None;
+            for (idx, (_, _, circuit)) in aggregations.iter().enumerate() {
+                match self
+                    .block_source
+                    .get_leaf_layer_proof(recursive_circuit_type, idx)
+                {
+                    Ok(_) => continue,
+                    Err(err) if is_missing(err.as_ref()) => {}
+                    Err(err) => return Err(err),
+                }
+
+                let proof = self.prove_recursive_circuit(circuit.clone(), &mut setup_cache)?;
+                self.block_source.set_leaf_layer_proof(idx, proof)?;
+            }
+
+            all_leaf_aggregations.push(aggregations);
+        }
+
+        Ok(all_leaf_aggregations)
+    }
+
+    /// Aggregates leaves of every circuit type into a single node.
+    /// Returns the depth of the last node for every recursive circuit type that has one
+    pub fn prove_node_layer(
+        &mut self,
+        leaf_aggregations: Vec<Vec<LeafAggregation>>,
+    ) -> SourceResult<HashMap<u8, usize>> {
+        let leaf_params = self.leaf_params()?;
+        let node_vk = self.setup_source.get_recursion_layer_node_vk()?;
+        let node_vk_commitment = compute_node_vk_commitment(node_vk.clone());
+
+        let mut node_depths = HashMap::new();
+        let mut setup_cache = None;
+
+        for per_circuit_subtree in leaf_aggregations.into_iter() {
+            if per_circuit_subtree.is_empty() {
+                continue;
+            }
+
+            let base_circuit_type = per_circuit_subtree[0].0 as u8;
+            let recursive_circuit_type = base_circuit_type_into_recursive_leaf_circuit_type(
+                BaseLayerCircuitType::from_numeric_value(base_circuit_type),
+            ) as u8;
+            let leaf_vk = self
+                .setup_source
+                .get_recursion_layer_vk(recursive_circuit_type)?;
+
+            let mut depth = 0;
+            let mut next_aggregations = per_circuit_subtree;
+
+            loop {
+                let mut proofs = Vec::with_capacity(next_aggregations.len());
+                for idx in 0..next_aggregations.len() {
+                    let proof = if depth == 0 {
+                        self.block_source
+                            .get_leaf_layer_proof(recursive_circuit_type, idx)?
+                    } else {
+                        self.block_source.get_node_layer_proof(
+                            recursive_circuit_type,
+                            depth - 1,
+                            idx,
+                        )?
+                    };
+                    self.check_proof_mode(&proof.clone().into_inner(), "recursion layer proof")?;
+                    proofs.push(proof);
+                }
+
+                let vk = if depth == 0 {
+                    leaf_vk.clone()
+                } else {
+                    node_vk.clone()
+                };
+                next_aggregations = create_node_witnesses_with_arity(
+                    next_aggregations,
+                    proofs,
+                    vk,
+                    node_vk_commitment,
+                    &leaf_params,
+                    self.arity,
+                )?;
+
+                for (idx, (_, _, circuit)) in next_aggregations.iter().enumerate() {
+                    match self
+                        .block_source
+                        .get_node_layer_proof(recursive_circuit_type, depth, idx)
+                    {
+                        Ok(_) => continue,
+                        Err(err) if is_missing(err.as_ref()) => {}
+                        Err(err) => return Err(err),
+                    }
+
+                    let proof = self.prove_recursive_circuit(circuit.clone(), &mut setup_cache)?;
+                    self.block_source.set_node_layer_proof(
+                        recursive_circuit_type,
+                        depth,
+                        idx,
+                        proof,
+                    )?;
+                }
+
+                if next_aggregations.len() == 1 {
+                    node_depths.insert(recursive_circuit_type, depth);
+                    break;
+                }
+
+                depth += 1;
+            }
+        }
+
+        Ok(node_depths)
+    }
+
+    /// Proves the recursion tip over the last nodes of every circuit type.
+    /// Circuit types without nodes are padded with an empty node proof
+    pub fn prove_recursion_tip(
+        &mut self,
+        recursion_queues: &[RecursionQueue],
+        node_depths: &HashMap<u8, usize>,
+    ) -> SourceResult<ZkSyncRecursionLayerProof> {
+        if let Ok(proof) = self.block_source.get_recursive_tip_proof() {
+            self.check_proof_mode(&proof.clone().into_inner(), "recursion tip proof")?;
+            return Ok(proof);
+        }
+
+        let mut recursion_tip_proofs = vec![];
+        for recursive_circuit_type in (ZkSyncRecursionLayerStorageType::LeafLayerCircuitForMainVM
+            as u8)
+            ..=(ZkSyncRecursionLayerStorageType::LeafLayerCircuitForEIP4844Repack as u8)
+        {
+            let proof = match node_depths.get(&recursive_circuit_type) {
+                Some(depth) => {
+                    let proof = self.block_source.get_node_layer_proof(
+                        recursive_circuit_type,
+                        *depth,
+                        0,
+                    )?;
+                    self.check_proof_mode(&proof.clone().into_inner(), "node layer proof")?;
+                    proof
+                }
+                None => crate::empty_node_proof(),
+            };
+            recursion_tip_proofs.push(proof.into_inner());
+        }
+
+        let leaf_layer_params = self
+            .leaf_params()?
+            .into_iter()
+            .map(|el| el.1)
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap();
+        let node_vk = self.setup_source.get_recursion_layer_node_vk()?;
+        let node_layer_vk_commitment = compute_node_vk_commitment(node_vk.clone());
+
+        use crate::boojum::field::Field;
+        use crate::boojum::gadgets::queue::*;
+        use crate::zkevm_circuits::recursion::recursion_tip::input::*;
+        use crate::zkevm_circuits::recursion::recursion_tip::*;
+        use circuit_definitions::circuit_definitions::recursion_layer::recursion_tip::*;
+
+        let mut branch_circuit_type_set = [F::ZERO; RECURSION_TIP_ARITY];
+        let mut queue_set: [_; RECURSION_TIP_ARITY] =
+            std::array::from_fn(|_| QueueState::placeholder_witness());
+        if recursion_queues.len() > RECURSION_TIP_ARITY {
+            return Err(format!(
+                "recursion tip can aggregate at most {} circuit types",
+                RECURSION_TIP_ARITY
+            )
+            .into());
+        }
+        for ((circuit_type, queue_state), (src_type, src_queue, _)) in branch_circuit_type_set
+            .iter_mut()
+            .zip(queue_set.iter_mut())
+            .zip(recursion_queues.iter())
+        {
+            *circuit_type = F::from_u64_unchecked(*src_type);
+            *queue_state = take_sponge_like_queue_state_from_simulator(src_queue);
+        }
+
+        let input = RecursionTipInputWitness {
+            leaf_layer_parameters: leaf_layer_params,
+            node_layer_vk_commitment,
+            branch_circuit_type_set,
+            queue_set,
+        };
+
+        let witness = RecursionTipInstanceWitness {
+            input,
+            vk_witness: node_vk.clone().into_inner(),
+            proof_witnesses: recursion_tip_proofs.into(),
+        };
+
+        let config = RecursionTipConfig {
+            proof_config: ProofConfigProfile::of_vk(&node_vk.clone().into_inner())?
+                .recursion_layer_proof_config(),
+            vk_fixed_parameters: node_vk.into_inner().fixed_parameters,
+            _marker: std::marker::PhantomData,
+        };
+
+        let circuit = ZkSyncRecursiveLayerCircuit::RecursionTipCircuit(RecursionTipCircuit {
+            witness,
+            config,
+            transcript_params: (),
+            _marker: std::marker::PhantomData,
+        });
+
+        let proof = self.prove_recursive_circuit(circuit, &mut None)?;
+        self.block_source.set_recursive_tip_proof(proof.clone())?;
+
+        Ok(proof)
+    }
+
+    /// Proves the scheduler over the recursion tip proof
+    pub fn prove_scheduler(
+        &mut self,
+        mut scheduler_witness: SchedulerCircuitInstanceWitness<
+            F,
+            CircuitGoldilocksPoseidon2Sponge,
+            GoldilocksExt2,
+        >,
+        tip_proof: ZkSyncRecursionLayerProof,
+    ) -> SourceResult<ZkSyncRecursionLayerProof> {
+        if let Ok(proof) = self.block_source.get_scheduler_proof() {
+            self.check_proof_mode(&proof.clone().into_inner(), "scheduler proof")?;
+            return Ok(proof);
+        }
+        self.check_proof_mode(&tip_proof.clone().into_inner(), "recursion tip proof")?;
+
+        use crate::zkevm_circuits::scheduler::SchedulerConfig;
+        use circuit_definitions::circuit_definitions::recursion_layer::scheduler::SchedulerCircuit;
+
+        let leaf_layer_parameters = self
+            .leaf_params()?
+            .into_iter()
+            .map(|el| el.1)
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap();
+        let node_vk = self.setup_source.get_recursion_layer_node_vk()?;
+        let recursion_tip_vk = self.setup_source.get_recursion_tip_vk()?.into_inner();
+
+        let profile =
+            ProofConfigProfile::of_vks([&node_vk.clone().into_inner(), &recursion_tip_vk])?;
+        let config = SchedulerConfig {
+            proof_config: profile.recursion_layer_proof_config(),
+            leaf_layer_parameters,
+            node_layer_vk: node_vk.into_inner(),
+            recursion_tip_vk: recursion_tip_vk.clone(),
+            vk_fixed_parameters: recursion_tip_vk.fixed_parameters,
+            capacity: SCHEDULER_CAPACITY,
+            _marker: std::marker::PhantomData,
+        };
+
+        scheduler_witness.proof_witnesses = vec![tip_proof.into_inner()].into();
+
+        let circuit = ZkSyncRecursiveLayerCircuit::SchedulerCircuit(SchedulerCircuit {
+            witness: scheduler_witness,
+            config,
+            transcript_params: (),
+            _marker: std::marker::PhantomData,
+        });
+
+        let proof = self.prove_recursive_circuit(circuit, &mut None)?;
+        self.block_source.set_scheduler_proof(proof.clone())?;
+
+        Ok(proof)
+    }
+
+    /// Compresses the scheduler proof and wraps it into a SNARK.
+    /// Verification keys of the compression layers are written into the setup source.
+    /// Layers that already have their proof and keys in the sources are not recomputed
+    pub fn prove_compression_and_wrapper(&mut self) -> SourceResult<ZkSyncSnarkWrapperProof> {
+        let wrapper_type = self.wrapper_config.get_wrapper_type();
+        if let Ok(proof) = self.block_source.get_wrapper_proof(wrapper_type) {
+            return Ok(proof);
+        }
+        if self.mode == ProvingMode::Mock {
+            return Err(
+                "compression and wrapper can't be proven over a mock scheduler proof".into(),
+            );
+        }
+
+        try_check_trusted_setup_file_existace()?;
+
+        // compression helpers work over a single source, so the layers are staged in RAM
+        // and only the missing ones are written back
+        let scheduler_proof = self.block_source.get_scheduler_proof()?;
+        self.check_proof_mode(&scheduler_proof.clone().into_inner(), "scheduler proof")?;
+        let mut staging = InMemoryDataSource::new();
+        staging.set_scheduler_proof(scheduler_proof)?;
+        staging
+            .set_recursion_layer_vk(self.setup_source.get_recursion_layer_vk(
+                ZkSyncRecursionLayerStorageType::SchedulerCircuit as u8,
+            )?)?;
+
+        let mut missing_layers = vec![];
+        for circuit_type in self.wrapper_config.get_compression_types() {
+            match (
+                self.setup_source.get_compression_vk(circuit_type),
+                self.setup_source.get_compression_hint(circuit_type),
+                self.block_source.get_compression_proof(circuit_type),
+            ) {
+                (Ok(vk), Ok(hint), Ok(proof)) => {
+                    staging.set_compression_vk(vk)?;
+                    staging.set_compression_hint(hint)?;
+                    staging.set_compression_proof(proof)?;
+                }
+                _ => missing_layers.push(circuit_type),
+            }
+        }
+
+        let for_wrapper_type = self.wrapper_config.get_compression_for_wrapper_type();
+        let for_wrapper_present = match (
+            self.setup_source
+                .get_compression_for_wrapper_vk(for_wrapper_type),
+            self.setup_source
+                .get_compression_for_wrapper_hint(for_wrapper_type),
+            self.block_source
+                .get_compression_for_wrapper_proof(for_wrapper_type),
+        ) {
+            (Ok(vk), Ok(hint), Ok(proof)) => {
+                staging.set_compression_for_wrapper_vk(vk)?;
+                staging.set_compression_for_wrapper_hint(hint)?;
+                staging.set_compression_for_wrapper_proof(proof)?;
+                true
+            }
+            _ => false,
+        };
+
+        let wrapper_setup_present = match self.setup_source.get_wrapper_setup(wrapper_type) {
+            Ok(setup) => staging.set_wrapper_setup(setup).map(|_| true)?,
+            Err(err) if is_missing(err.as_ref()) => false,
+            Err(err) => return Err(err),
+        };
+        let wrapper_vk_present = match self.setup_source.get_wrapper_vk(wrapper_type) {
+            Ok(vk) => staging.set_wrapper_vk(vk).map(|_| true)?,
+            Err(err) if is_missing(err.as_ref()) => false,
+            Err(err) => return Err(err),
+        };
+
+        compute_compression_circuits(&mut staging, self.wrapper_config, &self.worker)?;
+        compute_compression_for_wrapper_circuit(&mut staging, self.wrapper_config, &self.worker)?;
+        compute_wrapper_proof_and_vk(&mut staging, self.wrapper_config, &BellmanWorker::new())?;
+
+        for circuit_type in missing_layers {
+            self.setup_source
+                .set_compression_vk(staging.get_compression_vk(circuit_type)?)?;
+            self.setup_source
+                .set_compression_hint(staging.get_compression_hint(circuit_type)?)?;
+            self.block_source
+                .set_compression_proof(staging.get_compression_proof(circuit_type)?)?;
+        }
+        if !for_wrapper_present {
+            self.setup_source.set_compression_for_wrapper_vk(
+                staging.get_compression_for_wrapper_vk(for_wrapper_type)?,
+            )?;
+            self.setup_source.set_compression_for_wrapper_hint(
+                staging.get_compression_for_wrapper_hint(for_wrapper_type)?,
+            )?;
+            self.block_source.set_compression_for_wrapper_proof(
+                staging.get_compression_for_wrapper_proof(for_wrapper_type)?,
+            )?;
+        }
+        if !wrapper_setup_present {
+            self.setup_source
+                .set_wrapper_setup(staging.get_wrapper_setup(wrapper_type)?)?;
+        }
+        if !wrapper_vk_present {
+            self.setup_source
+                .set_wrapper_vk(staging.get_wrapper_vk(wrapper_type)?)?;
+        }
+
+        let proof = staging.get_wrapper_proof(wrapper_type)?;
+        self.block_source.set_wrapper_proof(proof.clone())?;
+
+        Ok(proof)
+    }
+}
@@ -7,6 +7,7 @@ use crate::snark_wrapper::boojum::gadgets::recursion::recursive_tree_hasher::Cir
 use crate::toolset::create_tools;
 use crate::toolset::GeometryConfig;
 use crate::witness::oracle::create_artifacts_from_tracer;
+use crate::witness::storage_consistency::{check_storage_against_tree, StorageDiscrepancy};
 use crate::witness::tree::BinarySparseStorageTree;
 use crate::witness::tree::ZkSyncStorageLeaf;
 use crate::witness::utils::{
@@ -46,6 +47,28 @@ pub const SCHEDULER_TIMESTAMP: u32 = 1;
 pub enum RunVmError {
     InvalidInput(String),
     OutOfCircuitExecutionError(String),
+    StorageInconsistency(Vec<StorageDiscrepancy>),
+}
+
+impl RunVmError {
+    pub(crate) fn describe_storage_inconsistency(discrepancies: &[StorageDiscrepancy]) -> String {
+        let mut msg = format!(
+            "{} slots diverged between storage and tree:",
+            discrepancies.len()
+        );
+        for el in discrepancies.iter() {
+            msg.push_str(&format!(
+                "\naddress {:?}, key 0x{:x} (derived key {}): storage returned 0x{:x}, tree has 0x{:x}",
+                el.address,
+                el.key,
+                hex::encode(&el.derived_key),
+                el.storage_value,
+                el.tree_value
+            ));
+        }
+
+        msg
+    }
 }
 
 pub type RunVMsResult = (
@@ -87,6 +110,67 @@ pub fn run_vms<
     circuit_callback: CB,
     queue_simulator_callback: QSCB,
     out_of_circuit_tracer: &mut impl Tracer<SupportedMemory = SimpleMemory>,
+) -> Result<RunVMsResult, RunVmError> {
+    run_vms_with_options(
+        caller,
+        entry_point_address,
+        entry_point_code,
+        initial_heap_content,
+        zk_porter_is_available,
+        default_aa_code_hash,
+        evm_simulator_code_hash,
+        used_bytecodes,
+        ram_verification_queries,
+        cycle_limit,
+        geometry,
+        storage,
+        tree,
+        trusted_setup_path,
+        eip_4844_repack_inputs,
+        circuit_callback,
+        queue_simulator_callback,
+        out_of_circuit_tracer,
+        RunVmOptions::default(),
+    )
+}
+
+/// Optional checks done by `run_vms_with_options`, all of them are disabled by default
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RunVmOptions {
+    /// Compare values that storage returned during execution with the tree,
+    /// and report all the discrepancies before any circuit is created
+    pub check_storage_consistency: bool,
+}
+
+/// Same as `run_vms`, with additional checks enabled by the options
+pub fn run_vms_with_options<
+    S: Storage,
+    CB: FnMut(ZkSyncBaseLayerCircuit),
+    QSCB: FnMut(
+        u64,
+        RecursionQueueSimulator<MainField>,
+        Vec<ClosedFormInputCompactFormWitness<MainField>>,
+    ),
+>(
+    caller: Address,                 // for real block must be zero
+    entry_point_address: Address,    // for real block must be the bootloader
+    entry_point_code: Vec<[u8; 32]>, // for read block must be a bootloader code
+    initial_heap_content: Vec<u8>,   // bootloader starts with non-deterministic heap
+    zk_porter_is_available: bool,
+    default_aa_code_hash: U256,
+    evm_simulator_code_hash: U256,
+    used_bytecodes: std::collections::HashMap<U256, Vec<[u8; 32]>>, // auxilary information to avoid passing a full set of all used codes
+    ram_verification_queries: Vec<(u32, U256)>, // we may need to check that after the bootloader's memory is filled
+    cycle_limit: usize,
+    geometry: GeometryConfig,
+    storage: S,
+    tree: &mut impl BinarySparseStorageTree<256, 32, 32, 8, 32, Blake2s256, ZkSyncStorageLeaf>,
+    trusted_setup_path: &str,
+    eip_4844_repack_inputs: [Option<Vec<u8>>; MAX_4844_BLOBS_PER_BLOCK],
+    circuit_callback: CB,
+    queue_simulator_callback: QSCB,
+    out_of_circuit_tracer: &mut impl Tracer<SupportedMemory = SimpleMemory>,
+    options: RunVmOptions,
 ) -> Result<RunVMsResult, RunVmError> {
     let round_function = ZkSyncDefaultRoundFunction::default();
 
@@ -237,6 +321,14 @@ pub fn run_vms<
 
     // dbg!(tools.witness_tracer.vm_snapshots.len());
 
+    if options.check_storage_consistency {
+        let discrepancies =
+            check_storage_against_tree(&out_of_circuit_vm.witness_tracer.storage_queries, tree);
+        if !discrepancies.is_empty() {
+            return Err(RunVmError::StorageInconsistency(discrepancies));
+        }
+    }
+
     let (basic_circuits, compact_form_witnesses, eip4844_circuits) = create_artifacts_from_tracer(
         out_of_circuit_vm.witness_tracer,
         &round_function,
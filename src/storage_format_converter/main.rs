@@ -0,0 +1,49 @@
+use structopt::StructOpt;
+use zkevm_test_harness::data_source::local_file_data_source::LocalFileDataSource;
+use zkevm_test_harness::data_source::migration::{copy_block_data, copy_setup_data};
+use zkevm_test_harness::data_source::storage_format::StorageFormat;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "Storage format converter",
+    about = "Tool for migrating setup or proof directories between storage formats"
+)]
+struct Opt {
+    /// Directory to read from. Files in any format are accepted.
+    #[structopt(long)]
+    from: String,
+    /// Directory to write to, created if missing.
+    #[structopt(long)]
+    to: String,
+    /// Target format: json, bincode or compressed-bincode.
+    #[structopt(long)]
+    format: StorageFormat,
+    /// Convert proofs (like `test_proofs/`) instead of setup data (like `setup/`).
+    #[structopt(long)]
+    proofs: bool,
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    assert_ne!(
+        opt.from, opt.to,
+        "output directory must differ from the input one"
+    );
+
+    let source = LocalFileDataSource::new(opt.from.clone(), opt.from.clone());
+    let mut target =
+        LocalFileDataSource::new(opt.to.clone(), opt.to.clone()).with_format(opt.format);
+    target.create_folders_for_storing_data();
+
+    let copied = if opt.proofs {
+        copy_block_data(&source, &mut target)
+    } else {
+        copy_setup_data(&source, &mut target)
+    }
+    .expect("Unable to convert");
+
+    println!(
+        "Converted {} items from {} to {} as {:?}",
+        copied, opt.from, opt.to, opt.format
+    );
+}
@@ -129,29 +129,7 @@ use crate::boojum::gadgets::recursion::recursive_tree_hasher::CircuitGoldilocksP
 use crate::data_source::in_memory_data_source::InMemoryDataSource;
 use crate::witness::full_block_artifact::*;
 
-/// Lover memory requirements
-/// Used only for base layer debugging
-fn get_testing_geometry_config() -> GeometryConfig {
-    GeometryConfig {
-        // cycles_per_vm_snapshot: 1,
-        cycles_per_vm_snapshot: 1024,
-        cycles_per_ram_permutation: 1024,
-        cycles_per_code_decommitter: 256,
-        cycles_per_storage_application: 4,
-        cycles_per_keccak256_circuit: 7,
-        cycles_per_sha256_circuit: 7,
-        cycles_per_ecrecover_circuit: 2,
-        // cycles_code_decommitter_sorter: 512,
-        cycles_code_decommitter_sorter: 3,
-        cycles_per_log_demuxer: 16,
-        cycles_per_storage_sorter: 16,
-        cycles_per_events_or_l1_messages_sorter: 4,
-        cycles_per_secp256r1_verify_circuit: 2,
-        cycles_per_transient_storage_sorter: 16,
-
-        limit_for_l1_messages_pudata_hasher: 32,
-    }
-}
+use crate::toolset::get_testing_geometry_config;
 
 pub(crate) fn generate_base_layer(
     mut test_artifact: TestArtifact,
@@ -173,7 +151,7 @@ pub(crate) fn generate_base_layer(
 ) {
     use crate::zk_evm::zkevm_opcode_defs::system_params::BOOTLOADER_FORMAL_ADDRESS;
 
-    use crate::external_calls::run;
+    use crate::external_calls::{run_with_options, RunVmOptions};
     use crate::toolset::GeometryConfig;
 
     let mut storage_impl = InMemoryStorage::new();
@@ -255,7 +233,7 @@ pub(crate) fn generate_base_layer(
 
     let mut basic_block_circuits = vec![];
     let mut recursion_queues = vec![];
-    let (scheduler_partial_input, _aux_data) = run(
+    let (scheduler_partial_input, _aux_data) = run_with_options(
         Address::zero(),
         test_artifact.entry_point_address,
         test_artifact.entry_point_code,
@@ -281,8 +259,20 @@ pub(crate) fn generate_base_layer(
                     .collect(),
             ))
         },
+        RunVmOptions {
+            check_storage_consistency: true,
+        },
     );
 
+    use crate::witness::chain_linkage::verify_circuit_chain;
+    if let Err(broken_link) = verify_circuit_chain(&basic_block_circuits, &scheduler_partial_input)
+    {
+        panic!(
+            "Base layer circuits are not linked properly: {}",
+            broken_link
+        );
+    }
+
     (
         basic_block_circuits,
         recursion_queues,
@@ -324,6 +314,884 @@ fn basic_layer_test_one_per_kind() {
     }
 }
 
+/// Public inputs computed from the witnesses must match the ones set when circuits were created
+#[test]
+fn public_input_calculator_matches_circuit_maker() {
+    use crate::witness::public_inputs::{expected_public_input, PublicInputCalculator};
+
+    let test_artifact = read_basic_test_artifact();
+    let blobs = std::array::from_fn(|i| {
+        if i == 0 {
+            Some(vec![0xff; ENCODABLE_BYTES_PER_BLOB])
+        } else {
+            None
+        }
+    });
+    let geometry = get_testing_geometry_config();
+    let (circuits, _, _) = generate_base_layer(test_artifact, 40000, geometry, blobs);
+
+    let mut calculator = PublicInputCalculator::new();
+    for circuit in circuits.iter() {
+        let expected = expected_public_input(circuit);
+        assert!(expected.is_some());
+        assert_eq!(
+            calculator.compute(circuit),
+            expected,
+            "public input of {} differs",
+            circuit.short_description()
+        );
+    }
+
+    // and restored once erased
+    let mut circuit = circuits
+        .iter()
+        .find(|el| matches!(el, ZkSyncBaseLayerCircuit::MainVM(_)))
+        .unwrap()
+        .clone();
+    let expected = expected_public_input(&circuit);
+    if let ZkSyncBaseLayerCircuit::MainVM(inner) = &mut circuit {
+        inner.expected_public_input = None;
+    }
+    assert!(calculator.fill_expected_public_input(&mut circuit));
+    assert_eq!(expected_public_input(&circuit), expected);
+}
+
+/// Tampers with the witnesses of the correct base layer and checks that every kind of broken link is found
+#[test]
+fn broken_chain_linkage_is_detected() {
+    use crate::witness::chain_linkage::{verify_circuit_chain, BrokenLinkKind};
+    use crate::zkevm_circuits::scheduler::aux::BaseLayerCircuitType;
+
+    let test_artifact = read_basic_test_artifact();
+    let blobs = std::array::from_fn(|_| None);
+    let geometry = get_testing_geometry_config();
+    let (circuits, _, scheduler_witness) =
+        generate_base_layer(test_artifact, 40000, geometry, blobs);
+
+    let first_of = |circuit_type: BaseLayerCircuitType| {
+        circuits
+            .iter()
+            .position(|el| el.numeric_circuit_type() == circuit_type as u8)
+            .unwrap()
+    };
+    let expect_broken = |circuits: &[ZkSyncBaseLayerCircuit],
+                         circuit_type: BaseLayerCircuitType,
+                         kind: BrokenLinkKind| {
+        let broken_link = verify_circuit_chain(circuits, &scheduler_witness).unwrap_err();
+        assert_eq!(broken_link.circuit_type, circuit_type as u8);
+        assert_eq!(broken_link.kind, kind);
+    };
+
+    // instance of a long chain is lost
+    let vm_instances: Vec<_> = circuits
+        .iter()
+        .enumerate()
+        .filter(|(_, el)| el.numeric_circuit_type() == BaseLayerCircuitType::VM as u8)
+        .map(|(idx, _)| idx)
+        .collect();
+    assert!(vm_instances.len() > 2);
+    let mut tampered = circuits.clone();
+    tampered.remove(vm_instances[1]);
+    expect_broken(
+        &tampered,
+        BaseLayerCircuitType::VM,
+        BrokenLinkKind::HiddenFsmMismatch,
+    );
+
+    // last instance doesn't finish the chain
+    let mut tampered = circuits.clone();
+    tampered.remove(*vm_instances.last().unwrap());
+    expect_broken(
+        &tampered,
+        BaseLayerCircuitType::VM,
+        BrokenLinkKind::MissingCompletionFlag,
+    );
+
+    // demuxer doesn't start from the log queue of the VM
+    let tampered = circuits.clone();
+    let ZkSyncBaseLayerCircuit::LogDemuxer(inner) =
+        &tampered[first_of(BaseLayerCircuitType::LogDemultiplexer)]
+    else {
+        unreachable!()
+    };
+    let mut witness = inner.clone_witness().unwrap();
+    witness
+        .closed_form_input
+        .observable_input
+        .initial_log_queue_state
+        .tail
+        .length += 1;
+    inner.witness.store(Some(witness));
+    expect_broken(
+        &tampered,
+        BaseLayerCircuitType::LogDemultiplexer,
+        BrokenLinkKind::ObservableInputMismatch,
+    );
+
+    // decommitter doesn't continue the memory queue of the VM
+    let tampered = circuits.clone();
+    let ZkSyncBaseLayerCircuit::CodeDecommitter(inner) =
+        &tampered[first_of(BaseLayerCircuitType::Decommiter)]
+    else {
+        unreachable!()
+    };
+    let mut witness = inner.clone_witness().unwrap();
+    witness
+        .closed_form_input
+        .observable_input
+        .memory_queue_initial_state
+        .tail
+        .length += 1;
+    inner.witness.store(Some(witness));
+    expect_broken(
+        &tampered,
+        BaseLayerCircuitType::Decommiter,
+        BrokenLinkKind::ObservableInputMismatch,
+    );
+
+    // storage application doesn't apply what the sorter produced
+    let tampered = circuits.clone();
+    let ZkSyncBaseLayerCircuit::StorageApplication(inner) =
+        &tampered[first_of(BaseLayerCircuitType::StorageApplicator)]
+    else {
+        unreachable!()
+    };
+    let mut witness = inner.clone_witness().unwrap();
+    witness
+        .closed_form_input
+        .observable_input
+        .storage_application_log_state
+        .tail
+        .length += 1;
+    inner.witness.store(Some(witness));
+    expect_broken(
+        &tampered,
+        BaseLayerCircuitType::StorageApplicator,
+        BrokenLinkKind::ObservableInputMismatch,
+    );
+
+    // scheduler expects another root
+    let mut tampered_scheduler_witness = scheduler_witness.clone();
+    tampered_scheduler_witness
+        .storage_application_observable_output
+        .new_root_hash[0] ^= 1;
+    let broken_link = verify_circuit_chain(&circuits, &tampered_scheduler_witness).unwrap_err();
+    assert_eq!(
+        broken_link.circuit_type,
+        BaseLayerCircuitType::StorageApplicator as u8
+    );
+    assert_eq!(broken_link.kind, BrokenLinkKind::SchedulerOutputMismatch);
+
+    assert!(verify_circuit_chain(&circuits, &scheduler_witness).is_ok());
+}
+
+// Verification keys of all the circuits for the given geometry, with the test proof config profile
+fn test_profile_setup_source(geometry: &GeometryConfig) -> InMemoryDataSource {
+    let options = SetupGenerationOptions {
+        profile: circuit_definitions::ProofConfigProfile::Test,
+        geometry: *geometry,
+        ..Default::default()
+    };
+    let mut source = InMemoryDataSource::new();
+    generate_base_layer_vks_with_options(&mut source, &options).unwrap();
+    generate_recursive_layer_vks_with_options(&mut source, &options).unwrap();
+
+    source
+}
+
+// L1 messages hasher is one of the smallest circuits with the testing geometry
+fn small_base_layer_circuit() -> ZkSyncBaseLayerCircuit {
+    let test_artifact = read_basic_test_artifact();
+    let blobs = std::array::from_fn(|i| {
+        if i == 0 {
+            Some(vec![0xff; ENCODABLE_BYTES_PER_BLOB])
+        } else {
+            None
+        }
+    });
+    let geometry = get_testing_geometry_config();
+    let (circuits, _, _) = generate_base_layer(test_artifact, 40000, geometry, blobs);
+
+    circuits
+        .into_iter()
+        .find(|el| matches!(el, ZkSyncBaseLayerCircuit::L1MessagesHasher(_)))
+        .unwrap()
+}
+
+#[test]
+fn test_profile_prove_and_verify() {
+    use circuit_definitions::ProofConfigProfile;
+
+    let circuit = small_base_layer_circuit();
+    let worker = Worker::new();
+    let proof_config = ProofConfigProfile::Test.base_layer_proof_config();
+    let (setup_base, setup, vk, setup_tree, vars_hint, wits_hint, finalization_hint) =
+        create_base_layer_setup_data(
+            circuit.clone(),
+            &worker,
+            proof_config.fri_lde_factor,
+            proof_config.merkle_tree_cap_size,
+        );
+    assert_eq!(ProofConfigProfile::of_vk(&vk), Ok(ProofConfigProfile::Test));
+
+    let proof = prove_base_layer_circuit::<NoPow>(
+        circuit.clone(),
+        &worker,
+        proof_config,
+        &setup_base,
+        &setup,
+        &setup_tree,
+        &vk,
+        &vars_hint,
+        &wits_hint,
+        &finalization_hint,
+    );
+    assert!(verify_base_layer_proof::<NoPow>(&circuit, &proof, &vk));
+    assert!(ProofConfigProfile::Test.matches_proof_config(&proof.proof_config));
+    assert!(!ProofConfigProfile::Production.matches_proof_config(&proof.proof_config));
+}
+
+#[test]
+fn setup_data_round_trip() {
+    use crate::data_source::local_file_data_source::LocalFileDataSource;
+    use crate::data_source::migration::is_missing;
+    use circuit_definitions::ProofConfigProfile;
+
+    let circuit_type = small_base_layer_circuit().numeric_circuit_type();
+    let options = SetupGenerationOptions {
+        profile: ProofConfigProfile::Test,
+        geometry: get_testing_geometry_config(),
+        store_setup_data: true,
+        ..Default::default()
+    };
+    let mut in_memory = InMemoryDataSource::new();
+    assert!(is_missing(
+        in_memory
+            .get_base_layer_setup_data(circuit_type)
+            .unwrap_err()
+            .as_ref()
+    ));
+    let setup_data =
+        generate_circuit_setup_data_with_options(true, circuit_type, &mut in_memory, &options)
+            .unwrap();
+    let serialized = bincode::serialize(&setup_data).unwrap();
+    assert_eq!(
+        bincode::serialize(&in_memory.get_base_layer_setup_data(circuit_type).unwrap()).unwrap(),
+        serialized
+    );
+
+    let root = std::env::temp_dir().join(format!("setup_data_{}", std::process::id()));
+    let location = root.to_str().unwrap().to_string();
+    let mut local = LocalFileDataSource::new(location.clone(), location);
+    local.create_folders_for_storing_data();
+    local
+        .set_base_layer_setup_data(circuit_type, &setup_data)
+        .unwrap();
+    assert_eq!(
+        bincode::serialize(&local.get_base_layer_setup_data(circuit_type).unwrap()).unwrap(),
+        serialized
+    );
+
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn eip4844_round_trip() {
+    use crate::data_source::local_file_data_source::LocalFileDataSource;
+    use crate::data_source::migration::is_missing;
+    use circuit_definitions::circuit_definitions::aux_layer::{
+        EIP4844Proof, EIP4844VerificationKey,
+    };
+    use circuit_definitions::ProofConfigProfile;
+
+    // the EIP-4844 circuit uses the base layer hasher, so a small base layer setup stands in for it
+    let worker = Worker::new();
+    let proof_config = ProofConfigProfile::Test.base_layer_proof_config();
+    let (_, _, vk, _, _, _, finalization_hint) = create_base_layer_setup_data(
+        small_base_layer_circuit(),
+        &worker,
+        proof_config.fri_lde_factor,
+        proof_config.merkle_tree_cap_size,
+    );
+    let proof = create_mock_proof::<ZkSyncBaseLayerCircuit>(proof_config);
+    let serialized = (
+        bincode::serialize(&vk).unwrap(),
+        bincode::serialize(&finalization_hint).unwrap(),
+        bincode::serialize(&proof).unwrap(),
+    );
+
+    fn check<DS: SetupDataSource + BlockDataSource>(
+        source: &mut DS,
+        vk: &EIP4844VerificationKey,
+        finalization_hint: &FinalizationHintsForProver,
+        proof: &EIP4844Proof,
+    ) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        assert!(is_missing(source.get_eip4844_vk().unwrap_err().as_ref()));
+        assert!(is_missing(
+            source.get_eip4844_proof(1).unwrap_err().as_ref()
+        ));
+
+        source.set_eip4844_vk(vk.clone()).unwrap();
+        source
+            .set_eip4844_finalization_hint(finalization_hint.clone())
+            .unwrap();
+        source.set_eip4844_proof(1, proof.clone()).unwrap();
+        assert!(is_missing(
+            source.get_eip4844_proof(0).unwrap_err().as_ref()
+        ));
+        assert_eq!(source.list_eip4844_proofs().unwrap(), vec![1]);
+
+        (
+            bincode::serialize(&source.get_eip4844_vk().unwrap()).unwrap(),
+            bincode::serialize(&source.get_eip4844_finalization_hint().unwrap()).unwrap(),
+            bincode::serialize(&source.get_eip4844_proof(1).unwrap()).unwrap(),
+        )
+    }
+
+    let mut in_memory = InMemoryDataSource::new();
+    assert_eq!(
+        check(&mut in_memory, &vk, &finalization_hint, &proof),
+        serialized
+    );
+
+    let root = std::env::temp_dir().join(format!("eip4844_round_trip_{}", std::process::id()));
+    let location = root.to_str().unwrap().to_string();
+    let mut local = LocalFileDataSource::new(location.clone(), location);
+    local.create_folders_for_storing_data();
+    assert_eq!(
+        check(&mut local, &vk, &finalization_hint, &proof),
+        serialized
+    );
+
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn mixing_proof_config_profiles_fails() {
+    use crate::proving_pipeline::ProvingPipeline;
+    use circuit_definitions::{ProfileError, ProofConfigProfile};
+
+    let circuit = small_base_layer_circuit();
+    let circuit_type = circuit.numeric_circuit_type();
+    let worker = Worker::new();
+    let proof_config = ProofConfigProfile::Test.base_layer_proof_config();
+    let (_, _, vk, _, _, _, finalization_hint) = create_base_layer_setup_data(
+        circuit.clone(),
+        &worker,
+        proof_config.fri_lde_factor,
+        proof_config.merkle_tree_cap_size,
+    );
+
+    let mut setup_source = InMemoryDataSource::new();
+    setup_source
+        .set_base_layer_vk(ZkSyncBaseLayerVerificationKey::from_inner(
+            circuit_type,
+            vk.clone(),
+        ))
+        .unwrap();
+    setup_source
+        .set_base_layer_finalization_hint(ZkSyncBaseLayerFinalizationHint::from_inner(
+            circuit_type,
+            finalization_hint,
+        ))
+        .unwrap();
+
+    // pipeline proves with the production profile by default
+    let mut block_source = InMemoryDataSource::new();
+    let err = ProvingPipeline::new(&mut setup_source, &mut block_source, DEFAULT_WRAPPER_CONFIG)
+        .prove_base_layer(&[circuit])
+        .unwrap_err();
+    assert!(err.to_string().contains("Test proof config profile"));
+    assert!(block_source.get_base_layer_proof(circuit_type, 0).is_err());
+
+    // keys of different profiles can't be aggregated together
+    let production_config = ProofConfigProfile::Production.base_layer_proof_config();
+    let mut production_vk = vk.clone();
+    production_vk.fixed_parameters.cap_size = production_config.merkle_tree_cap_size;
+    assert_eq!(
+        ProofConfigProfile::of_vks([&vk, &production_vk]),
+        Err(ProfileError::Mixed(
+            ProofConfigProfile::Test,
+            ProofConfigProfile::Production
+        ))
+    );
+    // and keys of unknown parameters are reported instead of panicking
+    let mut unknown_vk = vk.clone();
+    unknown_vk.fixed_parameters.cap_size = 3;
+    assert!(matches!(
+        ProofConfigProfile::of_vks([&vk, &unknown_vk]),
+        Err(ProfileError::UnknownParameters { cap_size: 3, .. })
+    ));
+}
+
+#[test]
+fn batch_verification_outcomes() {
+    use crate::batch_verification::{verify_batch_proofs, VerificationOutcome};
+    use crate::boojum::field::SmallField;
+    use crate::data_source::local_file_data_source::LocalFileDataSource;
+    use crate::data_source::ProofId;
+    use circuit_definitions::ProofConfigProfile;
+
+    let circuit = small_base_layer_circuit();
+    let circuit_type = circuit.numeric_circuit_type();
+    // proofs stored under this type get the key of `circuit_type`
+    let other_type = BaseLayerCircuitType::VM as u8;
+    let worker = Worker::new();
+    let proof_config = ProofConfigProfile::Test.base_layer_proof_config();
+    let (setup_base, setup, vk, setup_tree, vars_hint, wits_hint, finalization_hint) =
+        create_base_layer_setup_data(
+            circuit.clone(),
+            &worker,
+            proof_config.fri_lde_factor,
+            proof_config.merkle_tree_cap_size,
+        );
+    let proof = prove_base_layer_circuit::<NoPow>(
+        circuit,
+        &worker,
+        proof_config,
+        &setup_base,
+        &setup,
+        &setup_tree,
+        &vk,
+        &vars_hint,
+        &wits_hint,
+        &finalization_hint,
+    );
+    let mut invalid_proof = proof.clone();
+    invalid_proof.public_inputs[0] =
+        GoldilocksField::from_u64_unchecked(invalid_proof.public_inputs[0].as_u64_reduced() ^ 1);
+
+    let root = std::env::temp_dir().join(format!("batch_verification_{}", std::process::id()));
+    let location = root.to_str().unwrap().to_string();
+    let mut source = LocalFileDataSource::new(location.clone(), location);
+    source.create_folders_for_storing_data();
+    source
+        .set_base_layer_vk(ZkSyncBaseLayerVerificationKey::from_inner(circuit_type, vk))
+        .unwrap();
+    std::fs::copy(
+        root.join(format!("base_layer/vk_{}.json", circuit_type)),
+        root.join(format!("base_layer/vk_{}.json", other_type)),
+    )
+    .unwrap();
+
+    source
+        .set_base_layer_proof(
+            0,
+            ZkSyncBaseLayerProof::from_inner(circuit_type, proof.clone()),
+        )
+        .unwrap();
+    source
+        .set_base_layer_proof(
+            1,
+            ZkSyncBaseLayerProof::from_inner(circuit_type, invalid_proof),
+        )
+        .unwrap();
+    source
+        .set_base_layer_proof(0, ZkSyncBaseLayerProof::from_inner(other_type, proof))
+        .unwrap();
+    std::fs::write(
+        root.join(format!(
+            "base_layer/basic_circuit_proof_{}_2.json",
+            circuit_type
+        )),
+        "not a proof",
+    )
+    .unwrap();
+
+    let report = verify_batch_proofs(&source, &source).unwrap();
+    let outcomes: Vec<_> = report
+        .results
+        .iter()
+        .map(|el| (el.id, el.outcome.clone()))
+        .collect();
+    assert_eq!(outcomes.len(), 4);
+    assert_eq!(
+        outcomes[0],
+        (
+            ProofId::BaseLayer {
+                circuit_type: other_type,
+                index: 0
+            },
+            VerificationOutcome::CircuitTypeMismatch {
+                proof: other_type,
+                vk: circuit_type
+            }
+        )
+    );
+    assert_eq!(
+        outcomes[1],
+        (
+            ProofId::BaseLayer {
+                circuit_type,
+                index: 0
+            },
+            VerificationOutcome::Valid
+        )
+    );
+    assert_eq!(
+        outcomes[2],
+        (
+            ProofId::BaseLayer {
+                circuit_type,
+                index: 1
+            },
+            VerificationOutcome::Invalid
+        )
+    );
+    assert!(matches!(
+        outcomes[3],
+        (
+            ProofId::BaseLayer { index: 2, .. },
+            VerificationOutcome::LoadError(..)
+        )
+    ));
+
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+/// Runs the whole pipeline in mock mode over sources in RAM, then resumes it from the stored proofs
+#[ignore = "Too slow"]
+#[test]
+fn mock_proving_pipeline_run_through() {
+    use crate::proving_pipeline::{BlockProvingInput, ProvingPipeline};
+    use circuit_definitions::ProofConfigProfile;
+
+    let test_artifact = read_basic_test_artifact();
+    let blobs = std::array::from_fn(|i| {
+        if i == 0 {
+            Some(vec![0xff; ENCODABLE_BYTES_PER_BLOB])
+        } else {
+            None
+        }
+    });
+    let geometry = get_testing_geometry_config();
+    let mut setup_source = test_profile_setup_source(&geometry);
+    let (base_layer_circuits, recursion_queues, scheduler_witness) =
+        generate_base_layer(test_artifact, 40000, geometry, blobs);
+
+    let mut block_source = InMemoryDataSource::new();
+    let input = BlockProvingInput {
+        base_layer_circuits: base_layer_circuits.clone(),
+        recursion_queues: recursion_queues.clone(),
+        scheduler_witness: scheduler_witness.clone(),
+    };
+    let mut pipeline =
+        ProvingPipeline::new(&mut setup_source, &mut block_source, DEFAULT_WRAPPER_CONFIG)
+            .with_proving_mode(ProvingMode::Mock)
+            .with_proof_config_profile(ProofConfigProfile::Test);
+    let scheduler_proof = pipeline.run_recursion(input).unwrap();
+    assert!(is_mock_proof(&scheduler_proof.clone().into_inner()));
+    // mock proofs can't be compressed
+    assert!(pipeline.prove_compression_and_wrapper().is_err());
+
+    let mut instance_indexes: HashMap<u8, usize> = HashMap::new();
+    for circuit in base_layer_circuits.iter() {
+        let circuit_type = circuit.numeric_circuit_type();
+        let idx = instance_indexes.entry(circuit_type).or_insert(0);
+        let proof = block_source
+            .get_base_layer_proof(circuit_type, *idx)
+            .unwrap();
+        assert!(is_mock_proof(proof.as_inner()));
+        *idx += 1;
+    }
+    assert!(block_source.get_recursive_tip_proof().is_ok());
+
+    // second run picks up the stored proofs
+    let input = BlockProvingInput {
+        base_layer_circuits,
+        recursion_queues,
+        scheduler_witness,
+    };
+    let mut pipeline =
+        ProvingPipeline::new(&mut setup_source, &mut block_source, DEFAULT_WRAPPER_CONFIG)
+            .with_proving_mode(ProvingMode::Mock)
+            .with_proof_config_profile(ProofConfigProfile::Test);
+    let resumed_proof = pipeline.run_recursion(input).unwrap();
+    assert_eq!(
+        bincode::serialize(&resumed_proof).unwrap(),
+        bincode::serialize(&scheduler_proof).unwrap()
+    );
+}
+
+/// Proofs left by a mock run must be rejected wherever a real run reuses or consumes them
+#[ignore = "Too slow"]
+#[test]
+fn mock_proofs_are_rejected_in_real_mode() {
+    use crate::proving_pipeline::{BlockProvingInput, ProvingPipeline};
+    use crate::witness::aggregation_plan::AggregationPlan;
+    use crate::witness::recursive_aggregation::RecursionArity;
+    use crate::zkevm_circuits::scheduler::aux::BaseLayerCircuitType;
+    use circuit_definitions::ProofConfigProfile;
+
+    let test_artifact = read_basic_test_artifact();
+    let blobs = std::array::from_fn(|i| {
+        if i == 0 {
+            Some(vec![0xff; ENCODABLE_BYTES_PER_BLOB])
+        } else {
+            None
+        }
+    });
+    let geometry = get_testing_geometry_config();
+    let mut setup_source = test_profile_setup_source(&geometry);
+    let (base_layer_circuits, recursion_queues, scheduler_witness) =
+        generate_base_layer(test_artifact, 40000, geometry, blobs);
+
+    let mut block_source = InMemoryDataSource::new();
+    let input = BlockProvingInput {
+        base_layer_circuits: base_layer_circuits.clone(),
+        recursion_queues: recursion_queues.clone(),
+        scheduler_witness: scheduler_witness.clone(),
+    };
+    ProvingPipeline::new(&mut setup_source, &mut block_source, DEFAULT_WRAPPER_CONFIG)
+        .with_proving_mode(ProvingMode::Mock)
+        .with_proof_config_profile(ProofConfigProfile::Test)
+        .run_recursion(input)
+        .unwrap();
+    let tip_proof = block_source.get_recursive_tip_proof().unwrap();
+
+    // base layer proofs are consumed by the leaves
+    let input = BlockProvingInput {
+        base_layer_circuits,
+        recursion_queues: recursion_queues.clone(),
+        scheduler_witness: scheduler_witness.clone(),
+    };
+    let mut pipeline =
+        ProvingPipeline::new(&mut setup_source, &mut block_source, DEFAULT_WRAPPER_CONFIG)
+            .with_proof_config_profile(ProofConfigProfile::Test);
+    let err = pipeline.run_recursion(input).unwrap_err();
+    assert!(err.to_string().contains("is a mock proof"));
+
+    // stored recursion tip and scheduler proofs are not returned as they are
+    let err = pipeline
+        .prove_recursion_tip(&recursion_queues, &HashMap::new())
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("recursion tip proof is a mock proof"));
+    let err = pipeline
+        .prove_scheduler(scheduler_witness.clone(), tip_proof.clone())
+        .unwrap_err();
+    assert!(err.to_string().contains("scheduler proof is a mock proof"));
+
+    // final nodes are consumed by the recursion tip, and the tip by the scheduler
+    let plan = AggregationPlan::from_recursion_queues(
+        recursion_queues
+            .iter()
+            .map(|(circuit_type, queue, _)| (*circuit_type, queue)),
+        RecursionArity::default(),
+    );
+    let mut node_source = InMemoryDataSource::new();
+    let mut node_depths = HashMap::new();
+    for circuit_plan in plan.circuit_types.iter() {
+        let Some(depth) = circuit_plan.final_node_depth() else {
+            continue;
+        };
+        let recursive_circuit_type = base_circuit_type_into_recursive_leaf_circuit_type(
+            BaseLayerCircuitType::from_numeric_value(circuit_plan.circuit_type),
+        ) as u8;
+        let proof = block_source
+            .get_node_layer_proof(recursive_circuit_type, depth, 0)
+            .unwrap();
+        node_source
+            .set_node_layer_proof(recursive_circuit_type, depth, 0, proof)
+            .unwrap();
+        node_depths.insert(recursive_circuit_type, depth);
+    }
+    let mut pipeline =
+        ProvingPipeline::new(&mut setup_source, &mut node_source, DEFAULT_WRAPPER_CONFIG)
+            .with_proof_config_profile(ProofConfigProfile::Test);
+    let err = pipeline
+        .prove_recursion_tip(&recursion_queues, &node_depths)
+        .unwrap_err();
+    assert!(err.to_string().contains("node layer proof is a mock proof"));
+    let err = pipeline
+        .prove_scheduler(scheduler_witness, tip_proof)
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("recursion tip proof is a mock proof"));
+}
+
+/// Incremental aggregation must create the same leaves and nodes as the batch one,
+/// even if the proofs come out of order
+#[ignore = "Too slow"]
+#[test]
+fn incremental_aggregation_matches_batch_witnesses() {
+    use crate::witness::incremental_aggregation::{AggregationLayer, IncrementalAggregator};
+    use crate::witness::recursive_aggregation::{
+        compute_node_vk_commitment, create_leaf_witnesses_with_arity,
+        create_node_witnesses_with_arity, RecursionArity,
+    };
+    use crate::zkevm_circuits::scheduler::aux::BaseLayerCircuitType;
+    use circuit_definitions::ProofConfigProfile;
+
+    let test_artifact = read_basic_test_artifact();
+    let blobs = std::array::from_fn(|i| {
+        if i == 0 {
+            Some(vec![0xff; ENCODABLE_BYTES_PER_BLOB])
+        } else {
+            None
+        }
+    });
+    let geometry = get_testing_geometry_config();
+    let mut setup_source = test_profile_setup_source(&geometry);
+    let (_, recursion_queues, _) = generate_base_layer(test_artifact, 40000, geometry, blobs);
+
+    // small arity, so that there are several node layers
+    let arity = RecursionArity::new(4, 2);
+    let leaf_params = compute_leaf_params(&mut setup_source).unwrap();
+    let node_vk = setup_source.get_recursion_layer_node_vk().unwrap();
+    let node_vk_commitment = compute_node_vk_commitment(node_vk.clone());
+    let base_proof = create_mock_proof::<ZkSyncBaseLayerCircuit>(
+        ProofConfigProfile::Test.base_layer_proof_config(),
+    );
+    let recursion_proof = create_mock_proof::<ZkSyncRecursiveLayerCircuit>(
+        ProofConfigProfile::Test.recursion_layer_proof_config(),
+    );
+
+    let mut base_vks = HashMap::new();
+    let mut leaf_vks = HashMap::new();
+    let mut expected = HashMap::new();
+    for (circuit_type, queue, closed_form_inputs) in recursion_queues.iter() {
+        let base_circuit_type = *circuit_type as u8;
+        let base_vk = setup_source.get_base_layer_vk(base_circuit_type).unwrap();
+        let leaf_vk = setup_source
+            .get_recursion_layer_vk(base_circuit_type_into_recursive_leaf_circuit_type(
+                BaseLayerCircuitType::from_numeric_value(base_circuit_type),
+            ) as u8)
+            .unwrap();
+        base_vks.insert(base_circuit_type, base_vk.clone());
+        leaf_vks.insert(base_circuit_type, leaf_vk.clone());
+        if queue.num_items == 0 {
+            continue;
+        }
+
+        let params = leaf_params
+            .iter()
+            .find(|el| el.0 == base_circuit_type)
+            .cloned()
+            .unwrap();
+        let proofs = (0..queue.num_items)
+            .map(|_| ZkSyncBaseLayerProof::from_inner(base_circuit_type, base_proof.clone()))
+            .collect();
+        let (mut aggregations, _) = create_leaf_witnesses_with_arity(
+            (*circuit_type, queue.clone(), closed_form_inputs.clone()),
+            proofs,
+            base_vk,
+            params,
+            arity,
+        )
+        .unwrap();
+        for (index, (_, _, circuit)) in aggregations.iter().enumerate() {
+            expected.insert(
+                (base_circuit_type, AggregationLayer::Leaf, index),
+                bincode::serialize(circuit).unwrap(),
+            );
+        }
+
+        let mut depth = 0;
+        loop {
+            let vk = if depth == 0 {
+                leaf_vk.clone()
+            } else {
+                node_vk.clone()
+            };
+            let proofs = aggregations
+                .iter()
+                .map(|_| recursion_proof.clone())
+                .map(|el| ZkSyncRecursionLayerProof::from_inner(vk.numeric_circuit_type(), el))
+                .collect();
+            aggregations = create_node_witnesses_with_arity(
+                aggregations,
+                proofs,
+                vk,
+                node_vk_commitment,
+                &leaf_params,
+                arity,
+            )
+            .unwrap();
+            for (index, (_, _, circuit)) in aggregations.iter().enumerate() {
+                expected.insert(
+                    (base_circuit_type, AggregationLayer::Node { depth }, index),
+                    bincode::serialize(circuit).unwrap(),
+                );
+            }
+            if aggregations.len() == 1 {
+                break;
+            }
+            depth += 1;
+        }
+    }
+
+    let mut aggregator =
+        IncrementalAggregator::new(base_vks, leaf_vks, node_vk, leaf_params, arity);
+    let mut ready = vec![];
+    for (circuit_type, queue, _) in recursion_queues.iter() {
+        let base_circuit_type = *circuit_type as u8;
+        // proofs come in reverse order, some of them before the queue
+        let num_items = queue.num_items as usize;
+        let num_early = num_items / 2;
+        for index in (num_items - num_early..num_items).rev() {
+            let proof = ZkSyncBaseLayerProof::from_inner(base_circuit_type, base_proof.clone());
+            ready.extend(
+                aggregator
+                    .add_base_proof(base_circuit_type, index, proof)
+                    .unwrap(),
+            );
+        }
+        ready.extend(
+            aggregator
+                .add_recursion_queue(base_circuit_type, queue.clone())
+                .unwrap(),
+        );
+        for index in (0..num_items - num_early).rev() {
+            let proof = ZkSyncBaseLayerProof::from_inner(base_circuit_type, base_proof.clone());
+            ready.extend(
+                aggregator
+                    .add_base_proof(base_circuit_type, index, proof)
+                    .unwrap(),
+            );
+        }
+    }
+
+    let mut emitted = HashMap::new();
+    while let Some(aggregation) = ready.pop() {
+        let proof = ZkSyncRecursionLayerProof::from_inner(
+            aggregation.circuit.numeric_circuit_type(),
+            recursion_proof.clone(),
+        );
+        ready.extend(
+            match aggregation.layer {
+                AggregationLayer::Leaf => {
+                    aggregator.add_leaf_proof(aggregation.circuit_type, aggregation.index, proof)
+                }
+                AggregationLayer::Node { depth } => aggregator.add_node_proof(
+                    aggregation.circuit_type,
+                    depth,
+                    aggregation.index,
+                    proof,
+                ),
+            }
+            .unwrap(),
+        );
+        let previous = emitted.insert(
+            (
+                aggregation.circuit_type,
+                aggregation.layer,
+                aggregation.index,
+            ),
+            bincode::serialize(&aggregation.circuit).unwrap(),
+        );
+        assert!(previous.is_none());
+    }
+
+    assert!(aggregator.is_finished());
+    assert_eq!(emitted.len(), expected.len());
+    for (key, circuit) in expected.iter() {
+        assert!(emitted.get(key) == Some(circuit), "{:?} differs", key);
+    }
+}
+
 struct Options {
     // Additional tests over the basic circuits.
     test_base_circuits: bool,
@@ -371,6 +1239,15 @@ fn run_and_try_create_witness_inner(
     // It is important that recursion queries are in sorted order - as we later match them with respective proofs.
     recursion_queues.sort_by_key(|(circuit, _, _)| circuit.clone());
 
+    use crate::witness::public_inputs::*;
+    check_recursion_queue_public_inputs(
+        &basic_block_circuits,
+        recursion_queues
+            .iter()
+            .map(|(circuit_type, queue, _)| (*circuit_type, queue)),
+    )
+    .expect("public inputs of circuits must match recursion queues");
+
     if options.test_base_circuits {
         for (idx, el) in basic_block_circuits.clone().into_iter().enumerate() {
             let descr = el.short_description();
@@ -533,6 +1410,12 @@ fn run_and_try_create_witness_inner(
         proofs.push(proofs_for_circuit_type);
     }
 
+    check_proof_public_inputs(
+        &basic_block_circuits,
+        &proofs.iter().flatten().cloned().collect::<Vec<_>>(),
+    )
+    .expect("public inputs of circuits must match proofs");
+
     println!("Computing leaf vks");
 
     for base_circuit_type in ((BaseLayerCircuitType::VM as u8)
@@ -28,9 +28,9 @@ pub(crate) fn test_compression_for_compression_num(config: WrapperConfig) {
         )
         .unwrap();
 
-    compute_compression_circuits(&mut source, config, &worker);
-    compute_compression_for_wrapper_circuit(&mut source, config, &worker);
-    compute_wrapper_proof_and_vk(&mut source, config, &bellman_worker);
+    compute_compression_circuits(&mut source, config, &worker).unwrap();
+    compute_compression_for_wrapper_circuit(&mut source, config, &worker).unwrap();
+    compute_wrapper_proof_and_vk(&mut source, config, &bellman_worker).unwrap();
 
     // Write wrapper proof and vk
     let wrapper_type = config.get_wrapper_type();
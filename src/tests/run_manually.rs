@@ -213,7 +213,7 @@ pub(crate) fn run_and_try_create_witness_for_extended_state(
 }
 
 pub(crate) fn run_with_options(entry_point_bytecode: Vec<[u8; 32]>, options: Options) {
-    use crate::run_vms::{run_vms, RunVmError};
+    use crate::run_vms::{run_vms_with_options, RunVmError, RunVmOptions};
     use crate::tests::utils::testing_tracer::TestingTracer;
     use crate::toolset::GeometryConfig;
     use crate::zk_evm::zkevm_opcode_defs::system_params::BOOTLOADER_FORMAL_ADDRESS;
@@ -262,7 +262,7 @@ pub(crate) fn run_with_options(entry_point_bytecode: Vec<[u8; 32]>, options: Opt
     // we are using TestingTracer to track prints and exceptions inside out_of_circuit_vm cycles
     let mut out_of_circuit_tracer = TestingTracer::default();
 
-    if let Err(err) = run_vms(
+    if let Err(err) = run_vms_with_options(
         Address::zero(),
         *BOOTLOADER_FORMAL_ADDRESS,
         entry_point_bytecode,
@@ -281,6 +281,9 @@ pub(crate) fn run_with_options(entry_point_bytecode: Vec<[u8; 32]>, options: Opt
         |circuit| basic_block_circuits.push(circuit),
         |_, _, _| {},
         &mut out_of_circuit_tracer,
+        RunVmOptions {
+            check_storage_consistency: true,
+        },
     ) {
         let error_text = match err {
             RunVmError::InvalidInput(msg) => {
@@ -294,6 +297,10 @@ pub(crate) fn run_with_options(entry_point_bytecode: Vec<[u8; 32]>, options: Opt
                 };
                 format!("Out-of-circuit execution error: {msg}")
             }
+            RunVmError::StorageInconsistency(discrepancies) => {
+                let msg = RunVmError::describe_storage_inconsistency(&discrepancies);
+                format!("Storage inconsistency error: {msg}")
+            }
         };
         panic!("{error_text}");
     }
@@ -20,6 +20,30 @@ pub struct ProvingToolset<S: Storage> {
 use circuit_definitions::zk_evm::aux_structures::PubdataCost;
 pub use circuit_sequencer_api::toolset::GeometryConfig;
 
+/// Lower memory requirements
+/// Used for base layer debugging and for experimental key sets
+pub const fn get_testing_geometry_config() -> GeometryConfig {
+    GeometryConfig {
+        // cycles_per_vm_snapshot: 1,
+        cycles_per_vm_snapshot: 1024,
+        cycles_per_ram_permutation: 1024,
+        cycles_per_code_decommitter: 256,
+        cycles_per_storage_application: 4,
+        cycles_per_keccak256_circuit: 7,
+        cycles_per_sha256_circuit: 7,
+        cycles_per_ecrecover_circuit: 2,
+        // cycles_code_decommitter_sorter: 512,
+        cycles_code_decommitter_sorter: 3,
+        cycles_per_log_demuxer: 16,
+        cycles_per_storage_sorter: 16,
+        cycles_per_events_or_l1_messages_sorter: 4,
+        cycles_per_secp256r1_verify_circuit: 2,
+        cycles_per_transient_storage_sorter: 16,
+
+        limit_for_l1_messages_pudata_hasher: 32,
+    }
+}
+
 pub fn create_tools<S: Storage>(storage: S, config: &GeometryConfig) -> ProvingToolset<S> {
     let memory = SimpleMemory::new_without_preallocations();
     let event_sink = InMemoryEventSink::new();
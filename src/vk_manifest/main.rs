@@ -0,0 +1,56 @@
+use std::fs;
+
+use structopt::StructOpt;
+use zkevm_test_harness::data_source::local_file_data_source::LocalFileDataSource;
+use zkevm_test_harness::vk_manifest::VkManifest;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "VK manifest",
+    about = "Tool for recording verification key hashes and comparing them between setups"
+)]
+enum Opt {
+    /// Write the manifest of all keys in the setup directory.
+    Generate {
+        #[structopt(long, default_value = "./setup")]
+        setup_dir: String,
+        #[structopt(long)]
+        output: String,
+    },
+    /// List circuits whose keys differ between two manifests.
+    Diff { old: String, new: String },
+}
+
+fn read_manifest(path: &str) -> VkManifest {
+    let json = fs::read_to_string(path).expect("Unable to read manifest");
+    VkManifest::from_json(&json).expect("Unable to parse manifest")
+}
+
+fn main() {
+    match Opt::from_args() {
+        Opt::Generate { setup_dir, output } => {
+            let mut source = LocalFileDataSource {
+                setup_data_location: setup_dir,
+                ..Default::default()
+            };
+            let manifest = VkManifest::from_source(&mut source).expect("Unable to build manifest");
+            fs::write(&output, manifest.to_json().unwrap()).expect("Unable to write manifest");
+            println!(
+                "Wrote manifest with {} entries to {}",
+                manifest.entries().len(),
+                output
+            );
+        }
+        Opt::Diff { old, new } => {
+            let changes = read_manifest(&old).diff(&read_manifest(&new));
+            if changes.is_empty() {
+                println!("No verification keys changed");
+                return;
+            }
+            for change in changes.iter() {
+                println!("{}", change);
+            }
+            std::process::exit(1);
+        }
+    }
+}
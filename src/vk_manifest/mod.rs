@@ -0,0 +1,316 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::boojum::field::goldilocks::GoldilocksField;
+use crate::boojum::field::SmallField;
+use crate::compute_setups::compute_leaf_params;
+use crate::data_source::migration::is_missing;
+use crate::data_source::{base_circuit_types, SetupDataSource, SourceResult};
+use crate::proof_wrapper_utils::WrapperConfig;
+use crate::sha3::{Digest, Keccak256};
+use crate::witness::recursive_aggregation::{
+    compute_leaf_vks_and_params_commitment, compute_node_vk_commitment,
+};
+use crate::zkevm_circuits::recursion::leaf_layer::input::RecursionLeafParametersWitness;
+use crate::zkevm_circuits::scheduler::aux::NUM_CIRCUIT_TYPES_TO_SCHEDULE;
+use circuit_definitions::circuit_definitions::recursion_layer::*;
+use serde::{Deserialize, Serialize};
+
+/// Hashes of all verification keys in a setup, together with the
+/// commitments that the recursion layer uses to bind them
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VkManifest {
+    pub base_layer: BTreeMap<u8, String>,
+    pub recursion_layer: BTreeMap<u8, String>,
+    pub compression: BTreeMap<u8, String>,
+    pub compression_for_wrapper: BTreeMap<u8, String>,
+    pub wrapper: BTreeMap<u8, String>,
+    pub leaf_params_commitment: Option<String>,
+    pub node_vk_commitment: Option<String>,
+    pub scheduler_vk_commitment: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VkManifestChange {
+    Added {
+        key: String,
+        hash: String,
+    },
+    Removed {
+        key: String,
+        hash: String,
+    },
+    Changed {
+        key: String,
+        old: String,
+        new: String,
+    },
+}
+
+impl VkManifestChange {
+    pub fn key(&self) -> &str {
+        match self {
+            VkManifestChange::Added { key, .. }
+            | VkManifestChange::Removed { key, .. }
+            | VkManifestChange::Changed { key, .. } => key,
+        }
+    }
+}
+
+impl fmt::Display for VkManifestChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VkManifestChange::Added { key, hash } => write!(f, "+ {}: {}", key, hash),
+            VkManifestChange::Removed { key, hash } => write!(f, "- {}: {}", key, hash),
+            VkManifestChange::Changed { key, old, new } => {
+                write!(f, "~ {}: {} -> {}", key, old, new)
+            }
+        }
+    }
+}
+
+fn keccak_hex(bytes: &[u8]) -> String {
+    hex::encode(Keccak256::digest(bytes))
+}
+
+fn hash_serialized<T: Serialize>(value: &T) -> SourceResult<String> {
+    let bytes =
+        bincode::serialize(value).map_err(|el| Box::new(el) as Box<dyn std::error::Error>)?;
+    Ok(keccak_hex(&bytes))
+}
+
+fn commitment_hex(elements: &[GoldilocksField]) -> String {
+    let bytes: Vec<u8> = elements
+        .iter()
+        .flat_map(|el| el.as_u64_reduced().to_be_bytes())
+        .collect();
+    hex::encode(bytes)
+}
+
+// Missing keys are skipped, any other read error is propagated
+fn if_present<T>(value: SourceResult<T>) -> SourceResult<Option<T>> {
+    match value {
+        Ok(value) => Ok(Some(value)),
+        Err(err) if is_missing(err.as_ref()) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+impl VkManifest {
+    /// Builds the manifest from whatever keys are present in the source,
+    /// commitments are only computed if all of their inputs are available.
+    /// Keys are considered missing if the source reports them with `ErrorKind::NotFound`
+    pub fn from_source(source: &mut dyn SetupDataSource) -> SourceResult<Self> {
+        let mut manifest = Self::default();
+
+        for circuit_type in base_circuit_types() {
+            if let Some(vk) = if_present(source.get_base_layer_vk(circuit_type))? {
+                manifest
+                    .base_layer
+                    .insert(circuit_type, hash_serialized(&vk)?);
+            }
+        }
+
+        for circuit_type in ZkSyncRecursionLayerStorageType::as_iter_u8() {
+            let vk = match circuit_type {
+                a if a == ZkSyncRecursionLayerStorageType::NodeLayerCircuit as u8 => {
+                    source.get_recursion_layer_node_vk()
+                }
+                a if a == ZkSyncRecursionLayerStorageType::RecursionTipCircuit as u8 => {
+                    source.get_recursion_tip_vk()
+                }
+                a => source.get_recursion_layer_vk(a),
+            };
+            if let Some(vk) = if_present(vk)? {
+                manifest
+                    .recursion_layer
+                    .insert(circuit_type, hash_serialized(&vk)?);
+            }
+        }
+
+        for circuit_type in 1..=WrapperConfig::MAX_COMPRESSION_LAYERS {
+            if let Some(vk) = if_present(source.get_compression_vk(circuit_type))? {
+                manifest
+                    .compression
+                    .insert(circuit_type, hash_serialized(&vk)?);
+            }
+            if let Some(vk) = if_present(source.get_compression_for_wrapper_vk(circuit_type))? {
+                manifest
+                    .compression_for_wrapper
+                    .insert(circuit_type, hash_serialized(&vk)?);
+            }
+            if let Some(vk) = if_present(source.get_wrapper_vk(circuit_type))? {
+                // snark VKs are not serde-serializable, so hash their canonical encoding
+                let mut bytes = vec![];
+                vk.into_inner()
+                    .write(&mut bytes)
+                    .map_err(|el| Box::new(el) as Box<dyn std::error::Error>)?;
+                manifest.wrapper.insert(circuit_type, keccak_hex(&bytes));
+            }
+        }
+
+        if let Some(leaf_params) = if_present(compute_leaf_params(source))? {
+            let leaf_params: [RecursionLeafParametersWitness<GoldilocksField>;
+                NUM_CIRCUIT_TYPES_TO_SCHEDULE] = leaf_params
+                .into_iter()
+                .map(|el| el.1)
+                .collect::<Vec<_>>()
+                .try_into()
+                .unwrap();
+            let commitment = compute_leaf_vks_and_params_commitment(leaf_params);
+            manifest.leaf_params_commitment = Some(commitment_hex(&commitment));
+        }
+
+        if let Some(node_vk) = if_present(source.get_recursion_layer_node_vk())? {
+            let commitment = compute_node_vk_commitment(node_vk);
+            manifest.node_vk_commitment = Some(commitment_hex(&commitment));
+        }
+
+        // committed the same way as the node VK
+        if let Some(scheduler_vk) = if_present(
+            source.get_recursion_layer_vk(ZkSyncRecursionLayerStorageType::SchedulerCircuit as u8),
+        )? {
+            let commitment = compute_node_vk_commitment(scheduler_vk);
+            manifest.scheduler_vk_commitment = Some(commitment_hex(&commitment));
+        }
+
+        Ok(manifest)
+    }
+
+    pub fn to_json(&self) -> SourceResult<String> {
+        serde_json::to_string_pretty(self).map_err(|el| Box::new(el) as Box<dyn std::error::Error>)
+    }
+
+    pub fn from_json(json: &str) -> SourceResult<Self> {
+        serde_json::from_str(json).map_err(|el| Box::new(el) as Box<dyn std::error::Error>)
+    }
+
+    /// Flat view of the manifest keyed by `layer/circuit_type`
+    pub fn entries(&self) -> BTreeMap<String, String> {
+        let mut entries = BTreeMap::new();
+        for (layer, hashes) in [
+            ("base_layer", &self.base_layer),
+            ("recursion_layer", &self.recursion_layer),
+            ("compression", &self.compression),
+            ("compression_for_wrapper", &self.compression_for_wrapper),
+            ("wrapper", &self.wrapper),
+        ] {
+            for (circuit_type, hash) in hashes.iter() {
+                entries.insert(format!("{}/{}", layer, circuit_type), hash.clone());
+            }
+        }
+        for (key, value) in [
+            ("leaf_params_commitment", &self.leaf_params_commitment),
+            ("node_vk_commitment", &self.node_vk_commitment),
+            ("scheduler_vk_commitment", &self.scheduler_vk_commitment),
+        ] {
+            if let Some(value) = value {
+                entries.insert(key.to_string(), value.clone());
+            }
+        }
+
+        entries
+    }
+
+    /// Lists every entry that differs between `self` (old) and `other` (new)
+    pub fn diff(&self, other: &Self) -> Vec<VkManifestChange> {
+        let old = self.entries();
+        let new = other.entries();
+        let mut changes = vec![];
+
+        for (key, old_hash) in old.iter() {
+            match new.get(key) {
+                None => changes.push(VkManifestChange::Removed {
+                    key: key.clone(),
+                    hash: old_hash.clone(),
+                }),
+                Some(new_hash) if new_hash != old_hash => changes.push(VkManifestChange::Changed {
+                    key: key.clone(),
+                    old: old_hash.clone(),
+                    new: new_hash.clone(),
+                }),
+                Some(_) => {}
+            }
+        }
+        for (key, new_hash) in new.iter() {
+            if !old.contains_key(key) {
+                changes.push(VkManifestChange::Added {
+                    key: key.clone(),
+                    hash: new_hash.clone(),
+                });
+            }
+        }
+        changes.sort_by(|a, b| a.key().cmp(b.key()));
+
+        changes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data_source::in_memory_data_source::InMemoryDataSource;
+
+    #[test]
+    fn diff_lists_changed_circuits() {
+        let mut source = InMemoryDataSource::new();
+        let empty = VkManifest::from_source(&mut source).unwrap();
+        assert!(empty.entries().is_empty());
+
+        let mut old = VkManifest::default();
+        old.base_layer.insert(1, "aa".to_string());
+        old.base_layer.insert(2, "bb".to_string());
+        old.node_vk_commitment = Some("cc".to_string());
+
+        let mut new = old.clone();
+        new.base_layer.insert(2, "dd".to_string());
+        new.base_layer.remove(&1);
+        new.compression.insert(1, "ee".to_string());
+
+        let restored = VkManifest::from_json(&new.to_json().unwrap()).unwrap();
+        assert_eq!(restored, new);
+
+        let changes = old.diff(&new);
+        assert_eq!(
+            changes,
+            vec![
+                VkManifestChange::Removed {
+                    key: "base_layer/1".to_string(),
+                    hash: "aa".to_string()
+                },
+                VkManifestChange::Changed {
+                    key: "base_layer/2".to_string(),
+                    old: "bb".to_string(),
+                    new: "dd".to_string()
+                },
+                VkManifestChange::Added {
+                    key: "compression/1".to_string(),
+                    hash: "ee".to_string()
+                },
+            ]
+        );
+        assert!(new.diff(&restored).is_empty());
+    }
+
+    #[test]
+    fn unreadable_keys_are_not_skipped() {
+        use crate::data_source::local_file_data_source::LocalFileDataSource;
+
+        let root = std::env::temp_dir().join(format!("vk_manifest_{}", std::process::id()));
+        let location = root.to_str().unwrap().to_string();
+        let mut source = LocalFileDataSource {
+            setup_data_location: location.clone(),
+            block_data_location: location,
+        };
+        source.create_folders_for_storing_data();
+        assert!(VkManifest::from_source(&mut source)
+            .unwrap()
+            .entries()
+            .is_empty());
+
+        std::fs::write(root.join("base_layer/vk_1.json"), "not a key").unwrap();
+        assert!(VkManifest::from_source(&mut source).is_err());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
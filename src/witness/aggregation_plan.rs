@@ -0,0 +1,335 @@
+//! Shape of the recursive aggregation tree for a batch, computed from the recursion queue lengths.
+
+use crate::boojum::field::goldilocks::GoldilocksField;
+use crate::witness::recursive_aggregation::RecursionArity;
+use crate::zkevm_circuits::scheduler::aux::BaseLayerCircuitType;
+use circuit_definitions::circuit_definitions::base_layer::ZkSyncBaseLayerStorage;
+use circuit_definitions::encodings::recursion_request::RecursionQueueSimulator;
+use std::fmt::Write;
+
+/// Number of leaves or nodes needed to aggregate the given number of proofs
+pub fn num_aggregating_circuits(num_proofs: usize, arity: usize) -> usize {
+    (num_proofs + arity - 1) / arity
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CircuitTypeAggregationPlan {
+    /// Type of the basic circuit
+    pub circuit_type: u8,
+    pub num_base_circuits: usize,
+    /// Number of basic circuits aggregated by every leaf
+    pub leaves: Vec<usize>,
+    /// Number of nodes at every depth, the last depth always has a single node.
+    /// Empty if there are no circuits of this type
+    pub nodes_per_depth: Vec<usize>,
+    pub arity: RecursionArity,
+}
+
+impl CircuitTypeAggregationPlan {
+    pub fn new(circuit_type: u8, num_base_circuits: usize, arity: RecursionArity) -> Self {
+        let leaves = (0..num_aggregating_circuits(num_base_circuits, arity.leaf()))
+            .map(|idx| std::cmp::min(arity.leaf(), num_base_circuits - idx * arity.leaf()))
+            .collect::<Vec<_>>();
+
+        // there is always at least one node over the leaves, even if there is a single leaf
+        let mut nodes_per_depth = vec![];
+        if !leaves.is_empty() {
+            let mut num_nodes = num_aggregating_circuits(leaves.len(), arity.node());
+            nodes_per_depth.push(num_nodes);
+            while num_nodes > 1 {
+                num_nodes = num_aggregating_circuits(num_nodes, arity.node());
+                nodes_per_depth.push(num_nodes);
+            }
+        }
+
+        Self {
+            circuit_type,
+            num_base_circuits,
+            leaves,
+            nodes_per_depth,
+            arity,
+        }
+    }
+
+    /// Depth of the final node, that goes into the recursion tip
+    pub fn final_node_depth(&self) -> Option<usize> {
+        self.nodes_per_depth.len().checked_sub(1)
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.nodes_per_depth.iter().sum()
+    }
+
+    /// Number of children of every node at the given depth
+    pub fn node_children(&self, depth: usize) -> Vec<usize> {
+        let num_children = match depth {
+            0 => self.leaves.len(),
+            _ => self.nodes_per_depth[depth - 1],
+        };
+
+        (0..self.nodes_per_depth[depth])
+            .map(|idx| std::cmp::min(self.arity.node(), num_children - idx * self.arity.node()))
+            .collect()
+    }
+
+    /// Short description of the basic circuit, `None` if the type is unknown
+    pub fn description(&self) -> Option<&'static str> {
+        let is_known = crate::data_source::base_circuit_types().any(|el| el == self.circuit_type);
+
+        is_known
+            .then(|| ZkSyncBaseLayerStorage::from_inner(self.circuit_type, ()).short_description())
+    }
+}
+
+/// Number of proofs at every layer of the batch
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct LayerTotals {
+    pub base: usize,
+    pub leaf: usize,
+    /// Nodes at every depth, summed over all circuit types
+    pub node_per_depth: Vec<usize>,
+    pub node: usize,
+    pub recursion_tip: usize,
+    pub scheduler: usize,
+    /// Everything except the base layer
+    pub recursive: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct AggregationPlan {
+    pub circuit_types: Vec<CircuitTypeAggregationPlan>,
+    pub arity: RecursionArity,
+}
+
+impl AggregationPlan {
+    /// Takes the number of basic circuits (recursion queue length) per circuit type
+    pub fn new(
+        queue_lengths: impl IntoIterator<Item = (u8, usize)>,
+        arity: RecursionArity,
+    ) -> Self {
+        let mut circuit_types = queue_lengths
+            .into_iter()
+            .map(|(circuit_type, length)| {
+                CircuitTypeAggregationPlan::new(circuit_type, length, arity)
+            })
+            .collect::<Vec<_>>();
+        circuit_types.sort_by_key(|el| el.circuit_type);
+
+        Self {
+            circuit_types,
+            arity,
+        }
+    }
+
+    /// Takes recursion queues as they are passed to `queue_simulator_callback`
+    pub fn from_recursion_queues<'a>(
+        recursion_queues: impl IntoIterator<Item = (u64, &'a RecursionQueueSimulator<GoldilocksField>)>,
+        arity: RecursionArity,
+    ) -> Self {
+        Self::new(
+            recursion_queues
+                .into_iter()
+                .map(|(circuit_type, queue)| (circuit_type as u8, queue.num_items as usize)),
+            arity,
+        )
+    }
+
+    pub fn totals(&self) -> LayerTotals {
+        let mut totals = LayerTotals::default();
+        for plan in self.circuit_types.iter() {
+            totals.base += plan.num_base_circuits;
+            totals.leaf += plan.leaves.len();
+            if totals.node_per_depth.len() < plan.nodes_per_depth.len() {
+                totals.node_per_depth.resize(plan.nodes_per_depth.len(), 0);
+            }
+            for (total, num_nodes) in totals
+                .node_per_depth
+                .iter_mut()
+                .zip(plan.nodes_per_depth.iter())
+            {
+                *total += num_nodes;
+            }
+        }
+        totals.node = totals.node_per_depth.iter().sum();
+        // there is nothing to aggregate without basic circuits
+        if totals.base > 0 {
+            totals.recursion_tip = 1;
+            totals.scheduler = 1;
+        }
+        totals.recursive = totals.leaf + totals.node + totals.recursion_tip + totals.scheduler;
+
+        totals
+    }
+
+    pub fn to_json(&self) -> String {
+        #[derive(serde::Serialize)]
+        struct Export<'a> {
+            arity: RecursionArity,
+            circuit_types: &'a [CircuitTypeAggregationPlan],
+            totals: LayerTotals,
+        }
+
+        serde_json::to_string_pretty(&Export {
+            arity: self.arity,
+            circuit_types: &self.circuit_types,
+            totals: self.totals(),
+        })
+        .unwrap()
+    }
+
+    /// Graph with a vertex for every leaf and node, edges go from the aggregated proof to the aggregating one
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        writeln!(dot, "digraph aggregation {{").unwrap();
+        writeln!(dot, "    rankdir=BT;").unwrap();
+        writeln!(dot, "    node [shape=box];").unwrap();
+        writeln!(dot, "    scheduler [label=\"Scheduler\"];").unwrap();
+        writeln!(dot, "    recursion_tip [label=\"Recursion tip\"];").unwrap();
+        writeln!(dot, "    recursion_tip -> scheduler;").unwrap();
+
+        for plan in self.circuit_types.iter() {
+            let t = plan.circuit_type;
+            if plan.leaves.is_empty() {
+                continue;
+            }
+            writeln!(dot, "    subgraph cluster_{} {{", t).unwrap();
+            writeln!(
+                dot,
+                "        label=\"{} ({}): {} circuits\";",
+                plan.description().unwrap_or("Unknown"),
+                t,
+                plan.num_base_circuits
+            )
+            .unwrap();
+
+            for (idx, num_circuits) in plan.leaves.iter().enumerate() {
+                writeln!(
+                    dot,
+                    "        t{}_leaf_{} [label=\"leaf {}\\n{} circuits\"];",
+                    t, idx, idx, num_circuits
+                )
+                .unwrap();
+            }
+            for (depth, num_nodes) in plan.nodes_per_depth.iter().enumerate() {
+                for idx in 0..*num_nodes {
+                    writeln!(
+                        dot,
+                        "        t{}_node_{}_{} [label=\"node {}.{}\"];",
+                        t, depth, idx, depth, idx
+                    )
+                    .unwrap();
+                }
+            }
+
+            for idx in 0..plan.leaves.len() {
+                writeln!(
+                    dot,
+                    "        t{}_leaf_{} -> t{}_node_0_{};",
+                    t,
+                    idx,
+                    t,
+                    idx / self.arity.node()
+                )
+                .unwrap();
+            }
+            for (depth, num_nodes) in plan.nodes_per_depth.iter().enumerate().skip(1) {
+                for idx in 0..plan.nodes_per_depth[depth - 1] {
+                    assert!(idx / self.arity.node() < *num_nodes);
+                    writeln!(
+                        dot,
+                        "        t{}_node_{}_{} -> t{}_node_{}_{};",
+                        t,
+                        depth - 1,
+                        idx,
+                        t,
+                        depth,
+                        idx / self.arity.node()
+                    )
+                    .unwrap();
+                }
+            }
+            writeln!(dot, "    }}").unwrap();
+
+            let final_depth = plan.final_node_depth().unwrap();
+            writeln!(dot, "    t{}_node_{}_0 -> recursion_tip;", t, final_depth).unwrap();
+        }
+        writeln!(dot, "}}").unwrap();
+
+        dot
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use circuit_definitions::circuit_definitions::recursion_layer::RECURSION_ARITY;
+
+    #[test]
+    fn aggregation_tree_shape() {
+        let empty = CircuitTypeAggregationPlan::new(3, 0, RecursionArity::default());
+        assert!(empty.leaves.is_empty());
+        assert!(empty.nodes_per_depth.is_empty());
+        assert_eq!(empty.final_node_depth(), None);
+
+        let single = CircuitTypeAggregationPlan::new(1, 1, RecursionArity::default());
+        assert_eq!(single.leaves, vec![1]);
+        assert_eq!(single.nodes_per_depth, vec![1]);
+
+        let wide = CircuitTypeAggregationPlan::new(
+            1,
+            RECURSION_ARITY * RECURSION_ARITY + 1,
+            RecursionArity::default(),
+        );
+        assert_eq!(wide.leaves.len(), RECURSION_ARITY + 1);
+        assert_eq!(*wide.leaves.last().unwrap(), 1);
+        assert_eq!(wide.nodes_per_depth, vec![2, 1]);
+        assert_eq!(wide.node_children(0), vec![RECURSION_ARITY, 1]);
+        assert_eq!(wide.node_children(1), vec![2]);
+        assert_eq!(wide.final_node_depth(), Some(1));
+
+        let plan = AggregationPlan::new(
+            [(1, RECURSION_ARITY * RECURSION_ARITY + 1), (2, 1), (3, 0)],
+            RecursionArity::default(),
+        );
+        let totals = plan.totals();
+        assert_eq!(totals.base, RECURSION_ARITY * RECURSION_ARITY + 2);
+        assert_eq!(totals.leaf, RECURSION_ARITY + 2);
+        assert_eq!(totals.node_per_depth, vec![3, 1]);
+        assert_eq!(totals.recursive, RECURSION_ARITY + 2 + 4 + 2);
+
+        let dot = plan.to_dot();
+        assert!(dot.contains("t1_node_1_0 -> recursion_tip;"));
+        assert!(dot.contains("t2_node_0_0 -> recursion_tip;"));
+        assert!(!dot.contains("t3_"));
+
+        // 10 circuits with leaves of 3 and nodes of 2: 4 leaves, then 2 and 1 nodes
+        let narrow = CircuitTypeAggregationPlan::new(1, 10, RecursionArity::new(3, 2));
+        assert_eq!(narrow.leaves, vec![3, 3, 3, 1]);
+        assert_eq!(narrow.nodes_per_depth, vec![2, 1]);
+        assert_eq!(narrow.node_children(0), vec![2, 2]);
+    }
+
+    #[test]
+    fn empty_plans_and_unknown_types() {
+        let totals = AggregationPlan::new([], RecursionArity::default()).totals();
+        assert_eq!(totals, LayerTotals::default());
+
+        let totals = AggregationPlan::new([(1, 0), (2, 0)], RecursionArity::default()).totals();
+        assert_eq!(totals.recursion_tip, 0);
+        assert_eq!(totals.scheduler, 0);
+        assert_eq!(totals.recursive, 0);
+
+        let known = CircuitTypeAggregationPlan::new(1, 1, RecursionArity::default());
+        assert!(known.description().is_some());
+        let eip4844 = CircuitTypeAggregationPlan::new(
+            BaseLayerCircuitType::EIP4844Repack as u8,
+            1,
+            RecursionArity::default(),
+        );
+        assert!(eip4844.description().is_some());
+
+        let plan = AggregationPlan::new([(100, 1)], RecursionArity::default());
+        assert_eq!(plan.circuit_types[0].description(), None);
+        assert!(plan.to_dot().contains("Unknown (100)"));
+    }
+}
@@ -0,0 +1,752 @@
+use crate::boojum::field::goldilocks::GoldilocksField;
+use crate::snark_wrapper::boojum::field::goldilocks::GoldilocksExt2;
+use crate::snark_wrapper::boojum::gadgets::recursion::recursive_tree_hasher::CircuitGoldilocksPoseidon2Sponge;
+use crate::zkevm_circuits::scheduler::aux::BaseLayerCircuitType;
+use crate::zkevm_circuits::scheduler::input::SchedulerCircuitInstanceWitness;
+use circuit_definitions::circuit_definitions::base_layer::ZkSyncBaseLayerCircuit;
+use circuit_definitions::zkevm_circuits::demux_log_queue::DemuxOutput;
+use std::collections::BTreeMap;
+
+/// Kind of inconsistency found between neighbouring circuits of the same type
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BrokenLinkKind {
+    /// Circuit was emitted without a witness
+    MissingWitness,
+    /// Hidden FSM input differs from the hidden FSM output of the previous instance
+    HiddenFsmMismatch,
+    /// Observable input of the first instance differs from the observable output
+    /// of the circuit that produces it
+    ObservableInputMismatch,
+    MissingStartFlag,
+    UnexpectedStartFlag,
+    MissingCompletionFlag,
+    UnexpectedCompletionFlag,
+    /// Circuit type that the scheduler can not skip was never emitted
+    NoInstances,
+    /// Observable input of the first instance is not what the scheduler expects
+    SchedulerInputMismatch,
+    /// Observable output of the last instance is not what the scheduler expects
+    SchedulerOutputMismatch,
+}
+
+/// First broken link in the chain of base layer circuits.
+/// `instance` is the position of the circuit among the circuits of the same type
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BrokenLink {
+    pub circuit_type: u8,
+    pub description: &'static str,
+    pub instance: usize,
+    pub kind: BrokenLinkKind,
+}
+
+impl std::fmt::Display for BrokenLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} circuit (type {}) instance {}: {:?}",
+            self.description, self.circuit_type, self.instance, self.kind
+        )
+    }
+}
+
+// Parts of the closed form input, serialized so circuits of different types can be kept together
+struct ClosedFormSummary {
+    start_flag: bool,
+    completion_flag: bool,
+    observable_output: Vec<u8>,
+    hidden_fsm_input: Vec<u8>,
+    hidden_fsm_output: Vec<u8>,
+}
+
+struct CircuitChain {
+    description: &'static str,
+    instances: usize,
+    // queues taken by the first instance and produced by the last one
+    inputs: Vec<(Link, Vec<u8>)>,
+    outputs: Vec<(Link, Vec<u8>)>,
+    last: ClosedFormSummary,
+}
+
+// Queues that circuits pass to each other through their observable inputs and outputs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Link {
+    // main VM -> log demuxer
+    LogQueue,
+    // main VM -> decommitments sorter
+    DecommitmentQueue,
+    // decommitments sorter -> decommitter
+    SortedDecommitments,
+    // main VM -> decommitter -> precompiles -> RAM permutation
+    Memory,
+    // log demuxer -> precompiles and sorters, indexed by `DemuxOutput`
+    Demuxed(usize),
+    // storage sorter -> storage application
+    SortedStorage,
+    // L1 messages sorter -> L1 messages hasher
+    SortedL1Messages,
+}
+
+// circuits that extend the memory queue, in the order the scheduler chains them
+const MEMORY_WRITERS: [BaseLayerCircuitType; 5] = [
+    BaseLayerCircuitType::Decommiter,
+    BaseLayerCircuitType::KeccakPrecompile,
+    BaseLayerCircuitType::Sha256Precompile,
+    BaseLayerCircuitType::EcrecoverPrecompile,
+    BaseLayerCircuitType::Secp256r1Verify,
+];
+
+// producer, consumer and the queue they share, except the memory queue
+const LINKS: [(BaseLayerCircuitType, BaseLayerCircuitType, Link); 13] = [
+    (
+        BaseLayerCircuitType::VM,
+        BaseLayerCircuitType::LogDemultiplexer,
+        Link::LogQueue,
+    ),
+    (
+        BaseLayerCircuitType::VM,
+        BaseLayerCircuitType::DecommitmentsFilter,
+        Link::DecommitmentQueue,
+    ),
+    (
+        BaseLayerCircuitType::DecommitmentsFilter,
+        BaseLayerCircuitType::Decommiter,
+        Link::SortedDecommitments,
+    ),
+    (
+        BaseLayerCircuitType::LogDemultiplexer,
+        BaseLayerCircuitType::KeccakPrecompile,
+        Link::Demuxed(DemuxOutput::Keccak as usize),
+    ),
+    (
+        BaseLayerCircuitType::LogDemultiplexer,
+        BaseLayerCircuitType::Sha256Precompile,
+        Link::Demuxed(DemuxOutput::Sha256 as usize),
+    ),
+    (
+        BaseLayerCircuitType::LogDemultiplexer,
+        BaseLayerCircuitType::EcrecoverPrecompile,
+        Link::Demuxed(DemuxOutput::ECRecover as usize),
+    ),
+    (
+        BaseLayerCircuitType::LogDemultiplexer,
+        BaseLayerCircuitType::Secp256r1Verify,
+        Link::Demuxed(DemuxOutput::Secp256r1Verify as usize),
+    ),
+    (
+        BaseLayerCircuitType::LogDemultiplexer,
+        BaseLayerCircuitType::StorageFilter,
+        Link::Demuxed(DemuxOutput::RollupStorage as usize),
+    ),
+    (
+        BaseLayerCircuitType::LogDemultiplexer,
+        BaseLayerCircuitType::TransientStorageChecker,
+        Link::Demuxed(DemuxOutput::TransientStorage as usize),
+    ),
+    (
+        BaseLayerCircuitType::LogDemultiplexer,
+        BaseLayerCircuitType::EventsRevertsFilter,
+        Link::Demuxed(DemuxOutput::Events as usize),
+    ),
+    (
+        BaseLayerCircuitType::LogDemultiplexer,
+        BaseLayerCircuitType::L1MessagesRevertsFilter,
+        Link::Demuxed(DemuxOutput::L2ToL1Messages as usize),
+    ),
+    (
+        BaseLayerCircuitType::StorageFilter,
+        BaseLayerCircuitType::StorageApplicator,
+        Link::SortedStorage,
+    ),
+    (
+        BaseLayerCircuitType::L1MessagesRevertsFilter,
+        BaseLayerCircuitType::L1MessagesHasher,
+        Link::SortedL1Messages,
+    ),
+];
+
+impl CircuitChain {
+    fn input(&self, link: Link) -> Option<&Vec<u8>> {
+        self.inputs
+            .iter()
+            .find(|(el, _)| *el == link)
+            .map(|(_, el)| el)
+    }
+
+    fn output(&self, link: Link) -> Option<&Vec<u8>> {
+        self.outputs
+            .iter()
+            .find(|(el, _)| *el == link)
+            .map(|(_, el)| el)
+    }
+}
+
+fn encode<T: serde::Serialize>(value: &T) -> Vec<u8> {
+    bincode::serialize(value).expect("closed form input witness must be serializable")
+}
+
+macro_rules! summarize_closed_form {
+    ($inner:expr) => {
+        $inner.clone_witness().map(|witness| {
+            let input = witness.closed_form_input;
+            ClosedFormSummary {
+                start_flag: input.start_flag,
+                completion_flag: input.completion_flag,
+                observable_output: encode(&input.observable_output),
+                hidden_fsm_input: encode(&input.hidden_fsm_input),
+                hidden_fsm_output: encode(&input.hidden_fsm_output),
+            }
+        })
+    };
+}
+
+fn summarize(circuit: &ZkSyncBaseLayerCircuit) -> Option<ClosedFormSummary> {
+    match circuit {
+        ZkSyncBaseLayerCircuit::MainVM(inner) => summarize_closed_form!(inner),
+        ZkSyncBaseLayerCircuit::CodeDecommittmentsSorter(inner) => summarize_closed_form!(inner),
+        ZkSyncBaseLayerCircuit::CodeDecommitter(inner) => summarize_closed_form!(inner),
+        ZkSyncBaseLayerCircuit::LogDemuxer(inner) => summarize_closed_form!(inner),
+        ZkSyncBaseLayerCircuit::KeccakRoundFunction(inner) => summarize_closed_form!(inner),
+        ZkSyncBaseLayerCircuit::Sha256RoundFunction(inner) => summarize_closed_form!(inner),
+        ZkSyncBaseLayerCircuit::ECRecover(inner) => summarize_closed_form!(inner),
+        ZkSyncBaseLayerCircuit::RAMPermutation(inner) => summarize_closed_form!(inner),
+        ZkSyncBaseLayerCircuit::StorageSorter(inner) => summarize_closed_form!(inner),
+        ZkSyncBaseLayerCircuit::StorageApplication(inner) => summarize_closed_form!(inner),
+        ZkSyncBaseLayerCircuit::EventsSorter(inner) => summarize_closed_form!(inner),
+        ZkSyncBaseLayerCircuit::L1MessagesSorter(inner) => summarize_closed_form!(inner),
+        ZkSyncBaseLayerCircuit::L1MessagesHasher(inner) => summarize_closed_form!(inner),
+        ZkSyncBaseLayerCircuit::TransientStorageSorter(inner) => summarize_closed_form!(inner),
+        ZkSyncBaseLayerCircuit::Secp256r1Verify(inner) => summarize_closed_form!(inner),
+        ZkSyncBaseLayerCircuit::EIP4844Repack(inner) => summarize_closed_form!(inner),
+    }
+}
+
+macro_rules! linked_queues {
+    ($inner:expr, $part:ident, |$data:ident| $links:expr) => {
+        $inner
+            .clone_witness()
+            .map(|witness| {
+                let $data = witness.closed_form_input.$part;
+                $links
+            })
+            .unwrap_or_default()
+    };
+}
+
+// queues that the circuit takes from other circuits, only meaningful for the first instance
+fn linked_inputs(circuit: &ZkSyncBaseLayerCircuit) -> Vec<(Link, Vec<u8>)> {
+    macro_rules! precompile_inputs {
+        ($inner:expr, $demux_output:expr) => {
+            linked_queues!($inner, observable_input, |input| vec![
+                (
+                    Link::Demuxed($demux_output as usize),
+                    encode(&input.initial_log_queue_state),
+                ),
+                (Link::Memory, encode(&input.initial_memory_queue_state)),
+            ])
+        };
+    }
+
+    match circuit {
+        ZkSyncBaseLayerCircuit::CodeDecommittmentsSorter(inner) => {
+            linked_queues!(inner, observable_input, |input| vec![(
+                Link::DecommitmentQueue,
+                encode(&input.initial_queue_state),
+            )])
+        }
+        ZkSyncBaseLayerCircuit::CodeDecommitter(inner) => {
+            linked_queues!(inner, observable_input, |input| vec![
+                (
+                    Link::SortedDecommitments,
+                    encode(&input.sorted_requests_queue_initial_state),
+                ),
+                (Link::Memory, encode(&input.memory_queue_initial_state)),
+            ])
+        }
+        ZkSyncBaseLayerCircuit::LogDemuxer(inner) => {
+            linked_queues!(inner, observable_input, |input| vec![(
+                Link::LogQueue,
+                encode(&input.initial_log_queue_state),
+            )])
+        }
+        ZkSyncBaseLayerCircuit::KeccakRoundFunction(inner) => {
+            precompile_inputs!(inner, DemuxOutput::Keccak)
+        }
+        ZkSyncBaseLayerCircuit::Sha256RoundFunction(inner) => {
+            precompile_inputs!(inner, DemuxOutput::Sha256)
+        }
+        ZkSyncBaseLayerCircuit::ECRecover(inner) => {
+            precompile_inputs!(inner, DemuxOutput::ECRecover)
+        }
+        ZkSyncBaseLayerCircuit::Secp256r1Verify(inner) => {
+            precompile_inputs!(inner, DemuxOutput::Secp256r1Verify)
+        }
+        ZkSyncBaseLayerCircuit::RAMPermutation(inner) => {
+            linked_queues!(inner, observable_input, |input| vec![(
+                Link::Memory,
+                encode(&input.unsorted_queue_initial_state),
+            )])
+        }
+        ZkSyncBaseLayerCircuit::StorageSorter(inner) => {
+            linked_queues!(inner, observable_input, |input| vec![(
+                Link::Demuxed(DemuxOutput::RollupStorage as usize),
+                encode(&input.unsorted_log_queue_state),
+            )])
+        }
+        ZkSyncBaseLayerCircuit::TransientStorageSorter(inner) => {
+            linked_queues!(inner, observable_input, |input| vec![(
+                Link::Demuxed(DemuxOutput::TransientStorage as usize),
+                encode(&input.unsorted_log_queue_state),
+            )])
+        }
+        ZkSyncBaseLayerCircuit::EventsSorter(inner) => {
+            linked_queues!(inner, observable_input, |input| vec![(
+                Link::Demuxed(DemuxOutput::Events as usize),
+                encode(&input.initial_log_queue_state),
+            )])
+        }
+        ZkSyncBaseLayerCircuit::L1MessagesSorter(inner) => {
+            linked_queues!(inner, observable_input, |input| vec![(
+                Link::Demuxed(DemuxOutput::L2ToL1Messages as usize),
+                encode(&input.initial_log_queue_state),
+            )])
+        }
+        ZkSyncBaseLayerCircuit::StorageApplication(inner) => {
+            linked_queues!(inner, observable_input, |input| vec![(
+                Link::SortedStorage,
+                encode(&input.storage_application_log_state),
+            )])
+        }
+        ZkSyncBaseLayerCircuit::L1MessagesHasher(inner) => {
+            linked_queues!(inner, observable_input, |input| vec![(
+                Link::SortedL1Messages,
+                encode(&input.queue_state),
+            )])
+        }
+        // main VM takes its inputs from the scheduler, and blobs are not queued
+        ZkSyncBaseLayerCircuit::MainVM(_) | ZkSyncBaseLayerCircuit::EIP4844Repack(_) => vec![],
+    }
+}
+
+// queues that the circuit passes to other circuits, only meaningful for the last instance
+fn linked_outputs(circuit: &ZkSyncBaseLayerCircuit) -> Vec<(Link, Vec<u8>)> {
+    macro_rules! precompile_outputs {
+        ($inner:expr) => {
+            linked_queues!($inner, observable_output, |output| vec![(
+                Link::Memory,
+                encode(&output.final_memory_state),
+            )])
+        };
+    }
+
+    match circuit {
+        ZkSyncBaseLayerCircuit::MainVM(inner) => {
+            linked_queues!(inner, observable_output, |output| vec![
+                (Link::LogQueue, encode(&output.log_queue_final_state)),
+                (
+                    Link::DecommitmentQueue,
+                    encode(&output.decommitment_queue_final_state),
+                ),
+                (Link::Memory, encode(&output.memory_queue_final_state)),
+            ])
+        }
+        ZkSyncBaseLayerCircuit::CodeDecommittmentsSorter(inner) => {
+            linked_queues!(inner, observable_output, |output| vec![(
+                Link::SortedDecommitments,
+                encode(&output.final_queue_state),
+            )])
+        }
+        ZkSyncBaseLayerCircuit::CodeDecommitter(inner) => {
+            linked_queues!(inner, observable_output, |output| vec![(
+                Link::Memory,
+                encode(&output.memory_queue_final_state),
+            )])
+        }
+        ZkSyncBaseLayerCircuit::LogDemuxer(inner) => {
+            linked_queues!(inner, observable_output, |output| output
+                .output_queue_states
+                .iter()
+                .enumerate()
+                .map(|(idx, el)| (Link::Demuxed(idx), encode(el)))
+                .collect())
+        }
+        ZkSyncBaseLayerCircuit::KeccakRoundFunction(inner) => precompile_outputs!(inner),
+        ZkSyncBaseLayerCircuit::Sha256RoundFunction(inner) => precompile_outputs!(inner),
+        ZkSyncBaseLayerCircuit::ECRecover(inner) => precompile_outputs!(inner),
+        ZkSyncBaseLayerCircuit::Secp256r1Verify(inner) => precompile_outputs!(inner),
+        ZkSyncBaseLayerCircuit::StorageSorter(inner) => {
+            linked_queues!(inner, observable_output, |output| vec![(
+                Link::SortedStorage,
+                encode(&output.final_sorted_queue_state),
+            )])
+        }
+        ZkSyncBaseLayerCircuit::L1MessagesSorter(inner) => {
+            linked_queues!(inner, observable_output, |output| vec![(
+                Link::SortedL1Messages,
+                encode(&output.final_queue_state),
+            )])
+        }
+        // the rest either have no observable output or only report it to the scheduler
+        _ => vec![],
+    }
+}
+
+// main VM is the only circuit whose observable input goes into the scheduler
+fn vm_scheduler_input(circuit: &ZkSyncBaseLayerCircuit) -> Option<Vec<u8>> {
+    match circuit {
+        ZkSyncBaseLayerCircuit::MainVM(inner) => inner.clone_witness().map(|witness| {
+            let input = witness.closed_form_input.observable_input;
+            encode(&(
+                input.rollback_queue_tail_for_block,
+                input.memory_queue_initial_state,
+            ))
+        }),
+        _ => None,
+    }
+}
+
+/// Checks that base layer circuits form consistent chains, using only their witnesses.
+/// Feed it every circuit passed to `circuit_callback` and call `finalize` with the
+/// scheduler witness once the run is completed
+#[derive(Default)]
+pub struct CircuitChainVerifier {
+    chains: BTreeMap<u8, CircuitChain>,
+    vm_scheduler_input: Option<Vec<u8>>,
+    // every blob is processed by a separate circuit, so these are not chained
+    eip4844_outputs: Vec<Vec<u8>>,
+    first_broken_link: Option<BrokenLink>,
+}
+
+impl CircuitChainVerifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn report(
+        &mut self,
+        circuit_type: u8,
+        description: &'static str,
+        instance: usize,
+        kind: BrokenLinkKind,
+    ) {
+        if self.first_broken_link.is_none() {
+            self.first_broken_link = Some(BrokenLink {
+                circuit_type,
+                description,
+                instance,
+                kind,
+            });
+        }
+    }
+
+    pub fn push(&mut self, circuit: &ZkSyncBaseLayerCircuit) {
+        if self.first_broken_link.is_some() {
+            return;
+        }
+
+        let circuit_type = circuit.numeric_circuit_type();
+        let description = circuit.short_description();
+        let instance = if circuit_type == BaseLayerCircuitType::EIP4844Repack as u8 {
+            self.eip4844_outputs.len()
+        } else {
+            self.chains
+                .get(&circuit_type)
+                .map(|el| el.instances)
+                .unwrap_or(0)
+        };
+
+        let Some(summary) = summarize(circuit) else {
+            self.report(
+                circuit_type,
+                description,
+                instance,
+                BrokenLinkKind::MissingWitness,
+            );
+            return;
+        };
+
+        if circuit_type == BaseLayerCircuitType::EIP4844Repack as u8 {
+            if !summary.start_flag {
+                self.report(
+                    circuit_type,
+                    description,
+                    instance,
+                    BrokenLinkKind::MissingStartFlag,
+                );
+            } else if !summary.completion_flag {
+                self.report(
+                    circuit_type,
+                    description,
+                    instance,
+                    BrokenLinkKind::MissingCompletionFlag,
+                );
+            }
+
+            self.eip4844_outputs.push(summary.observable_output);
+
+            return;
+        }
+
+        let Some(chain) = self.chains.get_mut(&circuit_type) else {
+            if !summary.start_flag {
+                self.report(
+                    circuit_type,
+                    description,
+                    0,
+                    BrokenLinkKind::MissingStartFlag,
+                );
+            }
+            if circuit_type == BaseLayerCircuitType::VM as u8 {
+                self.vm_scheduler_input = vm_scheduler_input(circuit);
+            }
+            let outputs = if summary.completion_flag {
+                linked_outputs(circuit)
+            } else {
+                vec![]
+            };
+            self.chains.insert(
+                circuit_type,
+                CircuitChain {
+                    description,
+                    instances: 1,
+                    inputs: linked_inputs(circuit),
+                    outputs,
+                    last: summary,
+                },
+            );
+
+            return;
+        };
+
+        let kind = if chain.last.completion_flag {
+            // previous instance claimed to be the last one
+            Some((instance - 1, BrokenLinkKind::UnexpectedCompletionFlag))
+        } else if summary.start_flag {
+            Some((instance, BrokenLinkKind::UnexpectedStartFlag))
+        } else if chain.last.hidden_fsm_output != summary.hidden_fsm_input {
+            Some((instance, BrokenLinkKind::HiddenFsmMismatch))
+        } else {
+            // observable input is only read by the first instance
+            None
+        };
+
+        if summary.completion_flag {
+            chain.outputs = linked_outputs(circuit);
+        }
+        chain.instances += 1;
+        chain.last = summary;
+
+        if let Some((instance, kind)) = kind {
+            self.report(circuit_type, description, instance, kind);
+        }
+    }
+
+    /// Finishes the chains and compares their ends with the scheduler witness.
+    /// Returns the first broken link in the order they were found
+    pub fn finalize(
+        mut self,
+        scheduler_witness: &SchedulerCircuitInstanceWitness<
+            GoldilocksField,
+            CircuitGoldilocksPoseidon2Sponge,
+            GoldilocksExt2,
+        >,
+    ) -> Result<(), BrokenLink> {
+        if let Some(broken_link) = self.first_broken_link.take() {
+            return Err(broken_link);
+        }
+
+        let vm_type = BaseLayerCircuitType::VM as u8;
+        if !self.chains.contains_key(&vm_type) {
+            return Err(BrokenLink {
+                circuit_type: vm_type,
+                description: "Main VM",
+                instance: 0,
+                kind: BrokenLinkKind::NoInstances,
+            });
+        }
+
+        for (circuit_type, chain) in self.chains.iter() {
+            if !chain.last.completion_flag {
+                return Err(BrokenLink {
+                    circuit_type: *circuit_type,
+                    description: chain.description,
+                    instance: chain.instances - 1,
+                    kind: BrokenLinkKind::MissingCompletionFlag,
+                });
+            }
+        }
+
+        self.check_links()?;
+
+        let expected_vm_input = encode(&(
+            scheduler_witness.storage_log_tail,
+            scheduler_witness.bootloader_heap_memory_state.clone(),
+        ));
+        if self.vm_scheduler_input.as_ref() != Some(&expected_vm_input) {
+            return Err(BrokenLink {
+                circuit_type: vm_type,
+                description: self.chains[&vm_type].description,
+                instance: 0,
+                kind: BrokenLinkKind::SchedulerInputMismatch,
+            });
+        }
+
+        // RAM permutation and transient storage sorter have no observable output
+        let expected_outputs = [
+            (
+                BaseLayerCircuitType::VM,
+                encode(&scheduler_witness.vm_end_of_execution_observable_output),
+            ),
+            (
+                BaseLayerCircuitType::DecommitmentsFilter,
+                encode(&scheduler_witness.decommits_sorter_observable_output),
+            ),
+            (
+                BaseLayerCircuitType::Decommiter,
+                encode(&scheduler_witness.code_decommitter_observable_output),
+            ),
+            (
+                BaseLayerCircuitType::LogDemultiplexer,
+                encode(&scheduler_witness.log_demuxer_observable_output),
+            ),
+            (
+                BaseLayerCircuitType::KeccakPrecompile,
+                encode(&scheduler_witness.keccak256_observable_output),
+            ),
+            (
+                BaseLayerCircuitType::Sha256Precompile,
+                encode(&scheduler_witness.sha256_observable_output),
+            ),
+            (
+                BaseLayerCircuitType::EcrecoverPrecompile,
+                encode(&scheduler_witness.ecrecover_observable_output),
+            ),
+            (
+                BaseLayerCircuitType::Secp256r1Verify,
+                encode(&scheduler_witness.secp256r1_verify_observable_output),
+            ),
+            (
+                BaseLayerCircuitType::StorageFilter,
+                encode(&scheduler_witness.storage_sorter_observable_output),
+            ),
+            (
+                BaseLayerCircuitType::StorageApplicator,
+                encode(&scheduler_witness.storage_application_observable_output),
+            ),
+            (
+                BaseLayerCircuitType::EventsRevertsFilter,
+                encode(&scheduler_witness.events_sorter_observable_output),
+            ),
+            (
+                BaseLayerCircuitType::L1MessagesRevertsFilter,
+                encode(&scheduler_witness.l1messages_sorter_observable_output),
+            ),
+            (
+                BaseLayerCircuitType::L1MessagesHasher,
+                encode(&scheduler_witness.l1messages_linear_hasher_observable_output),
+            ),
+        ];
+
+        for (circuit_type, expected_output) in expected_outputs.iter() {
+            let circuit_type = *circuit_type as u8;
+            // skipped circuit types are checked by the scheduler itself
+            let Some(chain) = self.chains.get(&circuit_type) else {
+                continue;
+            };
+            if &chain.last.observable_output != expected_output {
+                return Err(BrokenLink {
+                    circuit_type,
+                    description: chain.description,
+                    instance: chain.instances - 1,
+                    kind: BrokenLinkKind::SchedulerOutputMismatch,
+                });
+            }
+        }
+
+        let eip4844_type = BaseLayerCircuitType::EIP4844Repack as u8;
+        let eip4844_outputs = &self.eip4844_outputs;
+        let expected_eip4844_outputs: Vec<_> = scheduler_witness
+            .eip4844_witnesses
+            .iter()
+            .filter_map(|el| el.as_ref().map(encode))
+            .collect();
+        for (idx, expected_output) in expected_eip4844_outputs.iter().enumerate() {
+            if eip4844_outputs.get(idx) != Some(expected_output) {
+                return Err(BrokenLink {
+                    circuit_type: eip4844_type,
+                    description: "EIP4844 repacker",
+                    instance: idx,
+                    kind: BrokenLinkKind::SchedulerOutputMismatch,
+                });
+            }
+        }
+        if eip4844_outputs.len() > expected_eip4844_outputs.len() {
+            return Err(BrokenLink {
+                circuit_type: eip4844_type,
+                description: "EIP4844 repacker",
+                instance: expected_eip4844_outputs.len(),
+                kind: BrokenLinkKind::SchedulerOutputMismatch,
+            });
+        }
+
+        Ok(())
+    }
+
+    // first instance of every circuit must start from the queues produced by the previous circuits
+    fn check_links(&self) -> Result<(), BrokenLink> {
+        let broken_link = |circuit_type: BaseLayerCircuitType, chain: &CircuitChain| BrokenLink {
+            circuit_type: circuit_type as u8,
+            description: chain.description,
+            instance: 0,
+            kind: BrokenLinkKind::ObservableInputMismatch,
+        };
+
+        for (producer, consumer, link) in LINKS.iter() {
+            // skipped circuit types are checked by the scheduler itself
+            let (Some(producer), Some(chain)) = (
+                self.chains.get(&(*producer as u8)),
+                self.chains.get(&(*consumer as u8)),
+            ) else {
+                continue;
+            };
+            if producer.output(*link) != chain.input(*link) {
+                return Err(broken_link(*consumer, chain));
+            }
+        }
+
+        // memory queue is passed along, skipping the circuit types that were not emitted
+        let mut memory_state = self.chains[&(BaseLayerCircuitType::VM as u8)].output(Link::Memory);
+        for circuit_type in MEMORY_WRITERS
+            .into_iter()
+            .chain(std::iter::once(BaseLayerCircuitType::RamValidation))
+        {
+            let Some(chain) = self.chains.get(&(circuit_type as u8)) else {
+                continue;
+            };
+            if chain.input(Link::Memory) != memory_state {
+                return Err(broken_link(circuit_type, chain));
+            }
+            if circuit_type != BaseLayerCircuitType::RamValidation {
+                memory_state = chain.output(Link::Memory);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs `CircuitChainVerifier` over already collected circuits
+pub fn verify_circuit_chain(
+    circuits: &[ZkSyncBaseLayerCircuit],
+    scheduler_witness: &SchedulerCircuitInstanceWitness<
+        GoldilocksField,
+        CircuitGoldilocksPoseidon2Sponge,
+        GoldilocksExt2,
+    >,
+) -> Result<(), BrokenLink> {
+    let mut verifier = CircuitChainVerifier::new();
+    for circuit in circuits.iter() {
+        verifier.push(circuit);
+    }
+
+    verifier.finalize(scheduler_witness)
+}
@@ -0,0 +1,530 @@
+//! Builds leaf and node circuits as soon as the proofs they aggregate are available,
+//! instead of waiting for all the proofs of a circuit type like `create_leaf_witnesses` does.
+
+use super::aggregation_plan::num_aggregating_circuits;
+use super::recursive_aggregation::{create_leaf_witness, create_node_witness, RecursionArity};
+use crate::boojum::field::goldilocks::GoldilocksField;
+use crate::zkevm_circuits::recursion::leaf_layer::input::RecursionLeafParametersWitness;
+use crate::zkevm_circuits::recursion::VK_COMMITMENT_LENGTH;
+use circuit_definitions::circuit_definitions::base_layer::{
+    ZkSyncBaseLayerProof, ZkSyncBaseLayerVerificationKey,
+};
+use circuit_definitions::circuit_definitions::recursion_layer::*;
+use circuit_definitions::encodings::recursion_request::RecursionQueueSimulator;
+use circuit_definitions::{ProfileError, ZkSyncDefaultRoundFunction};
+use std::collections::{BTreeMap, HashMap};
+
+type F = GoldilocksField;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AggregationLayer {
+    Leaf,
+    Node { depth: usize },
+}
+
+/// Leaf or node circuit that has all of its proofs and can be proven
+pub struct ReadyAggregation {
+    /// Type of the basic circuit
+    pub circuit_type: u8,
+    pub layer: AggregationLayer,
+    pub index: usize,
+    pub circuit: ZkSyncRecursiveLayerCircuit,
+    /// True for the single node at the top of the tree for this circuit type
+    pub is_final: bool,
+}
+
+#[derive(Default)]
+struct CircuitTypeAggregation {
+    // known once the recursion queue for this type arrives
+    leaf_queues: Option<Vec<RecursionQueueSimulator<F>>>,
+    // queues covered by the nodes of every depth, filled when the node is emitted
+    node_queues: Vec<Vec<Option<RecursionQueueSimulator<F>>>>,
+    // proofs that are not aggregated yet
+    base_proofs: BTreeMap<usize, ZkSyncBaseLayerProof>,
+    leaf_proofs: BTreeMap<usize, ZkSyncRecursionLayerProof>,
+    node_proofs: BTreeMap<(usize, usize), ZkSyncRecursionLayerProof>,
+    final_node_proof: Option<ZkSyncRecursionLayerProof>,
+}
+
+impl CircuitTypeAggregation {
+    // number of circuits in the layer, zero until the queue is known
+    fn num_circuits(&self, layer: AggregationLayer, node_arity: usize) -> usize {
+        let num_leaves = self.leaf_queues.as_ref().map(|el| el.len()).unwrap_or(0);
+        match layer {
+            AggregationLayer::Leaf => num_leaves,
+            AggregationLayer::Node { depth } => {
+                let mut num_circuits = num_aggregating_circuits(num_leaves, node_arity);
+                for _ in 0..depth {
+                    num_circuits = num_aggregating_circuits(num_circuits, node_arity);
+                }
+                num_circuits
+            }
+        }
+    }
+
+    fn final_depth(&self, node_arity: usize) -> usize {
+        let mut depth = 0;
+        while self.num_circuits(AggregationLayer::Node { depth }, node_arity) > 1 {
+            depth += 1;
+        }
+        depth
+    }
+}
+
+/// Incrementally creates leaf and node circuits for all the basic circuit types.
+/// It's fed with recursion queues from `queue_simulator_callback` and with proofs,
+/// that may come in any order, and returns circuits as soon as
+/// a contiguous chunk of `arity.leaf()` (or `arity.node()`) proofs (or the last, shorter one) is available.
+/// Proofs are dropped once they are aggregated.
+pub struct IncrementalAggregator {
+    base_vks: HashMap<u8, ZkSyncBaseLayerVerificationKey>,
+    leaf_vks: HashMap<u8, ZkSyncRecursionLayerVerificationKey>,
+    node_vk: ZkSyncRecursionLayerVerificationKey,
+    node_vk_commitment: [F; VK_COMMITMENT_LENGTH],
+    leaf_layer_params: Vec<(u8, RecursionLeafParametersWitness<F>)>,
+    arity: RecursionArity,
+    aggregations: BTreeMap<u8, CircuitTypeAggregation>,
+}
+
+impl IncrementalAggregator {
+    /// `base_vks` and `leaf_vks` must cover every circuit type that will be aggregated,
+    /// leaf vks are keyed by the type of the basic circuit
+    pub fn new(
+        base_vks: HashMap<u8, ZkSyncBaseLayerVerificationKey>,
+        leaf_vks: HashMap<u8, ZkSyncRecursionLayerVerificationKey>,
+        node_vk: ZkSyncRecursionLayerVerificationKey,
+        leaf_layer_params: Vec<(u8, RecursionLeafParametersWitness<F>)>,
+        arity: RecursionArity,
+    ) -> Self {
+        use crate::witness::recursive_aggregation::compute_node_vk_commitment;
+
+        let node_vk_commitment = compute_node_vk_commitment(node_vk.clone());
+
+        Self {
+            base_vks,
+            leaf_vks,
+            node_vk,
+            node_vk_commitment,
+            leaf_layer_params,
+            arity,
+            aggregations: BTreeMap::new(),
+        }
+    }
+
+    /// Registers the recursion queue of the circuit type, as passed to `queue_simulator_callback`
+    pub fn add_recursion_queue(
+        &mut self,
+        circuit_type: u8,
+        queue: RecursionQueueSimulator<F>,
+    ) -> Result<Vec<ReadyAggregation>, ProfileError> {
+        let round_function = ZkSyncDefaultRoundFunction::default();
+        let entry = self.aggregations.entry(circuit_type).or_default();
+        assert!(
+            entry.leaf_queues.is_none(),
+            "recursion queue for circuit type {} is already set",
+            circuit_type
+        );
+        entry.leaf_queues = Some(queue.split_by(self.arity.leaf(), &round_function));
+
+        // some proofs may have come before the queue
+        let num_leaves = entry.num_circuits(AggregationLayer::Leaf, self.arity.node());
+        let mut results = vec![];
+        for index in 0..num_leaves {
+            results.extend(self.try_emit(circuit_type, AggregationLayer::Leaf, index)?);
+        }
+
+        Ok(results)
+    }
+
+    pub fn add_base_proof(
+        &mut self,
+        circuit_type: u8,
+        index: usize,
+        proof: ZkSyncBaseLayerProof,
+    ) -> Result<Vec<ReadyAggregation>, ProfileError> {
+        assert_eq!(circuit_type, proof.numeric_circuit_type());
+        let entry = self.aggregations.entry(circuit_type).or_default();
+        let previous = entry.base_proofs.insert(index, proof);
+        assert!(
+            previous.is_none(),
+            "duplicate proof {} {}",
+            circuit_type,
+            index
+        );
+
+        self.try_emit(
+            circuit_type,
+            AggregationLayer::Leaf,
+            index / self.arity.leaf(),
+        )
+    }
+
+    pub fn add_leaf_proof(
+        &mut self,
+        circuit_type: u8,
+        index: usize,
+        proof: ZkSyncRecursionLayerProof,
+    ) -> Result<Vec<ReadyAggregation>, ProfileError> {
+        let entry = self.aggregations.entry(circuit_type).or_default();
+        let previous = entry.leaf_proofs.insert(index, proof);
+        assert!(
+            previous.is_none(),
+            "duplicate leaf proof {} {}",
+            circuit_type,
+            index
+        );
+
+        self.try_emit(
+            circuit_type,
+            AggregationLayer::Node { depth: 0 },
+            index / self.arity.node(),
+        )
+    }
+
+    pub fn add_node_proof(
+        &mut self,
+        circuit_type: u8,
+        depth: usize,
+        index: usize,
+        proof: ZkSyncRecursionLayerProof,
+    ) -> Result<Vec<ReadyAggregation>, ProfileError> {
+        let entry = self.aggregations.entry(circuit_type).or_default();
+        if entry.leaf_queues.is_some() && entry.final_depth(self.arity.node()) == depth {
+            assert_eq!(index, 0);
+            entry.final_node_proof = Some(proof);
+            return Ok(vec![]);
+        }
+        let previous = entry.node_proofs.insert((depth, index), proof);
+        assert!(
+            previous.is_none(),
+            "duplicate node proof {} {} {}",
+            circuit_type,
+            depth,
+            index
+        );
+
+        self.try_emit(
+            circuit_type,
+            AggregationLayer::Node { depth: depth + 1 },
+            index / self.arity.node(),
+        )
+    }
+
+    /// Proof of the top node of the circuit type, once it's available
+    pub fn final_node_proof(&self, circuit_type: u8) -> Option<&ZkSyncRecursionLayerProof> {
+        self.aggregations
+            .get(&circuit_type)
+            .and_then(|el| el.final_node_proof.as_ref())
+    }
+
+    /// True if all the registered circuit types are aggregated into a single node
+    pub fn is_finished(&self) -> bool {
+        self.aggregations
+            .values()
+            .all(|el| match el.leaf_queues.as_ref() {
+                Some(leaf_queues) => leaf_queues.is_empty() || el.final_node_proof.is_some(),
+                None => false,
+            })
+    }
+
+    // creates the circuit at the given position if all of its proofs are available
+    fn try_emit(
+        &mut self,
+        circuit_type: u8,
+        layer: AggregationLayer,
+        index: usize,
+    ) -> Result<Vec<ReadyAggregation>, ProfileError> {
+        let node_arity = self.arity.node();
+        let entry = self.aggregations.get_mut(&circuit_type).unwrap();
+        if entry.leaf_queues.is_none() || index >= entry.num_circuits(layer, node_arity) {
+            // the proof came before the queue, the circuit will be emitted when it arrives
+            return Ok(vec![]);
+        }
+
+        let children = match layer {
+            AggregationLayer::Leaf => {
+                let num_items = entry.leaf_queues.as_ref().unwrap()[index].num_items as usize;
+                let start = index * self.arity.leaf();
+                start..(start + num_items)
+            }
+            AggregationLayer::Node { depth } => {
+                let num_children = match depth {
+                    0 => entry.num_circuits(AggregationLayer::Leaf, node_arity),
+                    _ => {
+                        entry.num_circuits(AggregationLayer::Node { depth: depth - 1 }, node_arity)
+                    }
+                };
+                let start = index * node_arity;
+                start..std::cmp::min(start + node_arity, num_children)
+            }
+        };
+
+        let is_final = match layer {
+            AggregationLayer::Leaf => false,
+            AggregationLayer::Node { depth } => depth == entry.final_depth(node_arity),
+        };
+
+        let circuit = match layer {
+            AggregationLayer::Leaf => {
+                if !children
+                    .clone()
+                    .all(|el| entry.base_proofs.contains_key(&el))
+                {
+                    return Ok(vec![]);
+                }
+                let proofs = children
+                    .map(|el| entry.base_proofs.remove(&el).unwrap())
+                    .collect();
+                let params = self
+                    .leaf_layer_params
+                    .iter()
+                    .find(|el| el.0 == circuit_type)
+                    .map(|el| &el.1)
+                    .expect("leaf layer parameters for circuit type");
+
+                create_leaf_witness(
+                    &entry.leaf_queues.as_ref().unwrap()[index],
+                    proofs,
+                    &self.base_vks[&circuit_type],
+                    params,
+                    self.arity,
+                )?
+            }
+            AggregationLayer::Node { depth } => {
+                let (proofs, queues, vk) = if depth == 0 {
+                    if !children
+                        .clone()
+                        .all(|el| entry.leaf_proofs.contains_key(&el))
+                    {
+                        return Ok(vec![]);
+                    }
+                    let proofs: Vec<_> = children
+                        .clone()
+                        .map(|el| entry.leaf_proofs.remove(&el).unwrap())
+                        .collect();
+                    let queues: Vec<_> = entry.leaf_queues.as_ref().unwrap()[children]
+                        .iter()
+                        .collect();
+                    (proofs, queues, &self.leaf_vks[&circuit_type])
+                } else {
+                    if !children
+                        .clone()
+                        .all(|el| entry.node_proofs.contains_key(&(depth - 1, el)))
+                    {
+                        return Ok(vec![]);
+                    }
+                    let proofs: Vec<_> = children
+                        .clone()
+                        .map(|el| entry.node_proofs.remove(&(depth - 1, el)).unwrap())
+                        .collect();
+                    let queues: Vec<_> = entry.node_queues[depth - 1][children]
+                        .iter()
+                        .map(|el| el.as_ref().expect("queue of the proven node"))
+                        .collect();
+                    (proofs, queues, &self.node_vk)
+                };
+
+                let (queue, circuit) = create_node_witness(
+                    circuit_type,
+                    &queues,
+                    proofs,
+                    vk,
+                    self.node_vk_commitment,
+                    &self.leaf_layer_params,
+                    self.arity,
+                )?;
+
+                if entry.node_queues.len() <= depth {
+                    entry.node_queues.resize(depth + 1, vec![]);
+                }
+                let num_nodes = entry.num_circuits(layer, node_arity);
+                let node_queues = &mut entry.node_queues[depth];
+                node_queues.resize(num_nodes, None);
+                node_queues[index] = Some(queue);
+
+                circuit
+            }
+        };
+
+        Ok(vec![ReadyAggregation {
+            circuit_type,
+            layer,
+            index,
+            circuit,
+            is_final,
+        }])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::boojum::field::U64Representable;
+    use crate::compute_setups::compute_leaf_params;
+    use crate::data_source::local_file_data_source::LocalFileDataSource;
+    use crate::data_source::SetupDataSource;
+    use crate::prover_utils::create_mock_proof;
+    use crate::witness::aggregation_plan::CircuitTypeAggregationPlan;
+    use crate::zkevm_circuits::scheduler::aux::BaseLayerCircuitType;
+    use circuit_definitions::circuit_definitions::base_layer::ZkSyncBaseLayerCircuit;
+    use circuit_definitions::encodings::recursion_request::RecursionRequest;
+    use circuit_definitions::ProofConfigProfile;
+
+    // (layer, index, is_final) of every emitted circuit
+    fn positions(ready: Vec<ReadyAggregation>) -> Vec<(AggregationLayer, usize, bool)> {
+        ready
+            .into_iter()
+            .map(|el| (el.layer, el.index, el.is_final))
+            .collect()
+    }
+
+    #[test]
+    fn out_of_order_events() {
+        // committed setup keys are enough to create the witnesses for mock proofs
+        let mut source = LocalFileDataSource::default();
+        let circuit_type = BaseLayerCircuitType::VM as u8;
+        let base_vk = source.get_base_layer_vk(circuit_type).unwrap();
+        let leaf_vk = source
+            .get_recursion_layer_vk(base_circuit_type_into_recursive_leaf_circuit_type(
+                BaseLayerCircuitType::from_numeric_value(circuit_type),
+            ) as u8)
+            .unwrap();
+        let node_vk = source.get_recursion_layer_node_vk().unwrap();
+        let leaf_params = compute_leaf_params(&mut source).unwrap();
+        let profile = ProofConfigProfile::of_vk(base_vk.as_inner()).unwrap();
+
+        // leaves of 4, 4 and 3 proofs, then 2 nodes and the final one
+        let num_proofs = 11;
+        let arity = RecursionArity::new(4, 2);
+        let plan = CircuitTypeAggregationPlan::new(circuit_type, num_proofs, arity);
+        assert_eq!(plan.leaves, vec![4, 4, 3]);
+        assert_eq!(plan.nodes_per_depth, vec![2, 1]);
+
+        let round_function = ZkSyncDefaultRoundFunction::default();
+        let mut queue = RecursionQueueSimulator::<F>::empty();
+        for index in 0..num_proofs {
+            let request = RecursionRequest {
+                circuit_type: F::from_u64_unchecked(circuit_type as u64),
+                public_input: std::array::from_fn(|_| F::from_u64_unchecked(index as u64)),
+            };
+            let _ = queue.push(request, &round_function);
+        }
+
+        let base_proof = || {
+            ZkSyncBaseLayerProof::from_inner(
+                circuit_type,
+                create_mock_proof::<ZkSyncBaseLayerCircuit>(profile.base_layer_proof_config()),
+            )
+        };
+        let recursion_proof = |vk: &ZkSyncRecursionLayerVerificationKey| {
+            ZkSyncRecursionLayerProof::from_inner(
+                vk.numeric_circuit_type(),
+                create_mock_proof::<ZkSyncRecursiveLayerCircuit>(
+                    profile.recursion_layer_proof_config(),
+                ),
+            )
+        };
+
+        let mut aggregator = IncrementalAggregator::new(
+            HashMap::from([(circuit_type, base_vk)]),
+            HashMap::from([(circuit_type, leaf_vk.clone())]),
+            node_vk.clone(),
+            leaf_params,
+            arity,
+        );
+        let mut emitted = vec![];
+        let mut check = |ready: Vec<ReadyAggregation>, expected: &[(AggregationLayer, usize)]| {
+            let ready = positions(ready);
+            assert_eq!(
+                ready.iter().map(|el| (el.0, el.1)).collect::<Vec<_>>(),
+                expected
+            );
+            emitted.extend(ready);
+        };
+        let leaf = AggregationLayer::Leaf;
+        let node = |depth| AggregationLayer::Node { depth };
+
+        // the short last chunk is complete before the queue arrives
+        for index in [10, 8, 9] {
+            check(
+                aggregator
+                    .add_base_proof(circuit_type, index, base_proof())
+                    .unwrap(),
+                &[],
+            );
+        }
+        check(
+            aggregator.add_recursion_queue(circuit_type, queue).unwrap(),
+            &[(leaf, 2)],
+        );
+
+        // chunks are completed out of order
+        for (index, expected) in [
+            (5, vec![]),
+            (4, vec![]),
+            (6, vec![]),
+            (3, vec![]),
+            (1, vec![]),
+            (0, vec![]),
+            (2, vec![(leaf, 0)]),
+            (7, vec![(leaf, 1)]),
+        ] {
+            check(
+                aggregator
+                    .add_base_proof(circuit_type, index, base_proof())
+                    .unwrap(),
+                &expected,
+            );
+        }
+
+        // the last node of the first depth has a single leaf
+        for (index, expected) in [
+            (1, vec![]),
+            (2, vec![(node(0), 1)]),
+            (0, vec![(node(0), 0)]),
+        ] {
+            check(
+                aggregator
+                    .add_leaf_proof(circuit_type, index, recursion_proof(&leaf_vk))
+                    .unwrap(),
+                &expected,
+            );
+        }
+        for (index, expected) in [(1, vec![]), (0, vec![(node(1), 0)])] {
+            check(
+                aggregator
+                    .add_node_proof(circuit_type, 0, index, recursion_proof(&node_vk))
+                    .unwrap(),
+                &expected,
+            );
+        }
+        assert!(!aggregator.is_finished());
+        check(
+            aggregator
+                .add_node_proof(circuit_type, 1, 0, recursion_proof(&node_vk))
+                .unwrap(),
+            &[],
+        );
+        assert!(aggregator.is_finished());
+        assert!(aggregator.final_node_proof(circuit_type).is_some());
+
+        // every circuit of the plan is emitted once, and only the top node is final
+        let indexes = |layer| {
+            let mut indexes: Vec<_> = emitted
+                .iter()
+                .filter(|el| el.0 == layer)
+                .map(|el| el.1)
+                .collect();
+            indexes.sort();
+            indexes
+        };
+        assert_eq!(indexes(leaf), (0..plan.leaves.len()).collect::<Vec<_>>());
+        for (depth, &num_nodes) in plan.nodes_per_depth.iter().enumerate() {
+            assert_eq!(indexes(node(depth)), (0..num_nodes).collect::<Vec<_>>());
+        }
+        let final_depth = plan.final_node_depth().unwrap();
+        for (layer, _, is_final) in emitted.iter() {
+            assert_eq!(*is_final, *layer == node(final_depth));
+        }
+    }
+}
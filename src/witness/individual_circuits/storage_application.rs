@@ -8,6 +8,7 @@ use crate::boojum::gadgets::keccak256::{self};
 use crate::witness::individual_circuits::keccak256_round_function::encode_kecca256_inner_state;
 use crate::witness::postprocessing::CircuitMaker;
 use crate::witness::tree::*;
+use crate::zk_evm::aux_structures::LogQuery;
 use crate::zk_evm::sha3::Keccak256;
 use crate::zk_evm::zk_evm_abstractions::precompiles::keccak256::transmute_state;
 use crate::zkevm_circuits::base_structures::state_diff_record::NUM_KECCAK256_ROUNDS_PER_RECORD_ACCUMULATION;
@@ -139,15 +140,17 @@ pub fn decompose_into_storage_application_witnesses<
 
         let chunk_len = chunk.len();
 
-        // apply one by one in chunk
+        // consecutive writes are applied to the tree as one batch, and reads break the batches
+        let mut pending_writes = vec![];
+
         for el in chunk.into_iter() {
             let _ = storage_application_simulator.pop_and_output_intermediate_data(round_function);
 
             let key = el.derive_final_address();
             if el.rw_flag {
-                // by convension we have read and write both
+                // by convension we have read and write both. Queries are deduplicated,
+                // so pending writes can not affect the leaf we read
                 let read_query = tree.get_leaf(&key);
-                // assert!(tree.verify_inclusion_proxy(&tree.root(), &read_query));
                 let mut buffer = [0u8; 32];
                 el.read_value.to_big_endian(&mut buffer);
                 assert_eq!(&buffer, read_query.leaf.value(), "While writing: divergent leaf read value for index {}: expecting to read {}, got {}", hex::encode(&key), hex::encode(&buffer), hex::encode(&read_query.leaf.value()));
@@ -155,44 +158,10 @@ pub fn decompose_into_storage_application_witnesses<
                 let leaf_index = read_query.leaf.current_index();
                 leaf_enumeration_index_for_read.push_back(leaf_index);
 
-                let mut leaf = ZkSyncStorageLeaf::empty();
-                el.written_value.to_big_endian(leaf.value_ref_mut());
-                // we expect that tree properly updates enumeration index on insert
-                let write_query = tree.insert_leaf(&key, leaf);
-                assert!(tree.verify_inclusion_proxy(&tree.root(), &write_query));
-
-                assert_eq!(&*read_query.merkle_path, &*write_query.merkle_path);
-
-                let LeafQuery {
-                    leaf: _,
-                    first_write: _,
-                    index: _,
-                    merkle_path,
-                } = write_query;
-
-                merkle_paths.push_back((*merkle_path).into_iter().collect());
-
-                // NOTE: we need enumeration index BEFORE writing
-                let state_diff = StateDiffRecord {
-                    address: el.address,
-                    key: el.key,
-                    derived_key: key,
-                    enumeration_index: read_query.leaf.current_index(),
-                    initial_value: el.read_value,
-                    final_value: el.written_value,
-                };
-
-                let mut extended_state_diff_encoding = [0u8; keccak256::KECCAK_RATE_BYTES
-                    * NUM_KECCAK256_ROUNDS_PER_RECORD_ACCUMULATION];
-                let packed_encoding = state_diff.encode();
-                extended_state_diff_encoding[0..packed_encoding.len()]
-                    .copy_from_slice(&packed_encoding);
-                // dbg!(hex::encode(&extended_state_diff_encoding));
-
-                Digest::update(&mut hasher, &extended_state_diff_encoding);
-
-                // dbg!(transmute_state(hasher.clone()));
+                pending_writes.push((el, key, leaf_index));
             } else {
+                apply_pending_writes(tree, &mut pending_writes, &mut merkle_paths, &mut hasher);
+
                 // read
                 let read_query = tree.get_leaf(&key);
                 assert!(tree.verify_inclusion_proxy(&tree.root(), &read_query));
@@ -213,6 +182,8 @@ pub fn decompose_into_storage_application_witnesses<
             }
         }
 
+        apply_pending_writes(tree, &mut pending_writes, &mut merkle_paths, &mut hasher);
+
         assert_eq!(leaf_enumeration_index_for_read.len(), merkle_paths.len());
 
         let state = transmute_state(hasher.clone());
@@ -291,3 +262,60 @@ pub fn decompose_into_storage_application_witnesses<
         storage_application_circuits_compact_forms_witnesses,
     )
 }
+
+fn apply_pending_writes(
+    tree: &mut impl BinarySparseStorageTree<256, 32, 32, 8, 32, Blake2s256, ZkSyncStorageLeaf>,
+    pending_writes: &mut Vec<(LogQuery, [u8; 32], u64)>,
+    merkle_paths: &mut VecDeque<Vec<[u8; 32]>>,
+    hasher: &mut Keccak256,
+) {
+    if pending_writes.is_empty() {
+        return;
+    }
+
+    let writes = std::mem::replace(pending_writes, vec![]);
+    let keys: Vec<_> = writes.iter().map(|(_, key, _)| *key).collect();
+    let leafs: Vec<_> = writes
+        .iter()
+        .map(|(el, _, _)| {
+            let mut leaf = ZkSyncStorageLeaf::empty();
+            el.written_value.to_big_endian(leaf.value_ref_mut());
+            leaf
+        })
+        .collect();
+
+    // we expect that tree properly updates enumeration index on insert
+    let write_queries = tree.insert_many_leafs(&keys, leafs);
+    // paths are valid against intermediate roots, and only the last one can be checked here
+    assert!(tree.verify_inclusion_proxy(&tree.root(), write_queries.last().unwrap()));
+
+    for ((el, key, enumeration_index), write_query) in
+        writes.into_iter().zip(write_queries.into_iter())
+    {
+        let LeafQuery {
+            leaf: _,
+            first_write: _,
+            index: _,
+            merkle_path,
+        } = write_query;
+
+        merkle_paths.push_back((*merkle_path).into_iter().collect());
+
+        // NOTE: we need enumeration index BEFORE writing
+        let state_diff = StateDiffRecord {
+            address: el.address,
+            key: el.key,
+            derived_key: key,
+            enumeration_index,
+            initial_value: el.read_value,
+            final_value: el.written_value,
+        };
+
+        let mut extended_state_diff_encoding =
+            [0u8; keccak256::KECCAK_RATE_BYTES * NUM_KECCAK256_ROUNDS_PER_RECORD_ACCUMULATION];
+        let packed_encoding = state_diff.encode();
+        extended_state_diff_encoding[0..packed_encoding.len()].copy_from_slice(&packed_encoding);
+
+        Digest::update(hasher, &extended_state_diff_encoding);
+    }
+}
@@ -1,13 +1,18 @@
 use super::*;
 
 mod advancing_range;
+pub mod aggregation_plan;
 pub mod callstack_handler;
+pub mod chain_linkage;
 pub mod full_block_artifact;
+pub mod incremental_aggregation;
 pub mod individual_circuits;
 pub mod oracle;
 pub mod postprocessing;
+pub mod public_inputs;
 pub mod recursive_aggregation;
 pub use circuit_sequencer_api::sort_storage_access;
+pub mod storage_consistency;
 pub mod tracer;
 pub mod tree;
 pub mod utils;
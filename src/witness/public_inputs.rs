@@ -0,0 +1,235 @@
+use crate::boojum::field::goldilocks::GoldilocksField;
+use crate::boojum::field::SmallField;
+use crate::prover_utils::for_each_base_layer_circuit;
+use crate::witness::utils::*;
+use crate::zkevm_circuits::fsm_input_output::circuit_inputs::INPUT_OUTPUT_COMMITMENT_LENGTH;
+use circuit_definitions::boojum::implementations::poseidon2::Poseidon2Goldilocks;
+use circuit_definitions::circuit_definitions::base_layer::{
+    ZkSyncBaseLayerCircuit, ZkSyncBaseLayerProof,
+};
+use circuit_definitions::encodings::recursion_request::RecursionQueueSimulator;
+use std::collections::BTreeMap;
+
+pub type BaseLayerPublicInput = [GoldilocksField; INPUT_OUTPUT_COMMITMENT_LENGTH];
+
+pub fn expected_public_input(circuit: &ZkSyncBaseLayerCircuit) -> Option<BaseLayerPublicInput> {
+    for_each_base_layer_circuit!(circuit, inner => inner.expected_public_input)
+}
+
+/// Computes public inputs of base layer circuits from their witnesses,
+/// the same way it's done when circuits are created
+pub struct PublicInputCalculator {
+    cs_for_witness_generation: ConstraintSystemImpl<GoldilocksField, Poseidon2Goldilocks>,
+    cycles_used: usize,
+}
+
+impl Default for PublicInputCalculator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PublicInputCalculator {
+    pub fn new() -> Self {
+        Self {
+            cs_for_witness_generation: create_cs_for_witness_generation::<
+                GoldilocksField,
+                Poseidon2Goldilocks,
+            >(
+                TRACE_LEN_LOG_2_FOR_CALCULATION,
+                MAX_VARS_LOG_2_FOR_CALCULATION,
+            ),
+            cycles_used: 0,
+        }
+    }
+
+    /// Returns `None` if the circuit has no witness
+    pub fn compute(&mut self, circuit: &ZkSyncBaseLayerCircuit) -> Option<BaseLayerPublicInput> {
+        let cs = &mut self.cs_for_witness_generation;
+        let public_input = for_each_base_layer_circuit!(circuit, inner => {
+            let witness = inner.clone_witness()?;
+            let (public_input, _) = simulate_public_input_value_from_witness(
+                cs,
+                witness.closed_form_input,
+                &*inner.round_function,
+            );
+
+            public_input
+        });
+
+        self.cycles_used += 1;
+        if self.cycles_used == CYCLES_PER_SCRATCH_SPACE {
+            self.cs_for_witness_generation =
+                create_cs_for_witness_generation::<GoldilocksField, Poseidon2Goldilocks>(
+                    TRACE_LEN_LOG_2_FOR_CALCULATION,
+                    MAX_VARS_LOG_2_FOR_CALCULATION,
+                );
+            self.cycles_used = 0;
+        }
+
+        Some(public_input)
+    }
+
+    /// Sets `expected_public_input` if the circuit doesn't have it yet.
+    /// Returns false if it's missing and can not be computed
+    pub fn fill_expected_public_input(&mut self, circuit: &mut ZkSyncBaseLayerCircuit) -> bool {
+        if expected_public_input(circuit).is_some() {
+            return true;
+        }
+        let Some(public_input) = self.compute(circuit) else {
+            return false;
+        };
+        for_each_base_layer_circuit!(circuit, inner => {
+            inner.expected_public_input = Some(public_input)
+        });
+
+        true
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PublicInputMismatch {
+    /// Circuit doesn't carry the expected public input
+    MissingExpectedInput { circuit_type: u8, instance: usize },
+    /// Different number of circuits and proofs or recursion queue entries of this type
+    CountMismatch {
+        circuit_type: u8,
+        circuits: usize,
+        other: usize,
+    },
+    ProofMismatch {
+        circuit_type: u8,
+        instance: usize,
+        expected: BaseLayerPublicInput,
+        proof_public_input: Vec<GoldilocksField>,
+    },
+    QueueMismatch {
+        circuit_type: u8,
+        instance: usize,
+        expected: BaseLayerPublicInput,
+        queue_circuit_type: GoldilocksField,
+        queue_public_input: BaseLayerPublicInput,
+    },
+}
+
+/// Groups expected public inputs by circuit type, preserving the order of circuits within a type
+pub fn expected_public_inputs_by_type(
+    circuits: &[ZkSyncBaseLayerCircuit],
+) -> Result<BTreeMap<u8, Vec<BaseLayerPublicInput>>, PublicInputMismatch> {
+    let mut result: BTreeMap<u8, Vec<BaseLayerPublicInput>> = BTreeMap::new();
+    for circuit in circuits.iter() {
+        let circuit_type = circuit.numeric_circuit_type();
+        let inputs = result.entry(circuit_type).or_default();
+        let Some(public_input) = expected_public_input(circuit) else {
+            return Err(PublicInputMismatch::MissingExpectedInput {
+                circuit_type,
+                instance: inputs.len(),
+            });
+        };
+        inputs.push(public_input);
+    }
+
+    Ok(result)
+}
+
+fn check_counts(
+    expected: &BTreeMap<u8, Vec<BaseLayerPublicInput>>,
+    actual_counts: &BTreeMap<u8, usize>,
+) -> Result<(), PublicInputMismatch> {
+    for circuit_type in expected.keys().chain(actual_counts.keys()) {
+        let circuits = expected.get(circuit_type).map(|el| el.len()).unwrap_or(0);
+        let other = actual_counts.get(circuit_type).copied().unwrap_or(0);
+        if circuits != other {
+            return Err(PublicInputMismatch::CountMismatch {
+                circuit_type: *circuit_type,
+                circuits,
+                other,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Compares expected public inputs of circuits with public inputs of their proofs.
+/// Proofs are matched with circuits of the same type in order
+pub fn check_proof_public_inputs(
+    circuits: &[ZkSyncBaseLayerCircuit],
+    proofs: &[ZkSyncBaseLayerProof],
+) -> Result<(), PublicInputMismatch> {
+    let expected = expected_public_inputs_by_type(circuits)?;
+
+    let mut proofs_by_type: BTreeMap<u8, Vec<&ZkSyncBaseLayerProof>> = BTreeMap::new();
+    for proof in proofs.iter() {
+        proofs_by_type
+            .entry(proof.numeric_circuit_type())
+            .or_default()
+            .push(proof);
+    }
+    let counts = proofs_by_type
+        .iter()
+        .map(|(circuit_type, proofs)| (*circuit_type, proofs.len()))
+        .collect();
+    check_counts(&expected, &counts)?;
+
+    for (circuit_type, inputs) in expected.iter() {
+        for (instance, (expected, proof)) in inputs
+            .iter()
+            .zip(proofs_by_type[circuit_type].iter())
+            .enumerate()
+        {
+            let proof_public_input = &proof.as_inner().public_inputs;
+            if proof_public_input[..] != expected[..] {
+                return Err(PublicInputMismatch::ProofMismatch {
+                    circuit_type: *circuit_type,
+                    instance,
+                    expected: *expected,
+                    proof_public_input: proof_public_input.clone(),
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Compares expected public inputs of circuits with the entries of recursion queues,
+/// as they are passed to `queue_simulator_callback`
+pub fn check_recursion_queue_public_inputs<'a>(
+    circuits: &[ZkSyncBaseLayerCircuit],
+    recursion_queues: impl IntoIterator<Item = (u64, &'a RecursionQueueSimulator<GoldilocksField>)>,
+) -> Result<(), PublicInputMismatch> {
+    let expected = expected_public_inputs_by_type(circuits)?;
+
+    let queues: BTreeMap<u8, &RecursionQueueSimulator<GoldilocksField>> = recursion_queues
+        .into_iter()
+        .map(|(circuit_type, queue)| (circuit_type as u8, queue))
+        .collect();
+    let counts = queues
+        .iter()
+        .map(|(circuit_type, queue)| (*circuit_type, queue.witness.len()))
+        .collect();
+    check_counts(&expected, &counts)?;
+
+    for (circuit_type, inputs) in expected.iter() {
+        for (instance, (expected, (_, _, request))) in inputs
+            .iter()
+            .zip(queues[circuit_type].witness.iter())
+            .enumerate()
+        {
+            if request.circuit_type.as_u64_reduced() != *circuit_type as u64
+                || request.public_input != *expected
+            {
+                return Err(PublicInputMismatch::QueueMismatch {
+                    circuit_type: *circuit_type,
+                    instance,
+                    expected: *expected,
+                    queue_circuit_type: request.circuit_type,
+                    queue_public_input: request.public_input,
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
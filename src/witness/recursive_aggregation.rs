@@ -18,10 +18,10 @@ use crate::zkevm_circuits::recursion::{
     VK_COMMITMENT_LENGTH,
 };
 use crate::zkevm_circuits::scheduler::LEAF_LAYER_PARAMETERS_COMMITMENT_LENGTH;
-use circuit_definitions::recursion_layer_proof_config;
 use circuit_definitions::{
     zkevm_circuits::scheduler::aux::NUM_CIRCUIT_TYPES_TO_SCHEDULE, ZkSyncDefaultRoundFunction,
 };
+use circuit_definitions::{ProfileError, ProofConfigProfile};
 use std::collections::VecDeque;
 
 type F = GoldilocksField;
@@ -67,7 +67,69 @@ pub(crate) fn compute_encodable_item_from_witness<
     commitment
 }
 
+/// Number of proofs aggregated by a single leaf and by a single node.
+/// Leaf and node verification keys depend on it, so it must match the one used for setup
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "RecursionArityParams")]
+pub struct RecursionArity {
+    leaf: usize,
+    node: usize,
+}
+
+// deserialized arity goes through the same checks as `RecursionArity::new`
+#[derive(serde::Deserialize)]
+struct RecursionArityParams {
+    leaf: usize,
+    node: usize,
+}
+
+impl TryFrom<RecursionArityParams> for RecursionArity {
+    type Error = String;
+
+    fn try_from(params: RecursionArityParams) -> Result<Self, Self::Error> {
+        Self::try_new(params.leaf, params.node)
+    }
+}
+
+impl Default for RecursionArity {
+    fn default() -> Self {
+        Self {
+            leaf: RECURSION_ARITY,
+            node: RECURSION_ARITY,
+        }
+    }
+}
+
+impl RecursionArity {
+    pub fn new(leaf: usize, node: usize) -> Self {
+        Self::try_new(leaf, node).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_new(leaf: usize, node: usize) -> Result<Self, String> {
+        if leaf == 0 {
+            return Err("leaf arity must be positive".to_string());
+        }
+        // otherwise nodes never converge into a single one
+        if node < 2 {
+            return Err(format!("node arity must be at least 2, got {}", node));
+        }
+
+        Ok(Self { leaf, node })
+    }
+
+    /// Number of basic circuits aggregated by a single leaf
+    pub fn leaf(&self) -> usize {
+        self.leaf
+    }
+
+    /// Number of leaves or nodes aggregated by a single node
+    pub fn node(&self) -> usize {
+        self.node
+    }
+}
+
 /// Creates leaf witnesses: each leaf aggregates RECURSION_ARITY (32) basic circuits of a given type.
+/// Panics if the verification key doesn't belong to any proof config profile
 pub fn create_leaf_witnesses(
     subset: (
         u64, // circuit type
@@ -85,6 +147,32 @@ pub fn create_leaf_witnesses(
     )>,
     Vec<ZkSyncBaseLayerClosedFormInput<F>>,
 ) {
+    create_leaf_witnesses_with_arity(subset, proofs, vk, leaf_params, RecursionArity::default())
+        .unwrap()
+}
+
+/// Same as `create_leaf_witnesses`, but each leaf aggregates `arity.leaf()` basic circuits.
+pub fn create_leaf_witnesses_with_arity(
+    subset: (
+        u64, // circuit type
+        RecursionQueueSimulator<F>,
+        Vec<ZkSyncBaseLayerClosedFormInput<F>>,
+    ),
+    proofs: Vec<ZkSyncBaseLayerProof>, // proofs coming from the base layer
+    vk: ZkSyncBaseLayerVerificationKey,
+    leaf_params: (u8, RecursionLeafParametersWitness<F>), // (cirtuit_type, and ??)
+    arity: RecursionArity,
+) -> Result<
+    (
+        Vec<(
+            u64,                         // type of the basic circuit
+            RecursionQueueSimulator<F>,  // chunk
+            ZkSyncRecursiveLayerCircuit, // proof for that chunk
+        )>,
+        Vec<ZkSyncBaseLayerClosedFormInput<F>>,
+    ),
+    ProfileError,
+> {
     let round_function = ZkSyncDefaultRoundFunction::default();
 
     let (circuit_type, queue, closed_form_inputs) = subset;
@@ -94,60 +182,14 @@ pub fn create_leaf_witnesses(
     let (t, params) = leaf_params;
     assert_eq!(t, circuit_type as u8);
 
-    let queue_splits = queue.split_by(RECURSION_ARITY, &round_function);
+    let queue_splits = queue.split_by(arity.leaf(), &round_function);
     let mut proofs_iter = proofs.into_iter();
 
     let mut results = Vec::with_capacity(queue_splits.len());
 
-    for el in queue_splits.iter().cloned() {
-        let mut proof_witnesses = VecDeque::new();
-        for _ in 0..el.num_items {
-            let t = proofs_iter.next().expect("proof");
-            proof_witnesses.push_back(t.into_inner());
-        }
-        let leaf_input = RecursionLeafInputWitness::<F> {
-            params: params.clone(),
-            queue_state: take_sponge_like_queue_state_from_simulator(&el),
-        };
-
-        let elements: VecDeque<_> = el
-            .witness
-            .iter()
-            .map(|(_, old_tail, element)| (element.reflect(), *old_tail))
-            .collect();
-
-        let witness = RecursionLeafInstanceWitness::<F, H, EXT> {
-            input: leaf_input,
-            vk_witness: vk.clone().into_inner(),
-            queue_witness: FullStateCircuitQueueRawWitness { elements: elements },
-            proof_witnesses: proof_witnesses,
-        };
-
-        let config = LeafLayerRecursionConfig::<
-            F,
-            <H as RecursiveTreeHasher<F, Num<F>>>::NonCircuitSimulator,
-            EXT,
-        > {
-            proof_config: recursion_layer_proof_config(),
-            vk_fixed_parameters: vk.clone().into_inner().fixed_parameters,
-            capacity: RECURSION_ARITY,
-            _marker: std::marker::PhantomData,
-        };
-
-        let base_layer_circuit_type =
-            BaseLayerCircuitType::from_numeric_value(vk.numeric_circuit_type());
-        let circuit = ZkSyncLeafLayerRecursiveCircuit {
-            witness,
-            config,
-            transcript_params: (),
-            base_layer_circuit_type,
-            _marker: std::marker::PhantomData,
-        };
-
-        let circuit = ZkSyncRecursiveLayerCircuit::leaf_circuit_from_base_type(
-            BaseLayerCircuitType::from_numeric_value(vk.numeric_circuit_type()),
-            circuit,
-        );
+    for el in queue_splits.into_iter() {
+        let proofs = (&mut proofs_iter).take(el.num_items as usize).collect();
+        let circuit = create_leaf_witness(&el, proofs, &vk, &params, arity)?;
 
         results.push((
             circuit_type,
@@ -157,7 +199,75 @@ pub fn create_leaf_witnesses(
         ));
     }
 
-    (results, closed_form_inputs)
+    Ok((results, closed_form_inputs))
+}
+
+/// Creates a single leaf over a chunk of the recursion queue (at most `arity.leaf()` elements)
+/// and the proofs of the basic circuits in this chunk.
+pub fn create_leaf_witness(
+    chunk: &RecursionQueueSimulator<F>,
+    proofs: Vec<ZkSyncBaseLayerProof>,
+    vk: &ZkSyncBaseLayerVerificationKey,
+    params: &RecursionLeafParametersWitness<F>,
+    arity: RecursionArity,
+) -> Result<ZkSyncRecursiveLayerCircuit, ProfileError> {
+    assert!(chunk.num_items as usize <= arity.leaf());
+    assert_eq!(chunk.num_items as usize, proofs.len(), "proof");
+
+    let proof_witnesses: VecDeque<_> = proofs.into_iter().map(|el| el.into_inner()).collect();
+    let leaf_input = RecursionLeafInputWitness::<F> {
+        params: params.clone(),
+        queue_state: take_sponge_like_queue_state_from_simulator(chunk),
+    };
+
+    let elements: VecDeque<_> = chunk
+        .witness
+        .iter()
+        .map(|(_, old_tail, element)| (element.reflect(), *old_tail))
+        .collect();
+
+    // leaf verifies base layer proofs with the parameters they were created with
+    let profile = ProofConfigProfile::of_vk(vk.as_inner())?;
+    for proof in proof_witnesses.iter() {
+        assert!(
+            profile.matches_proof_config(&proof.proof_config),
+            "base layer proof doesn't match {:?} proof config profile of its verification key",
+            profile
+        );
+    }
+
+    let witness = RecursionLeafInstanceWitness::<F, H, EXT> {
+        input: leaf_input,
+        vk_witness: vk.clone().into_inner(),
+        queue_witness: FullStateCircuitQueueRawWitness { elements: elements },
+        proof_witnesses: proof_witnesses,
+    };
+
+    let config = LeafLayerRecursionConfig::<
+        F,
+        <H as RecursiveTreeHasher<F, Num<F>>>::NonCircuitSimulator,
+        EXT,
+    > {
+        proof_config: profile.recursion_layer_proof_config(),
+        vk_fixed_parameters: vk.clone().into_inner().fixed_parameters,
+        capacity: arity.leaf(),
+        _marker: std::marker::PhantomData,
+    };
+
+    let base_layer_circuit_type =
+        BaseLayerCircuitType::from_numeric_value(vk.numeric_circuit_type());
+    let circuit = ZkSyncLeafLayerRecursiveCircuit {
+        witness,
+        config,
+        transcript_params: (),
+        base_layer_circuit_type,
+        _marker: std::marker::PhantomData,
+    };
+
+    Ok(ZkSyncRecursiveLayerCircuit::leaf_circuit_from_base_type(
+        BaseLayerCircuitType::from_numeric_value(vk.numeric_circuit_type()),
+        circuit,
+    ))
 }
 
 pub fn compute_leaf_params(
@@ -262,6 +372,7 @@ pub fn compute_node_vk_commitment(
 }
 
 /// Creates nodes witnesses, one witness is aggregating up to RECURSION_ARITY (32) leaves (or nodes) of a single circuit type.
+/// Panics if the verification key doesn't belong to any proof config profile
 pub fn create_node_witnesses(
     chunks: Vec<(
         u64,                         // circuit type
@@ -277,17 +388,94 @@ pub fn create_node_witnesses(
     RecursionQueueSimulator<F>,  // chunk
     ZkSyncRecursiveLayerCircuit, // proof for that chunk
 )> {
-    use crate::boojum::gadgets::queue::QueueState;
-    use crate::zkevm_circuits::recursion::NUM_BASE_LAYER_CIRCUITS;
-
-    assert_eq!(leaf_layer_params.len(), NUM_BASE_LAYER_CIRCUITS);
+    create_node_witnesses_with_arity(
+        chunks,
+        proofs,
+        vk,
+        node_vk_commitment,
+        leaf_layer_params,
+        RecursionArity::default(),
+    )
+    .unwrap()
+}
 
+/// Same as `create_node_witnesses`, but each node aggregates up to `arity.node()` leaves (or nodes).
+pub fn create_node_witnesses_with_arity(
+    chunks: Vec<(
+        u64,                         // circuit type
+        RecursionQueueSimulator<F>,  // chunk
+        ZkSyncRecursiveLayerCircuit, // proof for that chunk
+    )>,
+    proofs: Vec<ZkSyncRecursionLayerProof>,
+    vk: ZkSyncRecursionLayerVerificationKey,
+    node_vk_commitment: [F; VK_COMMITMENT_LENGTH],
+    leaf_layer_params: &Vec<(u8, RecursionLeafParametersWitness<F>)>,
+    arity: RecursionArity,
+) -> Result<
+    Vec<(
+        u64,
+        RecursionQueueSimulator<F>,  // chunk
+        ZkSyncRecursiveLayerCircuit, // proof for that chunk
+    )>,
+    ProfileError,
+> {
     assert_eq!(chunks.len(), proofs.len());
 
     assert!(chunks.len() > 0);
-    let circuit_type = chunks[0].0 as u8;
     let mut proofs_iter = proofs.into_iter();
 
+    let mut results = vec![];
+
+    for chunk in chunks.chunks(arity.node()) {
+        assert!(chunk.len() > 0);
+        let num_chunks = chunk.len();
+        // we can immediatelly collect proofs
+        let proofs: Vec<_> = (&mut proofs_iter).take(num_chunks).collect();
+        assert_eq!(proofs.len(), num_chunks); // so we indeed taken exactly enough
+
+        let circuit_type = chunk[0].0;
+        let queues: Vec<_> = chunk.iter().map(|(_, queue, _)| queue).collect();
+        let (queue, circuit) = create_node_witness(
+            circuit_type as u8,
+            &queues,
+            proofs,
+            &vk,
+            node_vk_commitment,
+            leaf_layer_params,
+            arity,
+        )?;
+
+        results.push((circuit_type, queue, circuit));
+    }
+
+    assert!(proofs_iter.next().is_none());
+
+    Ok(results)
+}
+
+/// Creates a single node over up to `arity.node()` queue chunks of leaves (or nodes) and their proofs.
+/// Returns the merged queue that this node covers together with the circuit.
+pub fn create_node_witness(
+    circuit_type: u8,
+    chunk: &[&RecursionQueueSimulator<F>],
+    proofs: Vec<ZkSyncRecursionLayerProof>,
+    vk: &ZkSyncRecursionLayerVerificationKey,
+    node_vk_commitment: [F; VK_COMMITMENT_LENGTH],
+    leaf_layer_params: &Vec<(u8, RecursionLeafParametersWitness<F>)>,
+    arity: RecursionArity,
+) -> Result<(RecursionQueueSimulator<F>, ZkSyncRecursiveLayerCircuit), ProfileError> {
+    use crate::boojum::gadgets::queue::QueueState;
+    use crate::zkevm_circuits::recursion::NUM_BASE_LAYER_CIRCUITS;
+
+    assert_eq!(leaf_layer_params.len(), NUM_BASE_LAYER_CIRCUITS);
+
+    assert!(chunk.len() > 0);
+    assert!(chunk.len() <= arity.node());
+    // if chunk exists it's elements are non-trivial
+    for c in chunk.iter() {
+        assert!(c.num_items > 0);
+    }
+
     let leaf_layer_params = leaf_layer_params
         .iter()
         .map(|el| {
@@ -306,111 +494,118 @@ pub fn create_node_witnesses(
         queue_state: QueueState::placeholder_witness(),
     };
 
+    let profile = ProofConfigProfile::of_vk(&vk.clone().into_inner())?;
+
     let config = NodeLayerRecursionConfig::<
         F,
         <H as RecursiveTreeHasher<F, Num<F>>>::NonCircuitSimulator,
         EXT,
     > {
-        proof_config: recursion_layer_proof_config(),
+        proof_config: profile.recursion_layer_proof_config(),
         vk_fixed_parameters: vk.clone().into_inner().fixed_parameters,
-        leaf_layer_capacity: RECURSION_ARITY,
-        node_layer_capacity: RECURSION_ARITY,
+        leaf_layer_capacity: arity.leaf(),
+        node_layer_capacity: arity.node(),
         _marker: std::marker::PhantomData,
     };
 
-    let mut results = vec![];
+    let proofs: Vec<_> = proofs.into_iter().map(|el| el.into_inner()).collect();
+    for proof in proofs.iter() {
+        assert!(
+            profile.matches_proof_config(&proof.proof_config),
+            "recursion layer proof doesn't match {:?} proof config profile of its verification key",
+            profile
+        );
+    }
 
-    for chunk in chunks.chunks(RECURSION_ARITY) {
-        assert!(chunk.len() > 0);
-        // if chunk exists it's elements are non-trivial
-        for (_, c, _) in chunk.iter() {
-            assert!(c.num_items > 0);
-        }
-        let num_chunks = chunk.len();
-        // we can immediatelly collect proofs
-        let proofs: Vec<_> = (&mut proofs_iter)
-            .take(num_chunks)
-            .map(|el| el.into_inner())
-            .collect();
-        assert_eq!(proofs.len(), num_chunks); // so we indeed taken exactly enough
+    // now even though we would have a chunk of len N, we should only create N-1 split points at the end
 
-        // now even though we would have a chunk of len N, we should only create N-1 split points at the end
+    let mut split_points = Vec::with_capacity(arity.node());
+    let mut it = chunk.iter();
 
-        let mut split_points = Vec::with_capacity(RECURSION_ARITY);
-        let mut it = chunk.into_iter();
+    // Take the first chunk (guaranteed to exist)
+    let mut queue = (*it.next().unwrap()).clone();
+    split_points.push(QueueTailStateWitness {
+        tail: queue.tail,
+        length: queue.num_items,
+    });
 
-        // Take the first chunk (guaranteed to exist)
-        let (circuit_type, queue, _) = (&mut it).next().unwrap();
-        let circuit_type = *circuit_type;
-        let mut queue = queue.clone();
+    // merge all of them, and record split points
+    for c in it {
+        // Split point is a tail of the subqueue
         split_points.push(QueueTailStateWitness {
-            tail: queue.tail,
-            length: queue.num_items,
+            tail: c.tail,
+            length: c.num_items,
         });
 
-        // merge all of them, and record split points
-        for (_, c, _) in it {
-            // Split point is a tail of the subqueue
-            split_points.push(QueueTailStateWitness {
-                tail: c.tail,
-                length: c.num_items,
-            });
+        queue = RecursionQueueSimulator::<F>::merge(queue, (*c).clone());
+    }
 
-            queue = RecursionQueueSimulator::<F>::merge(queue, c.clone());
-        }
+    // check that for every subqueue we have a proof
+    assert_eq!(split_points.len(), proofs.len());
 
-        // check that for every subqueue we have a proof
-        assert_eq!(split_points.len(), proofs.len());
+    // self-check that we have a matching length
+    let total_queue_len = queue.num_items;
+    let mut acc = 0;
+    for el in split_points.iter() {
+        acc += el.length;
+    }
+    assert_eq!(acc, total_queue_len);
 
-        // self-check that we have a matching length
-        let total_queue_len = queue.num_items;
-        let mut acc = 0;
-        for el in split_points.iter() {
-            acc += el.length;
-        }
-        assert_eq!(acc, total_queue_len);
-
-        // for N chunks we need N-1 split points, so either truncate, or pad
-        assert!(split_points.len() <= RECURSION_ARITY);
-
-        if split_points.len() == RECURSION_ARITY {
-            let _ = split_points.pop().unwrap();
-        } else {
-            // pad it
-            let padding = QueueTailStateWitness {
-                tail: queue.tail,
-                length: 0,
-            };
-            split_points.resize(RECURSION_ARITY - 1, padding);
-        }
+    // for N chunks we need N-1 split points, so either truncate, or pad
+    assert!(split_points.len() <= arity.node());
 
-        assert_eq!(split_points.len() + 1, RECURSION_ARITY);
+    if split_points.len() == arity.node() {
+        let _ = split_points.pop().unwrap();
+    } else {
+        // pad it
+        let padding = QueueTailStateWitness {
+            tail: queue.tail,
+            length: 0,
+        };
+        split_points.resize(arity.node() - 1, padding);
+    }
 
-        let mut input = partial_inputs.clone();
-        input.queue_state = take_sponge_like_queue_state_from_simulator(&queue);
+    assert_eq!(split_points.len() + 1, arity.node());
 
-        use crate::zkevm_circuits::recursion::node_layer::input::RecursionNodeInstanceWitness;
+    let mut input = partial_inputs;
+    input.queue_state = take_sponge_like_queue_state_from_simulator(&queue);
 
-        let witness = RecursionNodeInstanceWitness {
-            input,
-            vk_witness: vk.clone().into_inner(),
-            split_points: split_points.into(),
-            proof_witnesses: proofs.into(),
-        };
+    use crate::zkevm_circuits::recursion::node_layer::input::RecursionNodeInstanceWitness;
 
-        let circuit = ZkSyncNodeLayerRecursiveCircuit {
-            witness: witness,
-            config: config.clone(),
-            transcript_params: (),
-            _marker: std::marker::PhantomData,
-        };
+    let witness = RecursionNodeInstanceWitness {
+        input,
+        vk_witness: vk.clone().into_inner(),
+        split_points: split_points.into(),
+        proof_witnesses: proofs.into(),
+    };
 
-        let circuit = ZkSyncRecursiveLayerCircuit::NodeLayerCircuit(circuit);
+    let circuit = ZkSyncNodeLayerRecursiveCircuit {
+        witness: witness,
+        config,
+        transcript_params: (),
+        _marker: std::marker::PhantomData,
+    };
 
-        results.push((circuit_type, queue, circuit));
-    }
+    Ok((
+        queue,
+        ZkSyncRecursiveLayerCircuit::NodeLayerCircuit(circuit),
+    ))
+}
 
-    assert!(proofs_iter.next().is_none());
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn recursion_arity_is_checked_on_deserialization() {
+        let arity = RecursionArity::new(3, 2);
+        let json = serde_json::to_string(&arity).unwrap();
+        assert_eq!(
+            serde_json::from_str::<RecursionArity>(&json).unwrap(),
+            arity
+        );
 
-    results
+        assert!(serde_json::from_str::<RecursionArity>(r#"{"leaf":0,"node":2}"#).is_err());
+        assert!(serde_json::from_str::<RecursionArity>(r#"{"leaf":4,"node":1}"#).is_err());
+    }
 }
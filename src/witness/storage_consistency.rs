@@ -0,0 +1,108 @@
+use crate::blake2::Blake2s256;
+use crate::ethereum_types::{Address, U256};
+use crate::witness::tree::{BinarySparseStorageTree, EnumeratedBinaryLeaf, ZkSyncStorageLeaf};
+use crate::zk_evm::aux_structures::LogQuery;
+use crate::zk_evm::zkevm_opcode_defs::system_params::STORAGE_AUX_BYTE;
+use std::collections::HashSet;
+
+/// Slot for which the `Storage` implementation returned something different from the tree
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StorageDiscrepancy {
+    pub address: Address,
+    pub key: U256,
+    pub derived_key: [u8; 32],
+    pub storage_value: U256,
+    pub tree_value: U256,
+}
+
+/// Compares values that storage returned on the first access to every rollup slot
+/// (later accesses see writes made during the batch) with the leafs of the tree.
+/// Returns all the discrepancies in the order of access
+pub fn check_storage_against_tree(
+    storage_queries: &[(u32, LogQuery)],
+    tree: &mut impl BinarySparseStorageTree<256, 32, 32, 8, 32, Blake2s256, ZkSyncStorageLeaf>,
+) -> Vec<StorageDiscrepancy> {
+    let mut seen_slots = HashSet::new();
+    let mut discrepancies = vec![];
+
+    for (_, query) in storage_queries.iter() {
+        if query.aux_byte != STORAGE_AUX_BYTE || query.shard_id != 0 || query.rollback {
+            continue;
+        }
+        if !seen_slots.insert((query.address, query.key)) {
+            continue;
+        }
+
+        let derived_key = query.derive_final_address();
+        let leaf_query = tree.get_leaf(&derived_key);
+        let tree_value = U256::from_big_endian(leaf_query.leaf.value());
+
+        if tree_value != query.read_value {
+            discrepancies.push(StorageDiscrepancy {
+                address: query.address,
+                key: query.key,
+                derived_key,
+                storage_value: query.read_value,
+                tree_value,
+            });
+        }
+    }
+
+    discrepancies
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::witness::tree::ZKSyncTestingTree;
+    use crate::zk_evm::aux_structures::Timestamp;
+
+    fn storage_query(address: u64, key: u64, read_value: u64, written_value: u64) -> LogQuery {
+        LogQuery {
+            timestamp: Timestamp(0),
+            tx_number_in_block: 0,
+            aux_byte: STORAGE_AUX_BYTE,
+            shard_id: 0,
+            address: Address::from_low_u64_be(address),
+            key: U256::from(key),
+            read_value: U256::from(read_value),
+            written_value: U256::from(written_value),
+            rw_flag: written_value != read_value,
+            rollback: false,
+            is_service: false,
+        }
+    }
+
+    #[test]
+    fn reports_every_mismatch_on_first_access() {
+        let mut tree = ZKSyncTestingTree::empty();
+        let mut leaf = ZkSyncStorageLeaf::empty();
+        U256::from(5u64).to_big_endian(leaf.value_ref_mut());
+        tree.insert_leaf(
+            &LogQuery::derive_final_address_for_params(
+                &Address::from_low_u64_be(0x8001),
+                &U256::from(1u64),
+            ),
+            leaf,
+        );
+
+        let queries = vec![
+            // consistent
+            (0, storage_query(0x8001, 1, 5, 6)),
+            // later access sees the written value, not the tree one
+            (1, storage_query(0x8001, 1, 6, 6)),
+            // storage claims a value for an empty slot
+            (2, storage_query(0x8001, 2, 7, 7)),
+            (3, storage_query(0x8002, 1, 0, 1)),
+            (4, storage_query(0x8002, 3, 1, 1)),
+        ];
+
+        let discrepancies = check_storage_against_tree(&queries, &mut tree);
+        assert_eq!(discrepancies.len(), 2);
+        assert_eq!(discrepancies[0].key, U256::from(2u64));
+        assert_eq!(discrepancies[0].storage_value, U256::from(7u64));
+        assert_eq!(discrepancies[0].tree_value, U256::zero());
+        assert_eq!(discrepancies[1].address, Address::from_low_u64_be(0x8002));
+        assert_eq!(discrepancies[1].key, U256::from(3u64));
+    }
+}
@@ -1,3 +1,7 @@
+mod proofs;
+
+pub use self::proofs::*;
+
 pub trait EnumeratedBinaryLeaf<const LEAF_DATA_WIDTH: usize>: Clone + std::hash::Hash {
     fn empty() -> Self;
     fn empty_index() -> u64 {
@@ -96,9 +100,64 @@ pub trait BinarySparseStorageTree<
     }
 }
 
+/// Tree that keeps some of its older states, so it can be reverted to them or read at them
+pub trait VersionedStorageTree<
+    const DEPTH: usize,
+    const INDEX_BYTES: usize,
+    const LEAF_DATA_WIDTH: usize,
+    const LEAF_METADATA_WIDTH: usize,
+    const HASH_OUTPUT_WIDTH: usize,
+    H: BinaryHasher<HASH_OUTPUT_WIDTH>,
+    L: EnumeratedBinaryLeaf<LEAF_DATA_WIDTH>,
+>:
+    BinarySparseStorageTree<
+    DEPTH,
+    INDEX_BYTES,
+    LEAF_DATA_WIDTH,
+    LEAF_METADATA_WIDTH,
+    HASH_OUTPUT_WIDTH,
+    H,
+    L,
+>
+{
+    /// Remembers current root, enumeration index and starts tracking changed nodes,
+    /// so the tree can be later reverted to this state. Names do not have to be unique,
+    /// in this case the latest snapshot with the name is used
+    fn take_snapshot(&mut self, name: &str);
+    /// Reverts the tree to the latest snapshot with the given name. Snapshots taken after it
+    /// are discarded, while the snapshot itself is kept and can be used again.
+    /// Returns `false` if there is no such snapshot
+    fn rollback_to_snapshot(&mut self, name: &str) -> bool;
+    /// Forgets the latest snapshot with the given name, so that changes made before the
+    /// remaining snapshots are no longer tracked. Returns `false` if there is no such snapshot
+    fn release_snapshot(&mut self, name: &str) -> bool;
+    /// Same as `get_leaf`, but against some older root that was captured by a snapshot
+    /// (or the current one). Returns `None` if the root is not known
+    fn get_leaf_at_root(
+        &self,
+        root: &[u8; HASH_OUTPUT_WIDTH],
+        index: &[u8; INDEX_BYTES],
+    ) -> Option<LeafQuery<DEPTH, INDEX_BYTES, LEAF_DATA_WIDTH, HASH_OUTPUT_WIDTH, L>>;
+    /// Proof against the current root or some root captured by a snapshot.
+    /// Returns `None` if the root is not known or the leaf at the index was written by then
+    fn prove_non_inclusion(
+        &self,
+        root: &[u8; HASH_OUTPUT_WIDTH],
+        index: &[u8; INDEX_BYTES],
+    ) -> Option<NonInclusionProof<DEPTH, INDEX_BYTES, HASH_OUTPUT_WIDTH>>;
+    /// Proof against the current root or some root captured by a snapshot.
+    /// Returns `None` if the root is not known
+    fn prove_multiple_leafs(
+        &self,
+        root: &[u8; HASH_OUTPUT_WIDTH],
+        indexes: &[[u8; INDEX_BYTES]],
+    ) -> Option<MultiLeafProof<INDEX_BYTES, HASH_OUTPUT_WIDTH, L>>;
+}
+
 pub type ZKSyncTestingTree = InMemoryStorageTree<256, 32, 8, Blake2s256, ZkSyncStorageLeaf>;
 
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
 pub struct InMemoryStorageTree<
     const DEPTH: usize,
@@ -114,6 +173,41 @@ pub struct InMemoryStorageTree<
     pub root: [u8; 32],
     pub layers: [HashMap<[u8; INDEX_BYTES], [u8; 32]>; DEPTH],
     pub leafs: HashMap<[u8; INDEX_BYTES], L>,
+    snapshots: Vec<TreeSnapshot>,
+    // previous values of everything that was overwritten since the first snapshot
+    journal: Vec<TreeJournalEntry<INDEX_BYTES, L>>,
+    // state at the snapshots, keyed by their journal position and updated lazily on reads
+    historical_overlays: Mutex<HashMap<usize, Arc<HistoricalOverlay<INDEX_BYTES, L>>>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct TreeSnapshot {
+    pub name: String,
+    pub root: [u8; 32],
+    pub next_enumeration_index: u64,
+    // all the journal entries after this position are nodes and leafs changed since the snapshot
+    pub journal_position: usize,
+}
+
+// Nodes and leafs that were changed since some journal position, with their values at that time
+#[derive(Clone)]
+struct HistoricalOverlay<const INDEX_BYTES: usize, L> {
+    // journal entries before this position are already applied
+    applied_until: usize,
+    nodes: HashMap<(usize, [u8; INDEX_BYTES]), Option<[u8; 32]>>,
+    leafs: HashMap<[u8; INDEX_BYTES], Option<L>>,
+}
+
+enum TreeJournalEntry<const INDEX_BYTES: usize, L> {
+    Node {
+        level: usize,
+        index: [u8; INDEX_BYTES],
+        previous: Option<[u8; 32]>,
+    },
+    Leaf {
+        index: [u8; INDEX_BYTES],
+        previous: Option<L>,
+    },
 }
 
 fn create_neighbour_index<const N: usize>(index: &[u8; N], depth: usize) -> [u8; N] {
@@ -127,6 +221,18 @@ fn create_neighbour_index<const N: usize>(index: &[u8; N], depth: usize) -> [u8;
     result
 }
 
+fn path_element_index<const N: usize>(index: &[u8; N], level: usize) -> [u8; N] {
+    // the only important thing is to cleanup the lowest bits for consistency
+    let mut index = *index;
+    for bit in 0..level {
+        let word_idx = bit / 8;
+        let bit_idx = bit % 8;
+        index[word_idx] = index[word_idx] & (!(1 << bit_idx));
+    }
+
+    index
+}
+
 fn is_right_side_node<const N: usize>(index: &[u8; N], depth: usize) -> bool {
     debug_assert!(depth < N * 8);
     let byte_idx = depth / 8;
@@ -137,6 +243,204 @@ fn is_right_side_node<const N: usize>(index: &[u8; N], depth: usize) -> bool {
     is_right_side
 }
 
+struct BatchedLeafUpdate<const DEPTH: usize, const INDEX_BYTES: usize> {
+    index: [u8; INDEX_BYTES],
+    leaf_hash: [u8; 32],
+    merkle_path: Box<[[u8; 32]; DEPTH]>,
+}
+
+fn get_existing_node<const DEPTH: usize, const INDEX_BYTES: usize>(
+    layers: &[HashMap<[u8; INDEX_BYTES], [u8; 32]>; DEPTH],
+    empty_hashes: &[[u8; 32]; DEPTH],
+    level: usize,
+    index: &[u8; INDEX_BYTES],
+) -> [u8; 32] {
+    let index = path_element_index(index, level);
+
+    layers[level]
+        .get(&index)
+        .copied()
+        .unwrap_or(empty_hashes[level])
+}
+
+// Takes all the updates that fall into some node at the given level (in the order of insertion),
+// fills merkle paths of them below this level, and returns hashes of the node in the
+// states "after first `k` updates" for every requested `k` (sorted, non-zero, and always including the last one).
+// State zero is what is already in the tree. Also returns final values of all the changed nodes below the level.
+// Updates are split by bits of the index from the top, so this is effectively a radix sort
+// that keeps the order of insertion within every subtree
+fn hash_subtree_versions<const DEPTH: usize, const INDEX_BYTES: usize, H: BinaryHasher<32>>(
+    layers: &[HashMap<[u8; INDEX_BYTES], [u8; 32]>; DEPTH],
+    empty_hashes: &[[u8; 32]; DEPTH],
+    level: usize,
+    updates: Vec<BatchedLeafUpdate<DEPTH, INDEX_BYTES>>,
+    versions: &[usize],
+) -> (
+    Vec<BatchedLeafUpdate<DEPTH, INDEX_BYTES>>,
+    Vec<[u8; 32]>,
+    Vec<(usize, [u8; INDEX_BYTES], [u8; 32])>,
+) {
+    let num_updates = updates.len();
+    debug_assert!(num_updates > 0);
+    debug_assert_eq!(versions.last().copied(), Some(num_updates));
+    let reference_index = updates[0].index;
+
+    if level == 0 {
+        // all updates are for the same leaf
+        let hashes = versions.iter().map(|k| updates[k - 1].leaf_hash).collect();
+        let new_nodes = vec![(0, reference_index, updates[num_updates - 1].leaf_hash)];
+
+        return (updates, hashes, new_nodes);
+    }
+
+    let child_level = level - 1;
+
+    // number of updates that went to the left and to the right before every position
+    let mut is_right = Vec::with_capacity(num_updates);
+    let mut counts = Vec::with_capacity(num_updates + 1);
+    counts.push((0, 0));
+    let (mut num_left, mut num_right) = (0, 0);
+    for el in updates.iter() {
+        let right = is_right_side_node(&el.index, child_level);
+        if right {
+            num_right += 1;
+        } else {
+            num_left += 1;
+        }
+        is_right.push(right);
+        counts.push((num_left, num_right));
+    }
+
+    // left child is observed by updates on the right (as a part of their paths) and vice versa,
+    // and both are needed for every state of this node that is requested
+    let mut left_versions = vec![];
+    let mut right_versions = vec![];
+    for (right, (l, r)) in is_right.iter().zip(counts.iter()) {
+        if *right {
+            left_versions.push(*l);
+        } else {
+            right_versions.push(*r);
+        }
+    }
+    for k in versions.iter() {
+        let (l, r) = counts[*k];
+        left_versions.push(l);
+        right_versions.push(r);
+    }
+    for el in [&mut left_versions, &mut right_versions] {
+        el.retain(|k| *k != 0);
+        el.sort_unstable();
+        el.dedup();
+    }
+
+    let mut left = Vec::with_capacity(num_left);
+    let mut right = Vec::with_capacity(num_right);
+    for (el, is_right) in updates.into_iter().zip(is_right.iter()) {
+        if *is_right {
+            right.push(el);
+        } else {
+            left.push(el);
+        }
+    }
+
+    let ((left, left_hashes, mut new_nodes), (right, right_hashes, right_new_nodes)) =
+        match (left.is_empty(), right.is_empty()) {
+            (false, false) => rayon::join(
+                || {
+                    hash_subtree_versions::<DEPTH, INDEX_BYTES, H>(
+                        layers,
+                        empty_hashes,
+                        child_level,
+                        left,
+                        &left_versions,
+                    )
+                },
+                || {
+                    hash_subtree_versions::<DEPTH, INDEX_BYTES, H>(
+                        layers,
+                        empty_hashes,
+                        child_level,
+                        right,
+                        &right_versions,
+                    )
+                },
+            ),
+            (false, true) => (
+                hash_subtree_versions::<DEPTH, INDEX_BYTES, H>(
+                    layers,
+                    empty_hashes,
+                    child_level,
+                    left,
+                    &left_versions,
+                ),
+                (right, vec![], vec![]),
+            ),
+            (true, false) => (
+                (left, vec![], vec![]),
+                hash_subtree_versions::<DEPTH, INDEX_BYTES, H>(
+                    layers,
+                    empty_hashes,
+                    child_level,
+                    right,
+                    &right_versions,
+                ),
+            ),
+            (true, true) => unreachable!(),
+        };
+    new_nodes.extend(right_new_nodes);
+
+    let mut left_index = reference_index;
+    left_index[child_level / 8] &= !(1u8 << (child_level % 8));
+    let right_index = create_neighbour_index(&left_index, child_level);
+    let left_initial = get_existing_node(layers, empty_hashes, child_level, &left_index);
+    let right_initial = get_existing_node(layers, empty_hashes, child_level, &right_index);
+
+    let left_at = |k: usize| {
+        if k == 0 {
+            left_initial
+        } else {
+            left_hashes[left_versions.binary_search(&k).unwrap()]
+        }
+    };
+    let right_at = |k: usize| {
+        if k == 0 {
+            right_initial
+        } else {
+            right_hashes[right_versions.binary_search(&k).unwrap()]
+        }
+    };
+
+    // restore the original order and fill the paths at this level
+    let mut left = left.into_iter();
+    let mut right = right.into_iter();
+    let mut updates = Vec::with_capacity(num_updates);
+    for (is_right, (l, r)) in is_right.into_iter().zip(counts.iter()) {
+        if is_right {
+            let mut el = right.next().unwrap();
+            el.merkle_path[child_level] = left_at(*l);
+            updates.push(el);
+        } else {
+            let mut el = left.next().unwrap();
+            el.merkle_path[child_level] = right_at(*r);
+            updates.push(el);
+        }
+    }
+
+    let hashes: Vec<_> = versions
+        .iter()
+        .map(|k| {
+            let (l, r) = counts[*k];
+            H::node_hash(child_level, &left_at(l), &right_at(r))
+        })
+        .collect();
+
+    if level < DEPTH {
+        new_nodes.push((level, reference_index, *hashes.last().unwrap()));
+    }
+
+    (updates, hashes, new_nodes)
+}
+
 impl<
         const DEPTH: usize,
         const INDEX_BYTES: usize,
@@ -182,29 +486,37 @@ impl<
             root,
             layers: layers,
             leafs: HashMap::new(),
+            snapshots: vec![],
+            journal: vec![],
+            historical_overlays: Mutex::new(HashMap::new()),
         }
     }
 
+    /// Snapshots in the order they were taken
+    pub fn snapshots(&self) -> &[TreeSnapshot] {
+        &self.snapshots
+    }
+
+    fn is_journaling(&self) -> bool {
+        // there is no need to remember anything if we can not rollback
+        !self.snapshots.is_empty()
+    }
+
     fn insert_path_element(&mut self, level: usize, index: [u8; INDEX_BYTES], value: [u8; 32]) {
-        // the only important thing is to cleanup the lowest bits for consistency
-        let mut index = index;
-        for bit in 0..level {
-            let word_idx = bit / 8;
-            let bit_idx = bit % 8;
-            index[word_idx] = index[word_idx] & (!(1 << bit_idx));
+        let index = path_element_index(&index, level);
+
+        let previous = self.layers[level].insert(index, value);
+        if self.is_journaling() {
+            self.journal.push(TreeJournalEntry::Node {
+                level,
+                index,
+                previous,
+            });
         }
-
-        self.layers[level].insert(index, value);
     }
 
     fn get_path_element(&self, level: usize, index: [u8; INDEX_BYTES]) -> &[u8; 32] {
-        // the only important thing is to cleanup the lowest bits for consistency
-        let mut index = index;
-        for bit in 0..level {
-            let word_idx = bit / 8;
-            let bit_idx = bit % 8;
-            index[word_idx] = index[word_idx] & (!(1 << bit_idx));
-        }
+        let index = path_element_index(&index, level);
 
         if let Some(node_hash) = self.layers[level].get(&index) {
             node_hash
@@ -235,15 +547,19 @@ impl<
         }
     }
 
-    fn verify_inclusion(root: &[u8; 32], query: &LeafQuery<DEPTH, INDEX_BYTES, 32, 32, L>) -> bool {
+    fn hash_leaf(leaf: &L) -> [u8; 32] {
         let mut leaf_bytes = vec![0u8; LEAF_METADATA_WIDTH + 32]; // can make a scratch space somewhere later on
-        leaf_bytes[LEAF_METADATA_WIDTH..].copy_from_slice(query.leaf.value());
+        leaf_bytes[LEAF_METADATA_WIDTH..].copy_from_slice(leaf.value());
 
-        let leaf_index_bytes = query.leaf.current_index().to_be_bytes();
+        let leaf_index_bytes = leaf.current_index().to_be_bytes();
         leaf_bytes[(LEAF_METADATA_WIDTH - 8)..LEAF_METADATA_WIDTH]
             .copy_from_slice(&leaf_index_bytes);
 
-        let leaf_hash = H::leaf_hash(&leaf_bytes);
+        H::leaf_hash(&leaf_bytes)
+    }
+
+    fn verify_inclusion(root: &[u8; 32], query: &LeafQuery<DEPTH, INDEX_BYTES, 32, 32, L>) -> bool {
+        let leaf_hash = Self::hash_leaf(&query.leaf);
 
         let mut current_hash = leaf_hash;
         for level in 0..DEPTH {
@@ -289,15 +605,17 @@ impl<
         (next_index, first_writes, updates)
     }
 
-    fn insert_leaf(
-        &mut self,
-        index: &[u8; INDEX_BYTES],
-        leaf: L,
-    ) -> LeafQuery<DEPTH, INDEX_BYTES, 32, 32, L> {
-        // first decide if we enumerate
-
+    // updates the leaf value, or enumerates it if it's a first write
+    fn update_leaf(&mut self, index: &[u8; INDEX_BYTES], leaf: L) -> (L, bool) {
         let mut first_write = false;
 
+        if self.is_journaling() {
+            self.journal.push(TreeJournalEntry::Leaf {
+                index: *index,
+                previous: self.leafs.get(index).cloned(),
+            });
+        }
+
         if let Some(existing_leaf) = self.leafs.get_mut(index) {
             existing_leaf.set_value(leaf.value());
         } else {
@@ -309,16 +627,21 @@ impl<
             self.next_enumeration_index += 1;
         }
 
-        // now recompute the path
         let leaf = self.leafs.get(index).cloned().unwrap();
-        let mut leaf_bytes = vec![0u8; LEAF_METADATA_WIDTH + 32]; // can make a scratch space somewhere later on
-        leaf_bytes[LEAF_METADATA_WIDTH..].copy_from_slice(leaf.value());
 
-        let leaf_index_bytes = leaf.current_index().to_be_bytes();
-        leaf_bytes[(LEAF_METADATA_WIDTH - 8)..LEAF_METADATA_WIDTH]
-            .copy_from_slice(&leaf_index_bytes);
+        (leaf, first_write)
+    }
+
+    fn insert_leaf(
+        &mut self,
+        index: &[u8; INDEX_BYTES],
+        leaf: L,
+    ) -> LeafQuery<DEPTH, INDEX_BYTES, 32, 32, L> {
+        // first decide if we enumerate
+        let (leaf, first_write) = self.update_leaf(index, leaf);
 
-        let leaf_hash = H::leaf_hash(&leaf_bytes);
+        // now recompute the path
+        let leaf_hash = Self::hash_leaf(&leaf);
 
         let mut current_hash = leaf_hash;
         let mut path: Box<[[u8; 32]; DEPTH]> = Box::new([[0u8; 32]; DEPTH]);
@@ -348,6 +671,292 @@ impl<
             merkle_path: path,
         }
     }
+
+    /// Gives exactly the same result as inserting leafs one by one, but every node
+    /// is hashed only as many times as there are different states of it that somebody observes,
+    /// and disjoint subtrees are processed in parallel
+    pub fn insert_many_leafs(
+        &mut self,
+        indexes: &[[u8; INDEX_BYTES]],
+        leafs: Vec<L>,
+    ) -> Vec<LeafQuery<DEPTH, INDEX_BYTES, 32, 32, L>> {
+        assert_eq!(indexes.len(), leafs.len());
+        if indexes.is_empty() {
+            return vec![];
+        }
+
+        // enumeration is sequential by nature, but cheap
+        let updated_leafs = indexes
+            .iter()
+            .zip(leafs.into_iter())
+            .map(|(index, leaf)| {
+                let (leaf, first_write) = self.update_leaf(index, leaf);
+                (*index, leaf, first_write)
+            })
+            .collect();
+
+        self.rehash_updated_leafs(updated_leafs)
+    }
+
+    /// Inserts leafs that are already enumerated (e.g. restored from some other tree) as is.
+    /// Next enumeration index is moved forward if some leaf has larger index
+    pub fn insert_many_enumerated_leafs(
+        &mut self,
+        indexes: &[[u8; INDEX_BYTES]],
+        leafs: Vec<L>,
+    ) -> Vec<LeafQuery<DEPTH, INDEX_BYTES, 32, 32, L>> {
+        assert_eq!(indexes.len(), leafs.len());
+        if indexes.is_empty() {
+            return vec![];
+        }
+
+        let mut updated_leafs = Vec::with_capacity(indexes.len());
+        for (index, leaf) in indexes.iter().zip(leafs.into_iter()) {
+            assert!(leaf.current_index() != L::empty_index());
+            let previous = self.leafs.insert(*index, leaf.clone());
+            let first_write = previous.is_none();
+            if self.is_journaling() {
+                self.journal.push(TreeJournalEntry::Leaf {
+                    index: *index,
+                    previous,
+                });
+            }
+            self.next_enumeration_index =
+                std::cmp::max(self.next_enumeration_index, leaf.current_index() + 1);
+            updated_leafs.push((*index, leaf, first_write));
+        }
+
+        self.rehash_updated_leafs(updated_leafs)
+    }
+
+    fn rehash_updated_leafs(
+        &mut self,
+        updated_leafs: Vec<([u8; INDEX_BYTES], L, bool)>,
+    ) -> Vec<LeafQuery<DEPTH, INDEX_BYTES, 32, 32, L>> {
+        let num_updates = updated_leafs.len();
+        let updates = updated_leafs
+            .iter()
+            .map(|(index, leaf, _)| BatchedLeafUpdate {
+                index: *index,
+                leaf_hash: Self::hash_leaf(leaf),
+                merkle_path: Box::new([[0u8; 32]; DEPTH]),
+            })
+            .collect();
+
+        // we only need the final state of the root
+        let (updates, root_versions, new_nodes) = hash_subtree_versions::<DEPTH, INDEX_BYTES, H>(
+            &self.layers,
+            &self.empty_hashes,
+            DEPTH,
+            updates,
+            &[num_updates],
+        );
+
+        for (level, index, value) in new_nodes.into_iter() {
+            self.insert_path_element(level, index, value);
+        }
+        self.root = root_versions[0];
+
+        updates
+            .into_iter()
+            .zip(updated_leafs.into_iter())
+            .map(|(update, (index, leaf, first_write))| LeafQuery {
+                leaf,
+                first_write,
+                index,
+                merkle_path: update.merkle_path,
+            })
+            .collect()
+    }
+
+    pub fn take_snapshot(&mut self, name: &str) {
+        self.snapshots.push(TreeSnapshot {
+            name: name.to_owned(),
+            root: self.root,
+            next_enumeration_index: self.next_enumeration_index,
+            journal_position: self.journal.len(),
+        });
+    }
+
+    pub fn rollback_to_snapshot(&mut self, name: &str) -> bool {
+        let Some(position) = self.snapshots.iter().rposition(|el| el.name == name) else {
+            return false;
+        };
+        self.snapshots.truncate(position + 1);
+        let snapshot = self.snapshots[position].clone();
+
+        // undo in reverse order, so the oldest value of every node is the one that survives
+        while self.journal.len() > snapshot.journal_position {
+            match self.journal.pop().unwrap() {
+                TreeJournalEntry::Node {
+                    level,
+                    index,
+                    previous,
+                } => {
+                    if let Some(previous) = previous {
+                        self.layers[level].insert(index, previous);
+                    } else {
+                        self.layers[level].remove(&index);
+                    }
+                }
+                TreeJournalEntry::Leaf { index, previous } => {
+                    if let Some(previous) = previous {
+                        self.leafs.insert(index, previous);
+                    } else {
+                        self.leafs.remove(&index);
+                    }
+                }
+            }
+        }
+
+        self.root = snapshot.root;
+        self.next_enumeration_index = snapshot.next_enumeration_index;
+        // overlays may have applied entries that are now gone
+        self.historical_overlays.lock().unwrap().clear();
+
+        true
+    }
+
+    pub fn release_snapshot(&mut self, name: &str) -> bool {
+        let Some(position) = self.snapshots.iter().rposition(|el| el.name == name) else {
+            return false;
+        };
+        self.snapshots.remove(position);
+
+        // entries before the oldest remaining snapshot can not be rolled back to anymore
+        let unused = self
+            .snapshots
+            .first()
+            .map(|el| el.journal_position)
+            .unwrap_or(self.journal.len());
+        if unused > 0 {
+            self.journal.drain(..unused);
+            for snapshot in self.snapshots.iter_mut() {
+                snapshot.journal_position -= unused;
+            }
+        }
+        self.historical_overlays.lock().unwrap().clear();
+
+        true
+    }
+
+    /// Read-only view of the tree as it was at the given root. Only the current root
+    /// and roots captured by snapshots are available
+    pub fn historical_view(
+        &self,
+        root: &[u8; 32],
+    ) -> Option<InMemoryStorageTreeView<'_, DEPTH, INDEX_BYTES, LEAF_METADATA_WIDTH, H, L>> {
+        let journal_position = if root == &self.root {
+            self.journal.len()
+        } else {
+            self.snapshots
+                .iter()
+                .rev()
+                .find(|el| &el.root == root)?
+                .journal_position
+        };
+
+        let mut overlays = self.historical_overlays.lock().unwrap();
+        let overlay = overlays.entry(journal_position).or_insert_with(|| {
+            Arc::new(HistoricalOverlay {
+                applied_until: journal_position,
+                nodes: HashMap::new(),
+                leafs: HashMap::new(),
+            })
+        });
+        if overlay.applied_until < self.journal.len() {
+            // copies the overlay only if some older view still holds it
+            let overlay = Arc::make_mut(overlay);
+            // first entry for every node after the snapshot holds the value at the time of the snapshot
+            for entry in self.journal[overlay.applied_until..].iter() {
+                match entry {
+                    TreeJournalEntry::Node {
+                        level,
+                        index,
+                        previous,
+                    } => {
+                        overlay.nodes.entry((*level, *index)).or_insert(*previous);
+                    }
+                    TreeJournalEntry::Leaf { index, previous } => {
+                        overlay
+                            .leafs
+                            .entry(*index)
+                            .or_insert_with(|| previous.clone());
+                    }
+                }
+            }
+            overlay.applied_until = self.journal.len();
+        }
+
+        Some(InMemoryStorageTreeView {
+            tree: self,
+            root: *root,
+            overlay: Arc::clone(overlay),
+        })
+    }
+}
+
+pub struct InMemoryStorageTreeView<
+    'a,
+    const DEPTH: usize,
+    const INDEX_BYTES: usize,
+    const LEAF_METADATA_WIDTH: usize,
+    H: BinaryHasher<32>,
+    L: EnumeratedBinaryLeaf<32>,
+> {
+    tree: &'a InMemoryStorageTree<DEPTH, INDEX_BYTES, LEAF_METADATA_WIDTH, H, L>,
+    root: [u8; 32],
+    overlay: Arc<HistoricalOverlay<INDEX_BYTES, L>>,
+}
+
+impl<
+        'a,
+        const DEPTH: usize,
+        const INDEX_BYTES: usize,
+        const LEAF_METADATA_WIDTH: usize,
+        H: BinaryHasher<32>,
+        L: EnumeratedBinaryLeaf<32>,
+    > InMemoryStorageTreeView<'a, DEPTH, INDEX_BYTES, LEAF_METADATA_WIDTH, H, L>
+{
+    pub fn root(&self) -> [u8; 32] {
+        self.root
+    }
+
+    /// Leaf at the index, `None` if it was not written by this root
+    pub fn leaf(&self, index: &[u8; INDEX_BYTES]) -> Option<L> {
+        match self.overlay.leafs.get(index) {
+            Some(previous) => previous.clone(),
+            None => self.tree.leafs.get(index).cloned(),
+        }
+    }
+
+    /// Node at the level, where `index` is already shifted by `path_element_index`
+    pub(crate) fn node(&self, level: usize, index: &[u8; INDEX_BYTES]) -> [u8; 32] {
+        let node_hash = match self.overlay.nodes.get(&(level, *index)) {
+            Some(previous) => *previous,
+            None => self.tree.layers[level].get(index).copied(),
+        };
+
+        node_hash.unwrap_or(self.tree.empty_hashes[level])
+    }
+
+    pub fn get_leaf(&self, index: &[u8; INDEX_BYTES]) -> LeafQuery<DEPTH, INDEX_BYTES, 32, 32, L> {
+        let leaf = self.leaf(index).unwrap_or_else(L::empty);
+
+        let mut path: Box<[[u8; 32]; DEPTH]> = Box::new([[0u8; 32]; DEPTH]);
+        for level in 0..DEPTH {
+            let pair_idx = create_neighbour_index(index, level);
+            let pair_idx = path_element_index(&pair_idx, level);
+            path[level] = self.node(level, &pair_idx);
+        }
+
+        LeafQuery {
+            leaf,
+            first_write: false,
+            index: *index,
+            merkle_path: path,
+        }
+    }
 }
 
 impl<
@@ -381,6 +990,13 @@ impl<
     ) -> LeafQuery<DEPTH, INDEX_BYTES, 32, 32, L> {
         Self::insert_leaf(self, index, leaf)
     }
+    fn insert_many_leafs(
+        &mut self,
+        indexes: &[[u8; INDEX_BYTES]],
+        leafs: Vec<L>,
+    ) -> Vec<LeafQuery<DEPTH, INDEX_BYTES, 32, 32, L>> {
+        Self::insert_many_leafs(self, indexes, leafs)
+    }
     // fn filter_renumerate(&self, indexes: &[[u8; INDEX_BYTES]], leafs: &[L]) -> (u64, Vec<L>, Vec<L>) {
     fn filter_renumerate<'a>(
         &self,
@@ -394,6 +1010,47 @@ impl<
     }
 }
 
+impl<
+        const DEPTH: usize,
+        const INDEX_BYTES: usize,
+        const LEAF_METADATA_WIDTH: usize,
+        H: BinaryHasher<32>,
+        L: EnumeratedBinaryLeaf<32>,
+    > VersionedStorageTree<DEPTH, INDEX_BYTES, 32, LEAF_METADATA_WIDTH, 32, H, L>
+    for InMemoryStorageTree<DEPTH, INDEX_BYTES, LEAF_METADATA_WIDTH, H, L>
+{
+    fn take_snapshot(&mut self, name: &str) {
+        Self::take_snapshot(self, name)
+    }
+    fn rollback_to_snapshot(&mut self, name: &str) -> bool {
+        Self::rollback_to_snapshot(self, name)
+    }
+    fn release_snapshot(&mut self, name: &str) -> bool {
+        Self::release_snapshot(self, name)
+    }
+    fn get_leaf_at_root(
+        &self,
+        root: &[u8; 32],
+        index: &[u8; INDEX_BYTES],
+    ) -> Option<LeafQuery<DEPTH, INDEX_BYTES, 32, 32, L>> {
+        self.historical_view(root).map(|view| view.get_leaf(index))
+    }
+    fn prove_non_inclusion(
+        &self,
+        root: &[u8; 32],
+        index: &[u8; INDEX_BYTES],
+    ) -> Option<NonInclusionProof<DEPTH, INDEX_BYTES, 32>> {
+        Self::prove_non_inclusion(self, root, index)
+    }
+    fn prove_multiple_leafs(
+        &self,
+        root: &[u8; 32],
+        indexes: &[[u8; INDEX_BYTES]],
+    ) -> Option<MultiLeafProof<INDEX_BYTES, 32, L>> {
+        Self::prove_multiple_leafs(self, root, indexes)
+    }
+}
+
 use crate::blake2::{Blake2s256, Digest};
 
 impl BinaryHasher<32> for Blake2s256 {
@@ -830,3 +1487,304 @@ impl EnumeratedBinaryLeaf<32> for ZkSyncStorageLeaf {
 //     }
 
 // }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn leaf_with_value(value: u8) -> ZkSyncStorageLeaf {
+        ZkSyncStorageLeaf::from_value([value; 32])
+    }
+
+    #[test]
+    fn rollback_restores_root() {
+        let mut tree = ZKSyncTestingTree::empty();
+        tree.insert_leaf(&[1u8; 32], leaf_with_value(1));
+        tree.insert_leaf(&[2u8; 32], leaf_with_value(2));
+
+        let root_before = tree.root();
+        let enumeration_index_before = tree.next_enumeration_index();
+        tree.take_snapshot("batch_1");
+
+        // overwrite existing leaf and add a new one
+        tree.insert_leaf(&[1u8; 32], leaf_with_value(3));
+        tree.insert_leaf(&[4u8; 32], leaf_with_value(4));
+        assert_ne!(tree.root(), root_before);
+
+        assert!(tree.rollback_to_snapshot("batch_1"));
+        assert_eq!(tree.root(), root_before);
+        assert_eq!(tree.next_enumeration_index(), enumeration_index_before);
+
+        // the tree must be in exactly the same state as if nothing happened
+        let mut reference = ZKSyncTestingTree::empty();
+        reference.insert_leaf(&[1u8; 32], leaf_with_value(1));
+        reference.insert_leaf(&[2u8; 32], leaf_with_value(2));
+        let query = tree.insert_leaf(&[4u8; 32], leaf_with_value(5));
+        let reference_query = reference.insert_leaf(&[4u8; 32], leaf_with_value(5));
+        assert_eq!(tree.root(), reference.root());
+        assert_eq!(
+            query.leaf.current_index(),
+            reference_query.leaf.current_index()
+        );
+
+        assert!(!tree.rollback_to_snapshot("unknown"));
+    }
+
+    #[test]
+    fn nested_snapshots() {
+        let mut tree = ZKSyncTestingTree::empty();
+        let empty_root = tree.root();
+        tree.take_snapshot("empty");
+        tree.insert_leaf(&[1u8; 32], leaf_with_value(1));
+        let root_1 = tree.root();
+        tree.take_snapshot("one");
+        tree.insert_leaf(&[2u8; 32], leaf_with_value(2));
+
+        assert!(tree.rollback_to_snapshot("one"));
+        assert_eq!(tree.root(), root_1);
+        // snapshot is kept and can be used again
+        tree.insert_leaf(&[3u8; 32], leaf_with_value(3));
+        assert!(tree.rollback_to_snapshot("one"));
+        assert_eq!(tree.root(), root_1);
+
+        assert!(tree.rollback_to_snapshot("empty"));
+        assert_eq!(tree.root(), empty_root);
+        assert_eq!(tree.next_enumeration_index(), 1);
+        // later snapshots are discarded
+        assert!(!tree.rollback_to_snapshot("one"));
+    }
+
+    #[test]
+    fn historical_reads() {
+        let mut tree = ZKSyncTestingTree::empty();
+        tree.insert_leaf(&[1u8; 32], leaf_with_value(1));
+        let old_root = tree.root();
+        tree.take_snapshot("old");
+        tree.insert_leaf(&[1u8; 32], leaf_with_value(2));
+        tree.insert_leaf(&[7u8; 32], leaf_with_value(7));
+
+        let old_query = tree.get_leaf_at_root(&old_root, &[1u8; 32]).unwrap();
+        assert_eq!(old_query.leaf.value(), &[1u8; 32]);
+        assert!(ZKSyncTestingTree::verify_inclusion(&old_root, &old_query));
+
+        let old_query = tree.get_leaf_at_root(&old_root, &[7u8; 32]).unwrap();
+        assert_eq!(old_query.leaf.value(), &[0u8; 32]);
+        assert!(ZKSyncTestingTree::verify_inclusion(&old_root, &old_query));
+
+        let current_root = tree.root();
+        let current_query = tree.get_leaf_at_root(&current_root, &[1u8; 32]).unwrap();
+        assert_eq!(current_query.leaf.value(), &[2u8; 32]);
+        assert!(ZKSyncTestingTree::verify_inclusion(
+            &current_root,
+            &current_query
+        ));
+
+        assert!(tree.get_leaf_at_root(&[0u8; 32], &[1u8; 32]).is_none());
+
+        // the cached view of the snapshot follows later writes
+        tree.insert_leaf(&[9u8; 32], leaf_with_value(9));
+        let old_query = tree.get_leaf_at_root(&old_root, &[9u8; 32]).unwrap();
+        assert_eq!(old_query.leaf.value(), &[0u8; 32]);
+        assert!(ZKSyncTestingTree::verify_inclusion(&old_root, &old_query));
+    }
+
+    #[test]
+    fn released_snapshots_free_the_journal() {
+        let mut tree = ZKSyncTestingTree::empty();
+        tree.take_snapshot("first");
+        tree.insert_leaf(&[1u8; 32], leaf_with_value(1));
+        let root_1 = tree.root();
+        tree.take_snapshot("second");
+        tree.insert_leaf(&[2u8; 32], leaf_with_value(2));
+        assert!(tree.get_leaf_at_root(&root_1, &[2u8; 32]).is_some());
+
+        assert!(tree.release_snapshot("first"));
+        assert!(!tree.release_snapshot("first"));
+        assert!(!tree.rollback_to_snapshot("first"));
+        assert_eq!(tree.snapshots().len(), 1);
+        assert_eq!(tree.snapshots()[0].journal_position, 0);
+
+        let query = tree.get_leaf_at_root(&root_1, &[2u8; 32]).unwrap();
+        assert_eq!(query.leaf.value(), &[0u8; 32]);
+        assert!(tree.rollback_to_snapshot("second"));
+        assert_eq!(tree.root(), root_1);
+
+        assert!(tree.release_snapshot("second"));
+        assert!(tree.snapshots().is_empty());
+        assert!(tree.journal.is_empty());
+        // writes are not journaled without snapshots
+        tree.insert_leaf(&[3u8; 32], leaf_with_value(3));
+        assert!(tree.journal.is_empty());
+    }
+
+    fn pseudo_random_index(seed: u64) -> [u8; 32] {
+        <Blake2s256 as BinaryHasher<32>>::leaf_hash(&seed.to_le_bytes())
+    }
+
+    #[test]
+    fn batched_insertion_matches_sequential() {
+        let mut sequential = ZKSyncTestingTree::empty();
+        let mut batched = ZKSyncTestingTree::empty();
+        for i in 0..16 {
+            let index = pseudo_random_index(i);
+            sequential.insert_leaf(&index, leaf_with_value(i as u8));
+            batched.insert_leaf(&index, leaf_with_value(i as u8));
+        }
+
+        // mix of updates of existing leafs, new leafs, leafs with a long common prefix and repeated writes
+        let mut indexes: Vec<_> = (8..64).map(pseudo_random_index).collect();
+        let mut close_index = indexes[0];
+        close_index[0] ^= 1;
+        indexes.push(close_index);
+        indexes.push(indexes[3]);
+        indexes.push(indexes[20]);
+        let leafs: Vec<_> = (0..indexes.len())
+            .map(|i| leaf_with_value(100 + i as u8))
+            .collect();
+
+        // every query together with the root right after its write
+        let expected: Vec<_> = indexes
+            .iter()
+            .zip(leafs.iter())
+            .map(|(index, leaf)| {
+                let query = sequential.insert_leaf(index, *leaf);
+                (query, sequential.root())
+            })
+            .collect();
+        let queries = batched.insert_many_leafs(&indexes, leafs);
+
+        assert_eq!(batched.root(), sequential.root());
+        assert_eq!(
+            batched.next_enumeration_index(),
+            sequential.next_enumeration_index()
+        );
+        assert_eq!(queries.len(), expected.len());
+        for (query, (expected, intermediate_root)) in queries.iter().zip(expected.iter()) {
+            assert!(ZKSyncTestingTree::verify_inclusion(
+                intermediate_root,
+                query
+            ));
+            assert_eq!(query.index, expected.index);
+            assert_eq!(query.first_write, expected.first_write);
+            assert_eq!(query.leaf.current_index(), expected.leaf.current_index());
+            assert_eq!(query.leaf.value(), expected.leaf.value());
+            assert_eq!(&*query.merkle_path, &*expected.merkle_path);
+        }
+
+        // and the tree itself is in the same state
+        let index = pseudo_random_index(1000);
+        assert_eq!(
+            &*batched.get_leaf(&index).merkle_path,
+            &*sequential.get_leaf(&index).merkle_path
+        );
+    }
+
+    #[test]
+    fn non_inclusion_proofs() {
+        let mut tree = ZKSyncTestingTree::empty();
+        for i in 0..8 {
+            tree.insert_leaf(&pseudo_random_index(i), leaf_with_value(i as u8));
+        }
+        // enumerated leaf with zero value is still included
+        tree.insert_leaf(&pseudo_random_index(8), ZkSyncStorageLeaf::empty());
+        let root = tree.root();
+
+        let proof = tree
+            .prove_non_inclusion(&root, &pseudo_random_index(100))
+            .unwrap();
+        assert!(ZKSyncTestingTree::verify_non_inclusion(&root, &proof));
+
+        assert!(tree
+            .prove_non_inclusion(&root, &pseudo_random_index(3))
+            .is_none());
+        assert!(tree
+            .prove_non_inclusion(&root, &pseudo_random_index(8))
+            .is_none());
+        assert!(tree
+            .prove_non_inclusion(&[0u8; 32], &pseudo_random_index(100))
+            .is_none());
+
+        // proof for some other index must not verify
+        let mut proof = proof;
+        proof.index = pseudo_random_index(3);
+        assert!(!ZKSyncTestingTree::verify_non_inclusion(&root, &proof));
+    }
+
+    #[test]
+    fn multi_leaf_proofs() {
+        let mut tree = ZKSyncTestingTree::empty();
+        for i in 0..32 {
+            tree.insert_leaf(&pseudo_random_index(i), leaf_with_value(i as u8));
+        }
+        let root = tree.root();
+
+        let mut neighbour = pseudo_random_index(0);
+        neighbour[0] ^= 1;
+        // included, absent and a direct neighbour of an included one
+        let indexes = vec![
+            pseudo_random_index(0),
+            pseudo_random_index(5),
+            pseudo_random_index(17),
+            pseudo_random_index(1000),
+            neighbour,
+        ];
+        let proof = tree.prove_multiple_leafs(&root, &indexes).unwrap();
+        assert_eq!(proof.leafs.len(), indexes.len());
+        assert!(proof.siblings.len() < indexes.len() * 256);
+        assert!(ZKSyncTestingTree::verify_multiple_leafs(&root, &proof));
+
+        for (index, leaf) in proof.leafs.iter() {
+            let query = tree.get_leaf(index);
+            assert_eq!(leaf.value(), query.leaf.value());
+            assert_eq!(leaf.current_index(), query.leaf.current_index());
+        }
+
+        let mut tampered = proof.clone();
+        tampered.leafs[1].1.value = [0xffu8; 32];
+        assert!(!ZKSyncTestingTree::verify_multiple_leafs(&root, &tampered));
+
+        let mut tampered = proof.clone();
+        tampered.siblings.pop();
+        assert!(!ZKSyncTestingTree::verify_multiple_leafs(&root, &tampered));
+
+        let mut tampered = proof;
+        tampered.siblings.push([0u8; 32]);
+        assert!(!ZKSyncTestingTree::verify_multiple_leafs(&root, &tampered));
+    }
+
+    #[test]
+    fn proofs_against_older_roots() {
+        let mut tree = ZKSyncTestingTree::empty();
+        for i in 0..8 {
+            tree.insert_leaf(&pseudo_random_index(i), leaf_with_value(i as u8));
+        }
+        // e.g. root of the previous block, that our proofs are built against
+        let old_root = tree.root();
+        tree.take_snapshot("previous block");
+        for i in 4..12 {
+            tree.insert_leaf(&pseudo_random_index(i), leaf_with_value(100 + i as u8));
+        }
+        let new_root = tree.root();
+
+        let proof = tree
+            .prove_non_inclusion(&old_root, &pseudo_random_index(10))
+            .unwrap();
+        assert!(ZKSyncTestingTree::verify_non_inclusion(&old_root, &proof));
+        assert!(!ZKSyncTestingTree::verify_non_inclusion(&new_root, &proof));
+        assert!(tree
+            .prove_non_inclusion(&new_root, &pseudo_random_index(10))
+            .is_none());
+
+        let indexes: Vec<_> = (0..16).map(pseudo_random_index).collect();
+        let proof = tree.prove_multiple_leafs(&old_root, &indexes).unwrap();
+        assert!(ZKSyncTestingTree::verify_multiple_leafs(&old_root, &proof));
+        assert!(!ZKSyncTestingTree::verify_multiple_leafs(&new_root, &proof));
+        for (index, leaf) in proof.leafs.iter() {
+            let query = tree.get_leaf_at_root(&old_root, index).unwrap();
+            assert_eq!(leaf.value(), query.leaf.value());
+        }
+
+        let proof = tree.prove_multiple_leafs(&new_root, &indexes).unwrap();
+        assert!(ZKSyncTestingTree::verify_multiple_leafs(&new_root, &proof));
+    }
+}
@@ -0,0 +1,171 @@
+use super::*;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Proof that there is no leaf at the given index, so it was never written
+/// (and enumerated) under some root
+#[derive(Derivative)]
+#[derivative(Clone, Debug)]
+pub struct NonInclusionProof<
+    const DEPTH: usize,
+    const INDEX_BYTES: usize,
+    const HASH_OUTPUT_WIDTH: usize,
+> {
+    pub index: [u8; INDEX_BYTES],
+    pub merkle_path: Box<[[u8; HASH_OUTPUT_WIDTH]; DEPTH]>,
+}
+
+/// Proof for many leafs at once (including empty ones). Leafs are sorted by index,
+/// and only the sibling nodes that can not be computed from the leafs themselves are included,
+/// in the order in which verifier consumes them (level by level, and by index within the level)
+#[derive(Derivative)]
+#[derivative(Clone, Debug)]
+pub struct MultiLeafProof<const INDEX_BYTES: usize, const HASH_OUTPUT_WIDTH: usize, L> {
+    pub leafs: Vec<([u8; INDEX_BYTES], L)>,
+    pub siblings: Vec<[u8; HASH_OUTPUT_WIDTH]>,
+}
+
+impl<
+        const DEPTH: usize,
+        const INDEX_BYTES: usize,
+        const LEAF_METADATA_WIDTH: usize,
+        H: BinaryHasher<32>,
+        L: EnumeratedBinaryLeaf<32>,
+    > InMemoryStorageTree<DEPTH, INDEX_BYTES, LEAF_METADATA_WIDTH, H, L>
+{
+    /// Proves against the current root or a root captured by a snapshot, e.g. the state root
+    /// some earlier block was committed with
+    pub fn prove_non_inclusion(
+        &self,
+        root: &[u8; 32],
+        index: &[u8; INDEX_BYTES],
+    ) -> Option<NonInclusionProof<DEPTH, INDEX_BYTES, 32>> {
+        self.historical_view(root)?.prove_non_inclusion(index)
+    }
+
+    pub fn verify_non_inclusion(
+        root: &[u8; 32],
+        proof: &NonInclusionProof<DEPTH, INDEX_BYTES, 32>,
+    ) -> bool {
+        let query = LeafQuery {
+            leaf: L::empty(),
+            first_write: false,
+            index: proof.index,
+            merkle_path: proof.merkle_path.clone(),
+        };
+
+        Self::verify_inclusion(root, &query)
+    }
+
+    pub fn prove_multiple_leafs(
+        &self,
+        root: &[u8; 32],
+        indexes: &[[u8; INDEX_BYTES]],
+    ) -> Option<MultiLeafProof<INDEX_BYTES, 32, L>> {
+        Some(self.historical_view(root)?.prove_multiple_leafs(indexes))
+    }
+
+    pub fn verify_multiple_leafs(
+        root: &[u8; 32],
+        proof: &MultiLeafProof<INDEX_BYTES, 32, L>,
+    ) -> bool {
+        if proof.leafs.is_empty() {
+            return false;
+        }
+
+        let mut current = BTreeMap::new();
+        for (index, leaf) in proof.leafs.iter() {
+            if current.insert(*index, Self::hash_leaf(leaf)).is_some() {
+                // every leaf must be proven once
+                return false;
+            }
+        }
+
+        let mut siblings = proof.siblings.iter();
+        for level in 0..DEPTH {
+            let mut next = BTreeMap::new();
+            for (index, current_hash) in current.iter() {
+                let is_right = is_right_side_node(index, level);
+                let pair_index = path_element_index(&create_neighbour_index(index, level), level);
+                let pair_node_hash = if let Some(pair_node_hash) = current.get(&pair_index) {
+                    if is_right {
+                        // already hashed together with the left one
+                        continue;
+                    }
+                    *pair_node_hash
+                } else if let Some(sibling) = siblings.next() {
+                    *sibling
+                } else {
+                    return false;
+                };
+
+                let (l, r) = if is_right {
+                    (&pair_node_hash, current_hash)
+                } else {
+                    (current_hash, &pair_node_hash)
+                };
+
+                next.insert(
+                    path_element_index(index, level + 1),
+                    H::node_hash(level, l, r),
+                );
+            }
+            current = next;
+        }
+
+        // there must be no unused siblings
+        siblings.next().is_none() && current.values().next() == Some(root)
+    }
+}
+
+impl<
+        'a,
+        const DEPTH: usize,
+        const INDEX_BYTES: usize,
+        const LEAF_METADATA_WIDTH: usize,
+        H: BinaryHasher<32>,
+        L: EnumeratedBinaryLeaf<32>,
+    > InMemoryStorageTreeView<'a, DEPTH, INDEX_BYTES, LEAF_METADATA_WIDTH, H, L>
+{
+    /// Returns `None` if the leaf at the index was written by this root
+    pub fn prove_non_inclusion(
+        &self,
+        index: &[u8; INDEX_BYTES],
+    ) -> Option<NonInclusionProof<DEPTH, INDEX_BYTES, 32>> {
+        if self.leaf(index).is_some() {
+            return None;
+        }
+
+        let LeafQuery { merkle_path, .. } = self.get_leaf(index);
+
+        Some(NonInclusionProof {
+            index: *index,
+            merkle_path,
+        })
+    }
+
+    pub fn prove_multiple_leafs(
+        &self,
+        indexes: &[[u8; INDEX_BYTES]],
+    ) -> MultiLeafProof<INDEX_BYTES, 32, L> {
+        let mut current: BTreeSet<[u8; INDEX_BYTES]> = indexes.iter().copied().collect();
+        let leafs = current
+            .iter()
+            .map(|index| (*index, self.leaf(index).unwrap_or_else(L::empty)))
+            .collect();
+
+        let mut siblings = vec![];
+        for level in 0..DEPTH {
+            let mut next = BTreeSet::new();
+            for index in current.iter() {
+                let pair_index = path_element_index(&create_neighbour_index(index, level), level);
+                if !current.contains(&pair_index) {
+                    siblings.push(self.node(level, &pair_index));
+                }
+                next.insert(path_element_index(index, level + 1));
+            }
+            current = next;
+        }
+
+        MultiLeafProof { leafs, siblings }
+    }
+}